@@ -9,10 +9,13 @@
 //! - 延迟 (Latency)：完成单次缓存操作所需的时间。
 //! - 伸缩性 (Scalability)：随着数据规模增长，性能下降的曲线是否符合预期（如 O(1)）。
 //! - 淘汰策略开销 (Eviction Overhead)：当触发缓存满额时的处理成本。
+//! - 并发命中 (Concurrency)：多个任务同时命中缓存时，共享读锁相对互斥锁的吞吐量。
 
 use bytes::Bytes;
 use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use std::sync::Arc;
 use std::time::SystemTime;
+use tokio::sync::{Mutex, RwLock};
 
 use webserver::cache::FileCache;
 
@@ -110,7 +113,7 @@ fn cache_find_miss_benchmark(c: &mut Criterion) {
 /// ## 维度 4：缓存淘汰策略压力测试
 /// 
 /// 模拟缓存溢出场景。容量为 100，写入 200 个条目。
-/// 旨在观察 LRU 或 FIFO 淘汰算法在处理旧记录释放时的 CPU 密集程度。
+/// 旨在观察 LRU（second-chance）淘汰算法在处理旧记录释放时的 CPU 密集程度。
 fn cache_eviction_benchmark(c: &mut Criterion) {
     c.bench_function("cache_eviction", |b| {
         b.iter(|| {
@@ -130,7 +133,59 @@ fn cache_eviction_benchmark(c: &mut Criterion) {
     });
 }
 
-/// ## 维度 5：时间一致性校验开销
+/// ## 维度 5：热点条目下的淘汰开销
+/// 
+/// 生产环境中常有一小部分文件被反复访问。每次写入新条目前先查询热点集合，
+/// 使 second-chance 淘汰在找到冷条目之前需要把被访问过的热点条目移回链表头部。
+fn cache_eviction_with_hot_entries_benchmark(c: &mut Criterion) {
+    c.bench_function("cache_eviction_hot_entries", |b| {
+        let time = SystemTime::now();
+        let content = Bytes::from("test content");
+        let hot: Vec<String> = (0..10).map(|i| format!("hot{}.txt", i)).collect();
+
+        b.iter(|| {
+            let mut cache = FileCache::from_capacity(100);
+            for name in &hot {
+                cache.push(name, content.clone(), time);
+            }
+            for i in 0..200 {
+                for name in &hot {
+                    let _ = cache.find(black_box(name), time);
+                }
+                let filename = format!("file{}.txt", i);
+                cache.push(black_box(&filename), black_box(content.clone()), time);
+            }
+            // 热点条目始终被保留，缓存保持满载
+            debug_assert_eq!(cache.len(), cache.capacity());
+            debug_assert!(hot.iter().all(|name| cache.find(name, time).is_some()));
+        });
+    });
+}
+
+/// ## 维度 6：TTL 校验开销
+/// 
+/// 设置 TTL 后每次命中都需要读取时钟并计算条目年龄，衡量其相对于普通查询的额外成本。
+fn cache_find_with_ttl_benchmark(c: &mut Criterion) {
+    c.bench_function("cache_find_with_ttl", |b| {
+        let mut cache = FileCache::from_capacity(100).with_ttl(std::time::Duration::from_secs(60));
+        let time = SystemTime::now();
+        let content = Bytes::from("test content");
+
+        for i in 0..100 {
+            let filename = format!("file{}.txt", i);
+            cache.push(&filename, content.clone(), time);
+        }
+
+        b.iter(|| {
+            for i in 0..100 {
+                let filename = format!("file{}.txt", i);
+                let _ = cache.find(black_box(&filename), black_box(time));
+            }
+        });
+    });
+}
+
+/// ## 维度 7：时间一致性校验开销
 /// 
 /// 测试当文件系统修改时间 (mtime) 发生变化时，缓存自动失效逻辑的性能。
 fn cache_time_invalidation_benchmark(c: &mut Criterion) {
@@ -156,7 +211,7 @@ fn cache_time_invalidation_benchmark(c: &mut Criterion) {
     });
 }
 
-/// ## 维度 6：大规模数据块影响分析
+/// ## 维度 8：大规模数据块影响分析
 /// 
 /// 评估 `Bytes` 克隆（ARC 引用计数增加）在面对大字节流时的表现。
 /// 验证内容大小是否会对哈希表操作产生间接的内存缓存压力。
@@ -190,6 +245,80 @@ fn cache_large_content_benchmark(c: &mut Criterion) {
     group.finish();
 }
 
+/// ## 维度 9：并发命中吞吐量
+///
+/// 服务器把缓存放在 `tokio::sync::RwLock` 中，命中时只持有共享读锁。
+/// 在多线程运行时上让若干任务同时查询热点条目，并以互斥锁（每次查询都独占缓存）作为对照，
+/// 两者的差距随 CPU 核数与任务数增加而扩大；单核环境下结果相近。
+fn cache_concurrent_hits_benchmark(c: &mut Criterion) {
+    const LOOKUPS_PER_TASK: usize = 1000;
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(4)
+        .build()
+        .unwrap();
+    let time = SystemTime::now();
+    let names: Arc<Vec<String>> = Arc::new((0..100).map(|i| format!("file{}.txt", i)).collect());
+    let mut cache = FileCache::from_capacity(100);
+    for name in names.iter() {
+        cache.push(name, Bytes::from("test content"), time);
+    }
+    let shared = Arc::new(RwLock::new(cache));
+    let mut cache = FileCache::from_capacity(100);
+    for name in names.iter() {
+        cache.push(name, Bytes::from("test content"), time);
+    }
+    let exclusive = Arc::new(Mutex::new(cache));
+
+    let mut group = c.benchmark_group("cache_concurrent_hits");
+    for tasks in [1usize, 4, 16] {
+        group.bench_with_input(BenchmarkId::new("rwlock", tasks), &tasks, |b, &tasks| {
+            b.iter(|| {
+                runtime.block_on(async {
+                    let handles: Vec<_> = (0..tasks)
+                        .map(|t| {
+                            let cache = Arc::clone(&shared);
+                            let names = Arc::clone(&names);
+                            tokio::spawn(async move {
+                                for i in 0..LOOKUPS_PER_TASK {
+                                    let name = &names[(t + i) % names.len()];
+                                    let hit = cache.read().await.find(black_box(name), time).cloned();
+                                    black_box(hit);
+                                }
+                            })
+                        })
+                        .collect();
+                    for handle in handles {
+                        handle.await.unwrap();
+                    }
+                })
+            });
+        });
+        group.bench_with_input(BenchmarkId::new("mutex", tasks), &tasks, |b, &tasks| {
+            b.iter(|| {
+                runtime.block_on(async {
+                    let handles: Vec<_> = (0..tasks)
+                        .map(|t| {
+                            let cache = Arc::clone(&exclusive);
+                            let names = Arc::clone(&names);
+                            tokio::spawn(async move {
+                                for i in 0..LOOKUPS_PER_TASK {
+                                    let name = &names[(t + i) % names.len()];
+                                    let hit = cache.lock().await.find(black_box(name), time).cloned();
+                                    black_box(hit);
+                                }
+                            })
+                        })
+                        .collect();
+                    for handle in handles {
+                        handle.await.unwrap();
+                    }
+                })
+            });
+        });
+    }
+    group.finish();
+}
+
 // 注册所有基准测试组
 criterion_group!(
     benches,
@@ -197,8 +326,11 @@ criterion_group!(
     cache_find_benchmark,
     cache_find_miss_benchmark,
     cache_eviction_benchmark,
+    cache_eviction_with_hot_entries_benchmark,
+    cache_find_with_ttl_benchmark,
     cache_time_invalidation_benchmark,
-    cache_large_content_benchmark
+    cache_large_content_benchmark,
+    cache_concurrent_hits_benchmark
 );
 
 // 基准测试执行入口
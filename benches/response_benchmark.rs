@@ -0,0 +1,131 @@
+// Copyright (c) 2026 shaneyale (shaneyale86@gmail.com)
+// All rights reserved.
+
+//! # HTTP 响应构建性能基准测试
+//!
+//! 本模块评估 `Response::from` 构建响应的开销，这里才是每个请求真正的主要成本所在。
+//! 覆盖已缓存文件（含压缩）、目录列表、404 错误页以及条件请求。
+//! 所有场景都使用临时目录作为夹具，不依赖仓库中的任何静态资源。
+
+use std::sync::Arc;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use tokio::{runtime::Runtime, sync::RwLock};
+
+use webserver::{cache::FileCache, config::Config, request::Request, response::Response};
+
+/// 解析一段原始请求报文
+fn request(raw: &str) -> Request {
+    Request::try_from(&raw.as_bytes().to_vec(), 0).unwrap()
+}
+
+/// ## 场景 1：已缓存的小文本文件
+///
+/// 第一次请求后文件内容进入缓存，此后每次迭代都是缓存命中。
+/// 对比不压缩与 gzip 两种情况，可以直观看到缓存命中后仍需逐次压缩的成本。
+fn cached_file_benchmark(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let file_path = dir.path().join("small.txt");
+    std::fs::write(&file_path, "The quick brown fox jumps over the lazy dog.\n".repeat(100)).unwrap();
+    let path = file_path.to_str().unwrap();
+
+    let cache = Arc::new(RwLock::new(FileCache::from_capacity(10)));
+    let config = Config::new();
+    let plain = request("GET /small.txt HTTP/1.1\r\nHost: localhost\r\n\r\n");
+    let gzip = request("GET /small.txt HTTP/1.1\r\nHost: localhost\r\nAccept-Encoding: gzip\r\n\r\n");
+    // 预热缓存
+    rt.block_on(Response::from(path, &plain, 0, &cache, &config));
+
+    let mut group = c.benchmark_group("cached_file");
+    for (name, request) in [("identity", &plain), ("gzip", &gzip)] {
+        group.bench_function(name, |b| {
+            b.iter(|| rt.block_on(Response::from(black_box(path), request, 0, &cache, &config)));
+        });
+    }
+    group.finish();
+}
+
+/// ## 场景 2：目录列表（HTML 与 JSON）
+///
+/// 目录包含 100 个文件。列表结果会按目录修改时间缓存，这里测量的是缓存命中后的构建开销。
+fn dir_listing_benchmark(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    for i in 0..100 {
+        std::fs::write(dir.path().join(format!("file{:03}.txt", i)), "content").unwrap();
+    }
+    let path = dir.path().to_str().unwrap();
+
+    let cache = Arc::new(RwLock::new(FileCache::from_capacity(10)));
+    let config = Config::new();
+    let html = request("GET / HTTP/1.1\r\nHost: localhost\r\nAccept: text/html\r\n\r\n");
+    let json = request("GET / HTTP/1.1\r\nHost: localhost\r\nAccept: application/json\r\n\r\n");
+
+    let mut group = c.benchmark_group("dir_listing");
+    for (name, request) in [("html", &html), ("json", &json)] {
+        group.bench_function(name, |b| {
+            b.iter(|| rt.block_on(Response::from(black_box(path), request, 0, &cache, &config)));
+        });
+    }
+    group.finish();
+}
+
+/// ## 场景 3：404 错误页
+///
+/// 错误页由模板即时生成，测量其构建与序列化的开销。
+fn not_found_benchmark(c: &mut Criterion) {
+    let get = request("GET /missing.html HTTP/1.1\r\nHost: localhost\r\nAccept-Encoding: gzip\r\n\r\n");
+
+    c.bench_function("response_404", |b| {
+        b.iter(|| Response::response_404(black_box(&get), 0).as_bytes());
+    });
+}
+
+/// ## 场景 4：条件请求 200 与 304 的对比
+///
+/// 模拟浏览器对同一个文件分别发送普通 GET 与携带匹配 `If-None-Match` 的条件 GET（均接受 gzip）。
+/// 304 只依赖元数据，不获取缓存锁、不读取也不压缩文件，耗时应明显低于 200。
+fn conditional_request_benchmark(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let file_path = dir.path().join("page.html");
+    std::fs::write(&file_path, "<p>hello</p>".repeat(1024)).unwrap();
+    let path = file_path.to_str().unwrap();
+
+    let cache = Arc::new(RwLock::new(FileCache::from_capacity(10)));
+    let config = Config::new();
+    let get = request("GET /page.html HTTP/1.1\r\nHost: localhost\r\nAccept-Encoding: gzip\r\n\r\n");
+    let etag = rt
+        .block_on(Response::from(path, &get, 0, &cache, &config))
+        .as_bytes();
+    let etag = String::from_utf8_lossy(&etag)
+        .lines()
+        .find_map(|l| l.strip_prefix("ETag: ").map(str::to_string))
+        .expect("响应缺少ETag");
+    let conditional = request(&format!(
+        "GET /page.html HTTP/1.1\r\nHost: localhost\r\nAccept-Encoding: gzip\r\nIf-None-Match: {}\r\n\r\n",
+        etag
+    ));
+
+    let mut group = c.benchmark_group("conditional_request");
+    group.bench_function("200", |b| {
+        b.iter(|| rt.block_on(Response::from(black_box(path), &get, 0, &cache, &config)));
+    });
+    group.bench_function("304", |b| {
+        b.iter(|| rt.block_on(Response::from(black_box(path), &conditional, 0, &cache, &config)));
+    });
+    group.finish();
+}
+
+// 注册响应构建相关的基准测试任务
+criterion_group!(
+    benches,
+    cached_file_benchmark,
+    dir_listing_benchmark,
+    not_found_benchmark,
+    conditional_request_benchmark
+);
+
+// 执行基准测试程序入口
+criterion_main!(benches);
@@ -43,7 +43,8 @@ export const useFileStore = defineStore('file', () => {
 
     try {
       const data = await fetchFiles(path)
-      files.value = data
+      // 服务端开启 listing_json_summary 时返回 { entries, summary }，否则为条目数组
+      files.value = Array.isArray(data) ? data : data.entries
       currentPath.value = path
     } catch (e) {
       error.value = e.message
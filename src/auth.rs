@@ -0,0 +1,397 @@
+// Copyright (c) 2026 shaneyale (shaneyale86@gmail.com)
+// All rights reserved.
+
+//! # 鉴权模块
+//!
+//! 保护配置文件 `[auth]` 表中列出的路径前缀（见 [`AuthConfig`]），支持两种凭据：
+//!
+//! - **Basic**（RFC 7617）：用户名与密码对照 htpasswd 格式的用户文件，每行 `用户名:密码哈希`，
+//!   空行与 `#` 开头的行被忽略。支持 `htpasswd -m`（默认）生成的 `$apr1$` MD5 哈希与
+//!   `htpasswd -s` 生成的 `{SHA}` 哈希；bcrypt（`$2y$`）、crypt 与明文密码无法校验，对应用户始终认证失败。
+//!   解析后的用户文件按修改时间缓存，文件修改后下一次请求即重新读取，无需重载配置。
+//! - **Bearer**（RFC 6750）：`[auth.tokens]` 中配置的静态令牌，以令牌名作为用户名。
+//!
+//! 认证成功后用户名记录在 [`Request::remote_user`] 中并出现在访问日志里；
+//! 认证失败时以 [`challenge`] 生成带 `WWW-Authenticate` 质询的 401 响应。
+//!
+//! MD5、SHA-1 与 Base64 由 `md-5`、`sha1` 与 `base64` crate 提供，`$apr1$` 按 Apache 的 md5crypt 算法在 MD5 之上计算，
+//! 只用于校验 htpasswd 中的已有哈希，不应用于新的安全设计。
+
+use crate::{
+    config::AuthConfig,
+    request::Request,
+    response::Response,
+    util::{normalize_path, percent_decode},
+};
+
+use base64::{
+    alphabet,
+    engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig},
+    Engine,
+};
+use lazy_static::lazy_static;
+use log::{debug, warn};
+use md5::{Digest, Md5};
+use sha1::Sha1;
+
+use std::{
+    collections::HashMap,
+    fmt, io,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+/// 标准 Base64（RFC 4648 §4），解码时允许省略结尾的 `=`。
+const BASE64: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+/// 解析后的用户文件：用户名到密码哈希。
+type Users = HashMap<String, String>;
+
+/// 缓存的用户文件：读取时文件的修改时间、大小与解析结果。
+type CachedUsers = (SystemTime, u64, Arc<Users>);
+
+lazy_static! {
+    /// 按路径缓存解析后的用户文件，文件的修改时间与大小任一变化时重新读取。
+    static ref HTPASSWD_CACHE: Mutex<HashMap<PathBuf, CachedUsers>> = Mutex::new(HashMap::new());
+}
+
+/// 鉴权失败的原因。
+#[derive(Debug)]
+pub enum AuthError {
+    /// 请求没有携带可用的凭据（缺少 `Authorization` 头，或使用了未启用的认证方式）。
+    Missing,
+    /// Basic 凭据格式错误、用户不存在或密码错误。
+    InvalidCredentials,
+    /// Bearer 令牌不在配置的令牌表中。
+    InvalidToken,
+    /// 用户文件无法读取，应返回 500 而不是 401。
+    Unavailable(io::Error),
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthError::Missing => write!(f, "缺少凭据"),
+            AuthError::InvalidCredentials => write!(f, "用户名或密码错误"),
+            AuthError::InvalidToken => write!(f, "令牌无效"),
+            AuthError::Unavailable(e) => write!(f, "用户文件无法读取：{}", e),
+        }
+    }
+}
+
+impl std::error::Error for AuthError {}
+
+/// 判断请求路径是否落在 `auth` 保护的前缀之下。
+///
+/// 路径去掉查询字符串后，原始形式与解码、规范化后的形式只要有一个匹配即视为受保护，
+/// 避免 `/%61dmin/`、`//admin/` 等等价写法绕过保护。
+pub fn is_protected(path: &str, auth: &AuthConfig) -> bool {
+    let raw = path.split('?').next().unwrap_or_default();
+    let normalized = percent_decode(raw).and_then(|decoded| normalize_path(&decoded)).ok();
+    auth.paths().iter().any(|prefix| {
+        let prefix = prefix.trim_end_matches('/');
+        [Some(raw), normalized.as_deref()]
+            .into_iter()
+            .flatten()
+            .any(|path| path == prefix || path.starts_with(&format!("{}/", prefix)))
+    })
+}
+
+/// 校验请求的凭据。
+///
+/// 请求路径不受保护时返回 `Ok(None)`；认证成功时返回 `Ok(Some(用户名))`，Bearer 令牌以令牌名作为用户名。
+///
+/// # Errors
+///
+/// 凭据缺失或错误时返回对应的 [`AuthError`]；用户文件无法读取时返回 [`AuthError::Unavailable`]。
+pub async fn authenticate(request: &Request, auth: &AuthConfig) -> Result<Option<String>, AuthError> {
+    if !is_protected(request.path(), auth) {
+        return Ok(None);
+    }
+    let (scheme, credentials) = request
+        .header("authorization")
+        .and_then(|value| value.split_once(' '))
+        .ok_or(AuthError::Missing)?;
+    let credentials = credentials.trim();
+
+    if scheme.eq_ignore_ascii_case("basic") {
+        let Some(htpasswd) = auth.htpasswd() else {
+            return Err(AuthError::Missing);
+        };
+        let decoded = BASE64
+            .decode(credentials)
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .ok_or(AuthError::InvalidCredentials)?;
+        let (user, password) = decoded.split_once(':').ok_or(AuthError::InvalidCredentials)?;
+        let users = load_users(htpasswd).await.map_err(AuthError::Unavailable)?;
+        let hash = users.get(user).ok_or(AuthError::InvalidCredentials)?;
+        match verify_password(password, hash) {
+            Some(true) => Ok(Some(user.to_string())),
+            Some(false) => Err(AuthError::InvalidCredentials),
+            None => {
+                warn!("[ID{}]用户{}的密码哈希格式不受支持，只支持 $apr1$ 与 {{SHA}}", request.id(), user);
+                Err(AuthError::InvalidCredentials)
+            }
+        }
+    } else if scheme.eq_ignore_ascii_case("bearer") && !auth.tokens().is_empty() {
+        // 逐个比较全部令牌，比较本身也与内容无关，避免通过响应时间猜测令牌
+        let mut matched = None;
+        for (name, token) in auth.tokens() {
+            if constant_time_eq(token.as_bytes(), credentials.as_bytes()) {
+                matched = Some(name.clone());
+            }
+        }
+        matched.map(Some).ok_or(AuthError::InvalidToken)
+    } else {
+        debug!("[ID{}]不支持的认证方式：{}", request.id(), scheme);
+        Err(AuthError::Missing)
+    }
+}
+
+/// 构建鉴权失败的 401 响应，为每种启用的认证方式附加一个 `WWW-Authenticate` 质询。
+///
+/// 令牌无效时 Bearer 质询附带 `error="invalid_token"`（RFC 6750 §3.1）。
+pub fn challenge(request: &Request, id: u128, auth: &AuthConfig, error: &AuthError) -> Response {
+    let mut response = Response::response_401(request, id);
+    if auth.htpasswd().is_some() {
+        response.add_header("WWW-Authenticate", &format!("Basic realm=\"{}\", charset=\"UTF-8\"", auth.realm()));
+    }
+    if !auth.tokens().is_empty() {
+        let mut bearer = format!("Bearer realm=\"{}\"", auth.realm());
+        if matches!(error, AuthError::InvalidToken) {
+            bearer.push_str(", error=\"invalid_token\"");
+        }
+        response.add_header("WWW-Authenticate", &bearer);
+    }
+    response
+}
+
+/// 读取并解析用户文件。文件的修改时间与大小与上次读取时相同时直接返回缓存的结果。
+///
+/// 同名用户出现多次时以第一次出现的为准，与 Apache 一致。
+async fn load_users(path: &str) -> io::Result<Arc<Users>> {
+    let metadata = tokio::fs::metadata(path).await?;
+    let (modified, len) = (metadata.modified()?, metadata.len());
+    let key = PathBuf::from(path);
+    let cached = HTPASSWD_CACHE.lock().unwrap_or_else(|e| e.into_inner()).get(&key).cloned();
+    if let Some((cached_modified, cached_len, users)) = cached {
+        if (cached_modified, cached_len) == (modified, len) {
+            return Ok(users);
+        }
+    }
+
+    let text = tokio::fs::read_to_string(path).await?;
+    let mut users = Users::new();
+    for (name, hash) in text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once(':'))
+    {
+        users.entry(name.to_string()).or_insert_with(|| hash.to_string());
+    }
+    debug!("读取用户文件{}，共{}个用户", path, users.len());
+    let users = Arc::new(users);
+    HTPASSWD_CACHE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(key, (modified, len, Arc::clone(&users)));
+    Ok(users)
+}
+
+/// 用 htpasswd 中的哈希校验密码；哈希格式不受支持时返回 `None`。
+fn verify_password(password: &str, hash: &str) -> Option<bool> {
+    if let Some(encoded) = hash.strip_prefix("{SHA}") {
+        let expected = BASE64.decode(encoded).ok()?;
+        return Some(constant_time_eq(&Sha1::digest(password.as_bytes()), &expected));
+    }
+    if let Some(rest) = hash.strip_prefix("$apr1$") {
+        let (salt, _) = rest.split_once('$')?;
+        let computed = apr1_crypt(password.as_bytes(), salt.as_bytes());
+        return Some(constant_time_eq(computed.as_bytes(), hash.as_bytes()));
+    }
+    None
+}
+
+/// 长度相同时比较耗时与内容无关的字节比较。
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// 计算 Apache 的 `$apr1$` 密码哈希（基于 MD5 的 md5crypt 算法，魔数为 `$apr1$`），返回完整的哈希字符串。
+///
+/// 盐最多取前 8 个字节。
+fn apr1_crypt(password: &[u8], salt: &[u8]) -> String {
+    const MAGIC: &[u8] = b"$apr1$";
+    const ITOA64: &[u8] = b"./0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+    let salt = &salt[..salt.len().min(8)];
+
+    let alternate = Md5::new().chain_update(password).chain_update(salt).chain_update(password).finalize();
+    let mut context = Md5::new().chain_update(password).chain_update(MAGIC).chain_update(salt);
+    // 每 16 字节重复一次 alternate，共追加与密码等长的字节
+    for chunk in password.chunks(16) {
+        context.update(&alternate[..chunk.len()]);
+    }
+    let mut length = password.len();
+    while length > 0 {
+        context.update([if length & 1 == 1 { 0 } else { password.first().copied().unwrap_or(0) }]);
+        length >>= 1;
+    }
+    let mut digest = context.finalize();
+
+    // 1000 轮迭代，拖慢暴力破解
+    for round in 0..1000 {
+        let mut context = Md5::new();
+        if round & 1 == 1 {
+            context.update(password);
+        } else {
+            context.update(digest);
+        }
+        if round % 3 != 0 {
+            context.update(salt);
+        }
+        if round % 7 != 0 {
+            context.update(password);
+        }
+        if round & 1 == 1 {
+            context.update(digest);
+        } else {
+            context.update(password);
+        }
+        digest = context.finalize();
+    }
+
+    let mut hash = String::from_utf8_lossy(&[MAGIC, salt, b"$"].concat()).into_owned();
+    let mut to64 = |mut value: u32, count: usize| {
+        for _ in 0..count {
+            hash.push(ITOA64[(value & 0x3f) as usize] as char);
+            value >>= 6;
+        }
+    };
+    for (a, b, c) in [(0, 6, 12), (1, 7, 13), (2, 8, 14), (3, 9, 15), (4, 10, 5)] {
+        to64(((digest[a] as u32) << 16) | ((digest[b] as u32) << 8) | digest[c] as u32, 4);
+    }
+    to64(digest[11] as u32, 2);
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn auth_config(extra: &str) -> Config {
+        Config::from_toml_str(&format!(
+            "www_root = \"./static/\"\nport = 7878\nworker_threads = 1\ncache_size = 5\nlocal = true\n{}",
+            extra
+        ))
+    }
+
+    fn request(path: &str, authorization: Option<&str>) -> Request {
+        let header = authorization.map_or_else(String::new, |value| format!("Authorization: {}\r\n", value));
+        let raw = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n{}\r\n", path, header);
+        Request::try_from(&raw.into_bytes(), 0).unwrap()
+    }
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(BASE64.decode("YWxpY2U6c2VjcmV0").unwrap(), b"alice:secret");
+        assert_eq!(BASE64.decode("YQ==").unwrap(), b"a");
+        assert_eq!(BASE64.decode("YQ").unwrap(), b"a");
+        assert!(BASE64.decode("Y").is_err());
+        assert!(BASE64.decode("YW*j").is_err());
+    }
+
+    #[test]
+    fn test_verify_password() {
+        // 由 `openssl passwd -apr1 -salt Xa1b2C3d secret` 与 `htpasswd -s` 生成
+        let apr1 = "$apr1$Xa1b2C3d$MU7GsAmPIN1pKc0EEJybu1";
+        // 长于 16 字节的密码使 alternate 重复多次
+        let long = "$apr1$saltsalt$PU9q8.HoFJEM7m9NSIooE1";
+        assert_eq!(verify_password("correct horse battery staple", long), Some(true));
+        assert_eq!(verify_password("secret", apr1), Some(true));
+        assert_eq!(verify_password("Secret", apr1), Some(false));
+        assert_eq!(verify_password("secret", "{SHA}5en6G6MezRroT3XKqkdPOmY/BfQ="), Some(true));
+        assert_eq!(verify_password("wrong", "{SHA}5en6G6MezRroT3XKqkdPOmY/BfQ="), Some(false));
+        assert_eq!(verify_password("secret", "$2y$05$abcdefghijklmnopqrstuv"), None);
+        assert_eq!(verify_password("secret", "secret"), None);
+    }
+
+    #[test]
+    fn test_is_protected() {
+        let config = auth_config("[auth]\npaths = [\"/admin/\", \"/private\"]\n[auth.tokens]\nci = \"t\"");
+        let auth = config.auth();
+        for path in ["/admin", "/admin/", "/admin/x?y=1", "//admin/x", "/%61dmin/x", "/private/a/b"] {
+            assert!(is_protected(path, auth), "{}", path);
+        }
+        for path in ["/administrator", "/", "/public/admin/"] {
+            assert!(!is_protected(path, auth), "{}", path);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_authenticate() {
+        let dir = tempfile::tempdir().unwrap();
+        let htpasswd = dir.path().join("htpasswd");
+        std::fs::write(
+            &htpasswd,
+            "# 注释\nalice:$apr1$Xa1b2C3d$MU7GsAmPIN1pKc0EEJybu1\nbob:{SHA}5en6G6MezRroT3XKqkdPOmY/BfQ=\n",
+        )
+        .unwrap();
+        let config = auth_config(&format!(
+            "[auth]\npaths = [\"/admin/\"]\nhtpasswd = {:?}\n[auth.tokens]\nci = \"c2VjcmV0\"",
+            htpasswd.to_str().unwrap()
+        ));
+        config.validate().unwrap();
+        let auth = config.auth();
+
+        let check = |path: &str, authorization: Option<&str>| {
+            let request = request(path, authorization);
+            async move { authenticate(&request, auth).await }
+        };
+        assert_eq!(check("/index.html", None).await.unwrap(), None);
+        // alice:secret 与 bob:secret
+        assert_eq!(check("/admin/", Some("Basic YWxpY2U6c2VjcmV0")).await.unwrap().as_deref(), Some("alice"));
+        assert_eq!(check("/admin/", Some("basic Ym9iOnNlY3JldA==")).await.unwrap().as_deref(), Some("bob"));
+        assert_eq!(check("/admin/", Some("Bearer c2VjcmV0")).await.unwrap().as_deref(), Some("ci"));
+
+        assert!(matches!(check("/admin/", None).await, Err(AuthError::Missing)));
+        assert!(matches!(check("/admin/", Some("Digest x")).await, Err(AuthError::Missing)));
+        // alice:wrong 与不存在的用户 carol:secret
+        assert!(matches!(check("/admin/", Some("Basic YWxpY2U6d3Jvbmc=")).await, Err(AuthError::InvalidCredentials)));
+        assert!(matches!(check("/admin/", Some("Basic Y2Fyb2w6c2VjcmV0")).await, Err(AuthError::InvalidCredentials)));
+        assert!(matches!(check("/admin/", Some("Bearer nope")).await, Err(AuthError::InvalidToken)));
+
+        // 修改时间与大小都未变化时沿用缓存的解析结果，修改时间变化后重新读取
+        let modified = std::fs::metadata(&htpasswd).unwrap().modified().unwrap();
+        let rewrite = |text: &str, modified: std::time::SystemTime| {
+            std::fs::write(&htpasswd, text).unwrap();
+            std::fs::File::options().write(true).open(&htpasswd).unwrap().set_modified(modified).unwrap();
+        };
+        let renamed = "# 注释\nalize:$apr1$Xa1b2C3d$MU7GsAmPIN1pKc0EEJybu1\nbob:{SHA}5en6G6MezRroT3XKqkdPOmY/BfQ=\n";
+        rewrite(renamed, modified);
+        assert_eq!(check("/admin/", Some("Basic YWxpY2U6c2VjcmV0")).await.unwrap().as_deref(), Some("alice"));
+        rewrite(renamed, modified + std::time::Duration::from_secs(1));
+        assert!(matches!(check("/admin/", Some("Basic YWxpY2U6c2VjcmV0")).await, Err(AuthError::InvalidCredentials)));
+        // alize:secret
+        assert_eq!(check("/admin/", Some("Basic YWxpemU6c2VjcmV0")).await.unwrap().as_deref(), Some("alize"));
+
+        std::fs::remove_file(&htpasswd).unwrap();
+        assert!(matches!(check("/admin/", Some("Basic YWxpY2U6c2VjcmV0")).await, Err(AuthError::Unavailable(_))));
+    }
+
+    #[test]
+    fn test_challenge_headers() {
+        let config = auth_config("[auth]\npaths = [\"/\"]\nrealm = \"Admin\"\n[auth.tokens]\nci = \"t\"");
+        let request = request("/", None);
+        let text = String::from_utf8(challenge(&request, 0, config.auth(), &AuthError::InvalidToken).as_bytes()).unwrap();
+        assert!(text.starts_with("HTTP/1.1 401 Unauthorized\r\n"), "{}", text);
+        assert!(text.contains("WWW-Authenticate: Bearer realm=\"Admin\", error=\"invalid_token\"\r\n"), "{}", text);
+        assert!(!text.contains("Basic realm"), "{}", text);
+    }
+}
@@ -6,22 +6,71 @@
 //! 该模块实现了一个带有时效性验证的高性能文件内容缓存系统。
 //! 它结合了 LRU（最近最少使用）淘汰算法与文件修改时间（SystemTime）校验，
 //! 确保在高并发场景下既能提升访问速度，又能保证数据的最终一致性。
+//! 此外还可以为条目设置存活时间（TTL），时间来源由 [`Clock`] 提供，便于在测试中拨动时间。
+//! 除条目数量外，还可以用 [`FileCache::with_max_bytes`] 限制所有条目内容的总字节数。
+//!
+//! 查询（[`FileCache::find`]）只需要 `&self`：访问记录与命中统计都使用原子变量，
+//! 因此缓存可以放在 `tokio::sync::RwLock` 中，命中时多个请求只需共享读锁、互不阻塞。
+//! 最近访问顺序在写入（[`FileCache::push`]）需要淘汰条目时才同步到 LRU 链表（second-chance 算法）。
 
 use std::num::NonZeroUsize;
-use std::time::SystemTime;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
 use bytes::Bytes;
+use log::debug;
 use lru::LruCache;
 
+use crate::clock::{Clock, SystemClock};
+
 /// `CacheEntry` 存储缓存的实体数据。
 ///
 /// 包含文件的二进制原始数据以及该数据在读取时的磁盘最后修改时间。
-#[derive(Clone)]
+/// 条目还可以记录文件内容的哈希（用于强 ETag），只记录了哈希的条目不含内容。
 struct CacheEntry {
-    /// 文件的二进制内容，使用 `Bytes` 以支持跨线程的高效引用计数共享。
-    content: Bytes,
+    /// 文件的二进制内容，使用 `Bytes` 以支持跨线程的高效引用计数共享。只记录了哈希的条目为 `None`。
+    content: Option<Bytes>,
+    /// 文件内容的哈希，由 [`FileCache::set_content_hash`] 写入。
+    content_hash: Option<u64>,
+    /// 内容附带的条目数（如分页目录列表的条目总数），由 [`FileCache::push_with_item_count`] 写入。
+    item_count: Option<usize>,
     /// 记录文件被缓存时的最后修改时间，用于后续的失效校验。
     modified_time: SystemTime,
+    /// 条目写入缓存的时间，用于 TTL 过期判断。
+    cached_at: SystemTime,
+    /// 最近一次被命中时的访问序号，由只读的 `find` 更新。
+    last_used: AtomicU64,
+    /// 条目被放到 LRU 链表头部（最近使用端）时的访问序号。
+    /// `last_used` 大于该值说明条目在此之后被访问过，淘汰时会再给它一次机会。
+    promoted_at: u64,
+}
+
+impl CacheEntry {
+    /// 条目内容占用的字节数，只记录了哈希的条目为 0。
+    fn size(&self) -> usize {
+        self.content.as_ref().map_or(0, Bytes::len)
+    }
+}
+
+/// 条目被淘汰时调用的回调，参数为条目的键与内容大小（字节数）。
+pub type EvictCallback = Box<dyn Fn(&str, usize) + Send + Sync>;
+
+/// 缓存的运行统计，由 [`FileCache::stats`] 返回。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+    /// 当前条目数量。
+    pub entries: usize,
+    /// 条目数量上限。
+    pub capacity: usize,
+    /// 所有条目内容的总字节数。
+    pub bytes: usize,
+    /// 总字节数上限，`None` 表示不限制。
+    pub max_bytes: Option<usize>,
+    /// 累计命中次数。
+    pub hits: u64,
+    /// 累计未命中次数。
+    pub misses: u64,
 }
 
 /// 基于 LRU 策略的文件缓存器。
@@ -31,6 +80,22 @@ struct CacheEntry {
 pub struct FileCache {
     /// 内部维护的 LRU 缓存容器。
     cache: LruCache<String, CacheEntry>,
+    /// 条目的存活时间。为 `None` 时条目仅因文件修改或 LRU 淘汰而失效。
+    ttl: Option<Duration>,
+    /// 时间来源，默认为系统时钟。
+    clock: Arc<dyn Clock>,
+    /// 单调递增的访问序号，用于记录条目的访问先后。
+    tick: AtomicU64,
+    /// 累计命中次数。
+    hits: AtomicU64,
+    /// 累计未命中次数（包括条目不存在、已过期或文件已修改）。
+    misses: AtomicU64,
+    /// 条目因容量不足被淘汰时的回调。
+    on_evict: Option<EvictCallback>,
+    /// 所有条目内容的总字节数上限。为 `None` 时只限制条目数量。
+    max_bytes: Option<usize>,
+    /// 当前所有条目内容的总字节数。
+    used_bytes: usize,
 }
 
 impl FileCache {
@@ -56,12 +121,88 @@ impl FileCache {
         }
         Self {
             cache: LruCache::new(NonZeroUsize::new(capacity).unwrap()),
+            ttl: None,
+            clock: Arc::new(SystemClock),
+            tick: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            on_evict: None,
+            max_bytes: None,
+            used_bytes: 0,
+        }
+    }
+
+    /// 限制所有条目内容的总字节数。写入新条目前会淘汰最久未访问的条目直到总量不超过上限，
+    /// 单个内容超过上限的条目不会被缓存。
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// 运行期间修改总字节数上限，`None` 表示不限制。缩小上限时立即淘汰条目直到总量不超过上限。用于配置热重载。
+    pub fn set_max_bytes(&mut self, max_bytes: Option<usize>) {
+        self.max_bytes = max_bytes;
+        while self.max_bytes.is_some_and(|max| self.used_bytes > max) && !self.cache.is_empty() {
+            self.evict_one();
         }
     }
 
+    /// 为缓存条目设置存活时间，超过该时长的条目在查询时视为失效。
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// 运行期间修改条目存活时间，`None` 表示不限制。用于配置热重载。
+    pub fn set_ttl(&mut self, ttl: Option<Duration>) {
+        self.ttl = ttl;
+    }
+
+    /// 运行期间调整缓存容量。缩小容量时，最久未访问的条目会被立即淘汰。用于配置热重载。
+    ///
+    /// # Panics
+    ///
+    /// 与 [`FileCache::from_capacity`] 相同，`capacity` 为 0 时触发 Panic。
+    pub fn resize(&mut self, capacity: usize) {
+        let Some(capacity) = NonZeroUsize::new(capacity) else {
+            panic!("调用resize时指定的大小是0");
+        };
+        while self.cache.len() > capacity.get() {
+            if let Some((key, entry)) = self.cache.pop_lru() {
+                self.used_bytes -= entry.size();
+                self.notify_evicted(&key, &entry);
+            }
+        }
+        self.cache.resize(capacity);
+    }
+
+    /// 设置条目被淘汰时的回调，便于排查 `cache_size` 过小导致的频繁淘汰。
+    ///
+    /// 无论是否设置回调，淘汰时都会输出一条 `debug` 日志。覆盖同名条目不算淘汰。
+    pub fn on_evict(&mut self, callback: EvictCallback) {
+        self.on_evict = Some(callback);
+    }
+
+    /// 记录一次淘汰并调用回调。
+    fn notify_evicted(&self, key: &str, entry: &CacheEntry) {
+        debug!("缓存条目 {} 被淘汰，大小 {} 字节", key, entry.size());
+        if let Some(callback) = &self.on_evict {
+            callback(key, entry.size());
+        }
+    }
+
+    /// 替换缓存使用的时间来源，主要用于在测试中注入 `FakeClock`。
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
     /// 将文件内容及其元数据放入缓存。
     ///
-    /// 如果缓存中已存在同名文件，该操作会覆盖旧条目并将其标记为最近访问。
+    /// 如果缓存中已存在同名文件，该操作会覆盖旧条目并将其标记为最近访问；旧条目记录的同一版本文件的内容哈希予以保留。
+    /// 缓存已满时淘汰最久未访问的条目：链表尾部的条目若在上次移到头部之后被 `find` 命中过，
+    /// 则将其移回头部并继续检查下一个，直到找到确实未被访问的条目。
+    /// 设置了总字节数上限时，同样淘汰条目直到放得下新内容；内容本身超过上限时不缓存，同名的旧条目也会被移除。
     ///
     /// # 参数
     ///
@@ -69,12 +210,125 @@ impl FileCache {
     /// * `bytes` - 文件的二进制数据。
     /// * `modified_time` - 文件的最后修改时间。
     pub fn push(&mut self, filename: &str, bytes: Bytes, modified_time: SystemTime) {
-        let entry = CacheEntry {
-            content: bytes,
-            modified_time,
-        };
+        self.insert(filename, bytes, None, modified_time);
+    }
+
+    /// 与 [`FileCache::push`] 相同，同时记录内容附带的条目数，由 [`FileCache::item_count`] 读取。
+    ///
+    /// 用于分页的目录列表：缓存的只是当前页，条目总数（`X-Total-Count`）需要随之保存，而不是写进内容本身。
+    pub fn push_with_item_count(&mut self, filename: &str, bytes: Bytes, item_count: usize, modified_time: SystemTime) {
+        self.insert(filename, bytes, Some(item_count), modified_time);
+    }
+
+    /// 写入条目，见 [`FileCache::push`]。
+    fn insert(&mut self, filename: &str, bytes: Bytes, item_count: Option<usize>, modified_time: SystemTime) {
+        // 先移除同名的旧条目，以便按新内容的大小计算占用
+        let mut content_hash = None;
+        if let Some(old) = self.cache.pop(filename) {
+            self.used_bytes -= old.size();
+            content_hash = old.content_hash.filter(|_| old.modified_time == modified_time);
+        }
+        if self.max_bytes.is_some_and(|max| bytes.len() > max) {
+            debug!("{} 的内容（{} 字节）超过缓存总字节数上限，不缓存", filename, bytes.len());
+            return;
+        }
+        if self.cache.len() == self.cache.cap().get() {
+            self.evict_one();
+        }
+        while self.max_bytes.is_some_and(|max| self.used_bytes + bytes.len() > max) && !self.cache.is_empty() {
+            self.evict_one();
+        }
+        self.used_bytes += bytes.len();
+        let mut entry = self.new_entry(Some(bytes), content_hash, modified_time);
+        entry.item_count = item_count;
         self.cache.put(filename.to_string(), entry);
     }
+
+    /// 记录文件内容的哈希，供强 ETag 复用，避免每次请求都重新读取整个文件。
+    ///
+    /// 缓存中已有同一版本文件的条目时把哈希记在该条目上，不调整 LRU 顺序；
+    /// 否则写入一个只含哈希的条目，它不占用字节数，[`FileCache::find`] 视其为未命中。
+    pub fn set_content_hash(&mut self, filename: &str, hash: u64, modified_time: SystemTime) {
+        let current = self
+            .cache
+            .peek(filename)
+            .is_some_and(|entry| !self.is_expired(entry) && entry.modified_time == modified_time);
+        if current {
+            if let Some(entry) = self.cache.peek_mut(filename) {
+                entry.content_hash = Some(hash);
+            }
+            return;
+        }
+        if let Some(old) = self.cache.pop(filename) {
+            self.used_bytes -= old.size();
+        }
+        if self.cache.len() == self.cache.cap().get() {
+            self.evict_one();
+        }
+        let entry = self.new_entry(None, Some(hash), modified_time);
+        self.cache.put(filename.to_string(), entry);
+    }
+
+    /// 查询 [`FileCache::set_content_hash`] 记录的内容哈希，按 TTL 与文件修改时间校验，不计入命中统计。
+    pub fn content_hash(&self, filename: &str, current_modified_time: SystemTime) -> Option<u64> {
+        self.cache
+            .peek(filename)
+            .filter(|entry| !self.is_expired(entry) && entry.modified_time == current_modified_time)
+            .and_then(|entry| entry.content_hash)
+    }
+
+    /// 查询 [`FileCache::push_with_item_count`] 记录的条目数，按 TTL 与文件修改时间校验，不计入命中统计。
+    pub fn item_count(&self, filename: &str, current_modified_time: SystemTime) -> Option<usize> {
+        self.cache
+            .peek(filename)
+            .filter(|entry| !self.is_expired(entry) && entry.modified_time == current_modified_time)
+            .and_then(|entry| entry.item_count)
+    }
+
+    /// 构造一个新条目，访问序号取下一个序号。
+    fn new_entry(&self, content: Option<Bytes>, content_hash: Option<u64>, modified_time: SystemTime) -> CacheEntry {
+        let tick = self.next_tick();
+        CacheEntry {
+            content,
+            content_hash,
+            item_count: None,
+            modified_time,
+            cached_at: self.clock.now(),
+            last_used: AtomicU64::new(tick),
+            promoted_at: tick,
+        }
+    }
+
+    /// 淘汰一个最久未访问的条目（second-chance 算法）。
+    fn evict_one(&mut self) {
+        loop {
+            let key = match self.cache.peek_lru() {
+                Some((key, entry)) if entry.last_used.load(Ordering::Relaxed) > entry.promoted_at => {
+                    key.clone()
+                }
+                Some(_) => {
+                    if let Some((key, entry)) = self.cache.pop_lru() {
+                        self.used_bytes -= entry.size();
+                        self.notify_evicted(&key, &entry);
+                    }
+                    return;
+                }
+                None => return,
+            };
+            // 条目在上次移到头部后被访问过：移回头部，并以当前序号作为新的起点
+            let tick = self.next_tick();
+            if let Some(entry) = self.cache.peek_mut(&key) {
+                entry.promoted_at = tick;
+                entry.last_used.store(tick, Ordering::Relaxed);
+            }
+            self.cache.promote(&key);
+        }
+    }
+
+    /// 获取下一个访问序号。
+    fn next_tick(&self) -> u64 {
+        self.tick.fetch_add(1, Ordering::Relaxed) + 1
+    }
     
     /// 静态辅助方法：判断文件大小是否满足进入缓存的阈值要求。
     ///
@@ -91,38 +345,140 @@ impl FileCache {
     ///
     /// 该函数会通过 `current_modified_time` 校验缓存条目是否依然有效。
     /// 如果磁盘上的文件已被修改，即使缓存存在也会返回 `None`。
+    /// 若设置了 TTL 且条目已过期，同样返回 `None`；过期条目会在下次写入同名文件或被淘汰时移除。
+    /// 只记录了内容哈希的条目同样视为未命中。
     ///
     /// # 注意
     ///
-    /// 由于 LRU 算法在查询时会调整内部链表顺序，因此该方法需要 `&mut self`。
+    /// 该方法只需要 `&self`：命中时仅以原子操作记录访问序号，不调整 LRU 链表，
+    /// 因此多个请求可以在共享读锁下并发查询。
     ///
     /// # 返回值
     ///
     /// 返回命中的内容引用 `Option<&Bytes>`。如果未找到或已失效，则返回 `None`。
-    pub fn find(&mut self, filename: &str, current_modified_time: SystemTime) -> Option<&Bytes> {
-        match self.cache.get(filename) {
-            Some(entry) => {
-                if entry.modified_time == current_modified_time {
-                    Some(&entry.content)
-                } else {
-                    None
-                }
+    pub fn find(&self, filename: &str, current_modified_time: SystemTime) -> Option<&Bytes> {
+        match self.cache.peek(filename) {
+            Some(entry) if !self.is_expired(entry) && entry.modified_time == current_modified_time && entry.content.is_some() => {
+                entry.last_used.store(self.next_tick(), Ordering::Relaxed);
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                entry.content.as_ref()
+            }
+            _ => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
             }
-            None => None,
         }
     }
+
+    /// 查看指定条目的内容，不校验有效性。
+    ///
+    /// `peek` 用于观测（调试、管理接口、指标），`find` 用于实际响应请求：
+    /// `peek` 不记录访问序号，因此不会影响淘汰顺序，也不计入命中统计。
+    pub fn peek(&self, key: &str) -> Option<&Bytes> {
+        self.cache.peek(key).and_then(|entry| entry.content.as_ref())
+    }
+
+    /// 与 [`FileCache::peek`] 相同，但同 `find` 一样校验 TTL 与文件修改时间，条目失效时返回 `None`。
+    pub fn peek_valid(&self, key: &str, current_modified_time: SystemTime) -> Option<&Bytes> {
+        self.cache
+            .peek(key)
+            .filter(|entry| !self.is_expired(entry) && entry.modified_time == current_modified_time)
+            .and_then(|entry| entry.content.as_ref())
+    }
+
+    /// 获取累计的缓存命中次数。
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// 获取累计的缓存未命中次数。
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
     
+    /// 判断条目是否已超过 TTL。
+    fn is_expired(&self, entry: &CacheEntry) -> bool {
+        match self.ttl {
+            Some(ttl) => self
+                .clock
+                .now()
+                .duration_since(entry.cached_at)
+                .is_ok_and(|age| age > ttl),
+            None => false,
+        }
+    }
+
+    /// 按 LRU 链表顺序（最近使用的在前）列出所有缓存条目的键。
+    ///
+    /// 仅遍历链表，不会调整 LRU 顺序，也不计入命中统计。
+    pub fn keys(&self) -> Vec<String> {
+        self.cache.iter().map(|(key, _)| key.clone()).collect()
+    }
+
+    /// 移除所有键以 `prefix` 开头的条目，返回移除的数量。
+    ///
+    /// 目录列表以 `path`、`path:json` 以及带排序/分页后缀的多个键缓存，目录内容变化时（如 PUT 上传）应以目录路径为前缀
+    /// 一次性清除所有变体。注意前缀按字符串匹配，`/a/b` 同样会清除 `/a/bc` 的条目。
+    /// 主动失效不属于淘汰，不会触发 [`FileCache::on_evict`] 回调。
+    pub fn invalidate_prefix(&mut self, prefix: &str) -> usize {
+        let stale: Vec<String> = self
+            .cache
+            .iter()
+            .filter(|(key, _)| key.starts_with(prefix))
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in &stale {
+            if let Some(entry) = self.cache.pop(key) {
+                self.used_bytes -= entry.size();
+            }
+        }
+        if !stale.is_empty() {
+            debug!("按前缀 {} 清除了 {} 个缓存条目", prefix, stale.len());
+        }
+        stale.len()
+    }
+
+    /// 查看指定条目的大小（字节数）与缓存时记录的文件修改时间。
+    ///
+    /// 与 [`FileCache::find`] 不同，该方法不校验有效性、不记录访问，也不计入命中统计，
+    /// 适用于调试与管理接口。
+    pub fn entry_info(&self, key: &str) -> Option<(usize, SystemTime)> {
+        self.cache
+            .peek(key)
+            .map(|entry| (entry.size(), entry.modified_time))
+    }
+
     /// 获取当前缓存中已存储的条目数量。
-    #[cfg(test)]
     pub fn len(&self) -> usize {
         self.cache.len()
     }
 
+    /// 判断缓存是否为空。
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+
     /// 获取缓存的最大容量。
-    #[cfg(test)]
     pub fn capacity(&self) -> usize {
         self.cache.cap().get()
     }
+
+    /// 获取当前所有条目内容的总字节数。
+    pub fn used_bytes(&self) -> usize {
+        self.used_bytes
+    }
+
+    /// 获取缓存的运行统计：条目数量、内存占用及其上限、命中与未命中次数。
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            entries: self.cache.len(),
+            capacity: self.capacity(),
+            bytes: self.used_bytes,
+            max_bytes: self.max_bytes,
+            hits: self.hits(),
+            misses: self.misses(),
+        }
+    }
 }
 
 /// 自动化单元测试模块。
@@ -211,13 +567,38 @@ mod tests {
 
     #[test]
     fn test_cache_not_found() {
-        let mut cache = FileCache::from_capacity(3);
+        let cache = FileCache::from_capacity(3);
         let time = SystemTime::now();
 
         let found = cache.find("nonexistent.txt", time);
         assert!(found.is_none());
     }
 
+    /// 使用假时钟推进时间，验证 TTL 过期逻辑（无需 sleep）
+    #[test]
+    fn test_cache_ttl_expiry_with_fake_clock() {
+        use crate::clock::FakeClock;
+
+        let clock = Arc::new(FakeClock::new(SystemTime::UNIX_EPOCH));
+        let mut cache = FileCache::from_capacity(3)
+            .with_ttl(Duration::from_secs(60))
+            .with_clock(clock.clone());
+        let mtime = SystemTime::UNIX_EPOCH;
+
+        cache.push("file1.txt", Bytes::from("content"), mtime);
+
+        clock.advance(Duration::from_secs(60));
+        assert!(cache.find("file1.txt", mtime).is_some());
+
+        clock.advance(Duration::from_secs(1));
+        assert!(cache.find("file1.txt", mtime).is_none());
+
+        // 过期条目在重新写入后恢复可用
+        cache.push("file1.txt", Bytes::from("content"), mtime);
+        assert!(cache.find("file1.txt", mtime).is_some());
+        assert_eq!(cache.len(), 1);
+    }
+
     #[test]
     fn test_cache_multiple_files() {
         let mut cache = FileCache::from_capacity(5);
@@ -237,4 +618,225 @@ mod tests {
             assert!(found.is_some());
         }
     }
-}
\ No newline at end of file
+    #[test]
+    fn test_cache_hit_miss_counters() {
+        let mut cache = FileCache::from_capacity(5);
+        let now = SystemTime::now();
+        cache.push("a.txt", Bytes::from_static(b"a"), now);
+
+        assert!(cache.find("a.txt", now).is_some());
+        assert!(cache.find("a.txt", now + Duration::from_secs(1)).is_none());
+        assert!(cache.find("missing.txt", now).is_none());
+
+        assert_eq!(cache.hits(), 1);
+        assert_eq!(cache.misses(), 2);
+    }
+    /// find 只需要共享引用：多个线程同时持有读锁查询，命中统计与访问记录都不会丢失
+    #[test]
+    fn test_cache_concurrent_reads() {
+        use std::sync::RwLock;
+
+        let mut cache = FileCache::from_capacity(2);
+        let time = SystemTime::now();
+        cache.push("file1.txt", Bytes::from("content1"), time);
+        cache.push("file2.txt", Bytes::from("content2"), time);
+        let cache = RwLock::new(cache);
+
+        let guards: Vec<_> = (0..4).map(|_| cache.read().unwrap()).collect();
+        std::thread::scope(|s| {
+            for guard in &guards {
+                s.spawn(move || {
+                    for _ in 0..100 {
+                        assert!(guard.find("file1.txt", time).is_some());
+                    }
+                });
+            }
+        });
+        drop(guards);
+
+        let mut cache = cache.into_inner().unwrap();
+        assert_eq!(cache.hits(), 400);
+        // 读取过的 file1 被保留，未被读取的 file2 被淘汰
+        cache.push("file3.txt", Bytes::from("content3"), time);
+        assert!(cache.find("file1.txt", time).is_some());
+        assert!(cache.find("file2.txt", time).is_none());
+    }
+    #[test]
+    fn test_cache_resize_and_set_ttl() {
+        let mut cache = FileCache::from_capacity(3);
+        let time = SystemTime::now();
+        for name in ["a", "b", "c"] {
+            cache.push(name, Bytes::from(name), time);
+        }
+
+        // 缩小容量时淘汰最久未访问的条目
+        cache.resize(1);
+        assert_eq!(cache.capacity(), 1);
+        assert_eq!(cache.len(), 1);
+        assert!(cache.find("c", time).is_some());
+
+        cache.resize(10);
+        assert_eq!(cache.capacity(), 10);
+
+        cache.set_ttl(Some(Duration::ZERO));
+        std::thread::sleep(Duration::from_millis(2));
+        assert!(cache.find("c", time).is_none());
+        cache.set_ttl(None);
+        assert!(cache.find("c", time).is_some());
+    }
+    #[test]
+    fn test_keys_and_entry_info_do_not_disturb_lru() {
+        let mut cache = FileCache::from_capacity(2);
+        let t1 = SystemTime::UNIX_EPOCH + Duration::from_secs(1);
+        let t2 = SystemTime::UNIX_EPOCH + Duration::from_secs(2);
+        cache.push("a", Bytes::from_static(b"aaa"), t1);
+        cache.push("b", Bytes::from_static(b"bb"), t2);
+
+        assert_eq!(cache.keys(), vec!["b".to_string(), "a".to_string()]);
+        assert_eq!(cache.entry_info("a"), Some((3, t1)));
+        assert_eq!(cache.entry_info("b"), Some((2, t2)));
+        assert_eq!(cache.entry_info("c"), None);
+        assert_eq!((cache.hits(), cache.misses()), (0, 0));
+
+        // 查看过 "a" 之后它仍然是最久未使用的条目，写入新条目时被淘汰
+        cache.push("c", Bytes::from_static(b"c"), t1);
+        assert_eq!(cache.keys(), vec!["c".to_string(), "b".to_string()]);
+    }
+    #[test]
+    fn test_peek_does_not_change_eviction_order() {
+        let mut cache = FileCache::from_capacity(2);
+        let t1 = SystemTime::UNIX_EPOCH + Duration::from_secs(1);
+        let t2 = SystemTime::UNIX_EPOCH + Duration::from_secs(2);
+        cache.push("a", Bytes::from_static(b"a"), t1);
+        cache.push("b", Bytes::from_static(b"b"), t1);
+
+        assert_eq!(cache.peek("a"), Some(&Bytes::from_static(b"a")));
+        assert_eq!(cache.peek_valid("a", t1), Some(&Bytes::from_static(b"a")));
+        assert_eq!(cache.peek_valid("a", t2), None);
+        assert_eq!(cache.peek("missing"), None);
+        assert_eq!((cache.hits(), cache.misses()), (0, 0));
+
+        // 被 peek 过的 "a" 仍然最先被淘汰
+        cache.push("c", Bytes::from_static(b"c"), t1);
+        assert!(cache.peek("a").is_none());
+        assert!(cache.peek("b").is_some());
+
+        // 对照：被 find 命中的 "b" 会获得第二次机会，淘汰的是 "c"
+        assert!(cache.find("b", t1).is_some());
+        cache.push("d", Bytes::from_static(b"d"), t1);
+        assert!(cache.peek("b").is_some());
+        assert!(cache.peek("c").is_none());
+    }
+    #[test]
+    fn test_on_evict_callback() {
+        use std::sync::Mutex;
+
+        let evicted = Arc::new(Mutex::new(Vec::new()));
+        let mut cache = FileCache::from_capacity(2);
+        let sink = Arc::clone(&evicted);
+        cache.on_evict(Box::new(move |key, size| sink.lock().unwrap().push((key.to_string(), size))));
+
+        let now = SystemTime::now();
+        cache.push("a", Bytes::from_static(b"aaa"), now);
+        cache.push("b", Bytes::from_static(b"bb"), now);
+        cache.push("b", Bytes::from_static(b"bbbb"), now); // 覆盖同名条目不算淘汰
+        assert!(evicted.lock().unwrap().is_empty());
+
+        cache.push("c", Bytes::from_static(b"c"), now);
+        assert_eq!(*evicted.lock().unwrap(), vec![("a".to_string(), 3)]);
+
+        cache.resize(1);
+        assert_eq!(evicted.lock().unwrap()[1], ("b".to_string(), 4));
+    }
+    #[test]
+    fn test_invalidate_prefix_removes_all_listing_variants() {
+        let mut cache = FileCache::from_capacity(10);
+        let now = SystemTime::now();
+        for key in ["/www/dir", "/www/dir:json", "/www/dir?page=2&per_page=10", "/www/other", "/www/other:json"] {
+            cache.push(key, Bytes::from_static(b"listing"), now);
+        }
+
+        assert_eq!(cache.invalidate_prefix("/www/dir"), 3);
+        assert!(cache.peek("/www/dir").is_none());
+        assert!(cache.peek("/www/dir:json").is_none());
+        assert!(cache.peek("/www/dir?page=2&per_page=10").is_none());
+        assert_eq!(cache.len(), 2);
+        assert!(cache.peek("/www/other:json").is_some());
+
+        assert_eq!(cache.invalidate_prefix("/nothing"), 0);
+    }
+    #[test]
+    fn test_max_bytes_evicts_until_under_budget() {
+        let time = SystemTime::now();
+        let mut cache = FileCache::from_capacity(100).with_max_bytes(10);
+        cache.push("a", Bytes::from_static(b"aaaa"), time);
+        cache.push("b", Bytes::from_static(b"bbbb"), time);
+        assert_eq!(cache.used_bytes(), 8);
+
+        // 放入 c 需要淘汰最久未访问的 a；b 被访问过，得到第二次机会
+        cache.find("b", time);
+        cache.push("c", Bytes::from_static(b"cccc"), time);
+        assert_eq!(cache.keys(), ["c", "b"]);
+        assert_eq!(cache.used_bytes(), 8);
+
+        // 覆盖同名条目按新内容计算占用
+        cache.push("b", Bytes::from_static(b"bb"), time);
+        assert_eq!(cache.used_bytes(), 6);
+
+        // 超过上限的内容不缓存，同名旧条目也被移除
+        cache.push("c", Bytes::from(vec![0u8; 11]), time);
+        assert!(cache.peek("c").is_none());
+        assert_eq!(cache.used_bytes(), 2);
+
+        cache.push("d", Bytes::from_static(b"dddddddd"), time);
+        assert_eq!(
+            cache.stats(),
+            CacheStats { entries: 2, capacity: 100, bytes: 10, max_bytes: Some(10), hits: 1, misses: 0 }
+        );
+
+        // 缩小上限与按前缀失效同样更新占用
+        cache.set_max_bytes(Some(9));
+        assert_eq!(cache.keys(), ["d"]);
+        cache.invalidate_prefix("d");
+        assert_eq!(cache.used_bytes(), 0);
+        assert!(cache.is_empty());
+    }
+
+    /// 内容哈希记在条目上：只含哈希的条目不被 find 命中，写入内容后哈希保留，文件修改后失效
+    #[test]
+    fn test_content_hash() {
+        let mut cache = FileCache::from_capacity(2);
+        let time = SystemTime::now();
+        cache.set_content_hash("big.bin", 42, time);
+        assert_eq!(cache.content_hash("big.bin", time), Some(42));
+        assert!(cache.find("big.bin", time).is_none());
+        assert_eq!((cache.len(), cache.used_bytes()), (1, 0));
+
+        cache.push("big.bin", Bytes::from("data"), time);
+        assert_eq!(cache.content_hash("big.bin", time), Some(42));
+        assert_eq!(cache.find("big.bin", time), Some(&Bytes::from("data")));
+
+        let later = time + Duration::from_secs(1);
+        assert_eq!(cache.content_hash("big.bin", later), None);
+        cache.push("big.bin", Bytes::from("new"), later);
+        assert_eq!(cache.content_hash("big.bin", later), None);
+        cache.set_content_hash("big.bin", 7, later);
+        assert_eq!(cache.content_hash("big.bin", later), Some(7));
+        assert_eq!(cache.find("big.bin", later), Some(&Bytes::from("new")));
+        assert_eq!(cache.used_bytes(), 3);
+    }
+    #[test]
+    fn test_item_count() {
+        let mut cache = FileCache::from_capacity(2);
+        let time = SystemTime::now();
+        cache.push_with_item_count("/www/dir?page=2&per_page=10", Bytes::from("page"), 25, time);
+        assert_eq!(cache.find("/www/dir?page=2&per_page=10", time), Some(&Bytes::from("page")));
+        assert_eq!(cache.item_count("/www/dir?page=2&per_page=10", time), Some(25));
+        assert_eq!(cache.item_count("/www/dir?page=2&per_page=10", time + Duration::from_secs(1)), None);
+        assert_eq!(cache.used_bytes(), 4);
+
+        // 普通写入不记录条目数，覆盖时旧的条目数随之清除
+        cache.push("/www/dir?page=2&per_page=10", Bytes::from("page"), time);
+        assert_eq!(cache.item_count("/www/dir?page=2&per_page=10", time), None);
+    }
+}
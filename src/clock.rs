@@ -0,0 +1,75 @@
+// Copyright (c) 2026 shaneyale (shaneyale86@gmail.com)
+// All rights reserved.
+
+//! # 时钟抽象模块
+//!
+//! 缓存过期（TTL）与响应 `Date` 头等逻辑都依赖“当前时间”。
+//! 该模块将时间来源抽象为 [`Clock`] trait：
+//! - 生产环境使用 [`SystemClock`]，直接读取系统时间。
+//! - 测试中使用 [`FakeClock`]，可以手动拨动时间，无需 `sleep` 即可验证过期逻辑。
+
+use std::{
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+
+/// 当前时间的来源。
+pub trait Clock: Send + Sync {
+    /// 返回当前时间。
+    fn now(&self) -> SystemTime;
+}
+
+/// 读取系统时间的默认时钟。
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// 可手动拨动的假时钟，主要用于测试。
+///
+/// # Examples
+///
+/// ```
+/// use std::time::{Duration, SystemTime};
+/// use webserver::clock::{Clock, FakeClock};
+///
+/// let clock = FakeClock::new(SystemTime::UNIX_EPOCH);
+/// clock.advance(Duration::from_secs(60));
+/// assert_eq!(clock.now(), SystemTime::UNIX_EPOCH + Duration::from_secs(60));
+/// ```
+#[derive(Debug)]
+pub struct FakeClock {
+    /// 当前指向的时间点。
+    now: Mutex<SystemTime>,
+}
+
+impl FakeClock {
+    /// 构造一个停在 `start` 的假时钟。
+    pub fn new(start: SystemTime) -> Self {
+        Self {
+            now: Mutex::new(start),
+        }
+    }
+
+    /// 将时钟向前拨动 `duration`。
+    pub fn advance(&self, duration: Duration) {
+        let mut now = self.now.lock().unwrap_or_else(|e| e.into_inner());
+        *now += duration;
+    }
+
+    /// 将时钟直接设置到 `time`。
+    pub fn set(&self, time: SystemTime) {
+        let mut now = self.now.lock().unwrap_or_else(|e| e.into_inner());
+        *now = time;
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> SystemTime {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
@@ -10,31 +10,81 @@
 //! - 提供硬编码的默认值作为保底逻辑。
 //! - 支持根据系统硬件自动调整并发线程数（使用 `num_cpus`）。
 //! - 包含针对流式传输（Streaming）和范围请求（Range Requests）的调优参数。
+//! - 各类请求尺寸与超时上限统一收纳在 `[limits]` 表中（见 [`Limits`]）。
 
+use crate::fastcgi::FastCgiAddress;
+use crate::proxy::{self, Upstream};
+use crate::param::{
+    HttpEncoding, HttpRequestMethod, ALLOWED_METHODS, DEFAULT_ENCODING_PRIORITY, DEFAULT_PHP_PATH, SERVER_NAME,
+};
 use num_cpus;
 use serde_derive::Deserialize;
 use serde_derive::Serialize;
 
 use core::str;
-use log::{error, warn};
+use std::collections::BTreeMap;
+use std::fmt;
+use log::{error, info, warn};
 use std::fs::File;
-use std::io::prelude::*;
+use std::io::{self, prelude::*};
+use std::net::{AddrParseError, IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Component, Path};
+
+/// 环境变量覆盖配置项时使用的前缀，例如 `WEBSERVER_PORT` 对应配置项 `port`。
+pub const ENV_PREFIX: &str = "WEBSERVER_";
 
 /// 服务器运行时的全局配置对象。
 ///
 /// 包含网络设置、资源路径、线程模型以及缓存策略等核心参数。
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Config {
-    /// 静态资源文件的根目录路径。
-    www_root: String,
+    /// 静态资源文件的根目录路径。可以写成字符串数组（也可以使用键名 `www_roots`），
+    /// 路由时按顺序查找，返回第一个存在的匹配。
+    #[serde(alias = "www_roots")]
+    www_root: WwwRoot,
+    /// 启动时资源根目录（包括虚拟主机的）不存在是否自动创建。关闭时缺失的根目录会导致启动失败。
+    #[serde(default)]
+    create_www_root: bool,
+    /// 是否允许通过符号链接访问资源根目录之外的文件。关闭时，解析后位于根目录之外的路径返回 404。
+    #[serde(default)]
+    follow_symlinks: bool,
     /// 服务器监听的 TCP 端口号。
     port: u16,
     /// 工作线程池的数量。若设置为 0，系统将尝试匹配 CPU 物理核心数。
     worker_threads: usize,
+    /// 阻塞线程池（`spawn_blocking`，用于压缩等 CPU 密集任务）的线程数上限，防止其无限膨胀。
+    #[serde(default = "default_max_blocking_threads")]
+    max_blocking_threads: usize,
     /// 文件缓存条目的最大容量。
     cache_size: usize,
+    /// 文件缓存中所有条目内容的总字节数上限，超出时按 LRU 淘汰条目。
+    #[serde(default = "default_max_cache_bytes")]
+    max_cache_bytes: usize,
     /// 运行环境标识。通常用于区分本地开发环境与线上环境。
     local: bool,
+    /// 监听地址，支持 IPv4 与 IPv6，可以只写 IP（如 `"::1"`，端口取 `port`），
+    /// 也可以带端口（如 `"[::1]:8080"`）。未设置时根据 `local` 选择 127.0.0.1 或 0.0.0.0。
+    #[serde(default)]
+    bind_address: Option<String>,
+    /// Unix 域套接字路径。设置后改为监听该套接字而不是 TCP 端口（仅 Unix 平台）。
+    #[serde(default)]
+    unix_socket: Option<String>,
+    /// 是否启用 HTTPS（仅作用于 TCP 监听）。未设置 `tls_port` 时 `port` 只接受 TLS 连接；
+    /// 设置后 `port` 仍提供明文 HTTP，`tls_port` 另外提供 HTTPS。
+    #[serde(default)]
+    tls_enabled: bool,
+    /// TLS 证书链文件（PEM），启用 HTTPS 时必须设置。
+    #[serde(default)]
+    cert_path: Option<String>,
+    /// TLS 私钥文件（PEM，支持 PKCS#8、PKCS#1 与 SEC1 格式），启用 HTTPS 时必须设置。
+    #[serde(default)]
+    key_path: Option<String>,
+    /// 额外提供 HTTPS 的端口，见 `tls_enabled`。监听地址与 `port` 相同。
+    #[serde(default)]
+    tls_port: Option<u16>,
+    /// HTTPS 连接是否通过 ALPN 提供 HTTP/2（`h2`）。关闭时只协商 HTTP/1.1；明文连接始终使用 HTTP/1.1。
+    #[serde(default = "default_http2")]
+    http2: bool,
     /// 启用流式传输的文件大小阈值（字节）。超过此大小的文件将采用分块传输。
     #[serde(default = "default_streaming_threshold")]
     streaming_threshold: u64,
@@ -44,9 +94,694 @@ pub struct Config {
     /// 是否支持 HTTP Range 请求（用于断点续传或视频拖拽）。
     #[serde(default = "default_enable_range_requests")]
     enable_range_requests: bool,
+    /// 请求目录时依次尝试的索引文件名，命中第一个存在的文件即返回该文件，都不存在时按 `autoindex` 生成目录列表或返回 403。
+    /// 只能是文件名，不能包含路径分隔符。
+    #[serde(default = "default_index_files")]
+    index_files: Vec<String>,
+    /// 目录中没有索引文件时是否生成目录列表。关闭后返回 403 Forbidden。
+    #[serde(default = "default_autoindex")]
+    autoindex: bool,
+    /// 目录列表中隐藏的文件名 glob 模式（支持 `*` 与 `?`），默认隐藏以 `.` 开头的文件。
+    /// 匹配的文件与目录也无法被直接访问，请求时返回 404；顶层的 `/.well-known/` 目录例外。
+    #[serde(default = "default_listing_ignore")]
+    listing_ignore: Vec<String>,
+    /// JSON 目录列表是否返回 `{ "entries": [...], "summary": {...} }` 对象（附带文件数、文件夹数与总大小），
+    /// 关闭（默认）时保持原有的条目数组格式。开启会改变 JSON 接口的格式，属于不兼容变更。
+    #[serde(default)]
+    listing_json_summary: bool,
+    /// 目录列表默认每页显示的条目数，可被请求中的 `?per_page=` 覆盖。为 0（默认）时只在请求带有 `?per_page=` 时分页。
+    #[serde(default = "default_listing_per_page")]
+    listing_per_page: usize,
+    /// Prometheus 指标端点的路径。设置为空字符串可关闭该端点。
+    #[serde(default = "default_metrics_path")]
+    metrics_path: String,
+    /// 健康检查端点的路径，供负载均衡器探活，不访问文件系统。设置为空字符串可关闭该端点。
+    #[serde(default = "default_health_path")]
+    health_path: String,
+    /// 是否宽松处理内容协商。关闭（默认）时资源类型不在客户端 `Accept` 范围内会返回 406，
+    /// 开启后忽略 `Accept` 照常返回内容。
+    #[serde(default)]
+    lenient_accept: bool,
+    /// `/favicon.ico` 对应的图标文件路径。未设置时使用 `www_root` 下的 `favicon.ico`。
+    #[serde(default)]
+    favicon_path: Option<String>,
+    /// `/robots.txt` 对应的文件路径。未设置时使用 `www_root` 下的 `robots.txt`。
+    #[serde(default)]
+    robots_path: Option<String>,
+    /// 文件不存在时，是否为 `/favicon.ico`（空的 204）与 `/robots.txt`（允许所有爬虫）返回内置默认值，
+    /// 避免浏览器与爬虫产生大量 404 日志。
+    #[serde(default = "default_builtin_favicon_robots")]
+    builtin_favicon_robots: bool,
+    /// 未声明字符集的文本类型（`text/*`）默认追加的字符集，如 `text/plain;charset=utf-8`。设置为空字符串则不追加。
+    #[serde(default = "default_charset")]
+    default_charset: String,
+    /// 是否通过内容嗅探为没有扩展名的文件（如 `LICENSE`）确定 MIME 类型。关闭后这类文件返回 404。
+    #[serde(default = "default_sniff_extensionless")]
+    sniff_extensionless: bool,
+    /// 是否使用强 ETag：按 `chunk_size` 分块读取文件内容计算哈希，而不是仅依据大小与修改时间。
+    /// 计算结果按路径与修改时间缓存，每个版本的文件只需计算一次。
+    #[serde(default)]
+    strong_etag: bool,
+    /// 强制下载的文件扩展名（不含 `.`，不区分大小写），匹配的文件附带 `Content-Disposition: attachment`。
+    /// 任意文件都可以通过 `?download=1` 强制下载。
+    #[serde(default)]
+    download_extensions: Vec<String>,
+    /// 是否信任反向代理传来的 `X-Forwarded-For`，以其中记录的地址作为客户端 IP。
+    /// 仅在服务器只能经由可信代理访问时开启，否则客户端可以伪造自己的 IP。
+    #[serde(default)]
+    trust_proxy: bool,
+    /// gzip 与 deflate 的压缩级别（0–9），越大压缩率越高、越耗 CPU。超出范围时截断到 9。
+    #[serde(default = "default_gzip_level")]
+    gzip_level: u32,
+    /// brotli 的压缩质量（0–11），越大压缩率越高、越耗 CPU。超出范围时截断到 11。
+    /// 响应是即时压缩的，不建议在高并发时使用 10 以上的质量。
+    #[serde(default = "default_brotli_quality")]
+    brotli_quality: u32,
+    /// 客户端对多种压缩编码给出相同 q 值时的选择顺序（`br`、`gzip`、`deflate`），不区分大小写。
+    /// 未列出的编码不会被使用，设置为空数组即完全关闭压缩。
+    #[serde(default = "default_encoding_priority")]
+    encoding_priority: Vec<String>,
+    /// `Server` 响应头的内容。设置为空字符串则不发送该响应头，避免暴露服务器软件信息。
+    #[serde(default = "default_server_name")]
+    server_name: Option<String>,
+    /// 启用的 HTTP 方法（如 `["GET", "HEAD"]`），不区分大小写。
+    /// 能够解析但未启用的方法返回 405，路由、反向代理与内置端点同样如此；POST 只对 PHP 脚本开放（表单提交），启用后静态文件与目录仍返回 405。
+    /// PUT 还需要开启 `uploads`，且只对文件开放。
+    #[serde(default = "default_allowed_methods")]
+    allowed_methods: Vec<String>,
+    /// 是否允许以 PUT 上传文件（创建或替换第一个根目录下的文件），默认关闭。
+    /// 开启后还需要在 `allowed_methods` 中启用 PUT；上传大小受 `[limits] max_body_bytes` 限制。
+    /// 服务器会执行的 `.php` 脚本始终不能被上传。
+    #[serde(default)]
+    uploads: bool,
+    /// 单页应用（SPA）的路径前缀，该前缀下找不到文件时返回 `spa_fallback`。设置为空字符串可关闭回退。
+    #[serde(default = "default_spa_prefix")]
+    spa_prefix: Option<String>,
+    /// 单页应用的入口文件路径（相对于工作目录），通常为前端构建产物中的 `index.html`。
+    #[serde(default = "default_spa_fallback")]
+    spa_fallback: Option<String>,
+    /// 是否将 `.md` / `.markdown` 文件渲染为 HTML 页面返回。关闭后按原始 Markdown 文本返回。
+    #[serde(default = "default_render_markdown")]
+    render_markdown: bool,
+    /// 是否在响应中附加 `X-Response-Time` 头，报告服务端处理耗时，便于客户端调试。
+    #[serde(default)]
+    emit_response_time: bool,
+    /// 缓存条目的存活时间（秒）。未设置时条目仅因文件修改或 LRU 淘汰而失效。
+    #[serde(default)]
+    cache_ttl_secs: Option<u64>,
+    /// PHP 脚本允许运行的最长时间（秒），超时后终止解释器进程并返回 504。
+    #[serde(default = "default_script_timeout_secs")]
+    script_timeout_secs: u64,
+    /// PHP 解释器的路径或命令名（如 `php8.2`、`/usr/local/bin/php`）。不含路径分隔符时在 PATH 中查找。
+    /// php-cli 不会根据 CGI 环境变量填充 `$_GET`、`$_POST`，需要这些超全局变量时应配置为 `php-cgi`。
+    #[serde(default = "default_php_path")]
+    php_path: Option<String>,
+    /// FastCGI 应用（如 php-fpm）的地址：`127.0.0.1:9000` 或 `unix:/run/php/php-fpm.sock`。
+    /// 设置后 PHP 请求通过 FastCGI 处理，不再启动 `php_path` 指定的解释器；超时同样取 `script_timeout_secs`。
+    #[serde(default)]
+    fastcgi_address: Option<String>,
+    /// 请求相关的各类上限，对应 TOML 中的 `[limits]` 表。
+    #[serde(default)]
+    limits: Limits,
+    /// 自定义错误页面，对应 TOML 中的 `[error_pages]` 表，键为状态码，值为相对于资源根目录的文件路径：
+    ///
+    /// ```toml
+    /// [error_pages]
+    /// 404 = "errors/404.html"
+    /// 500 = "errors/500.html"
+    /// ```
+    ///
+    /// 未配置或文件不存在的状态码使用内置的错误页面。
+    #[serde(default)]
+    error_pages: BTreeMap<String, String>,
+    /// 反向代理规则，对应 TOML 中的 `[proxy]` 表，键为路径前缀，值为上游 HTTP 服务器地址：
+    ///
+    /// ```toml
+    /// [proxy]
+    /// "/api/" = "http://127.0.0.1:9000"
+    /// ```
+    ///
+    /// 路径以某个前缀开头的请求被转发给对应的上游，前缀替换为上游地址中的路径；多个前缀匹配时取最长的一个。
+    #[serde(default)]
+    proxy: BTreeMap<String, String>,
+    /// 反向代理连接上游与等待上游响应的超时时间（秒），超时后返回 504。
+    #[serde(default = "default_proxy_timeout_secs")]
+    proxy_timeout_secs: u64,
+    /// 访问控制，对应 TOML 中的 `[auth]` 表，见 [`AuthConfig`]。
+    #[serde(default)]
+    auth: AuthConfig,
+    /// 按客户端 IP 的请求限流，对应 TOML 中的 `[rate_limit]` 表，见 [`RateLimit`]。
+    #[serde(default)]
+    rate_limit: RateLimit,
+    /// 跨域资源共享，对应 TOML 中的 `[cors]` 表，见 [`CorsConfig`]。
+    #[serde(default)]
+    cors: CorsConfig,
+    /// 按 `Host` 请求头区分的虚拟主机，对应 TOML 中的 `[[vhost]]` 表数组。
+    /// 没有匹配的虚拟主机时使用顶层的 `www_root` 与 `index_files`。也接受 `[[vhosts]]` 写法。
+    #[serde(default, rename = "vhost", alias = "vhosts")]
+    vhosts: Vec<VirtualHost>,
+    /// 兼容旧版的扁平写法：`max_header_bytes = ...`，载入时合并进 `limits`。
+    #[serde(default, skip_serializing)]
+    max_header_bytes: Option<usize>,
+    /// 兼容旧版的扁平写法：`max_body_bytes = ...`，载入时合并进 `limits`。
+    #[serde(default, skip_serializing)]
+    max_body_bytes: Option<usize>,
+    /// 兼容旧版的扁平写法：`max_uri_length = ...`，载入时合并进 `limits`。
+    #[serde(default, skip_serializing)]
+    max_uri_length: Option<usize>,
+    /// 兼容旧版的扁平写法：`max_headers = ...`，载入时合并进 `limits`。
+    #[serde(default, skip_serializing)]
+    max_headers: Option<usize>,
+    /// 兼容旧版的扁平写法：`read_timeout_secs = ...`，载入时合并进 `limits`。
+    #[serde(default, skip_serializing)]
+    read_timeout_secs: Option<u64>,
+}
+
+/// 静态资源根目录配置，兼容单个字符串与字符串数组两种写法。
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum WwwRoot {
+    /// 单个根目录：`www_root = "./static/"`
+    One(String),
+    /// 多个根目录：`www_root = ["./dist/", "./static/"]`
+    Many(Vec<String>),
+}
+
+impl WwwRoot {
+    /// 以切片形式返回所有根目录（按查找顺序排列）。
+    pub fn as_slice(&self) -> &[String] {
+        match self {
+            WwwRoot::One(root) => std::slice::from_ref(root),
+            WwwRoot::Many(roots) => roots,
+        }
+    }
+}
+
+/// 一个虚拟主机，对应配置文件中的一个 `[[vhost]]` 表：
+///
+/// ```toml
+/// [[vhost]]
+/// host = "blog.example.com"
+/// www_root = "./blog/"
+/// index_files = ["index.htm"]
+///
+/// [[vhost]]
+/// host = "*"
+/// www_root = "./default/"
+/// ```
+///
+/// `host = "*"` 为通配的默认虚拟主机，在没有其他虚拟主机匹配时使用。
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct VirtualHost {
+    /// 主机名（不含端口，不区分大小写），或通配符 `*`。
+    host: String,
+    /// 该主机的静态资源根目录，写法与顶层的 `www_root` 相同。
+    #[serde(alias = "www_roots")]
+    www_root: WwwRoot,
+    /// 该主机的索引文件列表，未设置时使用顶层的 `index_files`。
+    #[serde(default)]
+    index_files: Option<Vec<String>>,
+}
+
+impl VirtualHost {
+    /// 获取主机名（或通配符 `*`）。
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// 获取该主机的所有静态资源根目录。
+    pub fn www_roots(&self) -> &[String] {
+        self.www_root.as_slice()
+    }
+
+    /// 获取该主机单独配置的索引文件列表。
+    pub fn index_files(&self) -> Option<&[String]> {
+        self.index_files.as_deref()
+    }
+}
+
+/// 运行期间无法生效、修改后需要重启服务器的配置项（监听地址、运行时线程数与启动时创建的限流器）。
+pub const RESTART_REQUIRED_KEYS: [&str; 12] = [
+    "port",
+    "local",
+    "bind_address",
+    "unix_socket",
+    "tls_enabled",
+    "cert_path",
+    "key_path",
+    "tls_port",
+    "http2",
+    "worker_threads",
+    "max_blocking_threads",
+    "rate_limit",
+];
+
+/// 两份配置之间的一项差异，由 [`Config::diff`] 生成。
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigChange {
+    /// 配置项的键名
+    pub key: String,
+    /// 旧值（TOML 形式）
+    pub old: String,
+    /// 新值（TOML 形式）
+    pub new: String,
+}
+
+impl ConfigChange {
+    /// 该配置项的修改是否需要重启服务器才能生效。
+    pub fn requires_restart(&self) -> bool {
+        RESTART_REQUIRED_KEYS.contains(&self.key.as_str())
+    }
+}
+
+/// 配置在语义上不合法的原因，由 [`Config::validate`] 返回。
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    /// 未配置 Unix 域套接字时端口不能为 0。
+    InvalidPort(u16),
+    /// 没有配置任何资源根目录，或其中某一项为空字符串。
+    EmptyWwwRoot,
+    /// 资源根目录不存在。
+    WwwRootNotFound(String),
+    /// 资源根目录存在但不是目录。
+    WwwRootNotDirectory(String),
+    /// 资源根目录无法读取（通常是权限不足），附带底层错误信息。
+    WwwRootUnreadable(String, String),
+    /// `chunk_size` 为 0，流式传输将无法推进。
+    ZeroChunkSize,
+    /// `streaming_threshold` 为 0，连空文件也会走流式传输。
+    InvalidStreamingThreshold(u64),
+    /// `encoding_priority` 中含有无法识别的编码名。
+    UnknownEncoding(String),
+    /// `allowed_methods` 中含有无法识别的方法名。
+    UnknownMethod(String),
+    /// `index_files`（包括虚拟主机的）中含有空文件名、`.`、`..` 或带路径分隔符的名称。
+    InvalidIndexFile(String),
+    /// `max_blocking_threads` 为 0，运行时无法执行任何阻塞任务。
+    ZeroMaxBlockingThreads,
+    /// `max_cache_bytes` 为 0，任何内容都无法缓存。
+    ZeroMaxCacheBytes,
+    /// 启用了 HTTPS，但缺少证书或私钥路径，附带缺少的配置项名。
+    MissingTlsFile(&'static str),
+    /// `[error_pages]` 中的键不是 4xx/5xx 状态码，或页面路径不是根目录下的相对路径。
+    InvalidErrorPage(String, String),
+    /// `[proxy]` 中的前缀不以 `/` 开头，或上游地址不是合法的 `http://` 地址。
+    InvalidProxy(String, String),
+    /// `fastcgi_address` 既不是 `host:port` 也不是 `unix:/path`。
+    InvalidFastCgiAddress(String),
+    /// `[auth]` 表不合法，附带具体原因。
+    InvalidAuth(String),
+    /// `[rate_limit]` 表不合法：速率不是非负的有限数，或启用限流时 `burst` 为 0。
+    InvalidRateLimit(f64, u32),
+    /// `[cors]` 表不合法，附带具体原因。
+    InvalidCors(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::InvalidPort(port) => write!(f, "端口号 {} 无效，必须在 1 ~ 65535 之间", port),
+            ConfigError::EmptyWwwRoot => write!(f, "www_root 不能为空"),
+            ConfigError::WwwRootNotFound(root) => write!(f, "资源根目录 {} 不存在", root),
+            ConfigError::WwwRootNotDirectory(root) => write!(f, "资源根目录 {} 不是目录", root),
+            ConfigError::WwwRootUnreadable(root, e) => write!(f, "资源根目录 {} 无法读取：{}", root, e),
+            ConfigError::ZeroChunkSize => write!(f, "chunk_size 必须大于 0"),
+            ConfigError::InvalidStreamingThreshold(threshold) => {
+                write!(f, "streaming_threshold {} 无效，必须大于 0", threshold)
+            }
+            ConfigError::UnknownEncoding(encoding) => write!(f, "encoding_priority 中的编码 {} 无法识别", encoding),
+            ConfigError::UnknownMethod(method) => write!(f, "allowed_methods 中的方法 {} 无法识别", method),
+            ConfigError::InvalidIndexFile(name) => {
+                write!(f, "index_files 中的 {:?} 无效，必须是不含路径分隔符的文件名", name)
+            }
+            ConfigError::ZeroMaxBlockingThreads => write!(f, "max_blocking_threads 必须大于 0"),
+            ConfigError::ZeroMaxCacheBytes => write!(f, "max_cache_bytes 必须大于 0"),
+            ConfigError::MissingTlsFile(key) => write!(f, "启用 tls_enabled 时必须设置 {}", key),
+            ConfigError::InvalidErrorPage(code, path) => {
+                write!(f, "错误页面 {} = {:?} 无效，状态码必须为 4xx/5xx，路径必须是根目录下的相对路径", code, path)
+            }
+            ConfigError::InvalidProxy(prefix, upstream) => {
+                write!(f, "代理规则 {:?} = {:?} 无效，前缀必须以 / 开头，上游必须是 http:// 地址", prefix, upstream)
+            }
+            ConfigError::InvalidFastCgiAddress(address) => {
+                write!(f, "fastcgi_address {:?} 无效，必须是 host:port 或 unix:/path", address)
+            }
+            ConfigError::InvalidAuth(reason) => write!(f, "[auth] 配置无效：{}", reason),
+            ConfigError::InvalidRateLimit(rate, burst) => write!(
+                f,
+                "[rate_limit] 配置无效：requests_per_second = {} 必须是非负数，启用限流时 burst = {} 必须大于 0",
+                rate, burst
+            ),
+            ConfigError::InvalidCors(reason) => write!(f, "[cors] 配置无效：{}", reason),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// 请求尺寸与超时相关的上限集合。
+///
+/// 对应配置文件中的 `[limits]` 表，未出现的字段取默认值：
+///
+/// ```toml
+/// [limits]
+/// max_header_bytes = 16384
+/// max_body_bytes = 1048576
+/// max_uri_length = 8192
+/// max_headers = 100
+/// read_timeout_secs = 30
+/// request_timeout_secs = 60
+/// max_script_output_bytes = 16777216
+/// max_connections = 0
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Limits {
+    /// 请求头（含请求行）允许的最大字节数。
+    max_header_bytes: usize,
+    /// 请求体允许的最大字节数。
+    max_body_bytes: usize,
+    /// 请求目标（URI）允许的最大长度。
+    max_uri_length: usize,
+    /// 查询字符串（`?` 之后的部分）允许的最大长度。
+    max_query_length: usize,
+    /// 单个请求允许携带的最大头部数量。
+    max_headers: usize,
+    /// 读取请求头的超时时间（秒）。
+    read_timeout_secs: u64,
+    /// 读取整个请求（请求头与请求体）的超时时间（秒），防止客户端以极慢的速度逐字节发送请求体。
+    request_timeout_secs: u64,
+    /// PHP 脚本标准输出允许的最大字节数，超出时终止脚本并返回 502。
+    max_script_output_bytes: usize,
+    /// 同时处理的连接数上限，达到上限后新连接收到 429 并被关闭。设置为 0 表示不限制。
+    max_connections: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_header_bytes: 16384,   // 16KB
+            max_body_bytes: 1048576,   // 1MB
+            max_uri_length: 8192,      // 8KB
+            max_query_length: 4096,    // 4KB
+            max_headers: 100,
+            read_timeout_secs: 30,
+            request_timeout_secs: 60,
+            max_script_output_bytes: 16777216, // 16MB
+            max_connections: 0,
+        }
+    }
+}
+
+impl Limits {
+    /// 获取请求头的最大字节数。
+    pub fn max_header_bytes(&self) -> usize {
+        self.max_header_bytes
+    }
+
+    /// 获取请求体的最大字节数。
+    pub fn max_body_bytes(&self) -> usize {
+        self.max_body_bytes
+    }
+
+    /// 获取 URI 的最大长度。
+    pub fn max_uri_length(&self) -> usize {
+        self.max_uri_length
+    }
+
+    /// 获取查询字符串的最大长度。
+    pub fn max_query_length(&self) -> usize {
+        self.max_query_length
+    }
+
+    /// 获取请求头部数量上限。
+    pub fn max_headers(&self) -> usize {
+        self.max_headers
+    }
+
+    /// 获取读取请求头的超时时间（秒）。
+    pub fn read_timeout_secs(&self) -> u64 {
+        self.read_timeout_secs
+    }
+
+    /// 获取读取整个请求的超时时间（秒）。
+    pub fn request_timeout_secs(&self) -> u64 {
+        self.request_timeout_secs
+    }
+
+    /// 获取 PHP 脚本输出的最大字节数。
+    pub fn max_script_output_bytes(&self) -> usize {
+        self.max_script_output_bytes
+    }
+
+    /// 获取同时处理的连接数上限，`None` 表示不限制。
+    pub fn max_connections(&self) -> Option<usize> {
+        (self.max_connections > 0).then_some(self.max_connections)
+    }
+}
+
+/// 访问控制配置，对应配置文件中的 `[auth]` 表：
+///
+/// ```toml
+/// [auth]
+/// paths = ["/admin/", "/private/"]
+/// realm = "Restricted"
+/// htpasswd = "config/htpasswd"
+///
+/// [auth.tokens]
+/// ci = "c2VjcmV0LXRva2Vu"
+/// ```
+///
+/// 路径以 `paths` 中任一前缀开头的请求必须携带 Basic 凭据（对照 `htpasswd` 用户文件）
+/// 或 Bearer 令牌（`tokens` 中的任一值，键为记录在访问日志中的令牌名），见 [`crate::auth`]。
+/// `paths` 为空时不保护任何路径。
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct AuthConfig {
+    /// 受保护的路径前缀，必须以 `/` 开头。`/admin/` 与 `/admin` 都保护 `/admin` 及其下的路径，但不包括 `/administrator`。
+    paths: Vec<String>,
+    /// `WWW-Authenticate` 质询中的保护域名称，浏览器会在登录框中显示。
+    realm: String,
+    /// htpasswd 格式的用户文件路径。未设置时不接受 Basic 认证。
+    htpasswd: Option<String>,
+    /// 静态 Bearer 令牌，键为令牌名，值为令牌。为空时不接受 Bearer 认证。
+    tokens: BTreeMap<String, String>,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            paths: Vec::new(),
+            realm: "Restricted".to_string(),
+            htpasswd: None,
+            tokens: BTreeMap::new(),
+        }
+    }
+}
+
+impl AuthConfig {
+    /// 获取受保护的路径前缀。
+    pub fn paths(&self) -> &[String] {
+        &self.paths
+    }
+
+    /// 获取保护域名称。
+    pub fn realm(&self) -> &str {
+        &self.realm
+    }
+
+    /// 获取 htpasswd 用户文件路径。
+    pub fn htpasswd(&self) -> Option<&str> {
+        self.htpasswd.as_deref()
+    }
+
+    /// 获取 Bearer 令牌表（令牌名 → 令牌）。
+    pub fn tokens(&self) -> &BTreeMap<String, String> {
+        &self.tokens
+    }
+
+    /// 检查 `[auth]` 表是否合法，返回不合法的原因。
+    fn check(&self) -> Result<(), String> {
+        if let Some(path) = self.paths.iter().find(|p| !p.starts_with('/')) {
+            return Err(format!("路径前缀 {:?} 必须以 / 开头", path));
+        }
+        if self.realm.contains(['"', '\\']) || self.realm.chars().any(|c| c.is_control()) {
+            return Err(format!("realm {:?} 不能包含引号、反斜杠或控制字符", self.realm));
+        }
+        if let Some((name, _)) = self.tokens.iter().find(|(_, token)| token.is_empty()) {
+            return Err(format!("令牌 {} 不能为空", name));
+        }
+        if let Some(htpasswd) = &self.htpasswd {
+            if let Err(e) = std::fs::metadata(htpasswd) {
+                return Err(format!("用户文件 {} 无法读取：{}", htpasswd, e));
+            }
+        }
+        if !self.paths.is_empty() && self.htpasswd.is_none() && self.tokens.is_empty() {
+            return Err("配置了受保护的路径，但既没有设置 htpasswd 也没有设置 tokens".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// 跨域资源共享（CORS）配置，对应配置文件中的 `[cors]` 表：
+///
+/// ```toml
+/// [cors]
+/// origins = ["https://app.example.com"]
+/// methods = ["GET", "POST"]
+/// headers = ["Content-Type", "Authorization"]
+/// max_age_secs = 600
+/// allow_credentials = false
+/// ```
+///
+/// 请求的 `Origin` 与 `origins` 中任一项匹配（`"*"` 匹配任意来源）时，响应携带 `Access-Control-Allow-Origin`；
+/// 预检请求（带 `Access-Control-Request-Method` 的 `OPTIONS`）直接以 204 应答，见 [`crate::cors`]。
+/// `origins` 为空（默认）时不处理跨域请求。
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct CorsConfig {
+    /// 允许的来源，形如 `https://app.example.com`，不区分大小写；`"*"` 表示任意来源。
+    origins: Vec<String>,
+    /// 预检响应中允许的方法。为空时使用服务器启用的方法（`allowed_methods`）。
+    methods: Vec<String>,
+    /// 预检响应中允许的请求头，`"*"` 表示任意请求头。为空时只允许 CORS 安全列表中的请求头。
+    headers: Vec<String>,
+    /// 浏览器缓存预检结果的时长（秒），0 表示不发送 `Access-Control-Max-Age`。
+    max_age_secs: u64,
+    /// 是否允许携带 Cookie 等凭据。启用时不能使用 `"*"`。
+    allow_credentials: bool,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            origins: Vec::new(),
+            methods: Vec::new(),
+            headers: Vec::new(),
+            max_age_secs: 600,
+            allow_credentials: false,
+        }
+    }
+}
+
+impl CorsConfig {
+    /// 是否启用了跨域处理。
+    pub fn enabled(&self) -> bool {
+        !self.origins.is_empty()
+    }
+
+    /// 获取允许的来源。
+    pub fn origins(&self) -> &[String] {
+        &self.origins
+    }
+
+    /// 获取预检响应中允许的方法，为空表示使用服务器启用的方法。
+    pub fn methods(&self) -> Vec<HttpRequestMethod> {
+        self.methods.iter().filter_map(|m| m.parse().ok()).collect()
+    }
+
+    /// 获取预检响应中允许的请求头。
+    pub fn headers(&self) -> &[String] {
+        &self.headers
+    }
+
+    /// 获取预检结果的缓存时长，`None` 表示不发送 `Access-Control-Max-Age`。
+    pub fn max_age_secs(&self) -> Option<u64> {
+        (self.max_age_secs > 0).then_some(self.max_age_secs)
+    }
+
+    /// 是否允许携带凭据。
+    pub fn allow_credentials(&self) -> bool {
+        self.allow_credentials
+    }
+
+    /// 检查 `[cors]` 表是否合法，返回不合法的原因。
+    fn check(&self) -> Result<(), String> {
+        let is_token_char = |c: char| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c);
+        for origin in self.origins.iter().filter(|o| o.as_str() != "*") {
+            let host = origin.strip_prefix("https://").or_else(|| origin.strip_prefix("http://"));
+            if host.is_none_or(|h| h.is_empty() || h.contains(['/', '?', '#']) || h.chars().any(char::is_whitespace)) {
+                return Err(format!("来源 {:?} 必须形如 https://host[:port]，不能带路径", origin));
+            }
+        }
+        if let Some(method) = self.methods.iter().find(|m| m.parse::<HttpRequestMethod>().is_err()) {
+            return Err(format!("方法 {} 无法识别", method));
+        }
+        if let Some(header) = self.headers.iter().find(|h| h.is_empty() || !h.chars().all(is_token_char)) {
+            return Err(format!("请求头 {:?} 不是合法的头部名称", header));
+        }
+        if self.allow_credentials && self.origins.iter().chain(&self.headers).any(|v| v == "*") {
+            return Err("启用 allow_credentials 时 origins 与 headers 不能使用 \"*\"".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// 按客户端 IP 的请求限流配置，对应配置文件中的 `[rate_limit]` 表：
+///
+/// ```toml
+/// [rate_limit]
+/// requests_per_second = 10.0
+/// burst = 20
+/// ```
+///
+/// 每个客户端 IP 拥有一个容量为 `burst` 的令牌桶，令牌以每秒 `requests_per_second` 个的速度补充，
+/// 每个请求消耗一个令牌，令牌耗尽时返回 429，见 [`crate::ratelimit`]。`requests_per_second` 为 0（默认）时不限流。
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct RateLimit {
+    /// 每个客户端 IP 每秒补充的令牌数，即长期平均的请求速率上限。
+    requests_per_second: f64,
+    /// 令牌桶容量，即允许的瞬时突发请求数。
+    burst: u32,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self {
+            requests_per_second: 0.0,
+            burst: 20,
+        }
+    }
+}
+
+impl RateLimit {
+    /// 获取每秒补充的令牌数，`None` 表示不限流。
+    pub fn requests_per_second(&self) -> Option<f64> {
+        (self.requests_per_second > 0.0).then_some(self.requests_per_second)
+    }
+
+    /// 获取令牌桶容量。
+    pub fn burst(&self) -> u32 {
+        self.burst
+    }
+}
+
+/// 默认 PHP 脚本超时：30 秒
+fn default_max_blocking_threads() -> usize {
+    // 与 Tokio 的默认值一致
+    512
+}
+
+fn default_script_timeout_secs() -> u64 {
+    30
+}
+
+/// 默认反向代理超时：30 秒
+fn default_proxy_timeout_secs() -> u64 {
+    30
+}
+
+/// 默认 PHP 解释器：PATH 中的 `php`
+fn default_php_path() -> Option<String> {
+    Some(DEFAULT_PHP_PATH.to_string())
 }
 
 /// 默认流式传输阈值：10MB
+fn default_max_cache_bytes() -> usize {
+    67108864 // 64MB
+}
+
 fn default_streaming_threshold() -> u64 {
     10485760 // 10MB
 }
@@ -61,20 +796,246 @@ fn default_enable_range_requests() -> bool {
     true
 }
 
+/// 默认在 HTTPS 连接上提供 HTTP/2
+fn default_http2() -> bool {
+    true
+}
+
+/// 默认索引文件：依次尝试 index.html、index.htm、index.php
+fn default_index_files() -> Vec<String> {
+    vec!["index.html".to_string(), "index.htm".to_string(), "index.php".to_string()]
+}
+
+/// 默认开启目录列表
+fn default_autoindex() -> bool {
+    true
+}
+
+/// 默认隐藏点文件（如 .git、.env、.htaccess）
+fn default_listing_ignore() -> Vec<String> {
+    vec![".*".to_string()]
+}
+
+/// 默认不分页：内置文件管理器（fileStore.js）一次取回整个目录，分页由客户端以 `?per_page=` 显式请求
+fn default_listing_per_page() -> usize {
+    0
+}
+
+/// 默认指标端点：/metrics
+fn default_metrics_path() -> String {
+    "/metrics".to_string()
+}
+
+/// 默认为 favicon.ico 与 robots.txt 提供内置默认值
+fn default_builtin_favicon_robots() -> bool {
+    true
+}
+
+/// 默认字符集：utf-8
+fn default_charset() -> String {
+    "utf-8".to_string()
+}
+
+/// 默认嗅探无扩展名文件的类型
+fn default_sniff_extensionless() -> bool {
+    true
+}
+
+/// 默认 gzip 压缩级别：6，与 flate2 的默认值一致
+fn default_gzip_level() -> u32 {
+    6
+}
+
+/// 默认 brotli 压缩质量：5。响应在请求时即时压缩，质量 11 的 CPU 开销是 gzip 的数十倍，
+/// 质量 5 的耗时与 gzip 6 相当，压缩率通常仍优于 gzip
+fn default_brotli_quality() -> u32 {
+    5
+}
+
+/// 默认服务器标识：`shaneyale-webserver`
+fn default_server_name() -> Option<String> {
+    Some(SERVER_NAME.to_string())
+}
+
+/// 默认启用的方法：GET、HEAD 与 OPTIONS
+fn default_encoding_priority() -> Vec<String> {
+    DEFAULT_ENCODING_PRIORITY.iter().map(|encoding| encoding.to_string()).collect()
+}
+
+fn default_allowed_methods() -> Vec<String> {
+    ALLOWED_METHODS.iter().map(|method| method.to_string()).collect()
+}
+
+/// 默认 SPA 前缀：内置文件管理器的 /browser
+fn default_spa_prefix() -> Option<String> {
+    Some("/browser".to_string())
+}
+
+/// 默认 SPA 入口：内置文件管理器的 static/browser/index.html
+fn default_spa_fallback() -> Option<String> {
+    Some("static/browser/index.html".to_string())
+}
+
+/// 默认将 Markdown 渲染为 HTML
+fn default_render_markdown() -> bool {
+    true
+}
+
+/// 默认健康检查端点：/healthz
+fn default_health_path() -> String {
+    "/healthz".to_string()
+}
+
+/// 用以 [`ENV_PREFIX`] 开头的环境变量覆盖解析出的 TOML 表。
+///
+/// 变量名去掉前缀后转为小写即为配置项名，双下划线表示嵌套表（如 `LIMITS__MAX_BODY_BYTES`）。
+/// 变量值先按 TOML 字面量解析（数字、布尔值、数组），不符合配置项类型时再作为字符串尝试；
+/// 两者都无法通过反序列化的值会记录错误日志并被忽略，未知的配置项只记录警告。
+fn apply_env_overrides<I>(table: &mut toml::Table, vars: I)
+where
+    I: IntoIterator<Item = (String, String)>,
+{
+    for (name, raw) in vars {
+        let Some(key) = name.strip_prefix(ENV_PREFIX) else {
+            continue;
+        };
+        let path: Vec<String> = key.to_lowercase().split("__").map(str::to_string).collect();
+        if path.iter().any(|p| p.is_empty()) {
+            warn!("环境变量 {} 的名称无效，已忽略", name);
+            continue;
+        }
+
+        let literal = toml::from_str::<toml::Table>(&format!("v = {}", raw))
+            .ok()
+            .and_then(|mut t| t.remove("v"));
+        let candidates = literal
+            .into_iter()
+            .chain(std::iter::once(toml::Value::String(raw.clone())));
+
+        let mut known = true;
+        let mut accepted = None;
+        for value in candidates {
+            let mut candidate = table.clone();
+            if !set_path(&mut candidate, &path, value) {
+                break;
+            }
+            let Ok(config) = toml::Value::Table(candidate.clone()).try_into::<Config>() else {
+                continue;
+            };
+            known = toml::Value::try_from(&config).is_ok_and(|v| get_path(&v, &path).is_some());
+            if known {
+                accepted = Some(candidate);
+            }
+            break;
+        }
+
+        match accepted {
+            Some(candidate) => {
+                *table = candidate;
+                info!("配置项 {} 被环境变量 {} 覆盖", path.join("."), name);
+            }
+            None if !known => warn!("环境变量 {} 不对应任何配置项，已忽略", name),
+            None => error!(
+                "环境变量 {} 的值 {:?} 无法解析为配置项 {}，已忽略",
+                name,
+                raw,
+                path.join(".")
+            ),
+        }
+    }
+}
+
+/// 在嵌套表中按路径写入值，中间缺失的表会被创建；路径途经非表的值时返回 `false`。
+fn set_path(table: &mut toml::Table, path: &[String], value: toml::Value) -> bool {
+    match path {
+        [last] => {
+            table.insert(last.clone(), value);
+            true
+        }
+        [first, rest @ ..] => match table
+            .entry(first.clone())
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+        {
+            toml::Value::Table(inner) => set_path(inner, rest, value),
+            _ => false,
+        },
+        [] => false,
+    }
+}
+
+/// 按路径读取嵌套表中的值。
+fn get_path<'a>(value: &'a toml::Value, path: &[String]) -> Option<&'a toml::Value> {
+    path.iter().try_fold(value, |v, key| v.get(key.as_str()))
+}
+
 impl Config {
     /// 构造一个具有初始默认值的配置实例。
     ///
     /// 该方法通常作为系统启动时的硬编码保底方案。
     pub fn new() -> Self {
         Self {
-            www_root: ".".to_string(),
+            www_root: WwwRoot::One(".".to_string()),
+            create_www_root: false,
+            follow_symlinks: false,
             port: 7878,
             worker_threads: 0,
+            max_blocking_threads: default_max_blocking_threads(),
             cache_size: 5,
+            max_cache_bytes: default_max_cache_bytes(),
             local: true,
+            bind_address: None,
+            unix_socket: None,
+            tls_enabled: false,
+            cert_path: None,
+            key_path: None,
+            tls_port: None,
+            http2: default_http2(),
             streaming_threshold: default_streaming_threshold(),
             chunk_size: default_chunk_size(),
             enable_range_requests: default_enable_range_requests(),
+            index_files: default_index_files(),
+            autoindex: default_autoindex(),
+            listing_ignore: default_listing_ignore(),
+            listing_json_summary: false,
+            listing_per_page: default_listing_per_page(),
+            metrics_path: default_metrics_path(),
+            health_path: default_health_path(),
+            lenient_accept: false,
+            favicon_path: None,
+            robots_path: None,
+            builtin_favicon_robots: default_builtin_favicon_robots(),
+            default_charset: default_charset(),
+            sniff_extensionless: default_sniff_extensionless(),
+            strong_etag: false,
+            download_extensions: Vec::new(),
+            trust_proxy: false,
+            gzip_level: default_gzip_level(),
+            brotli_quality: default_brotli_quality(),
+            server_name: default_server_name(),
+            encoding_priority: default_encoding_priority(),
+            allowed_methods: default_allowed_methods(),
+            uploads: false,
+            spa_prefix: default_spa_prefix(),
+            spa_fallback: default_spa_fallback(),
+            render_markdown: default_render_markdown(),
+            emit_response_time: false,
+            cache_ttl_secs: None,
+            script_timeout_secs: default_script_timeout_secs(),
+            php_path: default_php_path(),
+            fastcgi_address: None,
+            limits: Limits::default(),
+            error_pages: BTreeMap::new(),
+            proxy: BTreeMap::new(),
+            proxy_timeout_secs: default_proxy_timeout_secs(),
+            auth: AuthConfig::default(),
+            rate_limit: RateLimit::default(),
+            cors: CorsConfig::default(),
+            vhosts: Vec::new(),
+            max_header_bytes: None,
+            max_body_bytes: None,
+            max_uri_length: None,
+            max_headers: None,
+            read_timeout_secs: None,
         }
     }
 
@@ -94,6 +1055,10 @@ impl Config {
     /// 1. **格式降级**：如果 TOML 解析失败，将打印 `error!` 日志并回退至 `Config::new()` 默认配置。
     /// 2. **自动线程扩展**：若配置中的 `worker_threads` 为 0，会自动调用 `num_cpus::get()` 获取当前机器的核心数。
     /// 3. **缓存保护**：强制修正 `cache_size` 至少为 5，以防止缓存逻辑失效。
+    /// 4. **扁平键兼容**：顶层的 `max_header_bytes` 等旧写法会被合并进 `[limits]`。
+    /// 5. **环境变量覆盖**：`WEBSERVER_PORT`、`WEBSERVER_WWW_ROOT` 等环境变量优先于文件中的值，
+    ///    `[limits]` 表中的项用双下划线表示层级，如 `WEBSERVER_LIMITS__MAX_BODY_BYTES`。
+    ///    无法解析的值会记录错误日志并被忽略。
     pub fn from_toml(filename: &str) -> Self {
         let mut file = match File::open(filename) {
             Ok(f) => f,
@@ -104,30 +1069,355 @@ impl Config {
             Ok(s) => s,
             Err(e) => panic!("Error Reading file: {}", e),
         };
+        Self::from_toml_str_with_env(&str_val, std::env::vars())
+    }
 
-        let mut raw_config = match toml::from_str(&str_val) {
-            Ok(t) => t,
+    /// 从 TOML 字符串解析配置，规则与 [`Config::from_toml`] 相同，但不读取环境变量。
+    pub fn from_toml_str(str_val: &str) -> Self {
+        Self::from_toml_str_with_env(str_val, std::iter::empty())
+    }
+
+    /// 从 TOML 字符串解析配置，并用 `vars` 中以 [`ENV_PREFIX`] 开头的变量覆盖文件中的值。
+    fn from_toml_str_with_env<I>(str_val: &str, vars: I) -> Self
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        match Self::parse_with_env(str_val, vars) {
+            Ok(config) => config,
             Err(_) => {
                 error!("无法成功从配置文件构建配置对象，使用默认配置");
-                Config::new()
+                let mut config = Config::new();
+                config.normalize();
+                config
             }
+        }
+    }
+
+    /// 从指定的 TOML 配置文件解析配置，失败时返回错误而不是回退到默认配置。
+    ///
+    /// 用于运行期间重新载入配置：文件缺失或格式错误时应保留当前配置，而不是悄悄换成默认值。
+    pub fn try_from_toml(filename: &str) -> io::Result<Self> {
+        let str_val = std::fs::read_to_string(filename)?;
+        Self::parse_with_env(&str_val, std::env::vars())
+    }
+
+    /// 从 TOML 字符串解析配置，失败时返回错误，见 [`Config::try_from_toml`]。不读取环境变量。
+    pub fn try_from_toml_str(str_val: &str) -> io::Result<Self> {
+        Self::parse_with_env(str_val, std::iter::empty())
+    }
+
+    /// 解析 TOML 文本，叠加环境变量覆盖后再反序列化并规范化。
+    fn parse_with_env<I>(str_val: &str, vars: I) -> io::Result<Self>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let invalid = |e: toml::de::Error| io::Error::new(io::ErrorKind::InvalidData, e);
+        let mut table: toml::Table = toml::from_str(str_val).map_err(invalid)?;
+        apply_env_overrides(&mut table, vars);
+        let mut raw_config: Config = toml::Value::Table(table).try_into().map_err(invalid)?;
+        raw_config.normalize();
+        Ok(raw_config)
+    }
+
+    /// 比较两份配置，按键名顺序列出所有取值不同的顶层配置项。
+    ///
+    /// 取值以 TOML 形式给出，未设置的可选项显示为 `(未设置)`。
+    pub fn diff(&self, new: &Config) -> Vec<ConfigChange> {
+        let to_table = |config: &Config| match toml::Value::try_from(config) {
+            Ok(toml::Value::Table(table)) => table,
+            _ => toml::value::Table::new(),
         };
-        if raw_config.worker_threads == 0 {
-            raw_config.worker_threads = num_cpus::get();
+        let (old_table, new_table) = (to_table(self), to_table(new));
+        let mut keys: Vec<&String> = old_table.keys().chain(new_table.keys()).collect();
+        keys.sort();
+        keys.dedup();
+
+        let show = |v: Option<&toml::Value>| v.map_or_else(|| "(未设置)".to_string(), |v| v.to_string());
+        keys.into_iter()
+            .filter(|key| old_table.get(*key) != new_table.get(*key))
+            .map(|key| ConfigChange {
+                key: key.clone(),
+                old: show(old_table.get(key)),
+                new: show(new_table.get(key)),
+            })
+            .collect()
+    }
+
+    /// 创建所有不存在的资源根目录（包括虚拟主机的），返回新建的目录。
+    ///
+    /// 仅在开启 `create_www_root` 时由启动流程在 [`Config::validate`] 之前调用。
+    pub fn create_missing_roots(&self) -> io::Result<Vec<String>> {
+        let mut created = Vec::new();
+        let vhost_roots = self.vhosts.iter().flat_map(|v| v.www_roots());
+        for root in self.www_roots().iter().chain(vhost_roots) {
+            if !root.is_empty() && !Path::new(root).exists() {
+                std::fs::create_dir_all(root)?;
+                created.push(root.clone());
+            }
+        }
+        Ok(created)
+    }
+
+    /// 检查配置在语义上是否合法，返回发现的第一个问题。
+    ///
+    /// 依次检查：端口号（使用 Unix 域套接字时忽略）、每个资源根目录（包括虚拟主机的）是否存在且可读、
+    /// `chunk_size` 与 `streaming_threshold` 是否大于 0、`encoding_priority` 中的编码与 `allowed_methods` 中的方法是否都能识别、
+    /// `index_files` 是否都是文件名、
+    /// `max_blocking_threads` 与 `max_cache_bytes` 是否大于 0，
+    /// 启用 HTTPS 时是否设置了证书与私钥，以及 `[auth]`、`[rate_limit]` 与 `[cors]` 表是否合法（见 [`AuthConfig`]、[`RateLimit`]、[`CorsConfig`]）。
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.unix_socket.is_none() && self.port == 0 {
+            return Err(ConfigError::InvalidPort(self.port));
+        }
+        let roots = self.www_roots();
+        let vhost_roots = || self.vhosts.iter().flat_map(|v| v.www_roots());
+        if roots.is_empty()
+            || self.vhosts.iter().any(|v| v.www_roots().is_empty())
+            || roots.iter().chain(vhost_roots()).any(|root| root.is_empty())
+        {
+            return Err(ConfigError::EmptyWwwRoot);
+        }
+        for root in roots.iter().chain(vhost_roots()) {
+            match std::fs::metadata(root) {
+                Ok(metadata) if !metadata.is_dir() => {
+                    return Err(ConfigError::WwwRootNotDirectory(root.clone()))
+                }
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    return Err(ConfigError::WwwRootNotFound(root.clone()))
+                }
+                Err(e) => return Err(ConfigError::WwwRootUnreadable(root.clone(), e.to_string())),
+            }
+            if let Err(e) = std::fs::read_dir(root) {
+                return Err(ConfigError::WwwRootUnreadable(root.clone(), e.to_string()));
+            }
+        }
+        if self.chunk_size == 0 {
+            return Err(ConfigError::ZeroChunkSize);
+        }
+        if self.streaming_threshold == 0 {
+            return Err(ConfigError::InvalidStreamingThreshold(self.streaming_threshold));
+        }
+        if let Some(encoding) = self
+            .encoding_priority
+            .iter()
+            .find(|e| e.parse::<HttpEncoding>().is_err())
+        {
+            return Err(ConfigError::UnknownEncoding(encoding.clone()));
+        }
+        if let Some(method) = self
+            .allowed_methods
+            .iter()
+            .find(|m| m.parse::<HttpRequestMethod>().is_err())
+        {
+            return Err(ConfigError::UnknownMethod(method.clone()));
+        }
+        let vhost_index_files = self.vhosts.iter().flat_map(|v| v.index_files().unwrap_or_default());
+        if let Some(name) = self
+            .index_files
+            .iter()
+            .chain(vhost_index_files)
+            .find(|name| matches!(name.as_str(), "" | "." | "..") || name.contains(['/', '\\']))
+        {
+            return Err(ConfigError::InvalidIndexFile(name.clone()));
+        }
+        if self.max_blocking_threads == 0 {
+            return Err(ConfigError::ZeroMaxBlockingThreads);
+        }
+        if self.max_cache_bytes == 0 {
+            return Err(ConfigError::ZeroMaxCacheBytes);
+        }
+        if self.tls_enabled {
+            if self.cert_path.is_none() {
+                return Err(ConfigError::MissingTlsFile("cert_path"));
+            }
+            if self.key_path.is_none() {
+                return Err(ConfigError::MissingTlsFile("key_path"));
+            }
+            if self.tls_port == Some(0) {
+                return Err(ConfigError::InvalidPort(0));
+            }
+        }
+        for (code, path) in &self.error_pages {
+            let is_error_code = code.parse::<u16>().is_ok_and(|code| (400..600).contains(&code));
+            let relative = !path.is_empty()
+                && Path::new(path).components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+            if !is_error_code || !relative {
+                return Err(ConfigError::InvalidErrorPage(code.clone(), path.clone()));
+            }
+        }
+        if let Some(address) = &self.fastcgi_address {
+            if address.parse::<FastCgiAddress>().is_err() {
+                return Err(ConfigError::InvalidFastCgiAddress(address.clone()));
+            }
+        }
+        for (prefix, upstream) in &self.proxy {
+            if !prefix.starts_with('/') || upstream.parse::<Upstream>().is_err() {
+                return Err(ConfigError::InvalidProxy(prefix.clone(), upstream.clone()));
+            }
+        }
+        self.auth.check().map_err(ConfigError::InvalidAuth)?;
+        let (rate, burst) = (self.rate_limit.requests_per_second, self.rate_limit.burst);
+        if !rate.is_finite() || rate < 0.0 || (rate > 0.0 && burst == 0) {
+            return Err(ConfigError::InvalidRateLimit(rate, burst));
+        }
+        self.cors.check().map_err(ConfigError::InvalidCors)?;
+        Ok(())
+    }
+
+    /// 规范化刚解析出的配置：自动设置线程数、修正非法取值、合并旧版扁平写法。
+    fn normalize(&mut self) {
+        if self.worker_threads == 0 {
+            self.worker_threads = num_cpus::get();
         }
-        if raw_config.cache_size == 0 {
+        if self.cache_size == 0 {
             warn!("cache_size被设置为0，但目前尚不支持禁用缓存，因此该值将被改为5。");
-            raw_config.cache_size = 5;
+            self.cache_size = 5;
+        }
+        if self.gzip_level > 9 {
+            warn!("gzip_level的取值范围为0~9，{}将被改为9。", self.gzip_level);
+            self.gzip_level = 9;
+        }
+        if self.brotli_quality > 11 {
+            warn!("brotli_quality的取值范围为0~11，{}将被改为11。", self.brotli_quality);
+            self.brotli_quality = 11;
+        }
+        self.merge_flat_limits();
+    }
+
+    /// 计算服务器的监听地址。
+    ///
+    /// 优先使用 `bind_address`：带端口的写法直接作为完整地址，只有 IP 的写法与 `port` 组合。
+    /// 未设置时回退到 `local` 的行为（127.0.0.1 或 0.0.0.0）。
+    ///
+    /// # Errors
+    ///
+    /// `bind_address` 既不是合法的 IP 也不是合法的套接字地址时返回 [`AddrParseError`]。
+    pub fn listen_addr(&self) -> Result<SocketAddr, AddrParseError> {
+        match &self.bind_address {
+            Some(address) => match address.parse::<SocketAddr>() {
+                Ok(socket) => Ok(socket),
+                Err(_) => address
+                    .parse::<IpAddr>()
+                    .map(|ip| SocketAddr::new(ip, self.port)),
+            },
+            None => {
+                let ip = match self.local {
+                    true => Ipv4Addr::new(127, 0, 0, 1),
+                    false => Ipv4Addr::new(0, 0, 0, 0),
+                };
+                Ok(SocketAddr::new(IpAddr::V4(ip), self.port))
+            }
+        }
+    }
+
+    /// 将顶层扁平写法的上限字段合并进 `limits`。
+    ///
+    /// 扁平键仅为兼容旧配置而保留；若与 `[limits]` 表同时出现，则以扁平键为准并给出警告。
+    fn merge_flat_limits(&mut self) {
+        if let Some(v) = self.max_header_bytes.take() {
+            warn!("max_header_bytes 应写在 [limits] 表中，顶层写法已废弃");
+            self.limits.max_header_bytes = v;
+        }
+        if let Some(v) = self.max_body_bytes.take() {
+            warn!("max_body_bytes 应写在 [limits] 表中，顶层写法已废弃");
+            self.limits.max_body_bytes = v;
+        }
+        if let Some(v) = self.max_uri_length.take() {
+            warn!("max_uri_length 应写在 [limits] 表中，顶层写法已废弃");
+            self.limits.max_uri_length = v;
+        }
+        if let Some(v) = self.max_headers.take() {
+            warn!("max_headers 应写在 [limits] 表中，顶层写法已废弃");
+            self.limits.max_headers = v;
+        }
+        if let Some(v) = self.read_timeout_secs.take() {
+            warn!("read_timeout_secs 应写在 [limits] 表中，顶层写法已废弃");
+            self.limits.read_timeout_secs = v;
         }
-        raw_config
     }
 }
 
 /// 配置项的只读访问接口（Getters）。
 impl Config {
-    /// 获取静态资源根目录。
+    /// 获取静态资源根目录，配置了多个根目录时返回第一个。
     pub fn www_root(&self) -> &str {
-        &self.www_root
+        self.www_roots().first().map_or("", |r| r.as_str())
+    }
+
+    /// 获取所有静态资源根目录（按查找顺序排列）。
+    pub fn www_roots(&self) -> &[String] {
+        self.www_root.as_slice()
+    }
+
+    /// 获取启动时是否自动创建缺失的资源根目录。
+    pub fn create_www_root(&self) -> bool {
+        self.create_www_root
+    }
+
+    /// 获取是否允许符号链接指向资源根目录之外。
+    pub fn follow_symlinks(&self) -> bool {
+        self.follow_symlinks
+    }
+
+    /// 获取状态码对应的自定义错误页面路径（相对于资源根目录）。
+    pub fn error_page(&self, code: u16) -> Option<&str> {
+        self.error_pages.get(&code.to_string()).map(String::as_str)
+    }
+
+    /// 查找请求路径对应的反向代理规则，返回匹配的前缀与上游地址；多个前缀匹配时取最长的一个。
+    ///
+    /// 前缀按路径段边界匹配规范化后的路径（见 [`proxy::route_paths`]），`/api/` 不匹配 `/apiary`，
+    /// `//api/` 与 `/static/../api/` 等等价写法与 `/api/` 落到同一条规则。
+    pub fn proxy_route(&self, path: &str) -> Option<(&str, Upstream)> {
+        let paths = proxy::route_paths(path);
+        self.proxy
+            .iter()
+            .filter(|(prefix, _)| paths.iter().any(|path| proxy::prefix_matches(prefix, path)))
+            .max_by_key(|(prefix, _)| prefix.trim_end_matches('/').len())
+            .and_then(|(prefix, upstream)| Some((prefix.as_str(), upstream.parse().ok()?)))
+    }
+
+    /// 获取请求限流配置。
+    pub fn rate_limit(&self) -> &RateLimit {
+        &self.rate_limit
+    }
+
+    /// 获取跨域资源共享配置。
+    pub fn cors(&self) -> &CorsConfig {
+        &self.cors
+    }
+
+    /// 获取访问控制配置。
+    pub fn auth(&self) -> &AuthConfig {
+        &self.auth
+    }
+
+    /// 获取反向代理的超时时间（秒）。
+    pub fn proxy_timeout_secs(&self) -> u64 {
+        self.proxy_timeout_secs
+    }
+
+    /// 获取所有虚拟主机。
+    pub fn vhosts(&self) -> &[VirtualHost] {
+        &self.vhosts
+    }
+
+    /// 根据 `Host` 请求头选择虚拟主机。
+    ///
+    /// 比较时忽略端口、大小写与结尾的 `.`；精确匹配优先，其次是通配的 `*`，都没有时返回 `None`。
+    pub fn vhost(&self, host: Option<&str>) -> Option<&VirtualHost> {
+        let host = host.map(|h| {
+            let h = h.trim();
+            // `[::1]:8080` 形式的 IPv6 地址保留方括号，只去掉其后的端口
+            let h = match h.rfind(':') {
+                Some(i) if !h[i..].contains(']') => &h[..i],
+                _ => h,
+            };
+            h.trim_end_matches('.').to_ascii_lowercase()
+        });
+        let exact = host.and_then(|host| self.vhosts.iter().find(|v| v.host.eq_ignore_ascii_case(&host)));
+        exact.or_else(|| self.vhosts.iter().find(|v| v.host == "*"))
     }
 
     /// 获取服务器端口号。
@@ -140,16 +1430,61 @@ impl Config {
         self.worker_threads
     }
 
+    /// 获取阻塞线程池的线程数上限。
+    pub fn max_blocking_threads(&self) -> usize {
+        self.max_blocking_threads
+    }
+
     /// 获取缓存容量上限。
     pub fn cache_size(&self) -> usize {
         self.cache_size
     }
 
+    /// 获取缓存内容的总字节数上限。
+    pub fn max_cache_bytes(&self) -> usize {
+        self.max_cache_bytes
+    }
+
     /// 获取运行环境标识。
     pub fn local(&self) -> bool {
         self.local
     }
 
+    /// 获取配置的监听地址原文，解析后的结果见 [`Config::listen_addr`]。
+    pub fn bind_address(&self) -> Option<&str> {
+        self.bind_address.as_deref()
+    }
+
+    /// 获取 Unix 域套接字路径。
+    pub fn unix_socket(&self) -> Option<&str> {
+        self.unix_socket.as_deref()
+    }
+
+    /// 获取是否启用 HTTPS。
+    pub fn tls_enabled(&self) -> bool {
+        self.tls_enabled
+    }
+
+    /// 获取 TLS 证书链文件路径。
+    pub fn cert_path(&self) -> Option<&str> {
+        self.cert_path.as_deref()
+    }
+
+    /// 获取 TLS 私钥文件路径。
+    pub fn key_path(&self) -> Option<&str> {
+        self.key_path.as_deref()
+    }
+
+    /// 获取额外提供 HTTPS 的端口。
+    pub fn tls_port(&self) -> Option<u16> {
+        self.tls_port
+    }
+
+    /// 获取 HTTPS 连接是否提供 HTTP/2。
+    pub fn http2(&self) -> bool {
+        self.http2
+    }
+
     /// 获取流式传输的字节阈值。
     pub fn streaming_threshold(&self) -> u64 {
         self.streaming_threshold
@@ -164,4 +1499,595 @@ impl Config {
     pub fn enable_range_requests(&self) -> bool {
         self.enable_range_requests
     }
-}
\ No newline at end of file
+
+    /// 获取目录索引文件名列表（按优先级排列）。
+    pub fn index_files(&self) -> &[String] {
+        &self.index_files
+    }
+
+    /// 获取是否允许生成目录列表。
+    pub fn autoindex(&self) -> bool {
+        self.autoindex
+    }
+
+    /// 获取指标端点的路径，端点已关闭时返回 `None`。
+    pub fn metrics_path(&self) -> Option<&str> {
+        match self.metrics_path.as_str() {
+            "" => None,
+            path => Some(path),
+        }
+    }
+
+    /// 获取目录列表中隐藏的文件名 glob 模式。
+    pub fn listing_ignore(&self) -> &[String] {
+        &self.listing_ignore
+    }
+
+    /// 获取 JSON 目录列表是否附带汇总信息。
+    pub fn listing_json_summary(&self) -> bool {
+        self.listing_json_summary
+    }
+
+    /// 获取目录列表默认每页显示的条目数，0 表示不分页。
+    pub fn listing_per_page(&self) -> usize {
+        self.listing_per_page
+    }
+
+    /// 获取健康检查端点的路径，端点已关闭时返回 `None`。
+    pub fn health_path(&self) -> Option<&str> {
+        match self.health_path.as_str() {
+            "" => None,
+            path => Some(path),
+        }
+    }
+
+    /// 获取是否宽松处理内容协商（不返回 406）。
+    pub fn lenient_accept(&self) -> bool {
+        self.lenient_accept
+    }
+
+    /// 获取自定义的 favicon 文件路径。
+    pub fn favicon_path(&self) -> Option<&str> {
+        self.favicon_path.as_deref()
+    }
+
+    /// 获取自定义的 robots.txt 文件路径。
+    pub fn robots_path(&self) -> Option<&str> {
+        self.robots_path.as_deref()
+    }
+
+    /// 获取是否为 favicon.ico 与 robots.txt 提供内置默认值。
+    pub fn builtin_favicon_robots(&self) -> bool {
+        self.builtin_favicon_robots
+    }
+
+    /// 获取文本类型默认追加的字符集，为空表示不追加。
+    pub fn default_charset(&self) -> &str {
+        &self.default_charset
+    }
+
+    /// 获取是否嗅探无扩展名文件的 MIME 类型。
+    pub fn sniff_extensionless(&self) -> bool {
+        self.sniff_extensionless
+    }
+
+    /// 获取是否根据文件内容计算强 ETag。
+    pub fn strong_etag(&self) -> bool {
+        self.strong_etag
+    }
+
+    /// 判断扩展名为 `extension` 的文件是否需要强制下载。
+    pub fn is_download_extension(&self, extension: &str) -> bool {
+        self.download_extensions
+            .iter()
+            .any(|ext| ext.trim_start_matches('.').eq_ignore_ascii_case(extension))
+    }
+
+    /// 获取是否信任 `X-Forwarded-For` 请求头。
+    pub fn trust_proxy(&self) -> bool {
+        self.trust_proxy
+    }
+
+    /// 获取 gzip 与 deflate 的压缩级别（0–9）。
+    pub fn gzip_level(&self) -> u32 {
+        self.gzip_level
+    }
+
+    /// 获取 brotli 的压缩质量（0–11）。
+    pub fn brotli_quality(&self) -> u32 {
+        self.brotli_quality
+    }
+
+    /// 获取压缩编码的优先级，按配置中的顺序去重。无法识别的编码名会被忽略（[`Config::validate`] 会报告它们）。
+    pub fn encoding_priority(&self) -> Vec<HttpEncoding> {
+        let mut encodings = Vec::new();
+        for encoding in self.encoding_priority.iter().filter_map(|e| e.parse().ok()) {
+            if !encodings.contains(&encoding) {
+                encodings.push(encoding);
+            }
+        }
+        encodings
+    }
+
+    /// 获取 `Server` 响应头的内容，为空时返回 `None`，表示不发送该响应头。
+    pub fn server_name(&self) -> Option<&str> {
+        self.server_name.as_deref().filter(|name| !name.is_empty())
+    }
+
+    /// 获取启用的 HTTP 方法，按配置中的顺序去重。无法识别的方法名会被忽略（[`Config::validate`] 会报告它们）。
+    pub fn allowed_methods(&self) -> Vec<HttpRequestMethod> {
+        let mut methods = Vec::new();
+        for method in self.allowed_methods.iter().filter_map(|m| m.parse().ok()) {
+            if !methods.contains(&method) {
+                methods.push(method);
+            }
+        }
+        methods
+    }
+
+    /// 获取是否允许以 PUT 上传文件。
+    pub fn uploads(&self) -> bool {
+        self.uploads
+    }
+
+    /// 获取单页应用的路径前缀，未配置或为空时返回 `None`。
+    pub fn spa_prefix(&self) -> Option<&str> {
+        self.spa_prefix.as_deref().filter(|p| !p.is_empty())
+    }
+
+    /// 获取单页应用的入口文件路径，未配置或为空时返回 `None`。
+    pub fn spa_fallback(&self) -> Option<&str> {
+        self.spa_fallback.as_deref().filter(|p| !p.is_empty())
+    }
+
+    /// 获取是否将 Markdown 文件渲染为 HTML。
+    pub fn render_markdown(&self) -> bool {
+        self.render_markdown
+    }
+
+    /// 获取是否附加 `X-Response-Time` 响应头。
+    pub fn emit_response_time(&self) -> bool {
+        self.emit_response_time
+    }
+
+    /// 获取缓存条目的存活时间（秒）。
+    pub fn cache_ttl_secs(&self) -> Option<u64> {
+        self.cache_ttl_secs
+    }
+
+    /// 获取 PHP 脚本的运行超时时间（秒）。
+    pub fn script_timeout_secs(&self) -> u64 {
+        self.script_timeout_secs
+    }
+
+    /// 获取 PHP 解释器的路径，未配置或为空时返回 `php`。
+    pub fn php_path(&self) -> &str {
+        self.php_path
+            .as_deref()
+            .filter(|path| !path.is_empty())
+            .unwrap_or(DEFAULT_PHP_PATH)
+    }
+
+    /// 获取 FastCGI 应用的地址，未配置或格式错误时返回 `None`（此时 PHP 请求交给 `php_path` 处理）。
+    pub fn fastcgi_address(&self) -> Option<FastCgiAddress> {
+        self.fastcgi_address.as_deref()?.parse().ok()
+    }
+
+    /// 获取请求相关的各类上限。
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: &str = r#"
+        www_root = "./static/"
+        port = 7878
+        worker_threads = 4
+        cache_size = 10
+        local = true
+    "#;
+
+    #[test]
+    fn test_limits_default_when_absent() {
+        let config = Config::from_toml_str(BASE);
+        assert_eq!(config.limits(), &Limits::default());
+    }
+
+    #[test]
+    fn test_limits_table() {
+        let toml = format!(
+            "{}\n[limits]\nmax_header_bytes = 4096\nmax_uri_length = 1024\nmax_query_length = 256\nread_timeout_secs = 5\nrequest_timeout_secs = 12\n",
+            BASE
+        );
+        let config = Config::from_toml_str(&toml);
+        let limits = config.limits();
+        assert_eq!(limits.max_header_bytes(), 4096);
+        assert_eq!(limits.max_uri_length(), 1024);
+        assert_eq!(limits.max_query_length(), 256);
+        assert_eq!(limits.read_timeout_secs(), 5);
+        assert_eq!(limits.request_timeout_secs(), 12);
+        // 未出现的字段保持默认值
+        assert_eq!(limits.max_body_bytes(), Limits::default().max_body_bytes());
+        assert_eq!(limits.max_headers(), Limits::default().max_headers());
+    }
+
+    #[test]
+    fn test_limits_flat_keys_compat() {
+        let toml = format!("max_header_bytes = 2048\nmax_headers = 20\n{}", BASE);
+        let config = Config::from_toml_str(&toml);
+        assert_eq!(config.limits().max_header_bytes(), 2048);
+        assert_eq!(config.limits().max_headers(), 20);
+    }
+    #[test]
+    fn test_listen_addr_fallback_to_local() {
+        let config = Config::from_toml_str(BASE);
+        assert_eq!(config.listen_addr().unwrap(), "127.0.0.1:7878".parse().unwrap());
+
+        let config = Config::from_toml_str(&BASE.replace("local = true", "local = false"));
+        assert_eq!(config.listen_addr().unwrap(), "0.0.0.0:7878".parse().unwrap());
+    }
+
+    #[test]
+    fn test_listen_addr_bind_address() {
+        let cases = [
+            ("::1", "[::1]:7878"),
+            ("[::1]:8080", "[::1]:8080"),
+            ("192.168.1.10", "192.168.1.10:7878"),
+            ("10.0.0.1:9000", "10.0.0.1:9000"),
+        ];
+        for (bind_address, expected) in cases {
+            let config = Config::from_toml_str(&format!("bind_address = {:?}\n{}", bind_address, BASE));
+            assert_eq!(config.bind_address(), Some(bind_address));
+            assert_eq!(config.listen_addr().unwrap(), expected.parse().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_listen_addr_invalid() {
+        let config = Config::from_toml_str(&format!("bind_address = \"not-an-ip\"\n{}", BASE));
+        assert!(config.listen_addr().is_err());
+    }
+    #[test]
+    fn test_metrics_path() {
+        let config = Config::from_toml_str(BASE);
+        assert_eq!(config.metrics_path(), Some("/metrics"));
+
+        let config = Config::from_toml_str(&format!("metrics_path = \"/_stats\"\n{}", BASE));
+        assert_eq!(config.metrics_path(), Some("/_stats"));
+
+        let config = Config::from_toml_str(&format!("metrics_path = \"\"\n{}", BASE));
+        assert_eq!(config.metrics_path(), None);
+    }
+
+    #[test]
+    fn test_health_path() {
+        let config = Config::from_toml_str(BASE);
+        assert_eq!(config.health_path(), Some("/healthz"));
+
+        let config = Config::from_toml_str(&format!("health_path = \"\"\n{}", BASE));
+        assert_eq!(config.health_path(), None);
+    }
+    #[test]
+    fn test_www_roots() {
+        let config = Config::from_toml_str(BASE);
+        assert_eq!(config.www_root(), "./static/");
+        assert_eq!(config.www_roots(), ["./static/"]);
+
+        let toml = BASE.replace(r#"www_root = "./static/""#, r#"www_root = ["./dist/", "./static/"]"#);
+        let config = Config::from_toml_str(&toml);
+        assert_eq!(config.www_root(), "./dist/");
+        assert_eq!(config.www_roots(), ["./dist/", "./static/"]);
+
+        let toml = BASE.replace(r#"www_root = "./static/""#, r#"www_roots = ["./a/", "./b/"]"#);
+        assert_eq!(Config::from_toml_str(&toml).www_roots(), ["./a/", "./b/"]);
+    }
+    #[test]
+    fn test_compression_levels() {
+        let config = Config::from_toml_str(BASE);
+        assert_eq!((config.gzip_level(), config.brotli_quality()), (6, 5));
+
+        let config = Config::from_toml_str(&format!("gzip_level = 1\nbrotli_quality = 4\n{}", BASE));
+        assert_eq!((config.gzip_level(), config.brotli_quality()), (1, 4));
+
+        // 超出范围的取值在载入时被截断
+        let config = Config::from_toml_str(&format!("gzip_level = 42\nbrotli_quality = 99\n{}", BASE));
+        assert_eq!((config.gzip_level(), config.brotli_quality()), (9, 11));
+    }
+    #[test]
+    fn test_try_from_toml_str_and_diff() {
+        assert!(Config::try_from_toml_str("port = \"not a number\"").is_err());
+        assert!(Config::try_from_toml("/nonexistent/webserver.toml").is_err());
+
+        let old = Config::try_from_toml_str(BASE).unwrap();
+        let new = Config::try_from_toml_str(&format!("cache_ttl_secs = 60\n{}", BASE.replace("port = 7878", "port = 8080"))).unwrap();
+        assert!(old.diff(&old).is_empty());
+
+        let changes = old.diff(&new);
+        let keys: Vec<&str> = changes.iter().map(|c| c.key.as_str()).collect();
+        assert_eq!(keys, ["cache_ttl_secs", "port"]);
+        assert_eq!((changes[0].old.as_str(), changes[0].new.as_str()), ("(未设置)", "60"));
+        assert!(!changes[0].requires_restart());
+        assert_eq!((changes[1].old.as_str(), changes[1].new.as_str()), ("7878", "8080"));
+        assert!(changes[1].requires_restart());
+    }
+    fn env(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_env_overrides_win_over_file() {
+        let vars = env(&[
+            ("WEBSERVER_PORT", "9090"),
+            ("WEBSERVER_WWW_ROOT", "./public"),
+            ("WEBSERVER_CACHE_SIZE", "64"),
+            ("WEBSERVER_WORKER_THREADS", "2"),
+            ("WEBSERVER_LOCAL", "false"),
+            ("WEBSERVER_LIMITS__MAX_BODY_BYTES", "2048"),
+            ("PATH", "/usr/bin"),
+        ]);
+        let config = Config::parse_with_env(BASE, vars).unwrap();
+        assert_eq!(config.port(), 9090);
+        assert_eq!(config.www_root(), "./public");
+        assert_eq!(config.cache_size(), 64);
+        assert_eq!(config.worker_threads(), 2);
+        assert!(!config.local());
+        assert_eq!(config.limits().max_body_bytes(), 2048);
+        // 未覆盖的 [limits] 字段保持默认值
+        assert_eq!(config.limits().max_header_bytes(), Limits::default().max_header_bytes());
+    }
+
+    #[test]
+    fn test_env_override_invalid_values_are_ignored() {
+        let vars = env(&[
+            ("WEBSERVER_PORT", "not-a-port"),
+            ("WEBSERVER_CACHE_SIZE", "-1"),
+            ("WEBSERVER_NO_SUCH_KEY", "1"),
+            ("WEBSERVER_", "1"),
+        ]);
+        let config = Config::parse_with_env(BASE, vars).unwrap();
+        assert_eq!(config.port(), 7878);
+        assert_eq!(config.cache_size(), 10);
+    }
+
+    #[test]
+    fn test_env_override_string_fallback_and_arrays() {
+        // 不是合法 TOML 字面量的值按字符串处理，数组按 TOML 字面量解析
+        let vars = env(&[
+            ("WEBSERVER_BIND_ADDRESS", "127.0.0.1"),
+            ("WEBSERVER_WWW_ROOT", r#"["./a", "./b"]"#),
+        ]);
+        let config = Config::parse_with_env(BASE, vars).unwrap();
+        assert_eq!(config.bind_address(), Some("127.0.0.1"));
+        assert_eq!(config.www_roots(), ["./a".to_string(), "./b".to_string()]);
+    }
+
+    #[test]
+    fn test_from_toml_reads_process_env() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(BASE.as_bytes()).unwrap();
+        let path = file.path().to_str().unwrap();
+
+        std::env::set_var("WEBSERVER_CHUNK_SIZE", "4096");
+        let config = Config::from_toml(path);
+        let reloaded = Config::try_from_toml(path).unwrap();
+        std::env::remove_var("WEBSERVER_CHUNK_SIZE");
+
+        assert_eq!(config.chunk_size(), 4096);
+        assert_eq!(reloaded.chunk_size(), 4096);
+        // 字符串解析接口不读取环境变量
+        assert_eq!(Config::from_toml_str(BASE).chunk_size(), default_chunk_size());
+    }
+    #[test]
+    fn test_validate() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().to_str().unwrap();
+        let valid = format!("www_root = \"{}\"\nport = 7878\nworker_threads = 1\ncache_size = 5\nlocal = true\n", root);
+        let config = Config::from_toml_str(&valid);
+        assert_eq!(config.validate(), Ok(()));
+
+        let invalid = |from: &str, to: &str| Config::from_toml_str(&valid.replace(from, to)).validate().unwrap_err();
+        assert_eq!(invalid("port = 7878", "port = 0"), ConfigError::InvalidPort(0));
+        assert_eq!(invalid(root, ""), ConfigError::EmptyWwwRoot);
+        assert_eq!(
+            invalid(root, "/nonexistent/root"),
+            ConfigError::WwwRootNotFound("/nonexistent/root".to_string())
+        );
+        let file = dir.path().join("file.txt");
+        std::fs::write(&file, "x").unwrap();
+        let file = file.to_str().unwrap();
+        assert_eq!(invalid(root, file), ConfigError::WwwRootNotDirectory(file.to_string()));
+        assert_eq!(invalid("local = true", "local = true\nchunk_size = 0"), ConfigError::ZeroChunkSize);
+        assert_eq!(
+            invalid("local = true", "local = true\nencoding_priority = [\"gzip\", \"zstd\"]"),
+            ConfigError::UnknownEncoding("zstd".to_string())
+        );
+        assert_eq!(
+            invalid("local = true", "local = true\nmax_blocking_threads = 0"),
+            ConfigError::ZeroMaxBlockingThreads
+        );
+        assert_eq!(invalid("local = true", "local = true\nmax_cache_bytes = 0"), ConfigError::ZeroMaxCacheBytes);
+        assert_eq!(
+            invalid("local = true", "local = true\ntls_enabled = true\nkey_path = \"k.pem\""),
+            ConfigError::MissingTlsFile("cert_path")
+        );
+        assert_eq!(
+            invalid("local = true", "local = true\ntls_enabled = true\ncert_path = \"c.pem\""),
+            ConfigError::MissingTlsFile("key_path")
+        );
+        assert_eq!(
+            invalid("local = true", "local = true\nstreaming_threshold = 0"),
+            ConfigError::InvalidStreamingThreshold(0)
+        );
+        let error_pages = |pages: &str| format!("{}\n[error_pages]\n{}\n", valid, pages);
+        let config = Config::from_toml_str(&error_pages("404 = \"errors/404.html\""));
+        assert_eq!(config.validate(), Ok(()));
+        assert_eq!(config.error_page(404), Some("errors/404.html"));
+        assert_eq!(config.error_page(500), None);
+        assert_eq!(
+            Config::from_toml_str(&error_pages("404 = \"../404.html\"")).validate(),
+            Err(ConfigError::InvalidErrorPage("404".to_string(), "../404.html".to_string()))
+        );
+        assert_eq!(
+            Config::from_toml_str(&error_pages("200 = \"ok.html\"")).validate(),
+            Err(ConfigError::InvalidErrorPage("200".to_string(), "ok.html".to_string()))
+        );
+        let proxy = |rules: &str| format!("{}\n[proxy]\n{}\n", valid, rules);
+        let config = Config::from_toml_str(&proxy("\"/api/\" = \"http://127.0.0.1:9000\"\n\"/api/v2/\" = \"http://127.0.0.1:9001/v2\""));
+        assert_eq!(config.validate(), Ok(()));
+        let (prefix, upstream) = config.proxy_route("/api/v2/items").unwrap();
+        assert_eq!((prefix, upstream.to_string().as_str()), ("/api/v2/", "http://127.0.0.1:9001/v2"));
+        assert_eq!(config.proxy_route("/api/items").unwrap().0, "/api/");
+        assert!(config.proxy_route("/static/a.css").is_none());
+        // 按路径段边界匹配规范化后的路径
+        assert!(config.proxy_route("/apiary").is_none());
+        assert_eq!(config.proxy_route("/api").unwrap().0, "/api/");
+        assert_eq!(config.proxy_route("//api//v2/items").unwrap().0, "/api/v2/");
+        assert_eq!(config.proxy_route("/static/../api/items").unwrap().0, "/api/");
+        assert_eq!(config.proxy_route("/%61pi/items").unwrap().0, "/api/");
+        assert_eq!(
+            Config::from_toml_str(&proxy("\"/api/\" = \"https://backend\"")).validate(),
+            Err(ConfigError::InvalidProxy("/api/".to_string(), "https://backend".to_string()))
+        );
+        assert_eq!(
+            Config::from_toml_str(&proxy("\"api\" = \"http://backend\"")).validate(),
+            Err(ConfigError::InvalidProxy("api".to_string(), "http://backend".to_string()))
+        );
+        let auth = |table: &str| Config::from_toml_str(&format!("{}\n[auth]\n{}\n", valid, table)).validate();
+        assert_eq!(auth("paths = [\"/admin/\"]\n[auth.tokens]\nci = \"t\""), Ok(()));
+        assert!(matches!(auth("paths = [\"admin\"]\n[auth.tokens]\nci = \"t\""), Err(ConfigError::InvalidAuth(_))));
+        assert!(matches!(auth("paths = [\"/admin/\"]"), Err(ConfigError::InvalidAuth(_))));
+        assert!(matches!(
+            auth("paths = [\"/admin/\"]\nhtpasswd = \"/nonexistent/htpasswd\""),
+            Err(ConfigError::InvalidAuth(_))
+        ));
+        assert!(matches!(
+            auth("paths = [\"/\"]\nrealm = 'a\"b'\n[auth.tokens]\nci = \"t\""),
+            Err(ConfigError::InvalidAuth(_))
+        ));
+        let rate_limit = |table: &str| {
+            Config::from_toml_str(&format!("{}\n[rate_limit]\n{}\n", valid, table)).validate()
+        };
+        assert_eq!(rate_limit("requests_per_second = 2.5\nburst = 5"), Ok(()));
+        assert_eq!(rate_limit("burst = 0"), Ok(()));
+        assert_eq!(
+            rate_limit("requests_per_second = -1.0"),
+            Err(ConfigError::InvalidRateLimit(-1.0, 20))
+        );
+        assert_eq!(
+            rate_limit("requests_per_second = 1.0\nburst = 0"),
+            Err(ConfigError::InvalidRateLimit(1.0, 0))
+        );
+        assert_eq!(
+            invalid("local = true", "local = true\nindex_files = [\"index.html\", \"../secret.html\"]"),
+            ConfigError::InvalidIndexFile("../secret.html".to_string())
+        );
+        let vhost = format!("[[vhost]]\nhost = \"a.example\"\nwww_root = \"{}\"\nindex_files = [\"..\"]", root);
+        assert_eq!(
+            Config::from_toml_str(&format!("{}{}\n", valid, vhost)).validate(),
+            Err(ConfigError::InvalidIndexFile("..".to_string()))
+        );
+        let cors = |table: &str| Config::from_toml_str(&format!("{}\n[cors]\n{}\n", valid, table)).validate();
+        assert_eq!(cors("origins = [\"https://a.example\", \"http://b.example:8080\"]\nheaders = [\"X-Token\"]"), Ok(()));
+        assert_eq!(cors("origins = [\"*\"]\nmethods = [\"get\", \"POST\"]"), Ok(()));
+        assert!(matches!(cors("origins = [\"a.example\"]"), Err(ConfigError::InvalidCors(_))));
+        assert!(matches!(cors("origins = [\"https://a.example/app\"]"), Err(ConfigError::InvalidCors(_))));
+        assert!(matches!(cors("origins = [\"*\"]\nmethods = [\"DELETE\"]"), Err(ConfigError::InvalidCors(_))));
+        assert!(matches!(cors("origins = [\"*\"]\nheaders = [\"X Token\"]"), Err(ConfigError::InvalidCors(_))));
+        assert!(matches!(cors("origins = [\"*\"]\nallow_credentials = true"), Err(ConfigError::InvalidCors(_))));
+        // 使用 Unix 域套接字时不检查端口
+        let unix = valid.replace("port = 7878", "port = 0\nunix_socket = \"/tmp/ws.sock\"");
+        assert_eq!(Config::from_toml_str(&unix).validate(), Ok(()));
+    }
+    #[test]
+    fn test_vhost_matching() {
+        let toml = format!(
+            "{}\n[[vhost]]\nhost = \"Example.com\"\nwww_root = [\"./a\", \"./b\"]\n\n[[vhost]]\nhost = \"[::1]\"\nwww_root = \"./v6\"\nindex_files = [\"home.htm\"]\n",
+            BASE
+        );
+        let config = Config::from_toml_str(&toml);
+        assert_eq!(config.vhosts().len(), 2);
+        let roots = |host: Option<&str>| config.vhost(host).map(|v| v.www_roots().to_vec());
+        assert_eq!(roots(Some("example.com")), Some(vec!["./a".to_string(), "./b".to_string()]));
+        assert_eq!(roots(Some("EXAMPLE.COM:8080")), roots(Some("example.com")));
+        assert_eq!(roots(Some("example.com.")), roots(Some("example.com")));
+        assert_eq!(config.vhost(Some("[::1]:7878")).unwrap().index_files(), Some(&["home.htm".to_string()][..]));
+        assert_eq!(config.vhost(Some("example.com")).unwrap().index_files(), None);
+        // 没有匹配、也没有通配虚拟主机时使用顶层配置
+        assert!(config.vhost(Some("other.com")).is_none());
+        assert!(config.vhost(None).is_none());
+
+        let wildcard = format!("{}\n[[vhost]]\nhost = \"*\"\nwww_root = \"./default\"\n", toml);
+        let config = Config::from_toml_str(&wildcard);
+        assert_eq!(config.vhost(None).unwrap().host(), "*");
+        assert_eq!(config.vhost(Some("other.com")).unwrap().host(), "*");
+        assert_eq!(config.vhost(Some("example.com")).unwrap().host(), "Example.com");
+        assert!(config.validate().is_err());
+
+        let plural = format!("{}\n[[vhosts]]\nhost = \"blog.example.com\"\nwww_root = \"./blog\"\n", BASE);
+        let plural_config = Config::from_toml_str(&plural);
+        assert_eq!(plural_config.vhost(Some("blog.example.com")).unwrap().www_roots(), ["./blog".to_string()]);
+    }
+    #[test]
+    fn test_allowed_methods() {
+        use HttpRequestMethod::*;
+        let config = Config::from_toml_str(BASE);
+        assert_eq!(config.allowed_methods(), vec![Get, Head, Options]);
+
+        let custom = format!("{}\nallowed_methods = [\"get\", \"HEAD\", \"GET\"]", BASE);
+        assert_eq!(Config::from_toml_str(&custom).allowed_methods(), vec![Get, Head]);
+
+        let unknown = format!("{}\nallowed_methods = [\"GET\", \"DELETE\"]", BASE);
+        let config = Config::from_toml_str(&unknown);
+        assert_eq!(config.allowed_methods(), vec![Get]);
+        assert_eq!(config.validate(), Err(ConfigError::UnknownMethod("DELETE".to_string())));
+    }
+    #[test]
+    fn test_php_path() {
+        assert_eq!(Config::from_toml_str(BASE).php_path(), "php");
+        let custom = format!("{}\nphp_path = \"/usr/bin/php8.2\"", BASE);
+        assert_eq!(Config::from_toml_str(&custom).php_path(), "/usr/bin/php8.2");
+        let empty = format!("{}\nphp_path = \"\"", BASE);
+        assert_eq!(Config::from_toml_str(&empty).php_path(), "php");
+    }
+    #[test]
+    fn test_fastcgi_address() {
+        assert_eq!(Config::from_toml_str(BASE).fastcgi_address(), None);
+        let tcp = format!("{}\nfastcgi_address = \"127.0.0.1:9000\"", BASE);
+        assert_eq!(Config::from_toml_str(&tcp).fastcgi_address(), Some(FastCgiAddress::Tcp("127.0.0.1:9000".to_string())));
+        let unix = format!("{}\nfastcgi_address = \"unix:/run/php/php-fpm.sock\"", BASE);
+        assert_eq!(
+            Config::from_toml_str(&unix).fastcgi_address(),
+            Some(FastCgiAddress::Unix("/run/php/php-fpm.sock".to_string()))
+        );
+        let bad = format!("{}\nfastcgi_address = \"php-fpm\"", BASE);
+        let config = Config::from_toml_str(&bad);
+        assert_eq!(config.fastcgi_address(), None);
+        assert_eq!(config.validate(), Err(ConfigError::InvalidFastCgiAddress("php-fpm".to_string())));
+    }
+    #[test]
+    fn test_missing_www_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("site/public");
+        let missing = missing.to_str().unwrap();
+        let toml = format!("www_root = \"{}\"\nport = 7878\nworker_threads = 1\ncache_size = 5\nlocal = true\n", missing);
+
+        // 默认不创建：校验给出包含路径的明确错误
+        let config = Config::from_toml_str(&toml);
+        assert!(!config.create_www_root());
+        let err = config.validate().unwrap_err();
+        assert_eq!(err, ConfigError::WwwRootNotFound(missing.to_string()));
+        assert!(err.to_string().contains(missing));
+
+        // 开启 create_www_root 后创建缺失的目录，随后校验通过
+        let config = Config::from_toml_str(&format!("{}create_www_root = true\n", toml));
+        assert!(config.create_www_root());
+        assert_eq!(config.create_missing_roots().unwrap(), vec![missing.to_string()]);
+        assert!(std::path::Path::new(missing).is_dir());
+        assert_eq!(config.validate(), Ok(()));
+        assert!(config.create_missing_roots().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,163 @@
+// Copyright (c) 2026 shaneyale (shaneyale86@gmail.com)
+// All rights reserved.
+
+//! # 跨域资源共享模块
+//!
+//! 按配置文件中的 `[cors]` 表（见 [`CorsConfig`]）处理浏览器的跨域请求：
+//!
+//! - **预检请求**：带 `Origin` 与 `Access-Control-Request-Method` 的 `OPTIONS` 请求，来源被允许时由 [`preflight`]
+//!   直接生成 204 响应，列出允许的方法（`Access-Control-Allow-Methods`）、请求头（`Access-Control-Allow-Headers`）
+//!   与缓存时长（`Access-Control-Max-Age`）。预检请求不携带凭据，因此在鉴权之前应答。
+//! - **普通请求**：[`apply`] 在来源被允许时为响应追加 `Access-Control-Allow-Origin`（以及启用凭据时的
+//!   `Access-Control-Allow-Credentials`），错误响应同样追加，使浏览器中的脚本能读到 401、404 等状态。
+//!
+//! 响应头随请求的 `Origin` 变化时同时追加 `Vary: Origin`，避免共享缓存把一个来源的响应交给另一个来源。
+//! 来源不被允许时不追加任何 CORS 响应头，由浏览器拒绝脚本读取响应；服务器本身不拒绝请求。
+
+use crate::{config::CorsConfig, param::HttpRequestMethod, request::Request, response::Response};
+
+use log::debug;
+
+/// 若请求的 `Origin` 被 `cors` 允许，返回该来源。
+///
+/// 来源按 ASCII 不区分大小写比较；`"*"` 匹配任意来源（包括 `null`）。未启用跨域处理或请求没有 `Origin` 时返回 `None`。
+pub fn allowed_origin<'a>(request: &'a Request, cors: &CorsConfig) -> Option<&'a str> {
+    let origin = request.header("origin")?.trim();
+    cors.origins()
+        .iter()
+        .any(|allowed| allowed == "*" || allowed.trim_end_matches('/').eq_ignore_ascii_case(origin))
+        .then_some(origin)
+}
+
+/// 判断请求是否为 CORS 预检请求：带 `Origin` 与 `Access-Control-Request-Method` 的 `OPTIONS` 请求。
+pub fn is_preflight(request: &Request) -> bool {
+    request.method() == HttpRequestMethod::Options
+        && request.header("origin").is_some()
+        && request.header("access-control-request-method").is_some()
+}
+
+/// 应答预检请求。
+///
+/// 请求是预检请求且来源被允许时返回 204 响应，允许的方法取 `[cors] methods`，未配置时取 `enabled_methods`
+/// （即服务器启用的方法）。`Access-Control-Allow-Origin` 由随后的 [`apply`] 统一追加。
+/// 其余情况返回 `None`，请求按普通的 `OPTIONS` 请求处理。
+pub fn preflight(request: &Request, cors: &CorsConfig, enabled_methods: &[HttpRequestMethod], id: u128) -> Option<Response> {
+    if !is_preflight(request) {
+        return None;
+    }
+    let origin = allowed_origin(request, cors)?;
+    debug!("[ID{}]应答来自{}的CORS预检请求", id, origin);
+
+    let methods = match cors.methods() {
+        methods if methods.is_empty() => enabled_methods.to_vec(),
+        methods => methods,
+    };
+    let methods: Vec<String> = methods.iter().map(|m| m.to_string()).collect();
+    let mut response = Response::response_204(request, id);
+    response.add_header("Access-Control-Allow-Methods", &methods.join(", "));
+    if !cors.headers().is_empty() {
+        response.add_header("Access-Control-Allow-Headers", &cors.headers().join(", "));
+    }
+    if let Some(max_age) = cors.max_age_secs() {
+        response.add_header("Access-Control-Max-Age", &max_age.to_string());
+    }
+    Some(response)
+}
+
+/// 为来源被允许的请求追加 `Access-Control-Allow-Origin` 等响应头。
+///
+/// 配置了 `"*"` 且不允许凭据时响应 `*`，与请求的来源无关，因此不追加 `Vary`；
+/// 其余情况回显请求的来源，并追加 `Vary: Origin`（来源不被允许时同样追加）。
+pub fn apply(request: &Request, cors: &CorsConfig, response: &mut Response) {
+    if !cors.enabled() {
+        return;
+    }
+    let wildcard = !cors.allow_credentials() && cors.origins().iter().any(|o| o == "*");
+    if let Some(origin) = allowed_origin(request, cors) {
+        response.add_header("Access-Control-Allow-Origin", if wildcard { "*" } else { origin });
+        if cors.allow_credentials() {
+            response.add_header("Access-Control-Allow-Credentials", "true");
+        }
+    }
+    if !wildcard {
+        response.add_header("Vary", "Origin");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cors(table: &str) -> CorsConfig {
+        toml::from_str(table).unwrap()
+    }
+
+    fn request(method: &str, headers: &str) -> Request {
+        let raw = format!("{} /api/items HTTP/1.1\r\nHost: localhost\r\n{}\r\n", method, headers);
+        Request::try_from(&raw.into_bytes(), 0).unwrap()
+    }
+
+    fn serialize(response: &Response) -> String {
+        String::from_utf8(response.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_preflight() {
+        let config = cors("origins = [\"https://app.example\"]\nheaders = [\"Content-Type\", \"X-Token\"]\nmax_age_secs = 300");
+        let enabled = [HttpRequestMethod::Get, HttpRequestMethod::Head, HttpRequestMethod::Options];
+        let preflight_from = |origin: &str| {
+            let headers = format!("Origin: {}\r\nAccess-Control-Request-Method: GET\r\n", origin);
+            preflight(&request("OPTIONS", &headers), &config, &enabled, 0)
+        };
+
+        let mut response = preflight_from("https://APP.example").expect("来源被允许");
+        apply(&request("OPTIONS", "Origin: https://APP.example\r\n"), &config, &mut response);
+        let text = serialize(&response);
+        assert!(text.starts_with("HTTP/1.1 204 No Content\r\n"), "{}", text);
+        assert!(text.contains("\r\nAccess-Control-Allow-Methods: GET, HEAD, OPTIONS\r\n"), "{}", text);
+        assert!(text.contains("\r\nAccess-Control-Allow-Headers: Content-Type, X-Token\r\n"), "{}", text);
+        assert!(text.contains("\r\nAccess-Control-Max-Age: 300\r\n"), "{}", text);
+        assert!(text.contains("\r\nAccess-Control-Allow-Origin: https://APP.example\r\n"), "{}", text);
+        assert!(text.contains("\r\nVary: Origin\r\n"), "{}", text);
+
+        // 来源不被允许或不是预检请求时按普通请求处理
+        assert!(preflight_from("https://evil.example").is_none());
+        assert!(preflight(&request("OPTIONS", "Origin: https://app.example\r\n"), &config, &enabled, 0).is_none());
+        assert!(preflight(&request("OPTIONS", ""), &CorsConfig::default(), &enabled, 0).is_none());
+
+        // 配置的方法优先于服务器启用的方法
+        let config = cors("origins = [\"*\"]\nmethods = [\"POST\"]\nmax_age_secs = 0");
+        let headers = "Origin: https://any.example\r\nAccess-Control-Request-Method: POST\r\n";
+        let text = serialize(&preflight(&request("OPTIONS", headers), &config, &enabled, 0).unwrap());
+        assert!(text.contains("\r\nAccess-Control-Allow-Methods: POST\r\n"), "{}", text);
+        assert!(!text.contains("Access-Control-Max-Age"), "{}", text);
+        assert!(!text.contains("Access-Control-Allow-Headers"), "{}", text);
+    }
+
+    #[test]
+    fn test_apply() {
+        let get = |origin: &str, config: &CorsConfig| {
+            let mut response = Response::response_204(&request("GET", ""), 0);
+            apply(&request("GET", &format!("Origin: {}\r\n", origin)), config, &mut response);
+            serialize(&response)
+        };
+
+        // 未启用时不追加任何响应头
+        let text = get("https://app.example", &CorsConfig::default());
+        assert!(!text.contains("Access-Control") && !text.contains("Vary"), "{}", text);
+
+        let config = cors("origins = [\"https://app.example\"]\nallow_credentials = true");
+        let text = get("https://app.example", &config);
+        assert!(text.contains("\r\nAccess-Control-Allow-Origin: https://app.example\r\n"), "{}", text);
+        assert!(text.contains("\r\nAccess-Control-Allow-Credentials: true\r\n"), "{}", text);
+        assert!(text.contains("\r\nVary: Origin\r\n"), "{}", text);
+        let text = get("https://evil.example", &config);
+        assert!(!text.contains("Access-Control"), "{}", text);
+        assert!(text.contains("\r\nVary: Origin\r\n"), "{}", text);
+
+        // 任意来源且不允许凭据时响应 *，不随来源变化
+        let text = get("https://any.example", &cors("origins = [\"*\"]"));
+        assert!(text.contains("\r\nAccess-Control-Allow-Origin: *\r\n"), "{}", text);
+        assert!(!text.contains("Vary") && !text.contains("Credentials"), "{}", text);
+    }
+}
@@ -24,14 +24,47 @@ pub enum Exception {
     UnSupportedRequestMethod,
     /// 客户端使用了服务器不支持的 HTTP 协议版本（例如：HTTP/0.9 或过高的版本）。
     UnsupportedHttpVersion,
+    /// 请求行不是“方法 目标 版本”的形式，例如缺少协议版本或夹带了换行。对应 `400 Bad Request`。
+    MalformedRequestLine,
     /// 在指定的资源根目录下未找到所请求的文件。在 Web 语义中对应 `404 Not Found`。
     FileNotFound,
+    /// 文件存在但无法读取（如权限不足、读取时发生 I/O 错误）。对应 `500 Internal Server Error`。
+    FileUnreadable,
     /// 请求的路径格式非法或包含越权尝试（如目录遍历攻击）。对应 `400 Bad Request`。
     InvalidPath,
     /// 调用 PHP 解释器执行脚本失败。通常是由于环境配置错误或二进制路径无效引起的。
     PHPExecuteFailed,
     /// PHP 脚本内部运行错误。代表脚本已启动但执行过程中崩溃，对应 `500 Internal Server Error`。
     PHPCodeError,
+    /// PHP 脚本运行超过 `script_timeout_secs`，进程已被终止，对应 `504 Gateway Timeout`。
+    ScriptTimeout,
+    /// PHP 脚本的输出超过 `[limits] max_script_output_bytes`，进程已被终止，对应 `502 Bad Gateway`。
+    ScriptOutputTooLarge,
+    /// 请求的消息长度有歧义：出现多个取值不同的 `Content-Length`，
+    /// 或同时出现 `Content-Length` 与 `Transfer-Encoding`。这是请求走私的典型特征，对应 `400 Bad Request`。
+    AmbiguousLength,
+    /// 查询字符串超过 `[limits] max_query_length`，对应 `414 URI Too Long`。
+    QueryTooLong,
+    /// 请求目标（URI）超过 `[limits] max_uri_length`，对应 `414 URI Too Long`。
+    UriTooLong,
+    /// 请求体（解压后）超过 `[limits] max_body_bytes`，对应 `413 Content Too Large`。
+    BodyTooLarge,
+    /// 请求体的压缩数据已损坏，无法按 `Content-Encoding` 解压，对应 `400 Bad Request`。
+    MalformedBody,
+    /// 请求体使用了服务器不支持的 `Content-Encoding`，对应 `415 Unsupported Media Type`。
+    UnsupportedContentEncoding,
+    /// 请求头（含请求行）超过 `[limits] max_header_bytes`，对应 `431 Request Header Fields Too Large`。
+    HeaderTooLarge,
+    /// 连接在请求体读取完毕之前关闭或出错：收到的字节少于 `Content-Length`，或缺少分块编码的结束块。
+    /// 对应 `400 Bad Request`。
+    IncompleteBody,
+    /// 分块编码（`Transfer-Encoding: chunked`）的请求体格式错误，如块大小不是十六进制数字或缺少 CRLF，
+    /// 对应 `400 Bad Request`。
+    MalformedChunk,
+    /// 反向代理无法连接上游，或上游返回了无法解析的响应，对应 `502 Bad Gateway`。
+    BadGateway,
+    /// 反向代理连接上游或等待上游响应超过 `proxy_timeout_secs`，对应 `504 Gateway Timeout`。
+    GatewayTimeout,
 }
 
 use Exception::*;
@@ -46,10 +79,25 @@ impl fmt::Display for Exception {
             RequestIsNotUtf8 => write!(f, "Request bytes can't be parsed in UTF-8"),
             UnSupportedRequestMethod => write!(f, "Unsupported request method"),
             UnsupportedHttpVersion => write!(f, "Unsupported HTTP version"),
+            MalformedRequestLine => write!(f, "Malformed request line (400)"),
             FileNotFound => write!(f, "File not found (404)"),
+            FileUnreadable => write!(f, "File could not be read (500)"),
             InvalidPath => write!(f, "Invalid path (400)"),
             PHPExecuteFailed => write!(f, "Couldn't invoke PHP interpreter"),
             PHPCodeError => write!(f, "An error happened in php code"),
+            ScriptTimeout => write!(f, "PHP script timed out (504)"),
+            ScriptOutputTooLarge => write!(f, "PHP script output too large (502)"),
+            AmbiguousLength => write!(f, "Ambiguous message length (400)"),
+            QueryTooLong => write!(f, "Query string too long (414)"),
+            UriTooLong => write!(f, "URI too long (414)"),
+            BodyTooLarge => write!(f, "Request body too large (413)"),
+            MalformedBody => write!(f, "Malformed compressed request body (400)"),
+            UnsupportedContentEncoding => write!(f, "Unsupported request content encoding (415)"),
+            HeaderTooLarge => write!(f, "Request header fields too large (431)"),
+            IncompleteBody => write!(f, "Connection closed before the request body was complete (400)"),
+            MalformedChunk => write!(f, "Malformed chunked request body (400)"),
+            BadGateway => write!(f, "Upstream server unreachable or sent an invalid response (502)"),
+            GatewayTimeout => write!(f, "Upstream server timed out (504)"),
         }
     }
 }
\ No newline at end of file
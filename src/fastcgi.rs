@@ -0,0 +1,425 @@
+// Copyright (c) 2026 shaneyale (shaneyale86@gmail.com)
+// All rights reserved.
+
+//! # FastCGI 模块
+//!
+//! 实现 FastCGI 协议的客户端（Responder 角色），把 PHP 请求交给 php-fpm 等常驻进程处理，
+//! 代替每个请求启动一次 `php` 解释器。CGI 环境变量通过 `FCGI_PARAMS` 传递，请求体通过 `FCGI_STDIN` 传递；
+//! 脚本输出的 CGI 响应头解析完成后，响应体随 `FCGI_STDOUT` 记录逐块流式返回。
+//!
+//! 每个请求使用一条新连接，不复用连接（`FCGI_KEEP_CONN` 未设置），因此请求 ID 固定为 1。
+//! 协议细节见 FastCGI 规范 1.0。
+
+use crate::{exception::Exception, util::{parse_cgi_output, CgiOutput}};
+
+use bytes::Bytes;
+use log::{debug, error, warn};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::TcpStream,
+    sync::mpsc,
+    time::timeout,
+};
+
+use std::{io, time::Duration};
+
+/// FastCGI 协议版本
+const FCGI_VERSION_1: u8 = 1;
+/// 记录类型：开始请求
+const FCGI_BEGIN_REQUEST: u8 = 1;
+/// 记录类型：请求结束
+const FCGI_END_REQUEST: u8 = 3;
+/// 记录类型：CGI 环境变量
+const FCGI_PARAMS: u8 = 4;
+/// 记录类型：请求体
+const FCGI_STDIN: u8 = 5;
+/// 记录类型：脚本的标准输出
+const FCGI_STDOUT: u8 = 6;
+/// 记录类型：脚本的标准错误
+const FCGI_STDERR: u8 = 7;
+/// 应用角色：Responder，即普通的请求-响应
+const FCGI_RESPONDER: u16 = 1;
+/// 本客户端在每条连接上只发送一个请求，使用固定的请求 ID
+const REQUEST_ID: u16 = 1;
+/// 单条记录内容的最大长度
+const MAX_RECORD_CONTENT: usize = 65535;
+/// 查找 CGI 响应头与响应体之间空行的最大范围，超过时整个输出视为响应体
+const MAX_HEAD_BYTES: usize = 16384;
+/// 转发响应体时通道中最多缓存的块数
+const BODY_CHANNEL_CAPACITY: usize = 4;
+
+/// FastCGI 应用返回的响应：已解析的 CGI 响应头与流式的响应体。
+#[derive(Debug)]
+pub struct FastCgiResponse {
+    /// 按 CGI 约定解析出的状态码与响应头，`body` 字段始终为空
+    pub head: CgiOutput,
+    /// 响应体的各个数据块；应用出错或超时时收到 `Err`
+    pub body: mpsc::Receiver<io::Result<Bytes>>,
+}
+
+/// FastCGI 应用的地址：`host:port` 形式的 TCP 地址，或 `unix:/path/to/php-fpm.sock` 形式的 Unix 域套接字。
+#[derive(Debug, Clone, PartialEq)]
+pub enum FastCgiAddress {
+    /// TCP 地址，如 `127.0.0.1:9000`、`php:9000`
+    Tcp(String),
+    /// Unix 域套接字路径
+    Unix(String),
+}
+
+impl std::str::FromStr for FastCgiAddress {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(path) = s.strip_prefix("unix:") {
+            return match path.is_empty() {
+                true => Err(format!("FastCGI 地址 {} 缺少套接字路径", s)),
+                false => Ok(Self::Unix(path.to_string())),
+            };
+        }
+        match s.rsplit_once(':') {
+            Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok_and(|p| p != 0) => Ok(Self::Tcp(s.to_string())),
+            _ => Err(format!("FastCGI 地址 {} 必须是 host:port 或 unix:/path", s)),
+        }
+    }
+}
+
+/// 连接 FastCGI 应用，发送请求并等待 CGI 响应头。
+///
+/// 连接、发送请求与等待响应头受 `wait` 限制；之后响应体的每次读取同样受其限制。
+/// 响应体超过 `max_output` 字节时向响应体通道发送错误并断开连接。
+///
+/// # 错误
+///
+/// - 无法连接应用、应用拒绝请求或协议格式错误时返回 [`Exception::BadGateway`]（对应 502）。
+/// - 超时返回 [`Exception::ScriptTimeout`]（对应 504）。
+/// - 响应头之前的输出已超过 `max_output` 时返回 [`Exception::ScriptOutputTooLarge`]（对应 502）。
+pub async fn run(
+    address: &FastCgiAddress,
+    params: &[(String, String)],
+    stdin: &[u8],
+    id: u128,
+    wait: Duration,
+    max_output: usize,
+) -> Result<FastCgiResponse, Exception> {
+    debug!("[ID{}]通过FastCGI({:?})执行脚本", id, address);
+    let connected = match address {
+        FastCgiAddress::Tcp(addr) => match timeout(wait, TcpStream::connect(addr.as_str())).await {
+            Ok(Ok(stream)) => Ok(start(stream, params, stdin, id, wait, max_output).await?),
+            Ok(Err(e)) => Err(e.to_string()),
+            Err(_) => return Err(timed_out(id)),
+        },
+        #[cfg(unix)]
+        FastCgiAddress::Unix(path) => match timeout(wait, tokio::net::UnixStream::connect(path)).await {
+            Ok(Ok(stream)) => Ok(start(stream, params, stdin, id, wait, max_output).await?),
+            Ok(Err(e)) => Err(e.to_string()),
+            Err(_) => return Err(timed_out(id)),
+        },
+        #[cfg(not(unix))]
+        FastCgiAddress::Unix(_) => Err("当前平台不支持 Unix 域套接字".to_string()),
+    };
+    connected.map_err(|e| {
+        error!("[ID{}]无法连接FastCGI应用{:?}：{}", id, address, e);
+        Exception::BadGateway
+    })
+}
+
+fn timed_out(id: u128) -> Exception {
+    error!("[ID{}]FastCGI应用响应超时", id);
+    Exception::ScriptTimeout
+}
+
+/// 在已建立的连接上发送请求、读取响应头，然后在后台任务中转发响应体
+async fn start<S>(
+    mut stream: S,
+    params: &[(String, String)],
+    stdin: &[u8],
+    id: u128,
+    wait: Duration,
+    max_output: usize,
+) -> Result<FastCgiResponse, Exception>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let request = encode_request(params, stdin);
+    match timeout(wait, stream.write_all(&request)).await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => {
+            error!("[ID{}]向FastCGI应用发送请求失败：{}", id, e);
+            return Err(Exception::BadGateway);
+        }
+        Err(_) => return Err(timed_out(id)),
+    }
+
+    // 读取 STDOUT 直到出现 CGI 响应头与响应体之间的空行，或输出结束
+    let mut output = Vec::new();
+    let mut finished = false;
+    while !finished && head_end(&output).is_none() && output.len() < MAX_HEAD_BYTES {
+        let record = match timeout(wait, next_output(&mut stream, id)).await {
+            Ok(record) => record.map_err(|e| {
+                error!("[ID{}]读取FastCGI响应失败：{}", id, e);
+                Exception::BadGateway
+            })?,
+            Err(_) => return Err(timed_out(id)),
+        };
+        match record {
+            Some(data) => output.extend_from_slice(&data),
+            None => finished = true,
+        }
+        if output.len() > max_output {
+            error!("[ID{}]FastCGI脚本的输出超过{}字节", id, max_output);
+            return Err(Exception::ScriptOutputTooLarge);
+        }
+    }
+
+    // 输出上限包含响应头
+    let mut sent = output.len();
+    // 不输出响应头的脚本：全部输出都是响应体
+    let (head, rest) = match head_end(&output) {
+        Some(end) => {
+            let head = parse_cgi_output(&String::from_utf8_lossy(&output[..end]));
+            match head.body.is_empty() {
+                true => (head, output.split_off(end)),
+                false => (CgiOutput::default(), output),
+            }
+        }
+        None => (CgiOutput::default(), output),
+    };
+
+    let (tx, rx) = mpsc::channel(BODY_CHANNEL_CAPACITY);
+    if !rest.is_empty() {
+        let _ = tx.try_send(Ok(Bytes::from(rest)));
+    }
+    if !finished {
+        tokio::spawn(async move {
+            loop {
+                let record = match timeout(wait, next_output(&mut stream, id)).await {
+                    Ok(record) => record,
+                    Err(_) => Err(io::Error::new(io::ErrorKind::TimedOut, "FastCGI应用响应超时")),
+                };
+                let data = match record {
+                    Ok(Some(data)) => data,
+                    Ok(None) => return,
+                    Err(e) => {
+                        warn!("[ID{}]读取FastCGI响应体失败：{}", id, e);
+                        let _ = tx.send(Err(e)).await;
+                        return;
+                    }
+                };
+                sent += data.len();
+                if sent > max_output {
+                    error!("[ID{}]FastCGI脚本的输出超过{}字节，断开连接", id, max_output);
+                    let _ = tx.send(Err(io::Error::other("脚本输出过大"))).await;
+                    return;
+                }
+                // 客户端已断开：停止读取，丢弃连接使应用中止请求
+                if tx.send(Ok(Bytes::from(data))).await.is_err() {
+                    return;
+                }
+            }
+        });
+    }
+    Ok(FastCgiResponse { head, body: rx })
+}
+
+/// CGI 响应头结束的位置（含空行），没有空行时返回 `None`
+fn head_end(output: &[u8]) -> Option<usize> {
+    [&b"\r\n\r\n"[..], &b"\n\n"[..]]
+        .iter()
+        .filter_map(|sep| output.windows(sep.len()).position(|w| w == *sep).map(|pos| pos + sep.len()))
+        .min()
+}
+
+/// 读取记录直到得到一段 STDOUT 数据；STDERR 写入日志，`FCGI_END_REQUEST` 时返回 `None`。
+async fn next_output<S: AsyncRead + Unpin>(stream: &mut S, id: u128) -> io::Result<Option<Vec<u8>>> {
+    loop {
+        let (kind, content) = read_record(stream).await?;
+        match kind {
+            FCGI_STDOUT if !content.is_empty() => return Ok(Some(content)),
+            FCGI_STDOUT => {}
+            FCGI_STDERR => warn!("[ID{}]FastCGI应用输出错误：{}", id, String::from_utf8_lossy(&content).trim_end()),
+            FCGI_END_REQUEST => {
+                // 协议状态非 0 表示应用拒绝了请求（如过载）
+                if content.len() >= 5 && content[4] != 0 {
+                    return Err(io::Error::other(format!("FastCGI应用拒绝了请求，协议状态{}", content[4])));
+                }
+                return Ok(None);
+            }
+            other => debug!("[ID{}]忽略FastCGI记录类型{}", id, other),
+        }
+    }
+}
+
+/// 读取一条记录，返回记录类型与内容（已去掉填充）
+async fn read_record<S: AsyncRead + Unpin>(stream: &mut S) -> io::Result<(u8, Vec<u8>)> {
+    let mut header = [0u8; 8];
+    stream.read_exact(&mut header).await?;
+    if header[0] != FCGI_VERSION_1 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "FastCGI协议版本不正确"));
+    }
+    let length = u16::from_be_bytes([header[4], header[5]]) as usize;
+    let padding = header[6] as usize;
+    let mut content = vec![0u8; length + padding];
+    stream.read_exact(&mut content).await?;
+    content.truncate(length);
+    Ok((header[1], content))
+}
+
+/// 编码一条记录。`content` 不得超过 [`MAX_RECORD_CONTENT`] 字节
+fn encode_record(out: &mut Vec<u8>, kind: u8, content: &[u8]) {
+    let length = content.len() as u16;
+    // 按规范建议将记录对齐到 8 字节
+    let padding = (8 - content.len() % 8) % 8;
+    out.extend_from_slice(&[FCGI_VERSION_1, kind]);
+    out.extend_from_slice(&REQUEST_ID.to_be_bytes());
+    out.extend_from_slice(&length.to_be_bytes());
+    out.extend_from_slice(&[padding as u8, 0]);
+    out.extend_from_slice(content);
+    out.resize(out.len() + padding, 0);
+}
+
+/// 将数据流拆分为若干条记录，并以一条空记录结束
+fn encode_stream(out: &mut Vec<u8>, kind: u8, data: &[u8]) {
+    for chunk in data.chunks(MAX_RECORD_CONTENT) {
+        encode_record(out, kind, chunk);
+    }
+    encode_record(out, kind, &[]);
+}
+
+/// 名值对的长度：小于 128 时占 1 字节，否则占 4 字节且最高位为 1
+fn encode_length(out: &mut Vec<u8>, len: usize) {
+    if len < 128 {
+        out.push(len as u8);
+    } else {
+        out.extend_from_slice(&(len as u32 | 0x8000_0000).to_be_bytes());
+    }
+}
+
+/// 编码完整的请求：`FCGI_BEGIN_REQUEST`、`FCGI_PARAMS` 流与 `FCGI_STDIN` 流
+fn encode_request(params: &[(String, String)], stdin: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut begin = FCGI_RESPONDER.to_be_bytes().to_vec();
+    begin.extend_from_slice(&[0u8; 6]); // flags = 0：应用在响应后关闭连接
+    encode_record(&mut out, FCGI_BEGIN_REQUEST, &begin);
+
+    let mut pairs = Vec::new();
+    for (name, value) in params {
+        encode_length(&mut pairs, name.len());
+        encode_length(&mut pairs, value.len());
+        pairs.extend_from_slice(name.as_bytes());
+        pairs.extend_from_slice(value.as_bytes());
+    }
+    encode_stream(&mut out, FCGI_PARAMS, &pairs);
+    encode_stream(&mut out, FCGI_STDIN, stdin);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    /// 从编码后的请求中解出全部记录
+    fn decode_records(mut data: &[u8]) -> Vec<(u8, Vec<u8>)> {
+        let mut records = Vec::new();
+        while !data.is_empty() {
+            let length = u16::from_be_bytes([data[4], data[5]]) as usize;
+            let padding = data[6] as usize;
+            assert_eq!((8 + length + padding) % 8, 0);
+            records.push((data[1], data[8..8 + length].to_vec()));
+            data = &data[8 + length + padding..];
+        }
+        records
+    }
+
+    #[test]
+    fn test_parse_address() {
+        assert_eq!("127.0.0.1:9000".parse(), Ok(FastCgiAddress::Tcp("127.0.0.1:9000".to_string())));
+        assert_eq!("unix:/run/php-fpm.sock".parse(), Ok(FastCgiAddress::Unix("/run/php-fpm.sock".to_string())));
+        for bad in ["php", "php:", ":9000", "php:0", "unix:"] {
+            assert!(bad.parse::<FastCgiAddress>().is_err(), "{}", bad);
+        }
+    }
+
+    /// 请求由 BEGIN_REQUEST、PARAMS 流与 STDIN 流组成，长名值使用 4 字节长度
+    #[test]
+    fn test_encode_request() {
+        let long = "x".repeat(200);
+        let params = vec![("A".to_string(), "1".to_string()), ("LONG".to_string(), long.clone())];
+        let stdin = vec![7u8; MAX_RECORD_CONTENT + 1];
+        let records = decode_records(&encode_request(&params, &stdin));
+
+        let kinds: Vec<u8> = records.iter().map(|(kind, _)| *kind).collect();
+        assert_eq!(kinds, [FCGI_BEGIN_REQUEST, FCGI_PARAMS, FCGI_PARAMS, FCGI_STDIN, FCGI_STDIN, FCGI_STDIN]);
+        assert_eq!(records[0].1, [0, 1, 0, 0, 0, 0, 0, 0]);
+        let mut expected = vec![1, 1, b'A', b'1', 4, 0x80, 0, 0, 200];
+        expected.extend_from_slice(b"LONG");
+        expected.extend_from_slice(long.as_bytes());
+        assert_eq!(records[1].1, expected);
+        assert!(records[2].1.is_empty());
+        assert_eq!(records[3].1.len() + records[4].1.len(), stdin.len());
+        assert!(records[5].1.is_empty());
+    }
+
+    /// 启动只处理一个请求的 FastCGI 应用：读完请求后依次返回给定的 STDOUT 片段
+    async fn one_shot_app(stdout: &'static [&'static [u8]]) -> FastCgiAddress {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = FastCgiAddress::Tcp(listener.local_addr().unwrap().to_string());
+        tokio::spawn(async move {
+            let (mut conn, _) = listener.accept().await.unwrap();
+            // 读到空的 STDIN 记录为止
+            loop {
+                let (kind, content) = read_record(&mut conn).await.unwrap();
+                if kind == FCGI_STDIN && content.is_empty() {
+                    break;
+                }
+            }
+            let mut out = Vec::new();
+            encode_record(&mut out, FCGI_STDERR, b"notice");
+            for part in stdout {
+                encode_record(&mut out, FCGI_STDOUT, part);
+            }
+            encode_record(&mut out, FCGI_END_REQUEST, &[0; 8]);
+            conn.write_all(&out).await.unwrap();
+        });
+        address
+    }
+
+    async fn collect(mut body: mpsc::Receiver<io::Result<Bytes>>) -> Vec<u8> {
+        let mut out = Vec::new();
+        while let Some(chunk) = body.recv().await {
+            out.extend_from_slice(&chunk.unwrap());
+        }
+        out
+    }
+
+    /// 响应头跨越多条 STDOUT 记录时同样能够解析，其后的内容作为响应体流式返回
+    #[tokio::test]
+    async fn test_run_streams_response() {
+        let address = one_shot_app(&[b"Status: 201 Created\r\nContent-Type: text/plain\r\nX-A: 1\r", b"\n\r\nhello ", b"world"]).await;
+        let response = run(&address, &[], b"body", 0, Duration::from_secs(5), 1024).await.unwrap();
+        assert_eq!(response.head.status, Some(201));
+        assert_eq!(response.head.content_type.as_deref(), Some("text/plain"));
+        assert_eq!(response.head.headers, vec![("X-A".to_string(), "1".to_string())]);
+        assert_eq!(collect(response.body).await, b"hello world");
+
+        // 不输出响应头的脚本：全部输出都是响应体
+        let address = one_shot_app(&[b"<p>plain</p>"]).await;
+        let response = run(&address, &[], b"", 0, Duration::from_secs(5), 1024).await.unwrap();
+        assert_eq!(response.head, CgiOutput::default());
+        assert_eq!(collect(response.body).await, b"<p>plain</p>");
+    }
+
+    /// 应用不可达时返回 BadGateway；输出超限时响应体以错误结束
+    #[tokio::test]
+    async fn test_run_errors() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = FastCgiAddress::Tcp(listener.local_addr().unwrap().to_string());
+        drop(listener);
+        let result = run(&address, &[], b"", 0, Duration::from_secs(5), 1024).await;
+        assert!(matches!(result, Err(Exception::BadGateway)));
+
+        let address = one_shot_app(&[b"Content-Type: text/plain\r\n\r\n", b"0123456789"]).await;
+        let mut response = run(&address, &[], b"", 0, Duration::from_secs(5), 32).await.unwrap();
+        assert!(response.body.recv().await.unwrap().is_err());
+    }
+}
@@ -0,0 +1,566 @@
+// Copyright (c) 2026 shaneyale (shaneyale86@gmail.com)
+// All rights reserved.
+
+//! # HPACK 模块
+//!
+//! 实现 HTTP/2 的头部压缩格式（RFC 7541），供 [`crate::http2`] 编解码 HEADERS 帧中的头部块。
+//!
+//! - **解码**：完整支持静态表、动态表（含表大小更新与淘汰）以及 Huffman 编码的字符串，
+//!   浏览器与 curl 发送的头部块都依赖动态表。
+//! - **编码**：只使用静态表与“不索引的字面量”，从不向对端的动态表插入条目。
+//!   编码器因此没有状态，各个流可以并发编码自己的响应头，发送顺序不影响对端解码。
+//!   字符串在 Huffman 编码更短时使用 Huffman 编码。
+
+use lazy_static::lazy_static;
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+/// 动态表条目在计算表大小时额外占用的字节数（RFC 7541 §4.1）
+const ENTRY_OVERHEAD: usize = 32;
+
+/// 解码头部块失败的原因。除 `HeaderListTooLarge` 外都属于 HTTP/2 的 `COMPRESSION_ERROR`，连接随之终止。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HpackError {
+    /// 头部块在一个字段表示的中途结束。
+    Truncated,
+    /// 整数编码超过了合理的范围。
+    IntegerOverflow,
+    /// 索引为 0 或超出静态表与动态表的范围。
+    InvalidIndex,
+    /// Huffman 编码的字符串格式错误：包含 EOS 符号，或末尾的填充不是全 1 / 超过 7 位。
+    InvalidHuffman,
+    /// 动态表大小更新超过了本端允许的上限，或出现在头部块中间。
+    InvalidTableSizeUpdate,
+    /// 解码后的头部列表超过了调用方给出的上限。动态表仍保持同步，只需拒绝这一个请求。
+    HeaderListTooLarge,
+}
+
+impl fmt::Display for HpackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HpackError::Truncated => write!(f, "头部块不完整"),
+            HpackError::IntegerOverflow => write!(f, "整数编码溢出"),
+            HpackError::InvalidIndex => write!(f, "无效的表索引"),
+            HpackError::InvalidHuffman => write!(f, "Huffman编码格式错误"),
+            HpackError::InvalidTableSizeUpdate => write!(f, "无效的动态表大小更新"),
+            HpackError::HeaderListTooLarge => write!(f, "头部列表过大"),
+        }
+    }
+}
+
+impl std::error::Error for HpackError {}
+
+/// 静态表（RFC 7541 附录 A），索引从 1 开始
+const STATIC_TABLE: [(&str, &str); 61] = [
+    (":authority", ""),
+    (":method", "GET"),
+    (":method", "POST"),
+    (":path", "/"),
+    (":path", "/index.html"),
+    (":scheme", "http"),
+    (":scheme", "https"),
+    (":status", "200"),
+    (":status", "204"),
+    (":status", "206"),
+    (":status", "304"),
+    (":status", "400"),
+    (":status", "404"),
+    (":status", "500"),
+    ("accept-charset", ""),
+    ("accept-encoding", "gzip, deflate"),
+    ("accept-language", ""),
+    ("accept-ranges", ""),
+    ("accept", ""),
+    ("access-control-allow-origin", ""),
+    ("age", ""),
+    ("allow", ""),
+    ("authorization", ""),
+    ("cache-control", ""),
+    ("content-disposition", ""),
+    ("content-encoding", ""),
+    ("content-language", ""),
+    ("content-length", ""),
+    ("content-location", ""),
+    ("content-range", ""),
+    ("content-type", ""),
+    ("cookie", ""),
+    ("date", ""),
+    ("etag", ""),
+    ("expect", ""),
+    ("expires", ""),
+    ("from", ""),
+    ("host", ""),
+    ("if-match", ""),
+    ("if-modified-since", ""),
+    ("if-none-match", ""),
+    ("if-range", ""),
+    ("if-unmodified-since", ""),
+    ("last-modified", ""),
+    ("link", ""),
+    ("location", ""),
+    ("max-forwards", ""),
+    ("proxy-authenticate", ""),
+    ("proxy-authorization", ""),
+    ("range", ""),
+    ("referer", ""),
+    ("refresh", ""),
+    ("retry-after", ""),
+    ("server", ""),
+    ("set-cookie", ""),
+    ("strict-transport-security", ""),
+    ("transfer-encoding", ""),
+    ("user-agent", ""),
+    ("vary", ""),
+    ("via", ""),
+    ("www-authenticate", ""),
+];
+
+/// Huffman 编码表（RFC 7541 附录 B）：下标为符号（256 为 EOS），值为 `(编码, 位数)`
+const HUFFMAN_CODES: [(u32, u8); 257] = [
+    (0x1ff8, 13), (0x7fffd8, 23), (0xfffffe2, 28), (0xfffffe3, 28), (0xfffffe4, 28), (0xfffffe5, 28),
+    (0xfffffe6, 28), (0xfffffe7, 28), (0xfffffe8, 28), (0xffffea, 24), (0x3ffffffc, 30), (0xfffffe9, 28),
+    (0xfffffea, 28), (0x3ffffffd, 30), (0xfffffeb, 28), (0xfffffec, 28), (0xfffffed, 28), (0xfffffee, 28),
+    (0xfffffef, 28), (0xffffff0, 28), (0xffffff1, 28), (0xffffff2, 28), (0x3ffffffe, 30), (0xffffff3, 28),
+    (0xffffff4, 28), (0xffffff5, 28), (0xffffff6, 28), (0xffffff7, 28), (0xffffff8, 28), (0xffffff9, 28),
+    (0xffffffa, 28), (0xffffffb, 28), (0x14, 6), (0x3f8, 10), (0x3f9, 10), (0xffa, 12),
+    (0x1ff9, 13), (0x15, 6), (0xf8, 8), (0x7fa, 11), (0x3fa, 10), (0x3fb, 10),
+    (0xf9, 8), (0x7fb, 11), (0xfa, 8), (0x16, 6), (0x17, 6), (0x18, 6),
+    (0x0, 5), (0x1, 5), (0x2, 5), (0x19, 6), (0x1a, 6), (0x1b, 6),
+    (0x1c, 6), (0x1d, 6), (0x1e, 6), (0x1f, 6), (0x5c, 7), (0xfb, 8),
+    (0x7ffc, 15), (0x20, 6), (0xffb, 12), (0x3fc, 10), (0x1ffa, 13), (0x21, 6),
+    (0x5d, 7), (0x5e, 7), (0x5f, 7), (0x60, 7), (0x61, 7), (0x62, 7),
+    (0x63, 7), (0x64, 7), (0x65, 7), (0x66, 7), (0x67, 7), (0x68, 7),
+    (0x69, 7), (0x6a, 7), (0x6b, 7), (0x6c, 7), (0x6d, 7), (0x6e, 7),
+    (0x6f, 7), (0x70, 7), (0x71, 7), (0x72, 7), (0xfc, 8), (0x73, 7),
+    (0xfd, 8), (0x1ffb, 13), (0x7fff0, 19), (0x1ffc, 13), (0x3ffc, 14), (0x22, 6),
+    (0x7ffd, 15), (0x3, 5), (0x23, 6), (0x4, 5), (0x24, 6), (0x5, 5),
+    (0x25, 6), (0x26, 6), (0x27, 6), (0x6, 5), (0x74, 7), (0x75, 7),
+    (0x28, 6), (0x29, 6), (0x2a, 6), (0x7, 5), (0x2b, 6), (0x76, 7),
+    (0x2c, 6), (0x8, 5), (0x9, 5), (0x2d, 6), (0x77, 7), (0x78, 7),
+    (0x79, 7), (0x7a, 7), (0x7b, 7), (0x7ffe, 15), (0x7fc, 11), (0x3ffd, 14),
+    (0x1ffd, 13), (0xffffffc, 28), (0xfffe6, 20), (0x3fffd2, 22), (0xfffe7, 20), (0xfffe8, 20),
+    (0x3fffd3, 22), (0x3fffd4, 22), (0x3fffd5, 22), (0x7fffd9, 23), (0x3fffd6, 22), (0x7fffda, 23),
+    (0x7fffdb, 23), (0x7fffdc, 23), (0x7fffdd, 23), (0x7fffde, 23), (0xffffeb, 24), (0x7fffdf, 23),
+    (0xffffec, 24), (0xffffed, 24), (0x3fffd7, 22), (0x7fffe0, 23), (0xffffee, 24), (0x7fffe1, 23),
+    (0x7fffe2, 23), (0x7fffe3, 23), (0x7fffe4, 23), (0x1fffdc, 21), (0x3fffd8, 22), (0x7fffe5, 23),
+    (0x3fffd9, 22), (0x7fffe6, 23), (0x7fffe7, 23), (0xffffef, 24), (0x3fffda, 22), (0x1fffdd, 21),
+    (0xfffe9, 20), (0x3fffdb, 22), (0x3fffdc, 22), (0x7fffe8, 23), (0x7fffe9, 23), (0x1fffde, 21),
+    (0x7fffea, 23), (0x3fffdd, 22), (0x3fffde, 22), (0xfffff0, 24), (0x1fffdf, 21), (0x3fffdf, 22),
+    (0x7fffeb, 23), (0x7fffec, 23), (0x1fffe0, 21), (0x1fffe1, 21), (0x3fffe0, 22), (0x1fffe2, 21),
+    (0x7fffed, 23), (0x3fffe1, 22), (0x7fffee, 23), (0x7fffef, 23), (0xfffea, 20), (0x3fffe2, 22),
+    (0x3fffe3, 22), (0x3fffe4, 22), (0x7ffff0, 23), (0x3fffe5, 22), (0x3fffe6, 22), (0x7ffff1, 23),
+    (0x3ffffe0, 26), (0x3ffffe1, 26), (0xfffeb, 20), (0x7fff1, 19), (0x3fffe7, 22), (0x7ffff2, 23),
+    (0x3fffe8, 22), (0x1ffffec, 25), (0x3ffffe2, 26), (0x3ffffe3, 26), (0x3ffffe4, 26), (0x7ffffde, 27),
+    (0x7ffffdf, 27), (0x3ffffe5, 26), (0xfffff1, 24), (0x1ffffed, 25), (0x7fff2, 19), (0x1fffe3, 21),
+    (0x3ffffe6, 26), (0x7ffffe0, 27), (0x7ffffe1, 27), (0x3ffffe7, 26), (0x7ffffe2, 27), (0xfffff2, 24),
+    (0x1fffe4, 21), (0x1fffe5, 21), (0x3ffffe8, 26), (0x3ffffe9, 26), (0xffffffd, 28), (0x7ffffe3, 27),
+    (0x7ffffe4, 27), (0x7ffffe5, 27), (0xfffec, 20), (0xfffff3, 24), (0xfffed, 20), (0x1fffe6, 21),
+    (0x3fffe9, 22), (0x1fffe7, 21), (0x1fffe8, 21), (0x7ffff3, 23), (0x3fffea, 22), (0x3fffeb, 22),
+    (0x1ffffee, 25), (0x1ffffef, 25), (0xfffff4, 24), (0xfffff5, 24), (0x3ffffea, 26), (0x7ffff4, 23),
+    (0x3ffffeb, 26), (0x7ffffe6, 27), (0x3ffffec, 26), (0x3ffffed, 26), (0x7ffffe7, 27), (0x7ffffe8, 27),
+    (0x7ffffe9, 27), (0x7ffffea, 27), (0x7ffffeb, 27), (0xffffffe, 28), (0x7ffffec, 27), (0x7ffffed, 27),
+    (0x7ffffee, 27), (0x7ffffef, 27), (0x7fffff0, 27), (0x3ffffee, 26), (0x3fffffff, 30),
+];
+
+lazy_static! {
+    /// Huffman 解码表：`(位数, 编码)` 到符号的映射
+    static ref HUFFMAN_DECODE: HashMap<(u8, u32), u16> = HUFFMAN_CODES
+        .iter()
+        .enumerate()
+        .map(|(symbol, &(code, len))| ((len, code), symbol as u16))
+        .collect();
+}
+
+/// HPACK 解码器，保存连接上对端编码器对应的动态表。
+///
+/// 每条 HTTP/2 连接使用一个解码器，头部块必须按收到的顺序解码。
+#[derive(Debug)]
+pub struct Decoder {
+    /// 动态表，最新插入的条目在最前面
+    entries: VecDeque<(String, String)>,
+    /// 动态表当前的大小（按 RFC 7541 §4.1 计算）
+    size: usize,
+    /// 动态表当前的容量，由对端通过表大小更新调整
+    capacity: usize,
+    /// 本端允许的动态表容量上限，即通告给对端的 `SETTINGS_HEADER_TABLE_SIZE`
+    max_capacity: usize,
+}
+
+impl Decoder {
+    /// 创建一个动态表容量上限为 `max_capacity` 字节的解码器。
+    pub fn new(max_capacity: usize) -> Self {
+        Self { entries: VecDeque::new(), size: 0, capacity: max_capacity, max_capacity }
+    }
+
+    /// 动态表当前占用的大小。
+    pub fn table_size(&self) -> usize {
+        self.size
+    }
+
+    /// 解码一个完整的头部块，按顺序返回头部字段。
+    ///
+    /// 解码后的字段名与值的总大小（每个字段另计 32 字节，与 `SETTINGS_MAX_HEADER_LIST_SIZE` 的算法一致）
+    /// 超过 `max_list_size` 时返回 `HeaderListTooLarge`，避免少量引用动态表的字节展开成巨大的头部列表；
+    /// 这种情况下头部块仍被完整解码，动态表保持同步，只有这个请求需要被拒绝。
+    /// 字段值中不合法的 UTF-8 字节按替换字符处理。
+    pub fn decode(&mut self, block: &[u8], max_list_size: usize) -> Result<Vec<(String, String)>, HpackError> {
+        let mut headers = Vec::new();
+        let mut list_size = 0usize;
+        let mut pos = 0;
+        while pos < block.len() {
+            let first = block[pos];
+            let (name, value) = if first & 0x80 != 0 {
+                // 索引字段
+                let index = decode_integer(block, &mut pos, 7)?;
+                self.get(index)?
+            } else if first & 0x40 != 0 {
+                // 带增量索引的字面量
+                let field = self.decode_literal(block, &mut pos, 6)?;
+                self.insert(field.clone());
+                field
+            } else if first & 0x20 != 0 {
+                // 动态表大小更新只能出现在头部块开头（RFC 7541 §4.2）
+                let capacity = decode_integer(block, &mut pos, 5)?;
+                if list_size > 0 || capacity > self.max_capacity {
+                    return Err(HpackError::InvalidTableSizeUpdate);
+                }
+                self.capacity = capacity;
+                self.evict(0);
+                continue;
+            } else {
+                // 不索引 / 永不索引的字面量
+                self.decode_literal(block, &mut pos, 4)?
+            };
+            // 超过上限后仍要解码完整个头部块，保持动态表与对端同步，连接才能继续使用
+            list_size += name.len() + value.len() + ENTRY_OVERHEAD;
+            if list_size <= max_list_size {
+                headers.push((name, value));
+            }
+        }
+        if list_size > max_list_size {
+            return Err(HpackError::HeaderListTooLarge);
+        }
+        Ok(headers)
+    }
+
+    /// 按索引查找静态表或动态表中的条目。
+    fn get(&self, index: usize) -> Result<(String, String), HpackError> {
+        match index {
+            0 => Err(HpackError::InvalidIndex),
+            i if i <= STATIC_TABLE.len() => {
+                let (name, value) = STATIC_TABLE[i - 1];
+                Ok((name.to_string(), value.to_string()))
+            }
+            i => self.entries.get(i - STATIC_TABLE.len() - 1).cloned().ok_or(HpackError::InvalidIndex),
+        }
+    }
+
+    /// 解码一个字面量字段：名称为索引（非 0）或字面量字符串，随后是值的字面量字符串。
+    fn decode_literal(&self, block: &[u8], pos: &mut usize, prefix: u8) -> Result<(String, String), HpackError> {
+        let name = match decode_integer(block, pos, prefix)? {
+            0 => decode_string(block, pos)?,
+            index => self.get(index)?.0,
+        };
+        let value = decode_string(block, pos)?;
+        Ok((name, value))
+    }
+
+    /// 向动态表插入条目，必要时淘汰最旧的条目；比整个容量还大的条目使动态表被清空（RFC 7541 §4.4）。
+    fn insert(&mut self, field: (String, String)) {
+        let size = field.0.len() + field.1.len() + ENTRY_OVERHEAD;
+        self.evict(size);
+        if size <= self.capacity {
+            self.size += size;
+            self.entries.push_front(field);
+        }
+    }
+
+    /// 淘汰最旧的条目，直到再放入 `incoming` 字节后不超过容量。
+    fn evict(&mut self, incoming: usize) {
+        while self.size + incoming > self.capacity {
+            match self.entries.pop_back() {
+                Some((name, value)) => self.size -= name.len() + value.len() + ENTRY_OVERHEAD,
+                None => break,
+            }
+        }
+    }
+}
+
+/// 编码一组头部字段，追加到 `out` 中。
+///
+/// 名称与值都在静态表中时使用索引表示；只有名称在静态表中时使用带名称索引的“不索引的字面量”；
+/// 其余使用名称与值都是字面量的“不索引的字面量”。字段名应当已是小写。
+pub fn encode<'a>(headers: impl IntoIterator<Item = (&'a str, &'a str)>, out: &mut Vec<u8>) {
+    for (name, value) in headers {
+        let exact = STATIC_TABLE.iter().position(|&(n, v)| n == name && v == value);
+        if let Some(i) = exact {
+            encode_integer(out, i + 1, 7, 0x80);
+            continue;
+        }
+        match STATIC_TABLE.iter().position(|&(n, _)| n == name) {
+            Some(i) => encode_integer(out, i + 1, 4, 0x00),
+            None => {
+                out.push(0x00);
+                encode_string(out, name.as_bytes());
+            }
+        }
+        encode_string(out, value.as_bytes());
+    }
+}
+
+/// 按 `prefix` 位前缀解码整数（RFC 7541 §5.1），`pos` 前进到整数之后。
+fn decode_integer(buf: &[u8], pos: &mut usize, prefix: u8) -> Result<usize, HpackError> {
+    let mask = ((1u16 << prefix) - 1) as u8;
+    let first = buf.get(*pos).ok_or(HpackError::Truncated)? & mask;
+    *pos += 1;
+    if first < mask {
+        return Ok(first as usize);
+    }
+    let mut value = mask as usize;
+    let mut shift = 0;
+    loop {
+        let byte = *buf.get(*pos).ok_or(HpackError::Truncated)?;
+        *pos += 1;
+        if shift > 28 {
+            return Err(HpackError::IntegerOverflow);
+        }
+        value += ((byte & 0x7f) as usize) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+}
+
+/// 按 `prefix` 位前缀编码整数，第一个字节的高位取自 `flags`。
+fn encode_integer(out: &mut Vec<u8>, value: usize, prefix: u8, flags: u8) {
+    let mask = (1usize << prefix) - 1;
+    if value < mask {
+        out.push(flags | value as u8);
+        return;
+    }
+    out.push(flags | mask as u8);
+    let mut rest = value - mask;
+    while rest >= 0x80 {
+        out.push((rest & 0x7f) as u8 | 0x80);
+        rest >>= 7;
+    }
+    out.push(rest as u8);
+}
+
+/// 解码字符串字面量（RFC 7541 §5.2），最高位表示是否使用 Huffman 编码。
+fn decode_string(buf: &[u8], pos: &mut usize) -> Result<String, HpackError> {
+    let huffman = buf.get(*pos).ok_or(HpackError::Truncated)? & 0x80 != 0;
+    let len = decode_integer(buf, pos, 7)?;
+    let end = pos.checked_add(len).filter(|&end| end <= buf.len()).ok_or(HpackError::Truncated)?;
+    let raw = &buf[*pos..end];
+    *pos = end;
+    let bytes = if huffman { huffman_decode(raw)? } else { raw.to_vec() };
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// 编码字符串字面量，Huffman 编码更短时使用 Huffman 编码。
+fn encode_string(out: &mut Vec<u8>, value: &[u8]) {
+    let huffman_bits: usize = value.iter().map(|&b| HUFFMAN_CODES[b as usize].1 as usize).sum();
+    let huffman_len = huffman_bits.div_ceil(8);
+    if huffman_len < value.len() {
+        encode_integer(out, huffman_len, 7, 0x80);
+        huffman_encode(value, out);
+    } else {
+        encode_integer(out, value.len(), 7, 0x00);
+        out.extend_from_slice(value);
+    }
+}
+
+/// Huffman 编码，末尾不足一个字节的部分用 EOS 编码的高位（全 1）填充。
+fn huffman_encode(input: &[u8], out: &mut Vec<u8>) {
+    let mut acc: u64 = 0;
+    let mut bits = 0u32;
+    for &byte in input {
+        let (code, len) = HUFFMAN_CODES[byte as usize];
+        acc = (acc << len) | code as u64;
+        bits += len as u32;
+        while bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+        }
+        acc &= (1u64 << bits) - 1;
+    }
+    if bits > 0 {
+        out.push(((acc << (8 - bits)) as u8) | (0xff >> bits));
+    }
+}
+
+/// Huffman 解码。逐位累积编码并在解码表中查找；最长的编码为 30 位。
+fn huffman_decode(input: &[u8]) -> Result<Vec<u8>, HpackError> {
+    let mut out = Vec::with_capacity(input.len() * 8 / 5);
+    let mut code = 0u32;
+    let mut len = 0u8;
+    for &byte in input {
+        for shift in (0..8).rev() {
+            code = (code << 1) | ((byte >> shift) & 1) as u32;
+            len += 1;
+            match HUFFMAN_DECODE.get(&(len, code)) {
+                Some(256) => return Err(HpackError::InvalidHuffman),
+                Some(&symbol) => {
+                    out.push(symbol as u8);
+                    code = 0;
+                    len = 0;
+                }
+                None if len >= 30 => return Err(HpackError::InvalidHuffman),
+                None => {}
+            }
+        }
+    }
+    // 填充最多 7 位，且必须是 EOS 编码的最高位（全 1）
+    if len > 7 || code != (1u32 << len) - 1 {
+        return Err(HpackError::InvalidHuffman);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(s: &str) -> Vec<u8> {
+        let s: String = s.split_whitespace().collect();
+        (0..s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap()).collect()
+    }
+
+    fn fields(list: &[(&str, &str)]) -> Vec<(String, String)> {
+        list.iter().map(|&(n, v)| (n.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_integer_examples() {
+        // RFC 7541 C.1
+        let mut out = Vec::new();
+        encode_integer(&mut out, 10, 5, 0);
+        encode_integer(&mut out, 1337, 5, 0);
+        encode_integer(&mut out, 42, 8, 0);
+        assert_eq!(out, vec![0x0a, 0x1f, 0x9a, 0x0a, 0x2a]);
+        let mut pos = 0;
+        assert_eq!(decode_integer(&out, &mut pos, 5), Ok(10));
+        assert_eq!(decode_integer(&out, &mut pos, 5), Ok(1337));
+        assert_eq!(decode_integer(&out, &mut pos, 8), Ok(42));
+        assert_eq!(decode_integer(&[0x1f, 0x9a], &mut 0, 5), Err(HpackError::Truncated));
+        assert_eq!(decode_integer(&[0x1f, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01], &mut 0, 5), Err(HpackError::IntegerOverflow));
+    }
+
+    #[test]
+    fn test_decode_request_examples() {
+        // RFC 7541 C.3（不使用 Huffman）与 C.4（使用 Huffman），三个请求共享同一个动态表
+        for blocks in [
+            [
+                "8286 8441 0f77 7777 2e65 7861 6d70 6c65 2e63 6f6d",
+                "8286 84be 5808 6e6f 2d63 6163 6865",
+                "8287 85bf 400a 6375 7374 6f6d 2d6b 6579 0c63 7573 746f 6d2d 7661 6c75 65",
+            ],
+            [
+                "8286 8441 8cf1 e3c2 e5f2 3a6b a0ab 90f4 ff",
+                "8286 84be 5886 a8eb 1064 9cbf",
+                "8287 85bf 4088 25a8 49e9 5ba9 7d7f 8925 a849 e95b b8e8 b4bf",
+            ],
+        ] {
+            let mut decoder = Decoder::new(4096);
+            let first = fields(&[(":method", "GET"), (":scheme", "http"), (":path", "/"), (":authority", "www.example.com")]);
+            assert_eq!(decoder.decode(&hex(blocks[0]), 16384).unwrap(), first);
+            assert_eq!(decoder.table_size(), 57);
+
+            let mut second = first.clone();
+            second.push(("cache-control".to_string(), "no-cache".to_string()));
+            assert_eq!(decoder.decode(&hex(blocks[1]), 16384).unwrap(), second);
+            assert_eq!(decoder.table_size(), 110);
+
+            let third = fields(&[
+                (":method", "GET"),
+                (":scheme", "https"),
+                (":path", "/index.html"),
+                (":authority", "www.example.com"),
+                ("custom-key", "custom-value"),
+            ]);
+            assert_eq!(decoder.decode(&hex(blocks[2]), 16384).unwrap(), third);
+            assert_eq!(decoder.table_size(), 164);
+        }
+    }
+
+    #[test]
+    fn test_dynamic_table_eviction_and_size_update() {
+        // 容量只够放下一个条目时，新条目淘汰旧条目
+        let mut decoder = Decoder::new(4096);
+        let mut block = Vec::new();
+        encode_integer(&mut block, 60, 5, 0x20);
+        for value in ["aaaa", "bbbb"] {
+            block.push(0x40);
+            encode_string(&mut block, b"x-key");
+            encode_string(&mut block, value.as_bytes());
+        }
+        block.push(0x80 | 62);
+        let headers = decoder.decode(&block, 16384).unwrap();
+        assert_eq!(headers.last().unwrap(), &("x-key".to_string(), "bbbb".to_string()));
+        assert_eq!(decoder.table_size(), 41);
+        assert_eq!(decoder.decode(&[0x80 | 63], 16384), Err(HpackError::InvalidIndex));
+
+        // 超过本端上限的表大小更新、出现在字段之后的表大小更新都是错误
+        let mut block = Vec::new();
+        encode_integer(&mut block, 8192, 5, 0x20);
+        assert_eq!(decoder.decode(&block, 16384), Err(HpackError::InvalidTableSizeUpdate));
+        assert_eq!(decoder.decode(&[0x82, 0x20], 16384), Err(HpackError::InvalidTableSizeUpdate));
+        assert_eq!(decoder.decode(&[0x80], 16384), Err(HpackError::InvalidIndex));
+    }
+
+    #[test]
+    fn test_header_list_limit() {
+        let mut decoder = Decoder::new(4096);
+        let mut block = vec![0x40];
+        encode_string(&mut block, b"x-big");
+        encode_string(&mut block, &[b'a'; 1000]);
+        // 同一个动态表条目被反复引用
+        block.extend(std::iter::repeat_n(0x80 | 62, 20));
+        assert_eq!(decoder.decode(&block, 16384), Err(HpackError::HeaderListTooLarge));
+        // 超限的头部块同样完成了插入，后续头部块仍能引用该条目
+        assert_eq!(decoder.decode(&[0x80 | 62], 16384).unwrap()[0].0, "x-big");
+    }
+
+    #[test]
+    fn test_huffman() {
+        let mut out = Vec::new();
+        huffman_encode(b"www.example.com", &mut out);
+        assert_eq!(out, hex("f1e3 c2e5 f23a 6ba0 ab90 f4ff"));
+        assert_eq!(huffman_decode(&out).unwrap(), b"www.example.com");
+
+        // 所有字节值都能往返
+        let all: Vec<u8> = (0..=255).collect();
+        let mut out = Vec::new();
+        huffman_encode(&all, &mut out);
+        assert_eq!(huffman_decode(&out).unwrap(), all);
+
+        // 超过 7 位的填充、不是全 1 的填充都是错误
+        assert_eq!(huffman_decode(&[0xff, 0xff]), Err(HpackError::InvalidHuffman));
+        assert_eq!(huffman_decode(&[0x00]), Err(HpackError::InvalidHuffman));
+    }
+
+    #[test]
+    fn test_encode_round_trip() {
+        let headers = [
+            (":status", "200"),
+            (":status", "206"),
+            ("content-type", "text/html;charset=utf-8"),
+            ("content-length", "1024"),
+            ("x-response-time", "3ms"),
+            ("server", "shaneyale-webserver"),
+        ];
+        let mut block = Vec::new();
+        encode(headers.iter().copied(), &mut block);
+        // 完全匹配静态表的字段只占一个字节
+        assert_eq!(block[0], 0x88);
+        assert_eq!(block[1], 0x8a);
+
+        let mut decoder = Decoder::new(4096);
+        assert_eq!(decoder.decode(&block, 16384).unwrap(), fields(&headers));
+        // 编码器从不插入动态表
+        assert_eq!(decoder.table_size(), 0);
+    }
+}
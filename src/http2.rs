@@ -0,0 +1,1211 @@
+// Copyright (c) 2026 shaneyale (shaneyale86@gmail.com)
+// All rights reserved.
+
+//! # HTTP/2 模块
+//!
+//! 在 TLS 握手通过 ALPN 协商出 `h2` 的连接上实现 HTTP/2（RFC 9113）的服务端。明文连接仍使用 HTTP/1.1。
+//!
+//! ## 连接结构
+//! - **读取**：独立的任务逐帧读取连接，经通道交给连接主循环；主循环处理 SETTINGS、PING、WINDOW_UPDATE 等控制帧，
+//!   并把 HEADERS/CONTINUATION/DATA 组装成请求。
+//! - **多路复用**：每个请求在自己的任务中生成并发送响应，不同流的 DATA 帧交错写出，慢请求不会阻塞同一连接上的其它请求。
+//!   写出由一把异步锁串行化，一个头部块的 HEADERS 与 CONTINUATION 帧总是连续发送。
+//! - **流量控制**：发送 DATA 前同时扣减连接与流的发送窗口，窗口耗尽时等待对端的 WINDOW_UPDATE；
+//!   收到的 DATA 立即以 WINDOW_UPDATE 归还，请求体总量由 `[limits] max_body_bytes` 限制。
+//!
+//! 请求被转换为等价的 HTTP/1.1 请求交给 [`Request`] 解析（`:authority` 转为 `Host`），因此路由、
+//! 内容协商与各项上限与 HTTP/1.1 完全一致；响应头取自 [`Response::header_fields`]，去掉 `Connection` 等
+//! 只属于 HTTP/1.1 连接的字段。不支持服务端推送，也不处理优先级。
+
+use crate::{
+    config::{Config, Limits},
+    hpack::{self, Decoder, HpackError},
+    metrics::Metrics,
+    param::{HttpRequestMethod, STATUS_CODES},
+    request::Request,
+    response::{Response, StreamSource},
+    exception::Exception,
+};
+
+use bytes::Bytes;
+use log::{debug, error, warn};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    sync::{mpsc, Mutex as AsyncMutex, Notify},
+    task::{AbortHandle, JoinSet},
+};
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    io,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// 客户端连接序言（RFC 9113 §3.4）
+const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+/// 帧头部长度
+const FRAME_HEADER_LEN: usize = 9;
+
+/// 帧类型
+const DATA: u8 = 0x0;
+const HEADERS: u8 = 0x1;
+const PRIORITY: u8 = 0x2;
+const RST_STREAM: u8 = 0x3;
+const SETTINGS: u8 = 0x4;
+const PUSH_PROMISE: u8 = 0x5;
+const PING: u8 = 0x6;
+const GOAWAY: u8 = 0x7;
+const WINDOW_UPDATE: u8 = 0x8;
+const CONTINUATION: u8 = 0x9;
+
+/// 帧标志
+const END_STREAM: u8 = 0x1;
+const ACK: u8 = 0x1;
+const END_HEADERS: u8 = 0x4;
+const PADDED: u8 = 0x8;
+const PRIORITY_FLAG: u8 = 0x20;
+
+/// SETTINGS 参数
+const SETTINGS_HEADER_TABLE_SIZE: u16 = 0x1;
+const SETTINGS_ENABLE_PUSH: u16 = 0x2;
+const SETTINGS_MAX_CONCURRENT_STREAMS: u16 = 0x3;
+const SETTINGS_INITIAL_WINDOW_SIZE: u16 = 0x4;
+const SETTINGS_MAX_FRAME_SIZE: u16 = 0x5;
+const SETTINGS_MAX_HEADER_LIST_SIZE: u16 = 0x6;
+
+/// 错误码
+const NO_ERROR: u32 = 0x0;
+const PROTOCOL_ERROR: u32 = 0x1;
+const INTERNAL_ERROR: u32 = 0x2;
+const FLOW_CONTROL_ERROR: u32 = 0x3;
+const STREAM_CLOSED: u32 = 0x5;
+const FRAME_SIZE_ERROR: u32 = 0x6;
+const REFUSED_STREAM: u32 = 0x7;
+const COMPRESSION_ERROR: u32 = 0x9;
+
+/// 协议规定的初始窗口大小与最小帧长度上限
+const DEFAULT_WINDOW_SIZE: i64 = 65535;
+const DEFAULT_MAX_FRAME_SIZE: usize = 16384;
+/// 窗口大小的上限 2^31 - 1
+const MAX_WINDOW_SIZE: i64 = (1 << 31) - 1;
+/// 本端允许对端帧长度的上限，以及动态表容量；均使用协议默认值
+const MAX_FRAME_SIZE: usize = DEFAULT_MAX_FRAME_SIZE;
+const HEADER_TABLE_SIZE: usize = 4096;
+/// 每条连接同时处理的流数上限
+const MAX_CONCURRENT_STREAMS: usize = 100;
+/// 读取任务与主循环之间、流式文件与发送任务之间通道中最多缓存的块数
+const CHANNEL_CAPACITY: usize = 16;
+/// HTTP/2 禁止使用的、只属于 HTTP/1.1 连接的头部字段（RFC 9113 §8.2.2）
+const CONNECTION_HEADERS: [&str; 5] = ["connection", "keep-alive", "proxy-connection", "transfer-encoding", "upgrade"];
+
+/// 为一个请求生成响应的处理函数；返回 `None` 时以 `INTERNAL_ERROR` 重置该流。
+type Handler = Arc<dyn Fn(Request) -> Pin<Box<dyn Future<Output = Option<Response>> + Send>> + Send + Sync>;
+/// 连接的写方向
+type Writer = Box<dyn AsyncWrite + Send + Unpin>;
+
+/// 收到的一个帧。
+#[derive(Debug)]
+struct Frame {
+    kind: u8,
+    flags: u8,
+    stream_id: u32,
+    payload: Vec<u8>,
+}
+
+/// 导致整个连接终止的错误：发送带错误码的 GOAWAY 后关闭连接。
+#[derive(Debug)]
+struct ConnectionError(u32, &'static str);
+
+/// 在 ALPN 协商出 `h2` 的连接上提供 HTTP/2 服务，直到对端关闭连接、发送 GOAWAY 或空闲超时。
+///
+/// 每个请求解析后交给 `handler` 生成响应；`handler` 在各自的任务中运行，多个请求并发处理。
+/// 读取连接序言与空闲等待最多持续 `[limits] read_timeout_secs`，连接级别的协议错误以 GOAWAY 结束连接。
+pub async fn serve<S, H, F>(stream: S, id: u128, config: &Config, metrics: &Arc<Metrics>, handler: H)
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+    H: Fn(Request) -> F + Send + Sync + 'static,
+    F: Future<Output = Option<Response>> + Send + 'static,
+{
+    let (mut reader, writer) = tokio::io::split(stream);
+    let timeout = Duration::from_secs(config.limits().read_timeout_secs());
+
+    let mut preface = [0u8; PREFACE.len()];
+    match tokio::time::timeout(timeout, reader.read_exact(&mut preface)).await {
+        Ok(Ok(_)) if preface == PREFACE => {}
+        Ok(Ok(_)) => {
+            warn!("[ID{}]HTTP/2连接序言不正确，关闭连接", id);
+            return;
+        }
+        _ => {
+            debug!("[ID{}]未能读取HTTP/2连接序言", id);
+            return;
+        }
+    }
+    debug!("[ID{}]HTTP/2连接已建立", id);
+
+    let (frames_tx, frames_rx) = mpsc::channel(CHANNEL_CAPACITY);
+    let read_task = tokio::spawn(async move {
+        loop {
+            let frame = read_frame(&mut reader).await;
+            let done = !matches!(frame, Ok(Some(_)));
+            if frames_tx.send(frame).await.is_err() || done {
+                break;
+            }
+        }
+    });
+
+    let handler: Handler = Arc::new(move |request| Box::pin(handler(request)));
+    let mut connection = Connection {
+        id,
+        shared: Arc::new(Shared {
+            writer: AsyncMutex::new(Box::new(writer)),
+            flow: Mutex::new(SendFlow {
+                connection: DEFAULT_WINDOW_SIZE,
+                streams: HashMap::new(),
+                initial_window: DEFAULT_WINDOW_SIZE,
+                max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            }),
+            window_changed: Notify::new(),
+        }),
+        decoder: Decoder::new(HEADER_TABLE_SIZE),
+        receiving: HashMap::new(),
+        responding: HashMap::new(),
+        tasks: JoinSet::new(),
+        last_stream_id: 0,
+        header_block: None,
+        going_away: false,
+        limits: config.limits().clone(),
+        allowed_methods: config.allowed_methods(),
+        chunk_size: config.chunk_size(),
+        metrics: Arc::clone(metrics),
+        handler,
+    };
+    let result = connection.run(frames_rx, timeout).await;
+    read_task.abort();
+
+    let (code, reason) = match result {
+        Ok(()) => (NO_ERROR, "连接正常结束"),
+        Err(ConnectionError(code, reason)) => {
+            warn!("[ID{}]HTTP/2连接错误(0x{:x})：{}", id, code, reason);
+            // 连接出错时不再等待进行中的流
+            connection.tasks.abort_all();
+            (code, reason)
+        }
+    };
+    while connection.tasks.join_next().await.is_some() {}
+    let mut payload = connection.last_stream_id.to_be_bytes().to_vec();
+    payload.extend_from_slice(&code.to_be_bytes());
+    payload.extend_from_slice(reason.as_bytes());
+    let _ = connection.shared.write_frame(GOAWAY, 0, 0, &payload).await;
+    let _ = connection.shared.writer.lock().await.shutdown().await;
+    debug!("[ID{}]HTTP/2连接已关闭", id);
+}
+
+/// 服务器排空期间拒绝 HTTP/2 连接：发送 SETTINGS 与不处理任何流的 GOAWAY 后关闭，客户端可以改用其它连接重试。
+pub async fn refuse<S>(mut stream: S, id: u128)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    debug!("[ID{}]服务器正在停机，以GOAWAY拒绝HTTP/2连接", id);
+    let mut goaway = 0u32.to_be_bytes().to_vec();
+    goaway.extend_from_slice(&NO_ERROR.to_be_bytes());
+    let mut frames = encode_frame(SETTINGS, 0, 0, &[]);
+    frames.extend(encode_frame(GOAWAY, 0, 0, &goaway));
+    let _ = stream.write_all(&frames).await;
+    let _ = stream.shutdown().await;
+}
+
+/// 发送方向的流量控制窗口，由读取循环与各个流的发送任务共享。
+#[derive(Debug)]
+struct SendFlow {
+    /// 连接级别的发送窗口
+    connection: i64,
+    /// 各个仍可发送的流的发送窗口；流被重置或发送完毕后移除
+    streams: HashMap<u32, i64>,
+    /// 对端的 `SETTINGS_INITIAL_WINDOW_SIZE`
+    initial_window: i64,
+    /// 对端的 `SETTINGS_MAX_FRAME_SIZE`
+    max_frame_size: usize,
+}
+
+/// 连接上各个任务共享的写方向与发送窗口。
+struct Shared {
+    writer: AsyncMutex<Writer>,
+    flow: Mutex<SendFlow>,
+    /// 发送窗口增大或流被重置时唤醒等待窗口的发送任务
+    window_changed: Notify,
+}
+
+impl Shared {
+    /// 写出一个帧，返回写出的字节数。
+    async fn write_frame(&self, kind: u8, flags: u8, stream_id: u32, payload: &[u8]) -> io::Result<u64> {
+        let frame = encode_frame(kind, flags, stream_id, payload);
+        let mut writer = self.writer.lock().await;
+        writer.write_all(&frame).await?;
+        writer.flush().await?;
+        Ok(frame.len() as u64)
+    }
+
+    /// 写出一个头部块：超过对端帧长度上限时拆分为 HEADERS 与若干 CONTINUATION 帧，期间不释放写锁。
+    async fn write_headers(&self, stream_id: u32, block: &[u8], end_stream: bool) -> io::Result<u64> {
+        let max_frame_size = self.flow.lock().unwrap().max_frame_size;
+        let mut frames = Vec::with_capacity(block.len() + FRAME_HEADER_LEN);
+        let mut fragments = block.chunks(max_frame_size).peekable();
+        let mut kind = HEADERS;
+        let mut flags = if end_stream { END_STREAM } else { 0 };
+        if fragments.peek().is_none() {
+            frames.extend(encode_frame(kind, flags | END_HEADERS, stream_id, &[]));
+        }
+        while let Some(fragment) = fragments.next() {
+            if fragments.peek().is_none() {
+                flags |= END_HEADERS;
+            }
+            frames.extend(encode_frame(kind, flags, stream_id, fragment));
+            kind = CONTINUATION;
+            flags = 0;
+        }
+        let mut writer = self.writer.lock().await;
+        writer.write_all(&frames).await?;
+        writer.flush().await?;
+        Ok(frames.len() as u64)
+    }
+
+    /// 按流量控制窗口把 `data` 拆分为 DATA 帧写出，返回写出的字节数（含帧头）。
+    ///
+    /// 窗口耗尽时等待对端的 WINDOW_UPDATE；流在等待期间被重置时返回 `ConnectionReset`。
+    /// `data` 为空且 `end_stream` 为真时写出一个空的结束帧。
+    async fn write_data(&self, stream_id: u32, mut data: Bytes, end_stream: bool) -> io::Result<u64> {
+        let mut total = 0;
+        if data.is_empty() {
+            if end_stream {
+                total += self.write_frame(DATA, END_STREAM, stream_id, &[]).await?;
+            }
+            return Ok(total);
+        }
+        while !data.is_empty() {
+            let n = self.reserve(stream_id, data.len()).await?;
+            let chunk = data.split_to(n);
+            let flags = if end_stream && data.is_empty() { END_STREAM } else { 0 };
+            total += self.write_frame(DATA, flags, stream_id, &chunk).await?;
+        }
+        Ok(total)
+    }
+
+    /// 从连接与流的发送窗口中预留最多 `wanted` 字节（不超过对端的帧长度上限），窗口为 0 时等待。
+    async fn reserve(&self, stream_id: u32, wanted: usize) -> io::Result<usize> {
+        loop {
+            // 先登记等待再检查窗口，避免错过两者之间到达的 WINDOW_UPDATE
+            let changed = self.window_changed.notified();
+            tokio::pin!(changed);
+            changed.as_mut().enable();
+            {
+                let mut guard = self.flow.lock().unwrap();
+                let flow = &mut *guard;
+                let max_frame_size = flow.max_frame_size as i64;
+                let connection = flow.connection;
+                let Some(stream) = flow.streams.get_mut(&stream_id) else {
+                    return Err(io::Error::new(io::ErrorKind::ConnectionReset, "流已被重置"));
+                };
+                let available = connection.min(*stream).min(max_frame_size).min(wanted as i64);
+                if available > 0 {
+                    *stream -= available;
+                    flow.connection -= available;
+                    return Ok(available as usize);
+                }
+            }
+            changed.await;
+        }
+    }
+}
+
+/// 请求头或请求体尚未接收完毕的流。
+#[derive(Debug, Default)]
+struct Receiving {
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+/// 尚未收到 END_HEADERS 的头部块。
+#[derive(Debug)]
+struct HeaderBlock {
+    stream_id: u32,
+    block: Vec<u8>,
+    end_stream: bool,
+}
+
+/// 一条 HTTP/2 连接的状态，由读取循环独占。
+struct Connection {
+    id: u128,
+    shared: Arc<Shared>,
+    /// 请求头的 HPACK 解码器，头部块必须按到达顺序解码
+    decoder: Decoder,
+    /// 正在接收请求的流
+    receiving: HashMap<u32, Receiving>,
+    /// 正在生成或发送响应的流
+    responding: HashMap<u32, AbortHandle>,
+    /// 各个流的响应任务，结束时返回流 ID
+    tasks: JoinSet<u32>,
+    /// 对端打开过的最大流 ID，流 ID 必须递增
+    last_stream_id: u32,
+    /// 正在接收 CONTINUATION 的头部块
+    header_block: Option<HeaderBlock>,
+    /// 对端已发送 GOAWAY，不再接受新的流
+    going_away: bool,
+    limits: Limits,
+    allowed_methods: Vec<HttpRequestMethod>,
+    chunk_size: usize,
+    metrics: Arc<Metrics>,
+    handler: Handler,
+}
+
+impl Connection {
+    /// 主循环：发送本端 SETTINGS，随后处理收到的帧并回收结束的响应任务。
+    ///
+    /// 对端关闭连接时中止进行中的响应；对端发送 GOAWAY 后在已接收的流全部完成时返回；
+    /// 没有进行中的流且 `idle_timeout` 内没有收到帧时返回。
+    async fn run(
+        &mut self,
+        mut frames: mpsc::Receiver<io::Result<Option<Frame>>>,
+        idle_timeout: Duration,
+    ) -> Result<(), ConnectionError> {
+        let mut settings = Vec::new();
+        for (id, value) in [
+            (SETTINGS_HEADER_TABLE_SIZE, HEADER_TABLE_SIZE as u32),
+            (SETTINGS_MAX_CONCURRENT_STREAMS, MAX_CONCURRENT_STREAMS as u32),
+            (SETTINGS_MAX_HEADER_LIST_SIZE, self.limits.max_header_bytes() as u32),
+        ] {
+            settings.extend_from_slice(&id.to_be_bytes());
+            settings.extend_from_slice(&value.to_be_bytes());
+        }
+        self.write_control(SETTINGS, 0, 0, &settings).await?;
+
+        loop {
+            let idle = self.receiving.is_empty() && self.responding.is_empty();
+            if self.going_away && idle {
+                return Ok(());
+            }
+            let next = async {
+                match idle {
+                    true => tokio::time::timeout(idle_timeout, frames.recv()).await,
+                    false => Ok(frames.recv().await),
+                }
+            };
+            tokio::select! {
+                Some(done) = self.tasks.join_next(), if !self.tasks.is_empty() => {
+                    if let Ok(stream_id) = done {
+                        self.responding.remove(&stream_id);
+                    }
+                }
+                frame = next => match frame {
+                    Ok(Some(Ok(Some(frame)))) => self.handle_frame(frame).await?,
+                    Ok(Some(Err(e))) if e.kind() == io::ErrorKind::InvalidData => {
+                        return Err(ConnectionError(FRAME_SIZE_ERROR, "帧长度超过上限"));
+                    }
+                    Ok(_) => {
+                        debug!("[ID{}]对端关闭了HTTP/2连接", self.id);
+                        self.tasks.abort_all();
+                        return Ok(());
+                    }
+                    Err(_) => {
+                        debug!("[ID{}]HTTP/2连接空闲超时", self.id);
+                        return Ok(());
+                    }
+                },
+            }
+        }
+    }
+
+    /// 写出控制帧，写入失败视为连接已断开。
+    async fn write_control(&self, kind: u8, flags: u8, stream_id: u32, payload: &[u8]) -> Result<(), ConnectionError> {
+        self.shared
+            .write_frame(kind, flags, stream_id, payload)
+            .await
+            .map(|_| ())
+            .map_err(|_| ConnectionError(INTERNAL_ERROR, "写入连接失败"))
+    }
+
+    /// 处理一个收到的帧。
+    async fn handle_frame(&mut self, frame: Frame) -> Result<(), ConnectionError> {
+        // 头部块必须连续：HEADERS 之后只能是同一个流的 CONTINUATION
+        if let Some(block) = &self.header_block {
+            if frame.kind != CONTINUATION || frame.stream_id != block.stream_id {
+                return Err(ConnectionError(PROTOCOL_ERROR, "头部块被其它帧打断"));
+            }
+        }
+        match frame.kind {
+            DATA => self.on_data(frame).await,
+            HEADERS => self.on_headers(frame).await,
+            PRIORITY => match (frame.stream_id, frame.payload.len()) {
+                (0, _) => Err(ConnectionError(PROTOCOL_ERROR, "PRIORITY帧的流ID为0")),
+                (_, 5) => Ok(()),
+                _ => Err(ConnectionError(FRAME_SIZE_ERROR, "PRIORITY帧长度错误")),
+            },
+            RST_STREAM => {
+                if frame.stream_id == 0 {
+                    return Err(ConnectionError(PROTOCOL_ERROR, "RST_STREAM帧的流ID为0"));
+                }
+                if frame.payload.len() != 4 {
+                    return Err(ConnectionError(FRAME_SIZE_ERROR, "RST_STREAM帧长度错误"));
+                }
+                debug!("[ID{}]对端重置了流{}", self.id, frame.stream_id);
+                self.receiving.remove(&frame.stream_id);
+                if let Some(task) = self.responding.remove(&frame.stream_id) {
+                    task.abort();
+                }
+                self.shared.flow.lock().unwrap().streams.remove(&frame.stream_id);
+                self.shared.window_changed.notify_waiters();
+                Ok(())
+            }
+            SETTINGS => self.on_settings(frame).await,
+            PUSH_PROMISE => Err(ConnectionError(PROTOCOL_ERROR, "客户端不能发送PUSH_PROMISE")),
+            PING => {
+                if frame.stream_id != 0 {
+                    return Err(ConnectionError(PROTOCOL_ERROR, "PING帧的流ID不为0"));
+                }
+                if frame.payload.len() != 8 {
+                    return Err(ConnectionError(FRAME_SIZE_ERROR, "PING帧长度错误"));
+                }
+                if frame.flags & ACK == 0 {
+                    self.write_control(PING, ACK, 0, &frame.payload).await?;
+                }
+                Ok(())
+            }
+            GOAWAY => {
+                debug!("[ID{}]对端发送了GOAWAY，不再接受新的流", self.id);
+                self.going_away = true;
+                Ok(())
+            }
+            WINDOW_UPDATE => self.on_window_update(frame),
+            CONTINUATION => {
+                let Some(mut block) = self.header_block.take() else {
+                    return Err(ConnectionError(PROTOCOL_ERROR, "意外的CONTINUATION帧"));
+                };
+                block.block.extend_from_slice(&frame.payload);
+                if block.block.len() > self.limits.max_header_bytes() {
+                    return Err(ConnectionError(PROTOCOL_ERROR, "头部块超过上限"));
+                }
+                match frame.flags & END_HEADERS {
+                    0 => {
+                        self.header_block = Some(block);
+                        Ok(())
+                    }
+                    _ => self.on_header_block(block).await,
+                }
+            }
+            // 未知类型的帧必须忽略
+            _ => Ok(()),
+        }
+    }
+
+    async fn on_data(&mut self, frame: Frame) -> Result<(), ConnectionError> {
+        let stream_id = frame.stream_id;
+        if stream_id == 0 {
+            return Err(ConnectionError(PROTOCOL_ERROR, "DATA帧的流ID为0"));
+        }
+        let data = strip_padding(&frame)?;
+        // 无论流的状态如何，收到的字节都计入连接窗口，因此立即归还
+        let consumed = frame.payload.len() as u32;
+        if consumed > 0 {
+            self.write_control(WINDOW_UPDATE, 0, 0, &consumed.to_be_bytes()).await?;
+        }
+        let end_stream = frame.flags & END_STREAM != 0;
+        let Some(receiving) = self.receiving.get_mut(&stream_id) else {
+            if stream_id > self.last_stream_id {
+                return Err(ConnectionError(PROTOCOL_ERROR, "DATA帧属于未打开的流"));
+            }
+            return self.reset(stream_id, STREAM_CLOSED).await;
+        };
+        receiving.body.extend_from_slice(data);
+        if receiving.body.len() > self.limits.max_body_bytes() {
+            warn!("[ID{}]流{}的请求体超过{}字节上限，返回413", self.id, stream_id, self.limits.max_body_bytes());
+            self.receiving.remove(&stream_id);
+            self.spawn_status(stream_id, 413, Vec::new(), true);
+            return Ok(());
+        }
+        if end_stream {
+            let receiving = self.receiving.remove(&stream_id).unwrap_or_default();
+            self.dispatch(stream_id, receiving);
+        } else if consumed > 0 {
+            self.write_control(WINDOW_UPDATE, 0, stream_id, &consumed.to_be_bytes()).await?;
+        }
+        Ok(())
+    }
+
+    async fn on_headers(&mut self, frame: Frame) -> Result<(), ConnectionError> {
+        if frame.stream_id == 0 {
+            return Err(ConnectionError(PROTOCOL_ERROR, "HEADERS帧的流ID为0"));
+        }
+        let mut fragment = strip_padding(&frame)?;
+        if frame.flags & PRIORITY_FLAG != 0 {
+            fragment = fragment.get(5..).ok_or(ConnectionError(FRAME_SIZE_ERROR, "HEADERS帧长度错误"))?;
+        }
+        let block = HeaderBlock {
+            stream_id: frame.stream_id,
+            block: fragment.to_vec(),
+            end_stream: frame.flags & END_STREAM != 0,
+        };
+        if block.block.len() > self.limits.max_header_bytes() {
+            return Err(ConnectionError(PROTOCOL_ERROR, "头部块超过上限"));
+        }
+        match frame.flags & END_HEADERS {
+            0 => {
+                self.header_block = Some(block);
+                Ok(())
+            }
+            _ => self.on_header_block(block).await,
+        }
+    }
+
+    /// 处理完整的头部块：打开新的流，或作为已打开的流的尾部字段（trailers）。
+    async fn on_header_block(&mut self, block: HeaderBlock) -> Result<(), ConnectionError> {
+        let stream_id = block.stream_id;
+        // 无论是否接受这个流都必须解码，使动态表与对端保持同步
+        let decoded = match self.decoder.decode(&block.block, self.limits.max_header_bytes()) {
+            Ok(headers) => Ok(headers),
+            Err(HpackError::HeaderListTooLarge) => Err(()),
+            Err(e) => {
+                error!("[ID{}]解码HTTP/2头部块失败：{}", self.id, e);
+                return Err(ConnectionError(COMPRESSION_ERROR, "无法解码头部块"));
+            }
+        };
+
+        if self.receiving.contains_key(&stream_id) {
+            // 请求体之后的尾部字段必须结束流；其内容不参与处理
+            if !block.end_stream {
+                self.receiving.remove(&stream_id);
+                return self.reset(stream_id, PROTOCOL_ERROR).await;
+            }
+            let receiving = self.receiving.remove(&stream_id).unwrap_or_default();
+            self.dispatch(stream_id, receiving);
+            return Ok(());
+        }
+        if stream_id.is_multiple_of(2) || stream_id <= self.last_stream_id {
+            return Err(ConnectionError(PROTOCOL_ERROR, "流ID无效"));
+        }
+        self.last_stream_id = stream_id;
+        if self.going_away {
+            return Ok(());
+        }
+        if self.receiving.len() + self.responding.len() >= MAX_CONCURRENT_STREAMS {
+            warn!("[ID{}]并发流超过{}个上限，拒绝流{}", self.id, MAX_CONCURRENT_STREAMS, stream_id);
+            return self.reset(stream_id, REFUSED_STREAM).await;
+        }
+        let initial_window = {
+            let mut flow = self.shared.flow.lock().unwrap();
+            let initial_window = flow.initial_window;
+            flow.streams.insert(stream_id, initial_window);
+            initial_window
+        };
+        debug!("[ID{}]打开HTTP/2流{}，发送窗口{}", self.id, stream_id, initial_window);
+        let Ok(headers) = decoded else {
+            warn!("[ID{}]流{}的请求头超过{}字节上限，返回431", self.id, stream_id, self.limits.max_header_bytes());
+            self.spawn_status(stream_id, 431, Vec::new(), !block.end_stream);
+            return Ok(());
+        };
+        let receiving = Receiving { headers, body: Vec::new() };
+        match block.end_stream {
+            true => self.dispatch(stream_id, receiving),
+            false => {
+                self.receiving.insert(stream_id, receiving);
+            }
+        }
+        Ok(())
+    }
+
+    async fn on_settings(&mut self, frame: Frame) -> Result<(), ConnectionError> {
+        if frame.stream_id != 0 {
+            return Err(ConnectionError(PROTOCOL_ERROR, "SETTINGS帧的流ID不为0"));
+        }
+        if frame.flags & ACK != 0 {
+            return match frame.payload.len() {
+                0 => Ok(()),
+                _ => Err(ConnectionError(FRAME_SIZE_ERROR, "SETTINGS确认帧不为空")),
+            };
+        }
+        if !frame.payload.len().is_multiple_of(6) {
+            return Err(ConnectionError(FRAME_SIZE_ERROR, "SETTINGS帧长度错误"));
+        }
+        {
+            let mut flow = self.shared.flow.lock().unwrap();
+            for setting in frame.payload.chunks(6) {
+                let id = u16::from_be_bytes([setting[0], setting[1]]);
+                let value = u32::from_be_bytes([setting[2], setting[3], setting[4], setting[5]]);
+                match id {
+                    SETTINGS_ENABLE_PUSH if value > 1 => {
+                        return Err(ConnectionError(PROTOCOL_ERROR, "ENABLE_PUSH取值无效"));
+                    }
+                    SETTINGS_INITIAL_WINDOW_SIZE => {
+                        let value = value as i64;
+                        if value > MAX_WINDOW_SIZE {
+                            return Err(ConnectionError(FLOW_CONTROL_ERROR, "INITIAL_WINDOW_SIZE过大"));
+                        }
+                        // 初始窗口的变化同样作用于所有已打开的流（RFC 9113 §6.9.2）
+                        let delta = value - flow.initial_window;
+                        flow.initial_window = value;
+                        for window in flow.streams.values_mut() {
+                            *window += delta;
+                            if *window > MAX_WINDOW_SIZE {
+                                return Err(ConnectionError(FLOW_CONTROL_ERROR, "流的发送窗口溢出"));
+                            }
+                        }
+                    }
+                    SETTINGS_MAX_FRAME_SIZE => {
+                        if !(DEFAULT_MAX_FRAME_SIZE as u32..=0xff_ffff).contains(&value) {
+                            return Err(ConnectionError(PROTOCOL_ERROR, "MAX_FRAME_SIZE取值无效"));
+                        }
+                        flow.max_frame_size = value as usize;
+                    }
+                    // 编码器从不使用动态表，对端的动态表容量与其余参数不影响本端的行为
+                    _ => {}
+                }
+            }
+        }
+        self.shared.window_changed.notify_waiters();
+        self.write_control(SETTINGS, ACK, 0, &[]).await
+    }
+
+    fn on_window_update(&mut self, frame: Frame) -> Result<(), ConnectionError> {
+        if frame.payload.len() != 4 {
+            return Err(ConnectionError(FRAME_SIZE_ERROR, "WINDOW_UPDATE帧长度错误"));
+        }
+        let increment = (u32::from_be_bytes([frame.payload[0], frame.payload[1], frame.payload[2], frame.payload[3]])
+            & 0x7fff_ffff) as i64;
+        if increment == 0 {
+            return Err(ConnectionError(PROTOCOL_ERROR, "WINDOW_UPDATE的增量为0"));
+        }
+        {
+            let mut guard = self.shared.flow.lock().unwrap();
+            let flow = &mut *guard;
+            let window = match frame.stream_id {
+                0 => Some(&mut flow.connection),
+                // 已结束的流仍可能收到 WINDOW_UPDATE，直接忽略
+                id => flow.streams.get_mut(&id),
+            };
+            if let Some(window) = window {
+                *window += increment;
+                if *window > MAX_WINDOW_SIZE {
+                    return Err(ConnectionError(FLOW_CONTROL_ERROR, "发送窗口溢出"));
+                }
+            }
+        }
+        self.shared.window_changed.notify_waiters();
+        Ok(())
+    }
+
+    /// 以错误码重置一个流。
+    async fn reset(&mut self, stream_id: u32, code: u32) -> Result<(), ConnectionError> {
+        self.shared.flow.lock().unwrap().streams.remove(&stream_id);
+        self.write_control(RST_STREAM, 0, stream_id, &code.to_be_bytes()).await
+    }
+
+    /// 请求接收完毕：转换为 [`Request`] 后在新任务中生成并发送响应。
+    fn dispatch(&mut self, stream_id: u32, receiving: Receiving) {
+        self.metrics.record_request();
+        let raw = match to_http1_request(&receiving.headers, &receiving.body) {
+            Some(raw) => raw,
+            None => {
+                warn!("[ID{}]流{}的请求头不符合HTTP/2规范，重置该流", self.id, stream_id);
+                let shared = Arc::clone(&self.shared);
+                let handle = self.tasks.spawn(async move {
+                    shared.flow.lock().unwrap().streams.remove(&stream_id);
+                    let _ = shared.write_frame(RST_STREAM, 0, stream_id, &PROTOCOL_ERROR.to_be_bytes()).await;
+                    stream_id
+                });
+                self.responding.insert(stream_id, handle);
+                return;
+            }
+        };
+        let request = match Request::try_from_with_limits(&raw, self.id, &self.limits) {
+            Ok(request) => request,
+            Err(e) => {
+                // 与 HTTP/1.1 一致：请求尚未解析成功，返回最简的纯文本响应
+                let code = match e {
+                    Exception::UriTooLong => 414,
+                    Exception::UnsupportedContentEncoding => 415,
+                    Exception::UnSupportedRequestMethod => 405,
+                    Exception::BodyTooLarge => 413,
+                    _ => 400,
+                };
+                warn!("[ID{}]拒绝流{}的请求({})，返回{}", self.id, stream_id, e, code);
+                let extra = match code {
+                    405 => {
+                        let methods: Vec<String> = self.allowed_methods.iter().map(ToString::to_string).collect();
+                        vec![("allow".to_string(), methods.join(", "))]
+                    }
+                    _ => Vec::new(),
+                };
+                self.spawn_status(stream_id, code, extra, false);
+                return;
+            }
+        };
+        debug!("[ID{}]流{}：{} {}", self.id, stream_id, request.method(), request.path());
+
+        let shared = Arc::clone(&self.shared);
+        let handler = Arc::clone(&self.handler);
+        let metrics = Arc::clone(&self.metrics);
+        let (id, chunk_size) = (self.id, self.chunk_size);
+        let headonly = request.method() == HttpRequestMethod::Head;
+        let handle = self.tasks.spawn(async move {
+            match handler(request).await {
+                Some(response) => {
+                    let status = response.status_code();
+                    match send_response(&shared, stream_id, response, headonly, chunk_size).await {
+                        Ok(sent) => {
+                            debug!("[ID{}]流{}的响应发送完成，共{}字节", id, stream_id, sent);
+                            metrics.record_response(status, sent);
+                        }
+                        Err(e) => debug!("[ID{}]流{}的响应未能发送完毕: {}", id, stream_id, e),
+                    }
+                }
+                None => {
+                    let _ = shared.write_frame(RST_STREAM, 0, stream_id, &INTERNAL_ERROR.to_be_bytes()).await;
+                }
+            }
+            shared.flow.lock().unwrap().streams.remove(&stream_id);
+            stream_id
+        });
+        self.responding.insert(stream_id, handle);
+    }
+
+    /// 在新任务中发送只包含状态码与原因短语的响应；`reset` 为真时随后以 `NO_ERROR` 重置流，
+    /// 告知客户端停止发送尚未发送完的请求体。
+    fn spawn_status(&mut self, stream_id: u32, code: u16, extra: Vec<(String, String)>, reset: bool) {
+        let shared = Arc::clone(&self.shared);
+        let metrics = Arc::clone(&self.metrics);
+        let handle = self.tasks.spawn(async move {
+            let reason = STATUS_CODES.get(&code).copied().unwrap_or("Bad Request");
+            let mut fields = vec![
+                (":status".to_string(), code.to_string()),
+                ("content-type".to_string(), "text/plain;charset=utf-8".to_string()),
+                ("content-length".to_string(), reason.len().to_string()),
+            ];
+            fields.extend(extra);
+            let mut block = Vec::new();
+            hpack::encode(fields.iter().map(|(n, v)| (n.as_str(), v.as_str())), &mut block);
+            let mut sent = 0;
+            if let Ok(n) = shared.write_headers(stream_id, &block, false).await {
+                sent += n;
+                sent += shared.write_data(stream_id, Bytes::from_static(reason.as_bytes()), true).await.unwrap_or(0);
+            }
+            if reset {
+                let _ = shared.write_frame(RST_STREAM, 0, stream_id, &NO_ERROR.to_be_bytes()).await;
+            }
+            metrics.record_response(code, sent);
+            shared.flow.lock().unwrap().streams.remove(&stream_id);
+            stream_id
+        });
+        self.responding.insert(stream_id, handle);
+    }
+}
+
+/// 把响应写到流上：先写出头部块，再按响应体的形式（内存中的内容、文件流或分块通道）写出 DATA 帧。
+/// 返回写出的字节数。
+async fn send_response(
+    shared: &Shared,
+    stream_id: u32,
+    mut response: Response,
+    headonly: bool,
+    chunk_size: usize,
+) -> io::Result<u64> {
+    let status = response.status_code();
+    let status_text = status.to_string();
+    let mut fields = vec![(":status".to_string(), status_text)];
+    for (name, value) in response.header_fields() {
+        let name = name.to_ascii_lowercase();
+        if !CONNECTION_HEADERS.contains(&name.as_str()) {
+            fields.push((name, value));
+        }
+    }
+    let mut block = Vec::new();
+    hpack::encode(fields.iter().map(|(n, v)| (n.as_str(), v.as_str())), &mut block);
+
+    // 取出分块响应体的接收端；不发送响应体时丢弃它，使生产者停止
+    let chunked = response.take_chunked_body();
+    let no_body = headonly || status == 204 || status == 304;
+    let mut body = match (chunked, response.stream_source()) {
+        _ if no_body => None,
+        (Some(receiver), _) => Some(receiver),
+        (None, Some(source)) => Some(stream_file(source.clone(), chunk_size)),
+        (None, None) => {
+            let content = response.content().filter(|c| !c.is_empty()).cloned();
+            let mut total = shared.write_headers(stream_id, &block, content.is_none()).await?;
+            if let Some(content) = content {
+                total += shared.write_data(stream_id, content, true).await?;
+            }
+            return Ok(total);
+        }
+    };
+
+    let mut total = shared.write_headers(stream_id, &block, body.is_none()).await?;
+    if let Some(body) = body.as_mut() {
+        while let Some(chunk) = body.recv().await {
+            match chunk {
+                Ok(chunk) => total += shared.write_data(stream_id, chunk, false).await?,
+                Err(e) => {
+                    // 响应体不完整：重置流而不是正常结束，客户端据此得知内容被截断
+                    let _ = shared.write_frame(RST_STREAM, 0, stream_id, &INTERNAL_ERROR.to_be_bytes()).await;
+                    return Err(e);
+                }
+            }
+        }
+        total += shared.write_data(stream_id, Bytes::new(), true).await?;
+    }
+    Ok(total)
+}
+
+/// 在后台任务中按 [`StreamSource::write_to`] 读取文件，把内容逐块送入通道。
+///
+/// 接收端被丢弃（流被重置）时，管道的读端随之关闭，文件读取因写入失败而停止。
+fn stream_file(source: StreamSource, chunk_size: usize) -> mpsc::Receiver<io::Result<Bytes>> {
+    let chunk_size = chunk_size.max(1);
+    let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+    tokio::spawn(async move {
+        let (mut pipe_writer, mut pipe_reader) = tokio::io::duplex(chunk_size);
+        let copy = async move { source.write_to(&mut pipe_writer, chunk_size).await };
+        let forward = {
+            let tx = tx.clone();
+            async move {
+                let mut buffer = vec![0u8; chunk_size];
+                loop {
+                    match pipe_reader.read(&mut buffer).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => {
+                            if tx.send(Ok(Bytes::copy_from_slice(&buffer[..n]))).await.is_err() {
+                                break;
+                            }
+                        }
+                    }
+                }
+            }
+        };
+        let (copied, ()) = tokio::join!(copy, forward);
+        if let Err(e) = copied {
+            let _ = tx.send(Err(e)).await;
+        }
+    });
+    rx
+}
+
+/// 把 HTTP/2 请求的头部字段与请求体转换为等价的 HTTP/1.1 请求报文。
+///
+/// 伪头部 `:method`、`:path` 组成请求行，`:authority` 在没有 `host` 头时转为 `Host`；
+/// 拆分发送的多个 `cookie` 字段按 RFC 9113 §8.2.3 以 `; ` 合并；`Content-Length` 按实际收到的请求体重新计算。
+/// 请求头不合规范（缺少伪头部、伪头部出现在普通字段之后、字段名含大写字母、含有 HTTP/1.1 连接专用的字段，
+/// 或值中含有 CR/LF/NUL）时返回 `None`。
+fn to_http1_request(headers: &[(String, String)], body: &[u8]) -> Option<Vec<u8>> {
+    let (mut method, mut path, mut authority, mut scheme) = (None, None, None, None);
+    let mut fields = Vec::new();
+    let mut cookies = Vec::new();
+    for (name, value) in headers {
+        if name.is_empty() || value.contains(['\r', '\n', '\0']) || name.bytes().any(|b| b.is_ascii_uppercase()) {
+            return None;
+        }
+        match name.as_str() {
+            ":method" if fields.is_empty() && method.is_none() => method = Some(value.as_str()),
+            ":path" if fields.is_empty() && path.is_none() && !value.is_empty() => path = Some(value.as_str()),
+            ":authority" if fields.is_empty() && authority.is_none() => authority = Some(value.as_str()),
+            ":scheme" if fields.is_empty() && scheme.is_none() => scheme = Some(value.as_str()),
+            n if n.starts_with(':') || CONNECTION_HEADERS.contains(&n) => return None,
+            "te" if value != "trailers" => return None,
+            "cookie" => cookies.push(value.as_str()),
+            "content-length" => {}
+            _ => fields.push((name.as_str(), value.as_str())),
+        }
+    }
+    let (method, path) = (method?, path?);
+    scheme?;
+
+    let mut head = format!("{} {} HTTP/1.1\r\n", method, path);
+    if let Some(authority) = authority.filter(|_| !fields.iter().any(|(n, _)| *n == "host")) {
+        head.push_str(&format!("host: {}\r\n", authority));
+    }
+    for (name, value) in fields {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    if !cookies.is_empty() {
+        head.push_str(&format!("cookie: {}\r\n", cookies.join("; ")));
+    }
+    if !body.is_empty() {
+        head.push_str(&format!("content-length: {}\r\n", body.len()));
+    }
+    head.push_str("\r\n");
+    let mut raw = head.into_bytes();
+    raw.extend_from_slice(body);
+    Some(raw)
+}
+
+/// 去掉 DATA/HEADERS 帧的填充，返回帧内容。
+fn strip_padding(frame: &Frame) -> Result<&[u8], ConnectionError> {
+    if frame.flags & PADDED == 0 {
+        return Ok(&frame.payload);
+    }
+    let pad = *frame.payload.first().ok_or(ConnectionError(FRAME_SIZE_ERROR, "填充长度缺失"))? as usize;
+    if pad >= frame.payload.len() {
+        return Err(ConnectionError(PROTOCOL_ERROR, "填充长度超过帧长度"));
+    }
+    Ok(&frame.payload[1..frame.payload.len() - pad])
+}
+
+/// 编码一个帧：24 位长度、类型、标志与 31 位流 ID，随后是帧内容。
+fn encode_frame(kind: u8, flags: u8, stream_id: u32, payload: &[u8]) -> Vec<u8> {
+    let len = payload.len() as u32;
+    let mut frame = Vec::with_capacity(FRAME_HEADER_LEN + payload.len());
+    frame.extend_from_slice(&len.to_be_bytes()[1..]);
+    frame.push(kind);
+    frame.push(flags);
+    frame.extend_from_slice(&(stream_id & 0x7fff_ffff).to_be_bytes());
+    frame.extend_from_slice(payload);
+    frame
+}
+
+/// 读取一个帧。连接在帧边界处关闭时返回 `None`；帧长度超过本端的上限时返回 `InvalidData`。
+async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<Option<Frame>> {
+    let mut header = [0u8; FRAME_HEADER_LEN];
+    match reader.read(&mut header[..1]).await? {
+        0 => return Ok(None),
+        _ => reader.read_exact(&mut header[1..]).await?,
+    };
+    let len = u32::from_be_bytes([0, header[0], header[1], header[2]]) as usize;
+    if len > MAX_FRAME_SIZE {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "帧长度超过上限"));
+    }
+    let mut payload = vec![0u8; len];
+    reader.read_exact(&mut payload).await?;
+    Ok(Some(Frame {
+        kind: header[3],
+        flags: header[4],
+        stream_id: u32::from_be_bytes([header[5], header[6], header[7], header[8]]) & 0x7fff_ffff,
+        payload,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::DuplexStream;
+
+    /// 测试用的客户端：发送序言与 SETTINGS，随后逐帧读写。
+    struct Client {
+        stream: DuplexStream,
+        decoder: Decoder,
+    }
+
+    impl Client {
+        async fn connect(handler: impl Fn(Request) -> Option<Response> + Send + Sync + 'static, settings: &[(u16, u32)]) -> Self {
+            let (client, server) = tokio::io::duplex(1 << 20);
+            let config = Config::new();
+            let metrics = Arc::new(Metrics::new());
+            let handler = Arc::new(handler);
+            tokio::spawn(async move {
+                serve(server, 0, &config, &metrics, move |request| {
+                    let handler = Arc::clone(&handler);
+                    async move { handler(request) }
+                })
+                .await;
+            });
+            let mut client = Client { stream: client, decoder: Decoder::new(HEADER_TABLE_SIZE) };
+            client.stream.write_all(PREFACE).await.unwrap();
+            let payload: Vec<u8> = settings
+                .iter()
+                .flat_map(|(id, value)| id.to_be_bytes().into_iter().chain(value.to_be_bytes()))
+                .collect();
+            client.send(SETTINGS, 0, 0, &payload).await;
+            // 服务端先发送自己的 SETTINGS，随后确认客户端的 SETTINGS
+            assert_eq!(client.next().await.kind, SETTINGS);
+            let ack = client.next().await;
+            assert_eq!((ack.kind, ack.flags), (SETTINGS, ACK));
+            client
+        }
+
+        async fn send(&mut self, kind: u8, flags: u8, stream_id: u32, payload: &[u8]) {
+            self.stream.write_all(&encode_frame(kind, flags, stream_id, payload)).await.unwrap();
+        }
+
+        async fn request(&mut self, stream_id: u32, headers: &[(&str, &str)], end_stream: bool) {
+            let mut block = Vec::new();
+            hpack::encode(headers.iter().copied(), &mut block);
+            let flags = END_HEADERS | if end_stream { END_STREAM } else { 0 };
+            self.send(HEADERS, flags, stream_id, &block).await;
+        }
+
+        async fn next(&mut self) -> Frame {
+            let frame = tokio::time::timeout(Duration::from_secs(5), read_frame(&mut self.stream)).await;
+            frame.expect("等待帧超时").unwrap().expect("连接已关闭")
+        }
+
+        /// 读取下一个 HEADERS 帧并解码，跳过其间的 WINDOW_UPDATE
+        async fn headers(&mut self) -> (Frame, Vec<(String, String)>) {
+            loop {
+                let frame = self.next().await;
+                if frame.kind == HEADERS {
+                    let headers = self.decoder.decode(&frame.payload, 1 << 20).unwrap();
+                    return (frame, headers);
+                }
+                assert_eq!(frame.kind, WINDOW_UPDATE, "{:?}", frame);
+            }
+        }
+    }
+
+    fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+        headers.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+
+    fn get(path: &str) -> Vec<(&str, &str)> {
+        vec![(":method", "GET"), (":scheme", "https"), (":path", path), (":authority", "localhost")]
+    }
+
+    fn echo(request: Request) -> Option<Response> {
+        let text = format!("{} {} host={}", request.method(), request.path(), request.header("host").unwrap_or(""));
+        Some(Response::from_text(&text, "text/plain", &request, 0))
+    }
+
+    #[tokio::test]
+    async fn test_get_request() {
+        let mut client = Client::connect(echo, &[]).await;
+        client.request(1, &get("/hello?x=1"), true).await;
+
+        let (frame, headers) = client.headers().await;
+        assert_eq!(frame.stream_id, 1);
+        assert_eq!(frame.flags & END_STREAM, 0);
+        assert_eq!(header(&headers, ":status"), Some("200"));
+        assert_eq!(header(&headers, "content-type"), Some("text/plain"));
+        // 只属于 HTTP/1.1 连接的字段不能出现在 HTTP/2 响应中
+        assert_eq!(header(&headers, "connection"), None);
+        let body = "GET /hello?x=1 host=localhost";
+        assert_eq!(header(&headers, "content-length"), Some(body.len().to_string().as_str()));
+
+        let data = client.next().await;
+        assert_eq!((data.kind, data.stream_id, data.flags), (DATA, 1, END_STREAM));
+        assert_eq!(data.payload, body.as_bytes());
+
+        // PING 原样确认
+        client.send(PING, 0, 0, b"12345678").await;
+        let pong = client.next().await;
+        assert_eq!((pong.kind, pong.flags, pong.payload.as_slice()), (PING, ACK, &b"12345678"[..]));
+    }
+
+    #[tokio::test]
+    async fn test_head_request_has_no_body() {
+        let mut client = Client::connect(echo, &[]).await;
+        let mut headers = get("/");
+        headers[0].1 = "HEAD";
+        client.request(1, &headers, true).await;
+        let (frame, _) = client.headers().await;
+        assert_eq!(frame.flags & END_STREAM, END_STREAM);
+    }
+
+    #[tokio::test]
+    async fn test_request_body_and_continuation() {
+        let handler = |request: Request| {
+            let text = format!("{}:{}", request.header("content-type").unwrap_or(""), String::from_utf8_lossy(request.body()));
+            Some(Response::from_text(&text, "text/plain", &request, 0))
+        };
+        let mut client = Client::connect(handler, &[]).await;
+
+        // 头部块拆分为 HEADERS 与 CONTINUATION，请求体分两个 DATA 帧发送（第二个带填充）
+        let mut block = Vec::new();
+        let headers = [(":method", "POST"), (":scheme", "https"), (":path", "/echo"), (":authority", "localhost"), ("content-type", "text/plain")];
+        hpack::encode(headers.iter().copied(), &mut block);
+        let (first, rest) = block.split_at(5);
+        client.send(HEADERS, 0, 3, first).await;
+        client.send(CONTINUATION, END_HEADERS, 3, rest).await;
+        client.send(DATA, 0, 3, b"hello ").await;
+        client.send(DATA, END_STREAM | PADDED, 3, &[2, b'h', b'2', 0, 0]).await;
+
+        let (_, headers) = client.headers().await;
+        assert_eq!(header(&headers, ":status"), Some("200"));
+        let data = client.next().await;
+        assert_eq!(data.payload, b"text/plain:hello h2");
+    }
+
+    #[tokio::test]
+    async fn test_flow_control_and_multiplexing() {
+        let handler = |request: Request| {
+            let size = if request.path() == "/big" { 100 } else { 10 };
+            Some(Response::from_text(&"x".repeat(size), "text/plain", &request, 0))
+        };
+        // 客户端的流初始窗口只有 40 字节
+        let mut client = Client::connect(handler, &[(SETTINGS_INITIAL_WINDOW_SIZE, 40)]).await;
+        client.request(1, &get("/big"), true).await;
+        let (frame, _) = client.headers().await;
+        assert_eq!(frame.stream_id, 1);
+        let data = client.next().await;
+        assert_eq!((data.kind, data.payload.len(), data.flags), (DATA, 40, 0));
+
+        // 流 1 等待窗口时，流 3 的响应照常发送
+        client.request(3, &get("/small"), true).await;
+        let (frame, _) = client.headers().await;
+        assert_eq!(frame.stream_id, 3);
+        let data = client.next().await;
+        assert_eq!((data.stream_id, data.payload.len(), data.flags), (3, 10, END_STREAM));
+
+        // 归还流 1 的窗口后，剩余内容发送完毕
+        client.send(WINDOW_UPDATE, 0, 1, &100u32.to_be_bytes()).await;
+        let data = client.next().await;
+        assert_eq!((data.stream_id, data.payload.len(), data.flags), (1, 60, END_STREAM));
+    }
+
+    #[tokio::test]
+    async fn test_rejected_requests() {
+        let mut client = Client::connect(echo, &[]).await;
+        // 缺少 :path 的请求被重置
+        client.request(1, &[(":method", "GET"), (":scheme", "https")], true).await;
+        let reset = client.next().await;
+        assert_eq!((reset.kind, reset.stream_id, reset.payload), (RST_STREAM, 1, PROTOCOL_ERROR.to_be_bytes().to_vec()));
+
+        // 不支持的方法与 HTTP/1.1 一样返回 405
+        let mut headers = get("/");
+        headers[0].1 = "BREW";
+        client.request(3, &headers, true).await;
+        let (_, headers) = client.headers().await;
+        assert_eq!(header(&headers, ":status"), Some("405"));
+        assert!(header(&headers, "allow").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_protocol_error_sends_goaway() {
+        let mut client = Client::connect(echo, &[]).await;
+        // 客户端只能使用奇数流 ID
+        client.request(2, &get("/"), true).await;
+        let goaway = client.next().await;
+        assert_eq!(goaway.kind, GOAWAY);
+        assert_eq!(&goaway.payload[4..8], &PROTOCOL_ERROR.to_be_bytes());
+        assert!(read_frame(&mut client.stream).await.unwrap().is_none());
+    }
+
+    #[test]
+    fn test_to_http1_request() {
+        let headers: Vec<(String, String)> = [
+            (":method", "POST"),
+            (":scheme", "https"),
+            (":path", "/a?b=1"),
+            (":authority", "example.com"),
+            ("cookie", "a=1"),
+            ("content-length", "999"),
+            ("cookie", "b=2"),
+        ]
+        .iter()
+        .map(|&(n, v)| (n.to_string(), v.to_string()))
+        .collect();
+        let raw = to_http1_request(&headers, b"body").unwrap();
+        assert_eq!(
+            String::from_utf8(raw).unwrap(),
+            "POST /a?b=1 HTTP/1.1\r\nhost: example.com\r\ncookie: a=1; b=2\r\ncontent-length: 4\r\n\r\nbody"
+        );
+
+        let invalid = |extra: (&str, &str)| {
+            let mut headers = headers.clone();
+            headers.push((extra.0.to_string(), extra.1.to_string()));
+            to_http1_request(&headers, b"")
+        };
+        assert!(invalid(("connection", "close")).is_none());
+        assert!(invalid(("x-name", "a\r\nb")).is_none());
+        assert!(invalid(("X-Upper", "a")).is_none());
+        assert!(invalid((":method", "GET")).is_none());
+        assert!(invalid(("te", "gzip")).is_none());
+        assert!(invalid(("te", "trailers")).is_some());
+    }
+}
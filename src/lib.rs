@@ -10,33 +10,62 @@
 //! 本项目采用了模块化的设计思路，各组件职责明确：
 //! - **请求处理**: `request` 与 `param` 模块负责解析与验证。
 //! - **响应构建**: `response` 与 `util` 模块负责生成输出。
-//! - **性能优化**: `cache` 模块提供基于内存的快速文件检索。
+//! - **性能优化**: `cache` 模块提供基于内存的快速文件检索，`clock` 模块为其提供可替换的时间来源。
 //! - **配置与异常**: `config` 与 `exception` 模块确保系统的可配置性与健壮性。
+//! - **可观测性**: `metrics` 模块以原子计数器统计运行状况并导出 Prometheus 指标。
+//! - **扩展点**: `middleware` 模块提供请求/响应钩子，用于日志、鉴权、注入响应头等横切逻辑；
+//!   `router` 模块的路由表把路径模式映射到异步处理器，用于编写动态接口；`ratelimit` 模块以中间件的形式按客户端 IP 限流。
+//! - **传输安全**: `tls` 模块加载证书与私钥，为 HTTPS 监听器提供 TLS 握手；`auth` 模块以 Basic 与 Bearer 认证保护指定路径；`cors` 模块处理浏览器的跨域请求。
+//! - **HTTP/2**: `http2` 模块在 ALPN 协商出 `h2` 的 TLS 连接上提供 HTTP/2，头部压缩由 `hpack` 模块实现。
 //!
 //! ## 快捷导出 (Public API)
 //!
 //! 为了简化调用方的使用，本项目通过 `pub use` 将核心类型重定向至根命名空间，
 //! 开发者可以直接通过 `crate::Request` 或 `crate::Response` 进行调用，而无需关心内部路径。
 
+/// 访问控制：以 Basic（htpasswd 用户文件）或 Bearer 令牌保护配置的路径前缀。
+pub mod auth;
 /// 内部缓存实现模块，支持过期验证。
 pub mod cache;
+/// 时钟抽象模块，便于在测试中注入可控的时间来源。
+pub mod clock;
 /// 配置管理模块，支持 TOML 解析。
 pub mod config;
+/// 跨域资源共享：应答 CORS 预检请求，并为允许的来源追加 `Access-Control-Allow-*` 响应头。
+pub mod cors;
 /// 全局异常与错误类型定义模块。
 pub mod exception;
+/// FastCGI 客户端，把 PHP 请求交给 php-fpm 等 FastCGI 应用处理。
+pub mod fastcgi;
+/// HTTP/2 头部压缩（HPACK）的编码与解码。
+pub mod hpack;
+/// HTTP/2 服务端：帧的编解码、流的多路复用与流量控制。
+pub mod http2;
+/// 运行指标统计与 Prometheus 格式导出。
+pub mod metrics;
+/// 请求/响应中间件钩子与内置的访问日志中间件。
+pub mod middleware;
 /// HTTP 协议相关的参数定义（方法、版本、编码）。
 pub mod param;
+/// 反向代理：按路径前缀把请求转发给上游 HTTP 服务器。
+pub mod proxy;
+/// 按客户端 IP 的令牌桶限流。
+pub mod ratelimit;
 /// HTTP 请求对象的定义与解析逻辑。
 pub mod request;
 /// HTTP 响应对象的构建与序列化。
 pub mod response;
+/// 可编程路由表与静态文件路由。
+pub mod router;
+/// 从 PEM 文件加载证书与私钥，构建 HTTPS 监听器使用的 TLS 接受器。
+pub mod tls;
 /// 通用辅助工具，包含 HTML 模板构建器等。
 pub mod util;
 
 // --- 统一对外的公共接口 (Facade Pattern) ---
 
 /// 重定向导出 `FileCache`：提供高效的文件内存缓存。
-pub use cache::FileCache;
+pub use cache::{CacheStats, FileCache};
 
 /// 重定向导出 `Exception`：统一的错误处理枚举。
 pub use exception::Exception;
@@ -44,11 +73,23 @@ pub use exception::Exception;
 /// 重定向导出基础协议参数。
 pub use param::{HttpEncoding, HttpRequestMethod, HttpVersion};
 
+/// 重定向导出 `Middleware`：可在请求前后执行自定义逻辑的中间件。
+pub use middleware::{Middleware, MiddlewareChain};
+
 /// 重定向导出 `Request`：代表一个解析后的客户端请求。
 pub use request::Request;
 
 /// 重定向导出 `Response`：用于构造发送回客户端的响应。
 pub use response::Response;
 
+/// 重定向导出 `Router`：按路径模式分发请求的路由表。
+pub use router::{Params, Router};
+
+/// 重定向导出 `CookieAttributes`：Set-Cookie 的可选属性。
+pub use response::{CookieAttributes, SameSite};
+
+/// 重定向导出 `StreamSource`：分块写出的流式响应体。
+pub use response::{StreamSegment, StreamSource};
+
 /// 重定向导出 `HtmlBuilder`：支持链式调用的 HTML 生成工具。
 pub use util::HtmlBuilder;
\ No newline at end of file
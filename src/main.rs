@@ -14,122 +14,266 @@
 
 #![allow(clippy::unused_io_amount)]
 
-// --- 模块定义 ---
-mod cache;      // 高效文件缓存实现
-mod config;     // 配置解析与管理
-mod exception;  // 自定义异常与错误处理
-mod param;      // 全局常量与静态参数
-mod request;    // HTTP 请求报文解析器
-mod response;   // HTTP 响应报文构建器
-mod util;       // 通用工具函数
-
-use cache::FileCache;
-use config::Config;
-use request::Request;
-use response::Response;
+// --- 模块引用 ---
+// 各模块统一由 `webserver` 库 crate 提供，二进制只负责组装与运行。
+use webserver::{
+    auth::{self, AuthError},
+    cache::FileCache,
+    config::Config,
+    cors,
+    exception::Exception,
+    metrics::{Metrics, METRICS_CONTENT_TYPE},
+    middleware::{LoggingMiddleware, MiddlewareChain},
+    param::{HttpRequestMethod, HttpVersion, DEFAULT_ROBOTS_TXT, STATUS_CODES},
+    proxy,
+    ratelimit::RateLimiter,
+    request::{chunked_body_len, BodyFraming, Request},
+    response::{write_chunked, write_close_delimited, Response},
+    router::{resolve_path, resolve_upload_path, Router},
+    http2,
+    tls::{load_acceptor, negotiated_h2, TlsAcceptor},
+};
 
 use log::{debug, error, info, warn};
 use log4rs;
 use regex::Regex;
 use tokio::{
-    fs::File as TokioFile,
-    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader},
     net::{TcpListener, TcpStream},
-    runtime::Builder,
+    runtime::{Builder, Runtime},
+    sync::{Notify, RwLock},
 };
+#[cfg(unix)]
+use tokio::net::{UnixListener, UnixStream};
 
 use std::{
-    net::{Ipv4Addr, SocketAddrV4},
+    io,
+    net::SocketAddr,
     path::{Path, PathBuf},
     process::Command,
-    sync::{Arc, Mutex},
-    time::Instant,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant, SystemTime},
 };
 
-use crate::{exception::Exception, param::HTML_INDEX};
+/// 配置文件路径。启动时从此处载入，`reload` 指令与 SIGHUP 也会重新载入该文件。
+const CONFIG_PATH: &str = "config/development.toml";
+
+/// 运行时线程名的前缀，线程依次命名为 `ws-worker-0`、`ws-worker-1`……便于在 `top` 与火焰图中辨认。
+const THREAD_NAME_PREFIX: &str = "ws-worker";
+
+/// 停机时等待进行中的连接处理完毕的最长时间，超时后直接退出。
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 停机期间检查进行中的连接是否已全部完成的间隔。
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// 停机期间的 503 响应建议客户端重试的间隔（秒）。
+const DRAIN_RETRY_AFTER_SECS: u64 = 5;
+
+/// 达到连接数上限时 429 响应建议客户端重试的间隔（秒）。
+const CONNECTION_LIMIT_RETRY_AFTER_SECS: u64 = 1;
+
+/// 可在运行期间原子替换的共享配置。
+///
+/// 每个新连接在建立时取得当前配置的快照（`Arc<Config>`），进行中的请求始终使用自己的快照，
+/// 热重载只影响之后建立的连接。
+type SharedConfig = Arc<std::sync::RwLock<Arc<Config>>>;
 
 /// # 程序入口点
 /// 
-/// 初始化系统环境、加载配置、探测外部依赖并启动主事件循环。
-#[tokio::main]
-async fn main() {
+/// 初始化系统环境、加载配置，按配置构建异步运行时并在其上运行服务器。
+fn main() {
     // 1. 初始化日志系统：采用 log4rs 异步日志架构，通过外部 YAML 灵活配置级别与输出目的地
     log4rs::init_file("config/log4rs.yaml", Default::default()).unwrap();
 
     // 2. 环境配置加载：从 TOML 文件读取运行参数
-    let config = Config::from_toml("config/development.toml");
+    // 解析失败或配置不合法时直接退出，而不是带着默认值或半合法的配置继续运行
+    let config = match Config::try_from_toml(CONFIG_PATH) {
+        Ok(config) => config,
+        Err(e) => {
+            error!("无法解析配置文件{}：{}", CONFIG_PATH, e);
+            std::process::exit(1);
+        }
+    };
+    if config.create_www_root() {
+        match config.create_missing_roots() {
+            Ok(created) => {
+                for root in created {
+                    info!("资源根目录{}不存在，已自动创建", root);
+                }
+            }
+            Err(e) => {
+                error!("无法创建资源根目录：{}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+    if let Err(e) = config.validate() {
+        error!("配置文件{}不合法：{}", CONFIG_PATH, e);
+        std::process::exit(1);
+    }
     info!("配置文件已载入");
-    let root = config.www_root().to_string();
-    info!("www root: {}", &root);
+    let roots = config.www_roots().to_vec();
+    info!("www root: {}", roots.join(", "));
 
-    // 3. 异步运行时定制：根据配置文件动态分配工作线程数，实现 CPU 绑定的并发优化
-    let worker_threads = config.worker_threads();
-    let runtime = Builder::new_multi_thread()
-        .worker_threads(worker_threads)
-        .build()
-        .unwrap();
+    // 3. 异步运行时定制：根据配置文件动态分配工作线程数与阻塞线程池上限，所有连接都在该运行时上处理
+    let runtime = match build_runtime(&config) {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            error!("无法创建异步运行时：{}", e);
+            std::process::exit(1);
+        }
+    };
+    runtime.block_on(serve(config));
+    // 控制台任务可能仍阻塞在读取标准输入上，不等待它结束
+    runtime.shutdown_background();
+}
 
+/// 探测外部依赖、绑定监听器并运行主事件循环，直到停机排空完成。
+async fn serve(config: Config) {
     // 4. 共享资源初始化：
-    // - 使用 Arc<Mutex<...>> 保证缓存系统在多线程环境下的线程安全
+    // - 使用 Arc<RwLock<...>>（tokio 异步读写锁）共享缓存，命中时多个请求只需共享读锁
     // - 采用容量受限的缓存机制防止内存溢出
     let cache_size = config.cache_size();
-    let cache = Arc::new(Mutex::new(FileCache::from_capacity(cache_size)));
-    let config_arc = Arc::new(config.clone());
-
-    // 5. 外部依赖探测：自动检查系统环境中的 PHP 解释器版本
-    let php_result = Command::new("php").arg("-v").output();
-    match php_result {
-        Ok(o) => {
-            if o.status.success() {
-                let output = String::from_utf8_lossy(&o.stdout);
-                // 使用正则表达式精准提取版本号
-                let re = Regex::new(r"PHP (\d+\.\d+\.\d+-\dubuntu\d+\.\d+)").unwrap();
-                if let Some(capture) = re.captures(&output) {
-                    if let Some(version) = capture.get(1) {
-                        info!("找到PHP解释器，版本：{}", version.as_str());
+    let mut file_cache = FileCache::from_capacity(cache_size).with_max_bytes(config.max_cache_bytes());
+    if let Some(ttl) = config.cache_ttl_secs() {
+        info!("缓存条目存活时间：{}秒", ttl);
+        file_cache = file_cache.with_ttl(Duration::from_secs(ttl));
+    }
+    let cache = Arc::new(RwLock::new(file_cache));
+    // - 中间件链在所有连接之间共享，访问日志由内置的日志中间件输出；启用限流时限流器也作为中间件注册，
+    //   管理控制台保留一份引用以显示限流计数
+    let rate_limiter = RateLimiter::from_config(config.rate_limit()).map(Arc::new);
+    let mut middlewares = MiddlewareChain::new().with(LoggingMiddleware);
+    if let Some(limiter) = &rate_limiter {
+        info!(
+            "按客户端IP限流：每秒{}个请求，突发{}个",
+            config.rate_limit().requests_per_second().unwrap_or_default(),
+            config.rate_limit().burst()
+        );
+        middlewares = middlewares.with(Arc::clone(limiter));
+    }
+    let middlewares = Arc::new(middlewares);
+    // - 路由表同样在所有连接之间共享；服务器本身不注册处理器，请求全部交给静态文件路由
+    let router = Arc::new(Router::new());
+    let shared_config: SharedConfig = Arc::new(std::sync::RwLock::new(Arc::new(config.clone())));
+
+    // 5. 外部依赖探测：配置了 FastCGI 应用时 PHP 请求交给它处理，否则检查系统环境中的 PHP 解释器版本
+    if let Some(address) = config.fastcgi_address() {
+        info!("PHP请求通过FastCGI应用{:?}处理", address);
+    } else {
+        let php_path = config.php_path();
+        match resolve_executable(php_path) {
+            Some(resolved) => info!("PHP解释器路径：{}", resolved.display()),
+            None => warn!("在PATH中找不到PHP解释器{}", php_path),
+        }
+        let php_result = Command::new(php_path).arg("-v").output();
+        match php_result {
+            Ok(o) => {
+                if o.status.success() {
+                    let output = String::from_utf8_lossy(&o.stdout);
+                    // 提取版本号，兼容各发行版附加的后缀（如 8.2.7、8.1.2-1ubuntu2.14）
+                    let re = Regex::new(r"PHP (\d+\.\d+\.\d+\S*)").unwrap();
+                    match re.captures(&output).and_then(|capture| capture.get(1)) {
+                        Some(version) => info!("找到PHP解释器，版本：{}", version.as_str()),
+                        None => info!("找到PHP解释器，但无法识别版本号"),
                     }
+                } else {
+                    warn!("PHP解释器{}无法正常运行。服务器将继续运行，但PHP请求将返回500。", php_path);
                 }
-            } else {
-                panic!("在查找PHP解释器时遇到未知错误");
             }
-        }
-        Err(_) => {
-            warn!("无法找到PHP解释器。服务器将继续运行，但将无法处理PHP请求。");
-        }
-    };
+            Err(_) => {
+                warn!("无法找到PHP解释器{}。服务器将继续运行，但将无法处理PHP请求。", php_path);
+            }
+        };
+    }
 
     // 6. 网络层初始化：
-    // 支持全地址监听 (0.0.0.0) 或本地回环监听 (127.0.0.1)
-    let port: u16 = config.port();
-    info!("服务端将在{}端口上监听Socket连接", port);
-    let address = match config.local() {
-        true => Ipv4Addr::new(127, 0, 0, 1),
-        false => Ipv4Addr::new(0, 0, 0, 0),
+    // 配置了 unix_socket 时使用 Unix 域套接字（便于置于 nginx 等反向代理之后）；
+    // 否则优先使用 bind_address（支持 IPv4/IPv6），未设置时根据 local 选择 127.0.0.1 或 0.0.0.0。
+    // 启用 HTTPS 时先载入证书，失败直接退出；设置了 tls_port 时在该端口另外监听 HTTPS，否则 port 本身改为 HTTPS
+    let tls_acceptor = match (config.tls_enabled(), config.cert_path(), config.key_path()) {
+        (true, Some(cert_path), Some(key_path)) => match load_acceptor(cert_path, key_path, config.http2()) {
+            Ok(acceptor) => {
+                info!("已载入TLS证书{}", cert_path);
+                Some(acceptor)
+            }
+            Err(e) => {
+                error!("无法载入TLS证书或私钥：{}", e);
+                std::process::exit(1);
+            }
+        },
+        _ => None,
     };
-    info!("服务端将在{}地址上监听Socket连接", address);
-    let socket = SocketAddrV4::new(address, port);
-
-    // 绑定端口并启动监听器
-    let listener = match TcpListener::bind(socket).await {
-        Ok(listener) => listener,
-        Err(e) => {
-            error!("无法绑定端口：{}，错误：{}", port, e);
-            panic!("无法绑定端口：{}，错误：{}", port, e);
+    let mut tls_listener = None;
+    let listener = match config.unix_socket() {
+        #[cfg(unix)]
+        Some(path) => {
+            info!("服务端将在Unix套接字{}上监听连接", path);
+            match bind_unix_socket(path) {
+                Ok(listener) => {
+                    info!("Unix套接字{}绑定完成", path);
+                    Listener::Unix(listener)
+                }
+                Err(e) => {
+                    error!("无法绑定Unix套接字：{}，错误：{}", path, e);
+                    panic!("无法绑定Unix套接字：{}，错误：{}", path, e);
+                }
+            }
+        }
+        #[cfg(not(unix))]
+        Some(_) => {
+            error!("当前平台不支持Unix域套接字");
+            panic!("当前平台不支持Unix域套接字");
+        }
+        None => {
+            let socket = match config.listen_addr() {
+                Ok(socket) => socket,
+                Err(e) => {
+                    let address = config.bind_address().unwrap_or_default();
+                    error!("无法解析监听地址bind_address：{}，错误：{}", address, e);
+                    panic!("无法解析监听地址bind_address：{}，错误：{}", address, e);
+                }
+            };
+            match (tls_acceptor, config.tls_port()) {
+                (Some(acceptor), Some(tls_port)) => {
+                    let tls_socket = SocketAddr::new(socket.ip(), tls_port);
+                    info!("服务端将在{}上监听HTTPS连接", tls_socket);
+                    tls_listener = Some(Listener::Tls(bind_tcp(tls_socket).await, acceptor));
+                    info!("服务端将在{}上监听Socket连接", socket);
+                    Listener::Tcp(bind_tcp(socket).await)
+                }
+                (Some(acceptor), None) => {
+                    info!("服务端将在{}上监听HTTPS连接", socket);
+                    Listener::Tls(bind_tcp(socket).await, acceptor)
+                }
+                (None, _) => {
+                    info!("服务端将在{}上监听Socket连接", socket);
+                    Listener::Tcp(bind_tcp(socket).await)
+                }
+            }
         }
     };
-    info!("端口{}绑定完成", port);
 
     // 7. 服务器状态与生命周期管理
-    // shutdown_flag: 用于优雅停机 (Graceful Shutdown)
-    // active_connection: 原子追踪当前并发连接数
+    // shutdown_flag: 用于优雅停机 (Graceful Shutdown)，shutdown_notify 唤醒正在等待新连接的主循环
+    // metrics: 原子计数器，追踪请求数、响应状态、发送字节与当前并发连接数
     let shutdown_flag = Arc::new(Mutex::new(false));
-    let active_connection = Arc::new(Mutex::new(0u32));
+    let shutdown_notify = Arc::new(Notify::new());
+    let metrics = Arc::new(Metrics::new());
 
     // 8. 启动交互式管理控制台任务
     // 该任务运行在后台，不阻塞监听循环，提供运维指令支持
-    runtime.spawn({
+    tokio::spawn({
         let shutdown_flag = Arc::clone(&shutdown_flag);
-        let active_connection = Arc::clone(&active_connection);
+        let shutdown_notify = Arc::clone(&shutdown_notify);
+        let metrics = Arc::clone(&metrics);
+        let shared_config = Arc::clone(&shared_config);
+        let cache = Arc::clone(&cache);
+        let rate_limiter = rate_limiter.clone();
         async move {
             let stdin = tokio::io::stdin();
             let mut reader = BufReader::new(stdin);
@@ -140,24 +284,44 @@ async fn main() {
                     let cmd = input.trim();
                     match cmd {
                         "stop" => {
-                            let mut flag = shutdown_flag.lock().unwrap();
-                            *flag = true;
-                            println!("停机指令已激活，服务器将在处理完下一个请求后关闭...");
+                            *shutdown_flag.lock().unwrap() = true;
+                            shutdown_notify.notify_one();
+                            println!("停机指令已激活，服务器将拒绝新请求，并在进行中的请求完成后关闭...");
                             break;
                         }
+                        "reload" => {
+                            match reload_config(CONFIG_PATH, &shared_config, &cache).await {
+                                true => println!("配置已重新载入，将对新建立的连接生效"),
+                                false => println!("重新载入配置失败，继续使用当前配置，详见日志"),
+                            }
+                        }
                         "help" => {
                             println!("== Webserver Help ==");
                             println!("stop   - 发出停机信号");
+                            println!("reload - 重新载入配置文件");
                             println!("status - 查看当前服务器运行状态");
+                            println!("cache list - 列出缓存中的文件及其大小、修改时间");
                             println!("help   - 显示此帮助信息");
                             println!("====================");
                         }
                         "status" => {
-                            let active_count = *active_connection.lock().unwrap();
+                            let active_count = metrics.active_connections();
                             println!("== Webserver 状态 ===");
                             println!("当前活跃连接数: {}", active_count);
+                            println!("因连接数上限拒绝的连接数: {}", metrics.connections_rejected());
+                            match &rate_limiter {
+                                Some(limiter) => {
+                                    println!("限流放行的请求数: {}", limiter.allowed());
+                                    println!("限流拒绝的请求数: {}", limiter.limited());
+                                    println!("限流跟踪的客户端IP数: {}", limiter.tracked_clients());
+                                }
+                                None => println!("限流: 未启用"),
+                            }
                             println!("====================");
                         }
+                        "cache list" => {
+                            print!("{}", render_cache_list(&*cache.read().await, SystemTime::now()));
+                        }
                         _ => {
                             println!("无效的命令：{}", cmd);
                         }
@@ -169,279 +333,2398 @@ async fn main() {
         }
     });
 
+    // Unix 平台上收到 SIGHUP 时同样重新载入配置
+    #[cfg(unix)]
+    tokio::spawn({
+        let shared_config = Arc::clone(&shared_config);
+        let cache = Arc::clone(&cache);
+        async move {
+            use tokio::signal::unix::{signal, SignalKind};
+            match signal(SignalKind::hangup()) {
+                Ok(mut hangup) => {
+                    while hangup.recv().await.is_some() {
+                        info!("收到SIGHUP信号，重新载入配置");
+                        reload_config(CONFIG_PATH, &shared_config, &cache).await;
+                    }
+                }
+                Err(e) => warn!("无法注册SIGHUP信号处理：{}", e),
+            }
+        }
+    });
+
     let mut id: u128 = 0;
 
     // 9. 主事件循环 (Accept Loop)
-    // 持续接收新连接并将其分发至 Tokio 线程池进行异步处理
+    // 持续接收新连接并将其分发至 Tokio 线程池进行异步处理。
+    // 收到停机指令后进入排空阶段：新连接一律返回 503，进行中的请求照常完成，全部完成（或超时）后退出
+    let mut drain_deadline: Option<Instant> = None;
     loop {
         // 检查停机标志位
-        if *shutdown_flag.lock().unwrap() {
-            info!("主循环接收到停机指令，正在退出...");
-            break;
+        if drain_deadline.is_none() && *shutdown_flag.lock().unwrap() {
+            info!(
+                "主循环接收到停机指令，停止处理新请求，等待{}个进行中的连接完成",
+                metrics.active_connections()
+            );
+            drain_deadline = Some(Instant::now() + DRAIN_TIMEOUT);
         }
+        if let Some(deadline) = drain_deadline {
+            if metrics.active_connections() == 0 {
+                info!("进行中的连接已全部完成，正在退出...");
+                break;
+            }
+            if Instant::now() >= deadline {
+                warn!("等待超时，仍有{}个连接未完成，强制退出", metrics.active_connections());
+                break;
+            }
+        }
+        let draining = drain_deadline.is_some();
 
-        // 等待新的 TCP 连接
-        let (mut stream, addr) = listener.accept().await.unwrap();
-        debug!("新的连接：{}", addr);
-
-        // 为每个连接克隆资源句柄（Arc 引用计数增加）
-        let active_connection_arc = Arc::clone(&active_connection);
-        let root_clone = root.clone();
-        let cache_arc = Arc::clone(&cache);
-        let config_arc_clone = Arc::clone(&config_arc);
-        
-        debug!("[ID{}]TCP连接已建立", id);
+        // 停机前由停机通知唤醒；排空期间定期醒来检查进行中的连接
+        let wakeup = async {
+            if draining {
+                tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+            } else {
+                shutdown_notify.notified().await;
+            }
+        };
 
-        // 使用轻量级绿色线程处理具体请求，确保非阻塞 IO
-        tokio::spawn(async move {
-            {
-                // 连接计数加 1
-                let mut lock = active_connection_arc.lock().unwrap();
-                *lock += 1;
-            }
-            
-            // 核心业务处理
-            handle_connection(&mut stream, id, &root_clone, cache_arc, config_arc_clone).await;
-            
-            {
-                // 处理完成后连接计数减 1
-                let mut lock = active_connection_arc.lock().unwrap();
-                *lock -= 1;
+        // 等待新的连接并分发。连接建立后才取得当前配置的快照，热重载不会影响已建立的连接
+        let accepted = tokio::select! {
+            accepted = listener.accept() => accepted.unwrap(),
+            accepted = accept_optional(tls_listener.as_ref()) => accepted.unwrap(),
+            _ = wakeup => continue,
+        };
+        let config = Arc::clone(&shared_config.read().unwrap());
+        match accepted {
+            Accepted::Tcp(stream, addr) => {
+                debug!("新的连接：{}", addr);
+                dispatch(stream, Some(addr), id, draining, &config, &cache, &metrics, &middlewares, &router);
             }
-        });
+            Accepted::Tls(stream, addr, acceptor) => {
+                debug!("新的TLS连接：{}", addr);
+                spawn_tls_handshake(stream, addr, acceptor, id, draining, &config, &cache, &metrics, &middlewares, &router);
+            }
+            #[cfg(unix)]
+            Accepted::Unix(stream) => {
+                // Unix 域套接字没有对端 IP，依赖 IP 的功能需按“未知地址”处理
+                debug!("新的Unix套接字连接");
+                dispatch(stream, None, id, draining, &config, &cache, &metrics, &middlewares, &router);
+            }
+        }
         id += 1; // 增加请求唯一标识序列
     }
+
+    // 正常停机时清理套接字文件，避免下次启动时残留
+    #[cfg(unix)]
+    if let Some(path) = config.unix_socket() {
+        match std::fs::remove_file(path) {
+            Ok(_) => info!("已删除Unix套接字文件{}", path),
+            Err(e) => warn!("无法删除Unix套接字文件{}：{}", path, e),
+        }
+    }
 }
 
-/// # 连接处理器
-/// 
-/// 负责单个 TCP 流的生命周期，包括读取解析请求、执行路由逻辑、以及构建并发送响应。
-async fn handle_connection(
-    stream: &mut TcpStream,
-    id: u128,
-    root: &str,
-    cache: Arc<Mutex<FileCache>>,
-    config: Arc<Config>,
-) {
-    let mut buffer = vec![0; 1024];
+/// 按配置构建多线程运行时：工作线程数取 `worker_threads`，阻塞线程池上限取 `max_blocking_threads`，
+/// 所有线程（包括阻塞线程）以 [`THREAD_NAME_PREFIX`] 加序号命名。
+fn build_runtime(config: &Config) -> io::Result<Runtime> {
+    let next_id = AtomicUsize::new(0);
+    Builder::new_multi_thread()
+        .worker_threads(config.worker_threads())
+        .max_blocking_threads(config.max_blocking_threads())
+        .thread_name_fn(move || format!("{}-{}", THREAD_NAME_PREFIX, next_id.fetch_add(1, Ordering::Relaxed)))
+        .enable_all()
+        .build()
+}
+
+/// 按 PATH 解析可执行文件的实际路径。含有路径分隔符的命令直接检查该路径是否存在。
+fn resolve_executable(command: &str) -> Option<PathBuf> {
+    let candidate = Path::new(command);
+    if candidate.components().count() > 1 {
+        return candidate.is_file().then(|| candidate.to_path_buf());
+    }
+    std::env::var_os("PATH").and_then(|paths| {
+        std::env::split_paths(&paths)
+            .map(|dir| dir.join(command))
+            .find(|path| path.is_file())
+    })
+}
 
-    // 等待流进入可读状态
-    stream.readable().await.unwrap();
+/// 将缓存中的条目渲染为控制台输出：每行一个路径，附带大小与文件修改时间距今的时长。
+///
+/// 只通过 [`FileCache::keys`] 与 [`FileCache::entry_info`] 查看，不会打乱 LRU 顺序。
+fn render_cache_list(cache: &FileCache, now: SystemTime) -> String {
+    let mut out = format!("== 文件缓存 ({}/{}) ==\n", cache.len(), cache.capacity());
+    for key in cache.keys() {
+        if let Some((size, modified)) = cache.entry_info(&key) {
+            let age = now.duration_since(modified).map(|d| d.as_secs()).unwrap_or(0);
+            out.push_str(&format!("{}  {} 字节  修改于 {} 秒前\n", key, size, age));
+        }
+    }
+    out.push_str("====================\n");
+    out
+}
 
-    // 尝试非阻塞读取 HTTP 报文
-    match stream.try_read(&mut buffer) {
-        Ok(0) => return, // 客户端主动关闭连接
+/// 重新载入配置文件并原子地替换共享配置，返回是否成功。
+///
+/// 文件缺失或格式错误时保留当前配置。成功时逐项记录变化的配置项，并立即调整缓存容量与存活时间；
+/// 监听地址、工作线程数等无法在运行期间生效的配置项只给出“需要重启”的提示。
+async fn reload_config(path: &str, shared_config: &SharedConfig, cache: &Arc<RwLock<FileCache>>) -> bool {
+    let new_config = match Config::try_from_toml(path) {
+        Ok(config) => config,
         Err(e) => {
-            error!("[ID{}]读取TCPStream时遇到错误: {}", id, e);
-            return;
+            error!("重新载入配置文件{}失败，继续使用当前配置：{}", path, e);
+            return false;
         }
-        _ => {}
+    };
+    if let Err(e) = new_config.validate() {
+        error!("重新载入的配置文件{}不合法，继续使用当前配置：{}", path, e);
+        return false;
     }
-    debug!("[ID{}]HTTP请求接收完毕", id);
+    let old_config = Arc::clone(&shared_config.read().unwrap());
 
-    let start_time = Instant::now();
+    let changes = old_config.diff(&new_config);
+    if changes.is_empty() {
+        info!("配置文件{}没有变化", path);
+        return true;
+    }
+    for change in &changes {
+        if change.requires_restart() {
+            warn!("配置项{}：{} -> {}（需要重启服务器才能生效）", change.key, change.old, change.new);
+        } else {
+            info!("配置项{}：{} -> {}", change.key, change.old, change.new);
+        }
+    }
 
-    // 1. 协议解析阶段：将字节流转换为结构化的 Request 对象
-    let request = match Request::try_from(&buffer, id) {
-        Ok(req) => req,
+    if new_config.cache_size() != old_config.cache_size()
+        || new_config.cache_ttl_secs() != old_config.cache_ttl_secs()
+        || new_config.max_cache_bytes() != old_config.max_cache_bytes()
+    {
+        let mut cache = cache.write().await;
+        cache.resize(new_config.cache_size());
+        cache.set_ttl(new_config.cache_ttl_secs().map(Duration::from_secs));
+        cache.set_max_bytes(Some(new_config.max_cache_bytes()));
+    }
+
+    *shared_config.write().unwrap() = Arc::new(new_config);
+    info!("配置文件{}已重新载入，共{}项变化", path, changes.len());
+    true
+}
+
+/// 服务器监听器：TCP 端口（明文或 TLS）或 Unix 域套接字。
+enum Listener {
+    Tcp(TcpListener),
+    Tls(TcpListener, TlsAcceptor),
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
+
+/// 监听器接受的新连接。TLS 连接此时尚未握手，握手在连接自己的任务中进行，不会阻塞主循环。
+enum Accepted {
+    Tcp(TcpStream, SocketAddr),
+    Tls(TcpStream, SocketAddr, TlsAcceptor),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl Listener {
+    /// 等待下一个连接。
+    async fn accept(&self) -> io::Result<Accepted> {
+        match self {
+            Listener::Tcp(listener) => listener.accept().await.map(|(stream, addr)| Accepted::Tcp(stream, addr)),
+            Listener::Tls(listener, acceptor) => listener
+                .accept()
+                .await
+                .map(|(stream, addr)| Accepted::Tls(stream, addr, acceptor.clone())),
+            #[cfg(unix)]
+            Listener::Unix(listener) => listener.accept().await.map(|(stream, _)| Accepted::Unix(stream)),
+        }
+    }
+}
+
+/// 等待可选的监听器上的下一个连接；没有监听器时永远不会完成。
+async fn accept_optional(listener: Option<&Listener>) -> io::Result<Accepted> {
+    match listener {
+        Some(listener) => listener.accept().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// 绑定 TCP 地址，失败时直接终止程序。
+async fn bind_tcp(socket: SocketAddr) -> TcpListener {
+    match TcpListener::bind(socket).await {
+        Ok(listener) => {
+            info!("地址{}绑定完成", socket);
+            listener
+        }
         Err(e) => {
-            error!("[ID{}]解析HTTP请求失败: {:?}", id, e);
-            let response = "HTTP/1.1 400 Bad Request\r\nContent-Length: 11\r\n\r\nBad Request";
-            let _ = stream.write_all(response.as_bytes()).await;
-            return;
+            error!("无法绑定地址：{}，错误：{}", socket, e);
+            panic!("无法绑定地址：{}，错误：{}", socket, e);
+        }
+    }
+}
+
+/// 绑定 Unix 域套接字。启动前先删除上次异常退出残留的套接字文件，否则 `bind` 会失败。
+///
+/// 只删除套接字文件：路径上已有普通文件、目录等其它类型的文件时（通常是 `unix_socket` 写错了），
+/// 返回 `AlreadyExists` 错误，拒绝启动，不删除该文件。
+#[cfg(unix)]
+fn bind_unix_socket(path: &str) -> io::Result<UnixListener> {
+    use std::os::unix::fs::FileTypeExt;
+
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => {
+            warn!("发现残留的Unix套接字文件{}，将其删除", path);
+            std::fs::remove_file(path)?;
+        }
+        Ok(_) => {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{}已存在且不是套接字文件，拒绝删除", path),
+            ))
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+    UnixListener::bind(path)
+}
+
+/// 为一个新连接克隆共享资源，并在 Tokio 线程池中处理它。`peer` 为连接的对端地址（Unix 域套接字为 `None`）。
+#[allow(clippy::too_many_arguments)]
+fn spawn_connection<S>(
+    mut stream: S,
+    peer: Option<SocketAddr>,
+    id: u128,
+    roots: &[String],
+    cache: &Arc<RwLock<FileCache>>,
+    config: &Arc<Config>,
+    metrics: &Arc<Metrics>,
+    middlewares: &Arc<MiddlewareChain>,
+    router: &Arc<Router>,
+) where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    // 为每个连接克隆资源句柄（Arc 引用计数增加）
+    let metrics_arc = Arc::clone(metrics);
+    let roots_clone = roots.to_vec();
+    let cache_arc = Arc::clone(cache);
+    let config_arc_clone = Arc::clone(config);
+    let middlewares = Arc::clone(middlewares);
+    let router = Arc::clone(router);
+
+    debug!("[ID{}]连接已建立", id);
+
+    // 使用轻量级绿色线程处理具体请求，确保非阻塞 IO
+    tokio::spawn(async move {
+        // 连接计数加 1
+        metrics_arc.connection_opened();
+
+        // 核心业务处理
+        handle_connection(
+            &mut stream,
+            peer,
+            id,
+            &roots_clone,
+            cache_arc,
+            config_arc_clone,
+            &metrics_arc,
+            &middlewares,
+            &router,
+        )
+        .await;
+        // 主动关闭写方向：TLS 连接借此发送 close_notify，客户端可以区分正常结束与截断
+        let _ = stream.shutdown().await;
+
+        // 处理完成后连接计数减 1
+        metrics_arc.connection_closed();
+    });
+}
+
+/// 分发一个新连接：停机排空期间返回 503，活跃连接数达到 `[limits] max_connections` 时返回 429，否则正常处理。
+#[allow(clippy::too_many_arguments)]
+fn dispatch<S>(
+    stream: S,
+    peer: Option<SocketAddr>,
+    id: u128,
+    draining: bool,
+    config: &Arc<Config>,
+    cache: &Arc<RwLock<FileCache>>,
+    metrics: &Arc<Metrics>,
+    middlewares: &Arc<MiddlewareChain>,
+    router: &Arc<Router>,
+) where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    if draining {
+        warn!("[ID{}]服务器正在停机，返回503", id);
+        spawn_rejected(stream, id, config, metrics, Response::response_503(id, DRAIN_RETRY_AFTER_SECS));
+    } else if connection_limit_reached(config, metrics) {
+        warn!("[ID{}]活跃连接数达到上限{}，返回429", id, metrics.active_connections());
+        metrics.connection_rejected();
+        let response = Response::response_429(id, CONNECTION_LIMIT_RETRY_AFTER_SECS);
+        spawn_rejected(stream, id, config, metrics, response);
+    } else {
+        spawn_connection(stream, peer, id, config.www_roots(), cache, config, metrics, middlewares, router);
+    }
+}
+
+/// 活跃连接数是否已经达到 `[limits] max_connections`。
+///
+/// 活跃连接数在连接任务开始运行时才增加，短时间内涌入的连接可能略微超出上限。
+fn connection_limit_reached(config: &Config, metrics: &Metrics) -> bool {
+    config
+        .limits()
+        .max_connections()
+        .is_some_and(|max| metrics.active_connections() >= max as u64)
+}
+
+/// 在新任务中完成 TLS 握手，成功后按 [`dispatch`] 处理解密后的连接；ALPN 协商出 `h2` 时改由 [`serve_http2`] 处理。
+///
+/// 握手最多等待 `[limits] read_timeout_secs`；握手失败（如客户端使用明文 HTTP 访问 HTTPS 端口）时直接关闭连接。
+#[allow(clippy::too_many_arguments)]
+fn spawn_tls_handshake(
+    stream: TcpStream,
+    peer: SocketAddr,
+    acceptor: TlsAcceptor,
+    id: u128,
+    draining: bool,
+    config: &Arc<Config>,
+    cache: &Arc<RwLock<FileCache>>,
+    metrics: &Arc<Metrics>,
+    middlewares: &Arc<MiddlewareChain>,
+    router: &Arc<Router>,
+) {
+    let config = Arc::clone(config);
+    let cache = Arc::clone(cache);
+    let metrics = Arc::clone(metrics);
+    let middlewares = Arc::clone(middlewares);
+    let router = Arc::clone(router);
+    tokio::spawn(async move {
+        let timeout = Duration::from_secs(config.limits().read_timeout_secs());
+        match tokio::time::timeout(timeout, acceptor.accept(stream)).await {
+            // HTTP/2 连接无法以 HTTP/1.1 响应拒绝，停机或达到连接数上限时直接发送 GOAWAY
+            Ok(Ok(stream)) if negotiated_h2(&stream) => match draining || connection_limit_reached(&config, &metrics) {
+                true => http2::refuse(stream, id).await,
+                false => serve_http2(stream, peer, id, &config, &cache, &metrics, &middlewares, &router).await,
+            },
+            Ok(Ok(stream)) => dispatch(stream, Some(peer), id, draining, &config, &cache, &metrics, &middlewares, &router),
+            Ok(Err(e)) => debug!("[ID{}]TLS握手失败：{}", id, e),
+            Err(_) => debug!("[ID{}]TLS握手超时", id),
+        }
+    });
+}
+
+/// 处理一条 HTTP/2 连接：连接上的每个请求都像 HTTP/1.1 请求一样经过 [`build_response`] 生成响应。
+///
+/// 整条连接计为一个活跃连接，其上的每个请求各自计入请求数。
+#[allow(clippy::too_many_arguments)]
+async fn serve_http2<S>(
+    stream: S,
+    peer: SocketAddr,
+    id: u128,
+    config: &Arc<Config>,
+    cache: &Arc<RwLock<FileCache>>,
+    metrics: &Arc<Metrics>,
+    middlewares: &Arc<MiddlewareChain>,
+    router: &Arc<Router>,
+) where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    metrics.connection_opened();
+    let handler = {
+        let config = Arc::clone(config);
+        let cache = Arc::clone(cache);
+        let metrics = Arc::clone(metrics);
+        let middlewares = Arc::clone(middlewares);
+        let router = Arc::clone(router);
+        move |mut request: Request| {
+            let config = Arc::clone(&config);
+            let cache = Arc::clone(&cache);
+            let metrics = Arc::clone(&metrics);
+            let middlewares = Arc::clone(&middlewares);
+            let router = Arc::clone(&router);
+            async move {
+                let start_time = Instant::now();
+                let roots = config.www_roots();
+                build_response(&mut request, Some(peer), id, roots, &cache, &config, &metrics, &middlewares, &router, start_time)
+                    .await
+            }
         }
     };
-    debug!("[ID{}]成功解析HTTP请求", id);
+    http2::serve(stream, id, config, metrics, handler).await;
+    metrics.connection_closed();
+}
 
-    // 2. 意图分析：根据 Accept 头部判断是否为 JSON 数据交互
-    let is_json = request
-        .accept()
-        .map_or(false, |a| a.contains("application/json"));
+/// 以 `response`（停机期间的 503 或达到连接数上限时的 429）拒绝一个新连接后关闭，
+/// 不计入活跃连接，因此既不会拖延停机，也不会占用连接数上限。
+fn spawn_rejected<S>(mut stream: S, id: u128, config: &Arc<Config>, metrics: &Arc<Metrics>, response: Response)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let metrics = Arc::clone(metrics);
+    let config = Arc::clone(config);
+    tokio::spawn(async move {
+        handle_rejected(&mut stream, id, &config, &metrics, response).await;
+        let _ = stream.shutdown().await;
+    });
+}
 
-    // 3. 路由匹配阶段：确定资源在文件系统中的物理路径
-    let result = route(&request.path(), id, root, is_json).await;
-    debug!("[ID{}]HTTP路由解析完毕", id);
+/// 读取请求头后返回拒绝连接的响应（带 `Retry-After`）。
+///
+/// 先读完请求头再响应，避免在客户端数据尚未读取时关闭连接导致 RST，使客户端看不到响应；
+/// 读取最多等待 `[limits] read_timeout_secs`。
+async fn handle_rejected<S>(stream: &mut S, id: u128, config: &Config, metrics: &Metrics, mut response: Response)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let timeout = Duration::from_secs(config.limits().read_timeout_secs());
+    let head = read_request_head(stream, id, config.limits().max_header_bytes());
+    if tokio::time::timeout(timeout, head).await.is_err() {
+        debug!("[ID{}]读取被拒绝连接的请求头超时", id);
+    }
+    metrics.record_request();
+    let response_bytes = response.set_server_header(config.server_name()).as_bytes();
+    let _ = stream.write_all(&response_bytes).await;
+    let _ = stream.flush().await;
+    metrics.record_response(response.status_code(), response_bytes.len() as u64);
+}
 
-    // 4. 响应构建阶段：根据路由结果和缓存状态生成 Response 对象
-    let response = match result {
-        Ok(path) => {
-            let path_str = match path.to_str() {
-                Some(s) => s,
-                None => {
-                    error!("[ID{}]无法将路径转换为str", id);
-                    return;
-                }
-            };
-            // 自动处理缓存命中与过期逻辑
-            Response::from(path_str, &request, id, &cache, &config)
+/// # 连接处理器
+/// 
+/// 负责单个连接的生命周期，包括读取解析请求、执行路由逻辑、以及构建并发送响应。
+/// 对流的类型只要求 `AsyncRead + AsyncWrite`，TCP、TLS 与 Unix 域套接字共用同一套逻辑。
+/// 请求解析成功后依次经过 `middlewares` 的 `before` 与 `after` 钩子。
+#[allow(clippy::too_many_arguments)]
+async fn handle_connection<S>(
+    stream: &mut S,
+    peer: Option<SocketAddr>,
+    id: u128,
+    roots: &[String],
+    cache: Arc<RwLock<FileCache>>,
+    config: Arc<Config>,
+    metrics: &Metrics,
+    middlewares: &MiddlewareChain,
+    router: &Router,
+) where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    // 读取请求头最多等待 read_timeout_secs，整个请求（含请求体）最多等待 request_timeout_secs，
+    // 以免缓慢发送数据的客户端（Slowloris）长期占用连接
+    let limits = config.limits();
+    let request_deadline = tokio::time::Instant::now() + Duration::from_secs(limits.request_timeout_secs());
+    let head_deadline = request_deadline.min(tokio::time::Instant::now() + Duration::from_secs(limits.read_timeout_secs()));
+
+    // 读取 HTTP 报文，请求头超过上限时直接返回 431
+    let head = tokio::time::timeout_at(head_deadline, read_request_head(stream, id, limits.max_header_bytes())).await;
+    let buffer = match head {
+        Ok(Some(Ok(buffer))) => buffer,
+        Ok(Some(Err(e))) => {
+            warn!("[ID{}]请求头超过{}字节上限，返回431: {}", id, limits.max_header_bytes(), e);
+            metrics.record_request();
+            send_early_response(stream, Response::response_431(id), &config, metrics).await;
+            return;
         }
-        Err(Exception::FileNotFound) => {
-            warn!("[ID{}]请求的路径：{} 不存在，返回404", id, &request.path());
-            Response::response_404(&request, id)
+        Ok(None) => return,
+        Err(_) => {
+            warn!("[ID{}]未能在时限内收到完整的请求头，返回408", id);
+            metrics.record_request();
+            send_early_response(stream, Response::response_408(id), &config, metrics).await;
+            return;
         }
-        Err(Exception::InvalidPath) => {
-            warn!("[ID{}]请求的路径：{} 包含非法字符，返回400", id, &request.path());
-            Response::response_400(&request, id)
+    };
+    debug!("[ID{}]HTTP请求接收完毕", id);
+    metrics.record_request();
+
+    let start_time = Instant::now();
+
+    // 1. 协议解析阶段：按请求头声明的长度读完请求体，再将字节流转换为结构化的 Request 对象。
+    // 请求体超过上限时不再读取剩余部分，直接返回 413 并关闭连接
+    let body = tokio::time::timeout_at(request_deadline, read_request_body(stream, buffer, id, limits.max_body_bytes())).await;
+    let parsed = match body {
+        Ok(Ok(buffer)) => Request::try_from_with_limits(&buffer, id, limits),
+        Ok(Err(Exception::BodyTooLarge)) => {
+            warn!("[ID{}]请求体超过{}字节上限，返回413", id, limits.max_body_bytes());
+            send_early_response(stream, Response::response_413(id), &config, metrics).await;
+            return;
         }
-        Err(Exception::UnsupportedHttpVersion) => {
-            warn!("[ID{}]不支持的协议版本，返回400", id);
-            Response::response_400(&request, id)
+        Ok(Err(e)) => Err(e),
+        Err(_) => {
+            warn!("[ID{}]未能在时限内收到完整的请求体，返回408", id);
+            send_early_response(stream, Response::response_408(id), &config, metrics).await;
+            return;
         }
+    };
+    let mut request = match parsed {
+        Ok(req) => req,
         Err(e) => {
-            error!("[ID{}]处理请求时发生未知异常: {}", id, e);
+            // 请求尚未解析成功，无法进行内容协商，直接返回最简的纯文本响应
+            let code = match e {
+                Exception::UriTooLong => 414,
+                Exception::UnsupportedContentEncoding => 415,
+                Exception::UnSupportedRequestMethod => 405,
+                _ => 400,
+            };
+            if code == 400 {
+                error!("[ID{}]解析HTTP请求失败: {:?}", id, e);
+            } else {
+                warn!("[ID{}]拒绝请求({})，返回{}", id, e, code);
+            }
+            let reason = STATUS_CODES.get(&code).copied().unwrap_or("Bad Request");
+            // 服务器无法处理的方法（如 DELETE）同样告知客户端配置启用的方法
+            let allow = match code {
+                405 => {
+                    let methods: Vec<String> = config.allowed_methods().iter().map(ToString::to_string).collect();
+                    format!("Allow: {}\r\n", methods.join(", "))
+                }
+                _ => String::new(),
+            };
+            let response = format!(
+                "HTTP/1.1 {} {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                code,
+                reason,
+                allow,
+                reason.len(),
+                reason
+            );
+            let _ = stream.write_all(response.as_bytes()).await;
+            metrics.record_response(code, response.len() as u64);
             return;
         }
     };
+    let Some(mut response) =
+        build_response(&mut request, peer, id, roots, &cache, &config, metrics, middlewares, router, start_time).await
+    else {
+        return;
+    };
 
-    debug!(
-        "[ID{}]HTTP响应构建完成，服务端用时{}ms。",
-        id,
-        start_time.elapsed().as_millis()
-    );
-
-    // 5. 结构化日志记录：便于后期审计与性能监控
-    info!(
-        "[ID{}] {}, {}, {}, {}, {}, {}, ",
-        id,
-        request.version(),
-        request.path(),
-        request.method(),
-        response.status_code(),
-        response.information(),
-        request.user_agent(),
-    );
-
-    // 6. 数据发送阶段
-    if response.is_streaming() {
+    // HTTP/1.0 客户端不支持分块传输，长度未知的响应体原样发送，以关闭连接表示结束；
+    // 不把响应体读入内存，代理或脚本的大响应不会占满内存
+    if *request.version() == HttpVersion::V1_0 {
+        response.set_close_delimited();
+    }
+
+    // 5. 数据发送阶段
+    if response.is_chunked() {
+        // --- 模式 C: 分块传输 (适用于长度未知的动态响应) ---
+        let response_bytes = response.as_bytes();
+        let body = response.take_chunked_body();
+        if let Err(e) = stream.write_all(&response_bytes).await {
+            if is_client_disconnect(&e) {
+                debug!("[ID{}]客户端在发送响应头时断开连接: {}", id, e);
+            } else {
+                error!("[ID{}]发送响应头失败: {}", id, e);
+            }
+            return;
+        }
+        // HEAD 请求只发送响应头，丢弃接收端使生产者停止
+        let mut total_sent = 0u64;
+        if let Some(mut body) = body.filter(|_| request.method() != HttpRequestMethod::Head) {
+            let written = match response.is_close_delimited() {
+                true => write_close_delimited(&mut body, stream).await,
+                false => write_chunked(&mut body, stream).await,
+            };
+            match written {
+                Ok(n) => total_sent = n,
+                Err(e) if is_client_disconnect(&e) => {
+                    debug!("[ID{}]客户端中途断开连接: {}", id, e);
+                    return;
+                }
+                Err(e) => {
+                    error!("[ID{}]分块传输中止: {}", id, e);
+                    return;
+                }
+            }
+        }
+        let _ = stream.flush().await;
+        debug!("[ID{}]分块传输完成，共发送 {} 字节", id, total_sent);
+        metrics.record_response(response.status_code(), response_bytes.len() as u64 + total_sent);
+    } else if response.is_streaming() {
         // --- 模式 A: 流式传输 (适用于大文件，避免内存暴涨) ---
         debug!("[ID{}]使用流式传输模式发送大文件", id);
         
         let response_bytes = response.as_bytes(); // 发送响应头
         if let Err(e) = stream.write_all(&response_bytes).await {
-            error!("[ID{}]发送响应头失败: {}", id, e);
+            if is_client_disconnect(&e) {
+                debug!("[ID{}]客户端在发送响应头时断开连接: {}", id, e);
+            } else {
+                error!("[ID{}]发送响应头失败: {}", id, e);
+            }
             return;
         }
         
-        // 重新获取物理路径以打开文件
-        if let Ok(path) = route(&request.path(), id, root, false).await {
-            if let Some(path_str) = path.to_str() {
-                match TokioFile::open(path_str).await {
-                    Ok(mut file) => {
-                        let chunk_size = config.chunk_size();
-                        let mut buffer = vec![0u8; chunk_size];
-                        let mut total_sent = 0u64;
-                        let content_length = response.get_content_length();
-                        
-                        debug!("[ID{}]开始流式传输，文件大小: {} bytes", id, content_length);
-                        
-                        loop {
-                            match file.read(&mut buffer).await {
-                                Ok(0) => break, // 文件读取完毕
-                                Ok(n) => {
-                                    // 持续将缓冲区内容写入 Socket
-                                    if let Err(e) = stream.write_all(&buffer[..n]).await {
-                                        error!("[ID{}]流式写入失败: {}", id, e);
-                                        return;
-                                    }
-                                    total_sent += n as u64;
-                                }
-                                Err(e) => {
-                                    error!("[ID{}]读取文件失败: {}", id, e);
-                                    return;
-                                }
-                            }
-                        }
-                        let _ = stream.flush().await;
-                        debug!("[ID{}]流式传输完成，共发送 {} 字节", id, total_sent);
-                    }
-                    Err(e) => {
-                        error!("[ID{}]无法打开流文件: {}", id, e);
-                    }
+        // 按响应记录的来源分块发送文件内容；HEAD 请求与无响应体的分片响应没有来源
+        let mut total_sent = 0u64;
+        if let Some(source) = response.stream_source() {
+            debug!("[ID{}]开始流式传输，响应体大小: {} bytes", id, response.get_content_length());
+            // 写入失败立即停止读取文件，避免无谓的磁盘 I/O
+            match source.write_to(stream, config.chunk_size()).await {
+                Ok(n) => total_sent = n,
+                Err(e) if is_client_disconnect(&e) => {
+                    debug!("[ID{}]客户端中途断开连接: {}", id, e);
+                    return;
+                }
+                Err(e) => {
+                    error!("[ID{}]流式传输{}失败: {}", id, source.path, e);
+                    return;
                 }
             }
         }
+        let _ = stream.flush().await;
+        debug!("[ID{}]流式传输完成，共发送 {} 字节", id, total_sent);
+        metrics.record_response(response.status_code(), response_bytes.len() as u64 + total_sent);
     } else {
         // --- 模式 B: 一次性传输 (适用于小文件或 API 响应) ---
         let response_bytes = response.as_bytes();
         debug!("[ID{}]发送全量响应，长度: {}", id, response_bytes.len());
         let _ = stream.write_all(&response_bytes).await;
         let _ = stream.flush().await;
+        metrics.record_response(response.status_code(), response_bytes.len() as u64);
     }
 }
 
-/// # 路由引擎
-/// 
-/// 将抽象的 URI 映射到服务器本地的文件系统路径。
-/// 
-/// ## 路由规则：
-/// 1. `/` -> 优先返回 `index.html`，若为 JSON 请求则返回根目录列表。
-/// 2. `/browser` -> 专门处理前端 Vue 应用，支持 SPA (Single Page Application) 的 History 模式。
-/// 3. `*` -> 特殊通配符匹配。
-/// 4. 静态文件映射 -> 将 URI 拼接到 `www_root` 下进行查找。
-async fn route(path: &str, id: u128, root: &str, is_json: bool) -> Result<PathBuf, Exception> {
-    debug!("[ID{}]路由匹配开始: path='{}', json_mode={}", id, path, is_json);
-    
-    // 根目录特殊处理
-    if path == "/" {
-        if is_json {
-            return Ok(PathBuf::from(root));
-        }
-        let index_path = PathBuf::from(HTML_INDEX);
-        if index_path.exists() {
-            return Ok(index_path);
-        } else {
-            return Ok(PathBuf::from(root));
+/// 为一个已解析的请求生成响应：依次经过中间件、内置端点、反向代理与路由，最后执行中间件的后处理。
+///
+/// HTTP/1.1 与 HTTP/2 连接共用；返回 `None` 表示发生了无法生成响应的内部错误，调用方直接关闭连接（HTTP/2 为重置流）。
+/// `start_time` 为开始处理请求的时刻，用于 `X-Response-Time`。
+#[allow(clippy::too_many_arguments)]
+async fn build_response(
+    request: &mut Request,
+    peer: Option<SocketAddr>,
+    id: u128,
+    roots: &[String],
+    cache: &Arc<RwLock<FileCache>>,
+    config: &Arc<Config>,
+    metrics: &Metrics,
+    middlewares: &MiddlewareChain,
+    router: &Router,
+    start_time: Instant,
+) -> Option<Response> {
+    request.set_peer_addr(peer, config.trust_proxy());
+    request.set_encoding_priority(&config.encoding_priority());
+    debug!("[ID{}]成功解析HTTP请求", id);
+
+    // 2. 意图分析：根据 Accept 头部判断是否为 JSON 数据交互
+    let is_json = request
+        .accept()
+        .map_or(false, |a| a.contains("application/json"));
+
+    // 按 Host 头选择虚拟主机，没有匹配时使用默认的根目录与索引文件
+    let vhost = config.vhost(request.header("host"));
+    let roots = vhost.map_or(roots, |v| v.www_roots());
+    let index_files = vhost
+        .and_then(|v| v.index_files())
+        .unwrap_or(config.index_files());
+
+    // 准入控制（如限流）先于预检与鉴权，鉴权失败的请求同样计入限流
+    let rejected = middlewares.admit(request);
+    if rejected.is_some() {
+        debug!("[ID{}]中间件拒绝了请求", id);
+    }
+
+    // CORS 预检请求不携带凭据，在鉴权之前直接应答
+    let preflight = match rejected {
+        Some(_) => None,
+        None => cors::preflight(request, config.cors(), &config.allowed_methods(), id),
+    };
+
+    // 鉴权：受保护的路径要求 Basic 或 Bearer 凭据，认证通过的用户名供中间件与访问日志使用
+    let denied = match (&rejected, &preflight) {
+        (None, None) => match auth::authenticate(request, config.auth()).await {
+            Ok(user) => {
+                request.set_remote_user(user);
+                None
+            }
+            Err(AuthError::Unavailable(e)) => {
+                error!("[ID{}]读取用户文件失败({})，返回500", id, e);
+                Some(Response::response_500(request, id))
+            }
+            Err(e) => {
+                warn!("[ID{}]请求的路径：{} 鉴权失败({})，返回401", id, request.path(), e);
+                Some(auth::challenge(request, id, config.auth(), &e))
+            }
+        },
+        _ => None,
+    };
+
+    // 3. 响应构建阶段：中间件可以直接给出响应（如鉴权失败）；
+    // 内置的健康检查与指标端点直接生成，其余请求经路由匹配后由文件系统生成
+    let mut response = if let Some(response) = rejected {
+        response
+    } else if let Some(response) = preflight {
+        response
+    } else if let Some(response) = denied {
+        response
+    } else if !config.allowed_methods().contains(&request.method()) {
+        // 未启用的方法对中间件、内置端点、反向代理与路由同样无效
+        warn!("[ID{}]未启用{}方法，返回405", id, request.method());
+        Response::response_405_with_allow(request, id, config.allowed_methods())
+    } else if let Some(response) = middlewares.before(request) {
+        debug!("[ID{}]中间件直接返回响应", id);
+        response
+    } else if is_builtin_request(request, config.health_path()) {
+        // 健康检查不依赖 www_root 与缓存状态
+        Response::from_text("ok", "text/plain;charset=utf-8", request, id)
+    } else if is_builtin_request(request, config.metrics_path()) {
+        let text = metrics.render(&*cache.read().await);
+        Response::from_text(&text, METRICS_CONTENT_TYPE, request, id)
+    } else if let Some((prefix, upstream)) = config.proxy_route(request.path()) {
+        // 反向代理：转发给上游服务器，上游响应体以分块传输流式返回
+        match proxy::forward(request, prefix, &upstream, config).await {
+            Ok(response) => response,
+            Err(Exception::GatewayTimeout) => {
+                warn!("[ID{}]上游{}响应超时，返回504", id, upstream);
+                Response::response_504(request, id)
+            }
+            Err(e) => {
+                warn!("[ID{}]转发到上游{}失败({})，返回502", id, upstream, e);
+                Response::response_502(request, id)
+            }
         }
-    } 
-    // 文件管理器路由（支持 SPA 静态资源）
-    else if path == "/browser/" || path == "/browser" {
-        if is_json {
-            let browser_path = PathBuf::from("static/browser");
-            if browser_path.exists() && browser_path.is_dir() {
-                return Ok(browser_path);
+    } else if let Some(response) = router.handle(request).await {
+        // 路由表中注册的处理器优先于静态文件
+        response
+    } else {
+        // 路由匹配：确定资源在文件系统中的物理路径
+        // PUT 上传的目标文件可以尚不存在，不查找索引文件
+        let result = match request.method() {
+            HttpRequestMethod::Put => resolve_upload_path(request.path(), id, roots, config).await,
+            _ => resolve_path(request.path(), id, roots, index_files, is_json, config).await,
+        };
+        debug!("[ID{}]HTTP路由解析完毕", id);
+
+        // 根据路由结果和缓存状态生成 Response 对象
+        match result {
+            Ok(path) => {
+                let path_str = match path.to_str() {
+                    Some(s) => s,
+                    None => {
+                        error!("[ID{}]无法将路径转换为str", id);
+                        return None;
+                    }
+                };
+                // 自动处理缓存命中与过期逻辑
+                Response::from(path_str, request, id, cache, config).await
+            }
+            Err(Exception::FileNotFound) => match builtin_default(request, config, id) {
+                Some(response) => response,
+                None => {
+                    warn!("[ID{}]请求的路径：{} 不存在，返回404", id, request.path());
+                    Response::response_404(request, id)
+                }
+            },
+            Err(Exception::InvalidPath) => {
+                warn!("[ID{}]请求的路径：{} 包含非法字符，返回400", id, request.path());
+                Response::response_400(request, id)
+            }
+            Err(Exception::QueryTooLong) => {
+                warn!("[ID{}]查询字符串过长，返回414", id);
+                Response::response_414(request, id)
+            }
+            Err(Exception::UnsupportedHttpVersion) => {
+                warn!("[ID{}]不支持的协议版本，返回400", id);
+                Response::response_400(request, id)
+            }
+            Err(e) => {
+                error!("[ID{}]处理请求时发生未知异常: {}", id, e);
+                return None;
             }
         }
-        let browser_index = PathBuf::from("static/browser/index.html");
-        if browser_index.exists() {
-            return Ok(browser_index);
-        } else {
-            return Err(Exception::FileNotFound);
-        }
-    } 
-    // 通配符处理
-    else if path == "*" {
-        return Ok(PathBuf::from("*"));
-    }
-
-    // 标准静态资源路径转换逻辑
-    // 去除领先的 '/' 以便进行路径拼接
-    let mut path_str = path.to_string();
-    path_str.remove(0);
-    let path_without_slash = Path::new(&path_str);
-    let root_path = Path::new(root);
-    let full_path = root_path.join(path_without_slash);
-
-    // 安全检查与路径存在性校验
-    let path_str_ref = match full_path.to_str() {
-        Some(s) => s,
-        None => return Err(Exception::InvalidPath),
     };
-    
-    debug!("[ID{}]映射物理路径：{}", id, path_str_ref);
-    
-    match full_path.exists() {
-        true => Ok(full_path),
-        false => {
-            // SPA (Single Page Application) 回退机制：
-            // 如果在 /browser/ 路径下找不到文件，则返回 index.html，交由前端路由处理
-            if path.starts_with("/browser/") || path.starts_with("/browser") {
-                let browser_index = PathBuf::from("static/browser/index.html");
-                if browser_index.exists() {
-                    debug!("[ID{}]SPA 路由触发：返回 Vue index.html", id);
-                    return Ok(browser_index);
+
+    // 配置了自定义错误页面时替换内置页面；页面在当前虚拟主机的根目录中按顺序查找。
+    // 上游返回的错误响应（分块响应）保留原样
+    if let Some(page) = config.error_page(response.status_code()).filter(|_| !response.is_chunked()) {
+        match find_error_page(roots, page).await {
+            Some(path) => {
+                let path = path.to_string_lossy();
+                if let Err(e) = response.set_error_page(&path, request, id, cache, config).await {
+                    warn!("[ID{}]读取自定义错误页面{}失败，使用内置页面: {}", id, path, e);
                 }
             }
-            Err(Exception::FileNotFound)
+            None => warn!("[ID{}]自定义错误页面{}不存在，使用内置页面", id, page),
         }
     }
+
+    // 在序列化之前计算耗时；这里只追加响应头，不影响已确定的 Content-Length
+    let elapsed_ms = start_time.elapsed().as_millis();
+    debug!("[ID{}]HTTP响应构建完成，服务端用时{}ms。", id, elapsed_ms);
+    if config.emit_response_time() {
+        response.add_header("X-Response-Time", &format!("{}ms", elapsed_ms));
+    }
+    cors::apply(request, config.cors(), &mut response);
+    // 每个连接只处理一个请求：无论客户端是否请求保持连接，响应都携带 `Connection: close`，
+    // 客户端因此不会等待更多数据。响应始终以 HTTP/1.1 发出，对 HTTP/1.0 客户端同样合法
+    response.set_keep_alive(false).set_server_header(config.server_name());
+    debug!("[ID{}]客户端请求保持连接：{}", id, request.keep_alive());
+
+    // 4. 中间件后处理：可以修改响应，结构化访问日志也在此由日志中间件输出
+    middlewares.after(request, &mut response);
+    Some(response)
+}
+
+/// 在各根目录中依次查找自定义错误页面文件，返回第一个存在的文件路径。
+async fn find_error_page(roots: &[String], page: &str) -> Option<PathBuf> {
+    for root in roots {
+        let path = Path::new(root).join(page);
+        if tokio::fs::metadata(&path).await.is_ok_and(|m| m.is_file()) {
+            return Some(path);
+        }
+    }
+    None
+}
+
+/// 发送在解析请求之前就已确定的错误响应（408、413、431），随后由调用方关闭连接。
+///
+/// `Connection: close` 由 [`Response::set_keep_alive`] 写出，不另行追加，避免响应中出现两个 `Connection` 头。
+async fn send_early_response<S>(stream: &mut S, mut response: Response, config: &Config, metrics: &Metrics)
+where
+    S: AsyncWrite + Unpin,
+{
+    let response_bytes = response
+        .set_server_header(config.server_name())
+        .set_keep_alive(false)
+        .as_bytes();
+    let _ = stream.write_all(&response_bytes).await;
+    let _ = stream.flush().await;
+    metrics.record_response(response.status_code(), response_bytes.len() as u64);
+}
+
+/// 从连接中读取请求头，直到遇到标志头部结束的空行（`\r\n\r\n`）。
+///
+/// 与头部一同读入的请求体片段会保留在返回的缓冲区中。
+/// 累计的头部字节数超过 `max_header_bytes` 时返回 `Exception::HeaderTooLarge`；
+/// 连接在发送任何数据前关闭或读取出错时返回 `None`。
+async fn read_request_head<S>(
+    stream: &mut S,
+    id: u128,
+    max_header_bytes: usize,
+) -> Option<Result<Vec<u8>, Exception>>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut buffer = Vec::with_capacity(1024);
+    let mut chunk = [0u8; 1024];
+    loop {
+        match stream.read(&mut chunk).await {
+            Ok(0) => {
+                // 客户端主动关闭连接；已读到部分数据时交给解析器处理
+                return (!buffer.is_empty()).then_some(Ok(buffer));
+            }
+            Ok(n) => {
+                // 结束标志可能跨越两次读取，因此从上一段末尾的 3 个字节开始查找
+                let start = buffer.len().saturating_sub(3);
+                buffer.extend_from_slice(&chunk[..n]);
+                let head_end = buffer[start..]
+                    .windows(4)
+                    .position(|w| w == b"\r\n\r\n")
+                    .map(|pos| start + pos + 4);
+                match head_end {
+                    Some(end) if end <= max_header_bytes => return Some(Ok(buffer)),
+                    Some(_) => return Some(Err(Exception::HeaderTooLarge)),
+                    None if buffer.len() > max_header_bytes => return Some(Err(Exception::HeaderTooLarge)),
+                    None => {}
+                }
+            }
+            Err(e) => {
+                error!("[ID{}]读取连接时遇到错误: {}", id, e);
+                return None;
+            }
+        }
+    }
+}
+
+/// 在请求头之后继续读取请求体，返回包含完整请求的缓冲区。
+///
+/// 请求体的长度由 [`BodyFraming`] 决定：`Content-Length` 时恰好读取声明的字节数，
+/// 分块编码时读到结束块为止；与请求头一同到达的部分请求体会计入其中。
+/// 请求头带有 `Expect: 100-continue` 且请求体尚未到达时，先发送 `100 Continue`。
+///
+/// # 错误
+/// - 声明的长度（或已收到的分块报文）超过 `max_body_bytes` 时返回 `BodyTooLarge`，不再读取。
+/// - 连接在请求体完整之前关闭或出错时返回 `IncompleteBody`；分块格式错误时返回 `MalformedChunk`。
+async fn read_request_body<S>(
+    stream: &mut S,
+    mut buffer: Vec<u8>,
+    id: u128,
+    max_body_bytes: usize,
+) -> Result<Vec<u8>, Exception>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    // 没有完整的请求头（客户端提前关闭连接）时交给解析器处理
+    let Some(head_end) = buffer.windows(4).position(|w| w == b"\r\n\r\n").map(|pos| pos + 4) else {
+        return Ok(buffer);
+    };
+    let framing = BodyFraming::from_head(&buffer[..head_end]);
+    let mut expect_continue = String::from_utf8_lossy(&buffer[..head_end])
+        .split("\r\n")
+        .filter_map(|line| line.split_once(':'))
+        .any(|(name, value)| name.trim().eq_ignore_ascii_case("expect") && value.trim().eq_ignore_ascii_case("100-continue"));
+
+    let mut chunk = [0u8; 8192];
+    loop {
+        let received = buffer.len() - head_end;
+        // 本次最多读取的字节数：固定长度时不越过请求体末尾
+        let wanted = match framing {
+            BodyFraming::None => return Ok(buffer),
+            BodyFraming::Length(len) if len > max_body_bytes => {
+                warn!("[ID{}]声明的请求体长度{}超过上限{}", id, len, max_body_bytes);
+                return Err(Exception::BodyTooLarge);
+            }
+            BodyFraming::Length(len) if received >= len => return Ok(buffer),
+            BodyFraming::Length(len) => (len - received).min(chunk.len()),
+            BodyFraming::Chunked => {
+                let complete = chunked_body_len(&buffer[head_end..], max_body_bytes)?;
+                if complete.is_some_and(|len| len <= max_body_bytes) {
+                    return Ok(buffer);
+                }
+                if complete.is_some() || received > max_body_bytes {
+                    warn!("[ID{}]分块请求体已超过上限{}", id, max_body_bytes);
+                    return Err(Exception::BodyTooLarge);
+                }
+                chunk.len()
+            }
+        };
+        if expect_continue && received == 0 {
+            debug!("[ID{}]客户端等待100 Continue", id);
+            stream.write_all(b"HTTP/1.1 100 Continue\r\n\r\n").await.map_err(|_| Exception::IncompleteBody)?;
+            expect_continue = false;
+        }
+        match stream.read(&mut chunk[..wanted]).await {
+            Ok(0) => {
+                warn!("[ID{}]连接在请求体读取完毕之前关闭，已收到{}字节", id, received);
+                return Err(Exception::IncompleteBody);
+            }
+            Ok(n) => buffer.extend_from_slice(&chunk[..n]),
+            Err(e) => {
+                error!("[ID{}]读取请求体时遇到错误: {}", id, e);
+                return Err(Exception::IncompleteBody);
+            }
+        }
+    }
+}
+
+/// 判断写入错误是否由客户端主动断开连接引起（如下载途中关闭页面）。
+///
+/// 这类错误属于正常现象，只需以 `debug!` 级别记录；其它错误仍按 `error!` 记录。
+fn is_client_disconnect(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::BrokenPipe | io::ErrorKind::ConnectionReset | io::ErrorKind::ConnectionAborted
+    )
+}
+
+/// 为不存在的 `/favicon.ico` 与 `/robots.txt` 生成内置默认响应，避免产生大量 404。
+///
+/// favicon 返回空的 204，robots.txt 返回允许所有爬虫的默认内容；
+/// 其它路径或关闭了 `builtin_favicon_robots` 时返回 `None`。
+fn builtin_default(request: &Request, config: &Config, id: u128) -> Option<Response> {
+    if !config.builtin_favicon_robots() {
+        return None;
+    }
+    match request.path().split('?').next().unwrap_or_default() {
+        "/favicon.ico" => {
+            debug!("[ID{}]favicon.ico不存在，返回内置的204", id);
+            Some(Response::response_204(request, id))
+        }
+        "/robots.txt" => {
+            debug!("[ID{}]robots.txt不存在，返回内置默认内容", id);
+            Some(Response::from_text(DEFAULT_ROBOTS_TXT, "text/plain;charset=utf-8", request, id))
+        }
+        _ => None,
+    }
+}
+
+/// 判断请求是否指向路径为 `endpoint` 的内置端点（忽略查询字符串，仅接受 GET/HEAD）。
+/// `endpoint` 为 `None` 表示该端点已关闭。
+fn is_builtin_request(request: &Request, endpoint: Option<&str>) -> bool {
+    let path = request.path().split('?').next().unwrap_or_default();
+    endpoint.is_some_and(|endpoint| endpoint == path)
+        && matches!(request.method(), HttpRequestMethod::Get | HttpRequestMethod::Head)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use webserver::cache::FileCache;
+
+    /// 构造一个以 `root` 为根目录的配置，`extra` 为附加的 TOML 片段
+    fn test_config(root: &Path, extra: &str) -> Config {
+        Config::from_toml_str(&format!(
+            "www_root = {:?}\nport = 7878\nworker_threads = 1\ncache_size = 5\nlocal = true\n{}",
+            root.to_str().unwrap(),
+            extra
+        ))
+    }
+
+    fn get_request(path: &str) -> Request {
+        let raw = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path);
+        Request::try_from(&raw.into_bytes(), 0).unwrap()
+    }
+
+    /// 准备一个包含 `with_index/index.html` 与 `without_index/a.txt` 的根目录
+    fn index_fixture() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("with_index")).unwrap();
+        fs::write(dir.path().join("with_index/index.html"), "<p>index</p>").unwrap();
+        fs::create_dir(dir.path().join("without_index")).unwrap();
+        fs::write(dir.path().join("without_index/a.txt"), "a").unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn test_route_subdirectory_serves_index() {
+        let dir = index_fixture();
+        let roots = [dir.path().to_str().unwrap().to_string()];
+        let config = test_config(dir.path(), "");
+
+        for path in ["/with_index", "/with_index/"] {
+            let resolved = resolve_path(path, 0, &roots, config.index_files(), false, &config).await.unwrap();
+            assert_eq!(resolved, dir.path().join("with_index/index.html"));
+        }
+
+        // JSON 请求仍然拿到目录本身，用于文件管理器的列表接口
+        let resolved = resolve_path("/with_index/", 0, &roots, config.index_files(), true, &config).await.unwrap();
+        assert_eq!(resolved, dir.path().join("with_index"));
+    }
+
+    #[tokio::test]
+    async fn test_route_index_priority() {
+        let dir = index_fixture();
+        fs::write(dir.path().join("with_index/default.htm"), "<p>default</p>").unwrap();
+        let roots = [dir.path().to_str().unwrap().to_string()];
+        let config = test_config(dir.path(), r#"index_files = ["default.htm", "index.html"]"#);
+
+        let resolved = resolve_path("/with_index/", 0, &roots, config.index_files(), false, &config).await.unwrap();
+        assert_eq!(resolved, dir.path().join("with_index/default.htm"));
+
+        // 默认依次尝试 index.html、index.htm、index.php
+        fs::write(dir.path().join("without_index/index.php"), "<?php echo 1;").unwrap();
+        fs::write(dir.path().join("without_index/index.htm"), "<p>htm</p>").unwrap();
+        let config = test_config(dir.path(), "");
+        let resolved = resolve_path("/without_index/", 0, &roots, config.index_files(), false, &config).await.unwrap();
+        assert_eq!(resolved, dir.path().join("without_index/index.htm"));
+        fs::remove_file(dir.path().join("without_index/index.htm")).unwrap();
+        let resolved = resolve_path("/without_index/", 0, &roots, config.index_files(), false, &config).await.unwrap();
+        assert_eq!(resolved, dir.path().join("without_index/index.php"));
+    }
+
+    #[tokio::test]
+    async fn test_route_subdirectory_without_index_lists_or_forbids() {
+        let dir = index_fixture();
+        let roots = [dir.path().to_str().unwrap().to_string()];
+        let cache = Arc::new(RwLock::new(FileCache::from_capacity(5)));
+        let request = get_request("/without_index/");
+
+        let config = test_config(dir.path(), "");
+        let resolved = resolve_path("/without_index/", 0, &roots, config.index_files(), false, &config).await.unwrap();
+        assert_eq!(resolved, dir.path().join("without_index"));
+        let response = Response::from(resolved.to_str().unwrap(), &request, 0, &cache, &config).await;
+        assert_eq!(response.status_code(), 200);
+
+        let config = test_config(dir.path(), "autoindex = false");
+        let resolved = resolve_path("/without_index/", 0, &roots, config.index_files(), false, &config).await.unwrap();
+        let response = Response::from(resolved.to_str().unwrap(), &request, 0, &cache, &config).await;
+        assert_eq!(response.status_code(), 403);
+    }
+
+    #[tokio::test]
+    async fn test_route_query_length_limit() {
+        let dir = index_fixture();
+        let roots = [dir.path().to_str().unwrap().to_string()];
+        let config = test_config(dir.path(), "[limits]\nmax_query_length = 16");
+
+        // 正常长度的查询字符串被剥离，路径照常解析
+        let resolved = resolve_path("/without_index/a.txt?page=2&sort=name", 0, &roots, config.index_files(), false, &config)
+            .await
+            .unwrap();
+        assert_eq!(resolved, dir.path().join("without_index/a.txt"));
+
+        // 超长的查询字符串返回 QueryTooLong（414）
+        let long_query = format!("/without_index/a.txt?q={}", "x".repeat(64));
+        match resolve_path(&long_query, 0, &roots, config.index_files(), false, &config).await {
+            Err(Exception::QueryTooLong) => {}
+            other => panic!("Expected QueryTooLong, got {:?}", other),
+        }
+    }
+    /// 通过任意双工流发送一次请求并读取完整响应
+    async fn exchange<S>(server: S, client: S, raw: &str, root: &Path, config: Config) -> String
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        exchange_with_middlewares(server, client, raw, root, config, MiddlewareChain::new()).await
+    }
+
+    /// 与 `exchange` 相同，但使用给定的中间件链处理请求
+    async fn exchange_with_middlewares<S>(
+        server: S,
+        client: S,
+        raw: &str,
+        root: &Path,
+        config: Config,
+        middlewares: MiddlewareChain,
+    ) -> String
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        exchange_with_router(server, client, raw, root, config, middlewares, Router::new()).await
+    }
+
+    /// 与 `exchange_with_middlewares` 相同，但同时使用给定的路由表
+    async fn exchange_with_router<S>(
+        mut server: S,
+        mut client: S,
+        raw: &str,
+        root: &Path,
+        config: Config,
+        middlewares: MiddlewareChain,
+        router: Router,
+    ) -> String
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let roots = [root.to_str().unwrap().to_string()];
+        let cache = Arc::new(RwLock::new(FileCache::from_capacity(5)));
+        let handle = tokio::spawn(async move {
+            let metrics = Metrics::new();
+            handle_connection(&mut server, None, 0, &roots, cache, Arc::new(config), &metrics, &middlewares, &router)
+                .await;
+        });
+        client.write_all(raw.as_bytes()).await.unwrap();
+        handle.await.unwrap();
+        let mut response = Vec::new();
+        client.read_to_end(&mut response).await.unwrap();
+        String::from_utf8_lossy(&response).to_string()
+    }
+
+    #[tokio::test]
+    async fn test_handle_connection_over_in_memory_stream() {
+        let dir = index_fixture();
+        let config = test_config(dir.path(), "");
+        let (client, server) = tokio::io::duplex(64 * 1024);
+
+        let raw = "GET /without_index/a.txt HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        let response = exchange(server, client, raw, dir.path(), config).await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(response.ends_with("\r\n\r\na"), "{}", response);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_unix_socket_serves_requests_and_replaces_stale_file() {
+        use tokio::net::UnixStream;
+
+        let dir = index_fixture();
+        let config = test_config(dir.path(), "");
+        let socket_dir = tempfile::tempdir().unwrap();
+        let socket_path = socket_dir.path().join("webserver.sock");
+        // 模拟上次异常退出残留的套接字文件：std 的监听器关闭时不会删除套接字文件
+        drop(std::os::unix::net::UnixListener::bind(&socket_path).unwrap());
+        assert!(socket_path.exists());
+
+        let listener = bind_unix_socket(socket_path.to_str().unwrap()).unwrap();
+        let client = UnixStream::connect(&socket_path).await.unwrap();
+        let (server, _) = listener.accept().await.unwrap();
+
+        let raw = "GET /without_index/a.txt HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        let response = exchange(server, client, raw, dir.path(), config).await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+    }
+
+    /// `unix_socket` 指向普通文件时拒绝启动，且不删除该文件
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_unix_socket_keeps_regular_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
+        fs::write(&path, "important").unwrap();
+
+        let err = bind_unix_socket(path.to_str().unwrap()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "important");
+    }
+    #[tokio::test]
+    async fn test_metrics_endpoint() {
+        let dir = index_fixture();
+        let raw = "GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n";
+
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange(server, client, raw, dir.path(), test_config(dir.path(), "")).await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(response.contains("Content-Type: text/plain; version=0.0.4\r\n"));
+        assert!(response.contains("webserver_requests_total 1\n"));
+        assert!(response.contains("webserver_active_connections"));
+
+        // 关闭端点后按普通路径处理
+        let config = test_config(dir.path(), "metrics_path = \"\"");
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange(server, client, raw, dir.path(), config).await;
+        assert!(response.starts_with("HTTP/1.1 404"), "{}", response);
+    }
+    #[tokio::test]
+    async fn test_health_endpoint_without_www_root() {
+        let missing = Path::new("/nonexistent/webserver-www-root");
+        let raw = "GET /healthz HTTP/1.1\r\nHost: localhost\r\n\r\n";
+
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange(server, client, raw, missing, test_config(missing, "")).await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(response.ends_with("\r\n\r\nok"), "{}", response);
+    }
+    #[tokio::test]
+    async fn test_response_time_header_opt_in() {
+        let dir = index_fixture();
+        let raw = "GET /without_index/a.txt HTTP/1.1\r\nHost: localhost\r\n\r\n";
+
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let config = test_config(dir.path(), "emit_response_time = true");
+        let response = exchange(server, client, raw, dir.path(), config).await;
+        let header = response
+            .lines()
+            .find(|l| l.starts_with("X-Response-Time: "))
+            .expect("缺少X-Response-Time头");
+        assert!(header.ends_with("ms"), "{}", header);
+
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange(server, client, raw, dir.path(), test_config(dir.path(), "")).await;
+        assert!(!response.contains("X-Response-Time"));
+    }
+    #[tokio::test]
+    async fn test_favicon_and_robots_defaults() {
+        let dir = index_fixture();
+        let favicon = "GET /favicon.ico HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        let robots = "GET /robots.txt HTTP/1.1\r\nHost: localhost\r\n\r\n";
+
+        // 文件不存在：返回内置默认值
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange(server, client, favicon, dir.path(), test_config(dir.path(), "")).await;
+        assert!(response.starts_with("HTTP/1.1 204"), "{}", response);
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange(server, client, robots, dir.path(), test_config(dir.path(), "")).await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(response.ends_with(DEFAULT_ROBOTS_TXT), "{}", response);
+
+        // 关闭内置默认值：回到 404
+        let config = test_config(dir.path(), "builtin_favicon_robots = false");
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange(server, client, robots, dir.path(), config).await;
+        assert!(response.starts_with("HTTP/1.1 404"), "{}", response);
+
+        // 真实文件存在：照常返回文件，包括配置指定的自定义路径
+        fs::write(dir.path().join("robots.txt"), "User-agent: *\nDisallow: /\n").unwrap();
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange(server, client, robots, dir.path(), test_config(dir.path(), "")).await;
+        assert!(response.ends_with("Disallow: /\n"), "{}", response);
+
+        let icon = dir.path().join("without_index/icon.ico");
+        fs::write(&icon, "icon").unwrap();
+        let config = test_config(dir.path(), &format!("favicon_path = {:?}", icon.to_str().unwrap()));
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange(server, client, favicon, dir.path(), config).await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(response.ends_with("\r\n\r\nicon"), "{}", response);
+    }
+    /// 点文件默认不出现在 HTML 与 JSON 目录列表中，直接请求时返回 404（`/.well-known/` 除外）
+    #[tokio::test]
+    async fn test_listing_ignore_hides_dotfiles() {
+        let dir = index_fixture();
+        fs::write(dir.path().join("without_index/.env"), "SECRET=1").unwrap();
+        fs::create_dir(dir.path().join(".git")).unwrap();
+        fs::write(dir.path().join(".git/config"), "[core]").unwrap();
+
+        for accept in ["text/html", "application/json"] {
+            let (client, server) = tokio::io::duplex(64 * 1024);
+            let raw = format!("GET /without_index/ HTTP/1.1\r\nHost: localhost\r\nAccept: {}\r\n\r\n", accept);
+            let text = exchange(server, client, &raw, dir.path(), test_config(dir.path(), "")).await;
+            assert!(text.starts_with("HTTP/1.1 200 OK\r\n"), "{}", text);
+            assert!(text.contains("a.txt"), "{}", text);
+            assert!(!text.contains(".env"), "{}", text);
+        }
+
+        for path in ["/without_index/.env", "/.git/config", "/without_index/%2eenv"] {
+            let (client, server) = tokio::io::duplex(64 * 1024);
+            let raw = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path);
+            let text = exchange(server, client, &raw, dir.path(), test_config(dir.path(), "")).await;
+            assert!(text.starts_with("HTTP/1.1 404 Not Found\r\n"), "{}: {}", path, text);
+        }
+
+        // `/.well-known/` 下的文件（如 ACME 验证文件）可以访问，其中的点文件仍然隐藏
+        fs::create_dir_all(dir.path().join(".well-known/acme-challenge")).unwrap();
+        fs::write(dir.path().join(".well-known/acme-challenge/token"), "token.key").unwrap();
+        fs::write(dir.path().join(".well-known/.secret"), "x").unwrap();
+        fs::create_dir(dir.path().join("without_index/.well-known")).unwrap();
+        fs::write(dir.path().join("without_index/.well-known/a.txt"), "a").unwrap();
+        let expected = [
+            ("/.well-known/acme-challenge/token", "HTTP/1.1 200 OK\r\n"),
+            ("/.well-known/.secret", "HTTP/1.1 404 Not Found\r\n"),
+            ("/without_index/.well-known/a.txt", "HTTP/1.1 404 Not Found\r\n"),
+        ];
+        for (path, status) in expected {
+            let (client, server) = tokio::io::duplex(64 * 1024);
+            let raw = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path);
+            let text = exchange(server, client, &raw, dir.path(), test_config(dir.path(), "")).await;
+            assert!(text.starts_with(status), "{}: {}", path, text);
+        }
+
+        // 自定义模式会替换默认值
+        let roots = [dir.path().to_str().unwrap().to_string()];
+        let config = test_config(dir.path(), "listing_ignore = [\"*.txt\"]");
+        assert!(resolve_path("/without_index/.env", 0, &roots, config.index_files(), false, &config).await.is_ok());
+        assert!(matches!(
+            resolve_path("/without_index/a.txt", 0, &roots, config.index_files(), false, &config).await,
+            Err(Exception::FileNotFound)
+        ));
+    }
+    /// 多个根目录按顺序查找，只存在于第二个根目录的文件也能被找到
+    #[tokio::test]
+    async fn test_route_multiple_roots() {
+        let first = index_fixture();
+        let second = tempfile::tempdir().unwrap();
+        fs::write(second.path().join("shared.css"), "body {}").unwrap();
+        fs::write(second.path().join("outside.txt"), "x").unwrap();
+        fs::create_dir(second.path().join("without_index")).unwrap();
+        fs::write(second.path().join("without_index/a.txt"), "second").unwrap();
+
+        let roots = [
+            first.path().to_str().unwrap().to_string(),
+            second.path().to_str().unwrap().to_string(),
+        ];
+        let config = Config::from_toml_str(&format!(
+            "www_root = [{:?}, {:?}]\nport = 7878\nworker_threads = 1\ncache_size = 5\nlocal = true\n",
+            roots[0], roots[1]
+        ));
+        assert_eq!(config.www_roots(), roots);
+
+        let resolved = resolve_path("/shared.css", 0, &roots, config.index_files(), false, &config).await.unwrap();
+        assert_eq!(resolved, second.path().join("shared.css"));
+
+        // 两个根目录都有的文件取第一个
+        let resolved = resolve_path("/without_index/a.txt", 0, &roots, config.index_files(), false, &config).await.unwrap();
+        assert_eq!(resolved, first.path().join("without_index/a.txt"));
+
+        // 越过任意根目录的路径仍被拒绝
+        match resolve_path("/without_index/../../outside.txt", 0, &roots, config.index_files(), false, &config).await {
+            Err(Exception::InvalidPath) => {}
+            other => panic!("Expected InvalidPath, got {:?}", other),
+        }
+        assert!(matches!(
+            resolve_path("/missing.txt", 0, &roots, config.index_files(), false, &config).await,
+            Err(Exception::FileNotFound)
+        ));
+    }
+    /// 配置的 SPA 前缀下找不到文件时，深层前端路由返回入口文件（200）
+    #[tokio::test]
+    async fn test_spa_fallback_for_client_routes() {
+        let dir = index_fixture();
+        fs::create_dir(dir.path().join("app")).unwrap();
+        fs::write(dir.path().join("app/index.html"), "<div id=\"app\"></div>").unwrap();
+        fs::write(dir.path().join("app/main.js"), "console.log(1)").unwrap();
+        let fallback = dir.path().join("app/index.html");
+        let extra = format!("spa_prefix = \"/app/\"\nspa_fallback = {:?}", fallback.to_str().unwrap());
+
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let raw = "GET /app/users/42/settings HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        let text = exchange(server, client, raw, dir.path(), test_config(dir.path(), &extra)).await;
+        assert!(text.starts_with("HTTP/1.1 200 OK\r\n"), "{}", text);
+        assert!(text.contains("<div id=\"app\"></div>"), "{}", text);
+
+        let roots = [dir.path().to_str().unwrap().to_string()];
+        let config = test_config(dir.path(), &extra);
+        // 存在的静态资源照常返回，前缀之外的缺失路径仍为 404
+        let resolved = resolve_path("/app/main.js", 0, &roots, config.index_files(), false, &config).await.unwrap();
+        assert_eq!(resolved, dir.path().join("app/main.js"));
+        assert_eq!(resolve_path("/app", 0, &roots, config.index_files(), false, &config).await.unwrap(), fallback);
+        assert!(matches!(
+            resolve_path("/application/x", 0, &roots, config.index_files(), false, &config).await,
+            Err(Exception::FileNotFound)
+        ));
+
+        // 前缀为空字符串时关闭回退
+        let config = test_config(dir.path(), &format!("spa_prefix = \"\"\nspa_fallback = {:?}", fallback.to_str().unwrap()));
+        assert!(matches!(
+            resolve_path("/app/users/42", 0, &roots, config.index_files(), false, &config).await,
+            Err(Exception::FileNotFound)
+        ));
+    }
+    /// 客户端断开类错误与其它 I/O 错误被正确区分
+    #[test]
+    fn test_is_client_disconnect() {
+        for kind in [
+            io::ErrorKind::BrokenPipe,
+            io::ErrorKind::ConnectionReset,
+            io::ErrorKind::ConnectionAborted,
+        ] {
+            assert!(is_client_disconnect(&io::Error::from(kind)), "{:?}", kind);
+        }
+        for kind in [
+            io::ErrorKind::PermissionDenied,
+            io::ErrorKind::TimedOut,
+            io::ErrorKind::Other,
+        ] {
+            assert!(!is_client_disconnect(&io::Error::from(kind)), "{:?}", kind);
+        }
+    }
+    /// 请求头超过 max_header_bytes 时返回 431；未超限的长请求头可以跨多次读取完整接收
+    #[tokio::test]
+    async fn test_oversized_header_returns_431() {
+        let dir = index_fixture();
+        let raw = |len: usize| {
+            format!(
+                "GET /without_index/a.txt HTTP/1.1\r\nHost: localhost\r\nX-Custom: {}\r\n\r\n",
+                "X".repeat(len)
+            )
+        };
+
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let config = test_config(dir.path(), "[limits]\nmax_header_bytes = 2048");
+        let response = exchange(server, client, &raw(4096), dir.path(), config).await;
+        assert!(response.starts_with("HTTP/1.1 431 Request Header Fields Too Large"), "{}", response);
+        assert_eq!(response.matches("Connection: close\r\n").count(), 1, "{}", response);
+
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let config = test_config(dir.path(), "[limits]\nmax_header_bytes = 8192");
+        let response = exchange(server, client, &raw(4096), dir.path(), config).await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(response.ends_with("\r\n\r\na"), "{}", response);
+    }
+    /// 请求 URI 超过 max_uri_length 时返回 414
+    #[tokio::test]
+    async fn test_oversized_uri_returns_414() {
+        let dir = index_fixture();
+        let raw = format!("GET /{} HTTP/1.1\r\nHost: localhost\r\n\r\n", "A".repeat(10000));
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange(server, client, &raw, dir.path(), test_config(dir.path(), "")).await;
+        assert!(response.starts_with("HTTP/1.1 414 URI Too Long"), "{}", response);
+    }
+    /// 多个取值不一致的 Content-Length 以 400 拒绝，并且不会把剩余字节当作下一个请求
+    #[tokio::test]
+    async fn test_ambiguous_content_length_returns_400() {
+        let dir = index_fixture();
+        let raw = "POST /without_index/a.txt HTTP/1.1\r\nHost: localhost\r\nContent-Length: 10\r\nContent-Length: 5\r\n\r\ntest";
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange(server, client, raw, dir.path(), test_config(dir.path(), "")).await;
+        assert!(response.starts_with("HTTP/1.1 400"), "{}", response);
+        assert_eq!(response.matches("HTTP/1.1 ").count(), 1, "{}", response);
+    }
+    /// 请求体使用不支持的 Content-Encoding 时返回 415
+    #[tokio::test]
+    async fn test_unsupported_body_encoding_returns_415() {
+        let dir = index_fixture();
+        let raw = "POST /without_index/a.txt HTTP/1.1\r\nHost: localhost\r\nContent-Encoding: compress\r\nContent-Length: 4\r\n\r\ndata";
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange(server, client, raw, dir.path(), test_config(dir.path(), "")).await;
+        assert!(response.starts_with("HTTP/1.1 415 Unsupported Media Type"), "{}", response);
+    }
+    /// reload 原子替换共享配置并调整缓存；配置文件错误时保留当前配置
+    #[tokio::test]
+    async fn test_reload_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        let path_str = path.to_str().unwrap();
+        let (root_a, root_b) = (dir.path().join("a"), dir.path().join("b"));
+        fs::create_dir(&root_a).unwrap();
+        fs::create_dir(&root_b).unwrap();
+        let (root_a, root_b) = (root_a.to_str().unwrap(), root_b.to_str().unwrap());
+        let base = format!(
+            "www_root = \"{}\"\nport = 7878\nworker_threads = 1\ncache_size = 5\nlocal = true\n",
+            root_a
+        );
+
+        let shared: SharedConfig = Arc::new(std::sync::RwLock::new(Arc::new(Config::from_toml_str(&base))));
+        let cache = Arc::new(RwLock::new(FileCache::from_capacity(5)));
+        let snapshot = Arc::clone(&shared.read().unwrap());
+
+        fs::write(&path, base.replace(root_a, root_b).replace("cache_size = 5", "cache_size = 20")).unwrap();
+        assert!(reload_config(path_str, &shared, &cache).await);
+        assert_eq!(shared.read().unwrap().www_root(), root_b);
+        assert_eq!(cache.read().await.capacity(), 20);
+        // 已取得的快照不受影响
+        assert_eq!(snapshot.www_root(), root_a);
+
+        fs::write(&path, "www_root = [not valid toml").unwrap();
+        assert!(!reload_config(path_str, &shared, &cache).await);
+        assert_eq!(shared.read().unwrap().www_root(), root_b);
+        // 语法正确但不合法的配置同样被拒绝
+        fs::write(&path, base.replace(root_a, "/nonexistent/root")).unwrap();
+        assert!(!reload_config(path_str, &shared, &cache).await);
+        assert_eq!(shared.read().unwrap().www_root(), root_b);
+        assert!(!reload_config("/nonexistent/config.toml", &shared, &cache).await);
+    }
+    /// 同一路径根据 Host 头映射到不同虚拟主机的文件，未匹配的主机回退到通配虚拟主机
+    #[tokio::test]
+    async fn test_vhost_selects_root_by_host() {
+        let dir = tempfile::tempdir().unwrap();
+        for site in ["default", "blog", "shop", "fallback"] {
+            fs::create_dir(dir.path().join(site)).unwrap();
+            fs::write(dir.path().join(site).join("page.txt"), format!("{} page", site)).unwrap();
+        }
+        fs::write(dir.path().join("shop/home.htm"), "shop home").unwrap();
+        let site = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+        let vhosts = format!(
+            "[[vhost]]\nhost = \"blog.example.com\"\nwww_root = {:?}\n\n\
+             [[vhost]]\nhost = \"Shop.Example.com\"\nwww_root = {:?}\nindex_files = [\"home.htm\"]\n",
+            site("blog"),
+            site("shop")
+        );
+        let request = |host: &str, path: &str| format!("GET {} HTTP/1.1\r\nHost: {}\r\n\r\n", path, host);
+
+        let default_root = dir.path().join("default");
+        let config = test_config(&default_root, &vhosts);
+        let cases = [
+            ("blog.example.com", "/page.txt", "blog page"),
+            ("shop.example.com:7878", "/page.txt", "shop page"),
+            ("shop.example.com", "/", "shop home"),
+            ("other.example.com", "/page.txt", "default page"),
+        ];
+        for (host, path, expected) in cases {
+            let (client, server) = tokio::io::duplex(64 * 1024);
+            let response = exchange(server, client, &request(host, path), &default_root, config.clone()).await;
+            assert!(response.starts_with("HTTP/1.1 200"), "{}: {}", host, response);
+            assert!(response.ends_with(expected), "{}: {}", host, response);
+        }
+
+        // 配置通配虚拟主机后，未匹配的主机使用它而不是顶层的 www_root
+        let wildcard = format!("{}\n[[vhost]]\nhost = \"*\"\nwww_root = {:?}\n", vhosts, site("fallback"));
+        let config = test_config(&default_root, &wildcard);
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange(server, client, &request("other.example.com", "/page.txt"), &default_root, config).await;
+        assert!(response.ends_with("fallback page"), "{}", response);
+    }
+    /// HTTP/1.0 请求得到正常响应，并被告知响应后关闭连接
+    #[tokio::test]
+    async fn test_http10_request() {
+        let dir = index_fixture();
+        let config = test_config(dir.path(), "");
+
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let raw = "GET /without_index/a.txt HTTP/1.0\r\n\r\n";
+        let response = exchange(server, client, raw, dir.path(), config.clone()).await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+        assert!(response.contains("\r\nConnection: close\r\n"), "{}", response);
+        assert!(response.ends_with("\r\n\r\na"), "{}", response);
+
+        // 尚不支持长连接，即使客户端要求保持连接也告知将关闭连接
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let raw = "GET /without_index/a.txt HTTP/1.1\r\nHost: localhost\r\nConnection: keep-alive\r\n\r\n";
+        let response = exchange(server, client, raw, dir.path(), config).await;
+        assert!(response.contains("\r\nConnection: close\r\n"), "{}", response);
+    }
+    /// 停机排空期间的新连接得到带 Retry-After 的 503，且不计入活跃连接
+    #[tokio::test]
+    async fn test_unavailable_while_draining() {
+        let dir = index_fixture();
+        let config = test_config(dir.path(), "");
+        let metrics = Metrics::new();
+        let (mut client, mut server) = tokio::io::duplex(64 * 1024);
+
+        client
+            .write_all(b"GET /without_index/a.txt HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        handle_rejected(&mut server, 0, &config, &metrics, Response::response_503(0, DRAIN_RETRY_AFTER_SECS)).await;
+        drop(server);
+
+        let mut response = Vec::new();
+        client.read_to_end(&mut response).await.unwrap();
+        let response = String::from_utf8_lossy(&response);
+        assert!(response.starts_with("HTTP/1.1 503 Service Unavailable\r\n"), "{}", response);
+        assert!(response.contains(&format!("\r\nRetry-After: {}\r\n", DRAIN_RETRY_AFTER_SECS)), "{}", response);
+        assert!(response.contains("\r\nConnection: close\r\n"), "{}", response);
+        assert_eq!(metrics.active_connections(), 0);
+        assert_eq!(metrics.requests_total(), 1);
+    }
+    /// 活跃连接数达到 `max_connections` 时新连接得到带 Retry-After 的 429，并计入被拒绝的连接数
+    #[tokio::test]
+    async fn test_connection_limit() {
+        let dir = index_fixture();
+        let config = Arc::new(test_config(dir.path(), "[limits]\nmax_connections = 1\n"));
+        let cache = Arc::new(RwLock::new(FileCache::from_capacity(5)));
+        let metrics = Arc::new(Metrics::new());
+        let middlewares = Arc::new(MiddlewareChain::new());
+        let router = Arc::new(Router::new());
+        metrics.connection_opened();
+        let (mut client, server) = tokio::io::duplex(64 * 1024);
+
+        client
+            .write_all(b"GET /without_index/a.txt HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        dispatch(server, None, 0, false, &config, &cache, &metrics, &middlewares, &router);
+
+        let mut response = Vec::new();
+        client.read_to_end(&mut response).await.unwrap();
+        let response = String::from_utf8_lossy(&response);
+        assert!(response.starts_with("HTTP/1.1 429 Too Many Requests\r\n"), "{}", response);
+        assert!(
+            response.contains(&format!("\r\nRetry-After: {}\r\n", CONNECTION_LIMIT_RETRY_AFTER_SECS)),
+            "{}",
+            response
+        );
+        assert_eq!(metrics.active_connections(), 1);
+        assert_eq!(metrics.connections_rejected(), 1);
+    }
+    /// OPTIONS 针对具体资源返回该资源的 Allow（POST 只对 PHP 脚本开放），`OPTIONS *` 返回全局集合，不存在的资源返回 404
+    #[tokio::test]
+    async fn test_options_on_resource() {
+        let dir = index_fixture();
+        fs::write(dir.path().join("form.php"), "<?php echo 1;").unwrap();
+        let config = test_config(dir.path(), "allowed_methods = [\"GET\", \"HEAD\", \"OPTIONS\", \"POST\"]");
+        let options = |target: &str| format!("OPTIONS {} HTTP/1.1\r\nHost: localhost\r\n\r\n", target);
+
+        let cases = [
+            ("/without_index/a.txt", "GET, HEAD, OPTIONS"),
+            ("/form.php", "GET, HEAD, OPTIONS, POST"),
+            ("*", "GET, HEAD, OPTIONS, POST"),
+        ];
+        for (target, allow) in cases {
+            let (client, server) = tokio::io::duplex(64 * 1024);
+            let response = exchange(server, client, &options(target), dir.path(), config.clone()).await;
+            assert!(response.starts_with("HTTP/1.1 204"), "{}: {}", target, response);
+            assert!(response.contains(&format!("\r\nAllow: {}\r\n", allow)), "{}: {}", target, response);
+        }
+
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange(server, client, &options("/missing.txt"), dir.path(), config).await;
+        assert!(response.starts_with("HTTP/1.1 404"), "{}", response);
+    }
+    #[test]
+    fn test_render_cache_list() {
+        let mut cache = FileCache::from_capacity(5);
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(100);
+        cache.push("static/a.txt", bytes::Bytes::from_static(b"hello"), modified);
+        cache.push("static/b.txt", bytes::Bytes::from_static(b"hi"), modified);
+
+        let text = render_cache_list(&cache, modified + Duration::from_secs(30));
+        assert_eq!(
+            text,
+            "== 文件缓存 (2/5) ==\nstatic/b.txt  2 字节  修改于 30 秒前\nstatic/a.txt  5 字节  修改于 30 秒前\n====================\n"
+        );
+    }
+    /// 原始或百分号编码的控制字符都在访问文件系统前以 400 拒绝
+    #[tokio::test]
+    async fn test_control_characters_in_path_rejected() {
+        let dir = index_fixture();
+        let config = test_config(dir.path(), "");
+        for target in ["/without_index/a.txt\0.jpg", "/without_index/a.txt%00.jpg", "/%0d%0aSet-Cookie:x=1"] {
+            let raw = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", target);
+            let (client, server) = tokio::io::duplex(64 * 1024);
+            let response = exchange(server, client, &raw, dir.path(), config.clone()).await;
+            assert!(response.starts_with("HTTP/1.1 400"), "{:?}: {}", target, response);
+        }
+    }
+    /// 请求行中夹带 CRLF 时整个请求以 400 拒绝，注入的头部不会出现在响应中
+    #[tokio::test]
+    async fn test_crlf_in_request_line_rejected() {
+        let dir = index_fixture();
+        let config = test_config(dir.path(), "");
+        for raw in [
+            "GET /\r\nX-Injected: header HTTP/1.1\r\nHost: localhost\r\n\r\n",
+            "GET /test\r\n\r\nGET /evil HTTP/1.1\r\nHost: localhost\r\n\r\n",
+        ] {
+            let (client, server) = tokio::io::duplex(64 * 1024);
+            let response = exchange(server, client, raw, dir.path(), config.clone()).await;
+            assert!(response.starts_with("HTTP/1.1 400"), "{:?}: {}", raw, response);
+            assert!(!response.contains("X-Injected"), "{:?}: {}", raw, response);
+        }
+    }
+    /// 未在 allowed_methods 中启用的方法返回 405，Allow 只列出启用的方法
+    #[tokio::test]
+    async fn test_disabled_method_returns_405() {
+        let dir = index_fixture();
+        let config = test_config(dir.path(), "allowed_methods = [\"GET\", \"HEAD\"]");
+        let request = |method: &str, target: &str| format!("{} {} HTTP/1.1\r\nHost: localhost\r\n\r\n", method, target);
+
+        for target in ["/without_index/a.txt", "*"] {
+            let (client, server) = tokio::io::duplex(64 * 1024);
+            let response = exchange(server, client, &request("OPTIONS", target), dir.path(), config.clone()).await;
+            assert!(response.starts_with("HTTP/1.1 405"), "{}: {}", target, response);
+            assert!(response.contains("\r\nAllow: GET, HEAD\r\n"), "{}: {}", target, response);
+        }
+
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange(server, client, &request("HEAD", "/without_index/a.txt"), dir.path(), config).await;
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+
+        // 只启用 OPTIONS 时，OPTIONS * 返回的全局集合随之变化
+        let config = test_config(dir.path(), "allowed_methods = [\"OPTIONS\", \"GET\"]");
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange(server, client, &request("OPTIONS", "*"), dir.path(), config).await;
+        assert!(response.starts_with("HTTP/1.1 204"), "{}", response);
+        assert!(response.contains("\r\nAllow: OPTIONS, GET\r\n"), "{}", response);
+    }
+
+    /// 服务器不支持的方法（DELETE）与资源不支持的方法（POST 静态文件）都返回带 Allow 的 405
+    #[tokio::test]
+    async fn test_405_lists_allowed_methods() {
+        let dir = index_fixture();
+        let config = test_config(dir.path(), "");
+        let request = |method: &str| format!("{} /without_index/a.txt HTTP/1.1\r\nHost: localhost\r\n\r\n", method);
+
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange(server, client, &request("DELETE"), dir.path(), config.clone()).await;
+        assert!(response.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"), "{}", response);
+        assert!(response.contains("Allow: GET, HEAD, OPTIONS\r\n"), "{}", response);
+
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange(server, client, &request("POST"), dir.path(), config).await;
+        assert!(response.starts_with("HTTP/1.1 405"), "{}", response);
+        assert!(response.contains("\r\nAllow: GET, HEAD, OPTIONS\r\n"), "{}", response);
+        assert!(response.contains("支持的方法：GET, HEAD, OPTIONS"), "{}", response);
+    }
+    /// 开启上传后文件的 Allow 包含 PUT：新建文件返回 201，替换返回 204，目录不接受 PUT
+    #[tokio::test]
+    async fn test_put_upload() {
+        let dir = index_fixture();
+        let config = test_config(dir.path(), "uploads = true\nallowed_methods = [\"GET\", \"HEAD\", \"OPTIONS\", \"PUT\"]");
+        let put = |target: &str, body: &str| {
+            format!("PUT {} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}", target, body.len(), body)
+        };
+
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let options = "OPTIONS /without_index/a.txt HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        let response = exchange(server, client, options, dir.path(), config.clone()).await;
+        assert!(response.contains("\r\nAllow: GET, HEAD, OPTIONS, PUT\r\n"), "{}", response);
+
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange(server, client, &put("/without_index/new.txt", "hello"), dir.path(), config.clone()).await;
+        assert!(response.starts_with("HTTP/1.1 201 Created\r\n"), "{}", response);
+        assert_eq!(fs::read_to_string(dir.path().join("without_index/new.txt")).unwrap(), "hello");
+
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange(server, client, &put("/without_index/a.txt", "replaced"), dir.path(), config.clone()).await;
+        assert!(response.starts_with("HTTP/1.1 204 No Content\r\n"), "{}", response);
+        assert_eq!(fs::read_to_string(dir.path().join("without_index/a.txt")).unwrap(), "replaced");
+        // 临时文件已重命名为目标文件
+        assert_eq!(fs::read_dir(dir.path().join("without_index")).unwrap().count(), 2);
+
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange(server, client, &put("/without_index", "x"), dir.path(), config.clone()).await;
+        assert!(response.starts_with("HTTP/1.1 405"), "{}", response);
+        assert!(response.contains("\r\nAllow: GET, HEAD, OPTIONS\r\n"), "{}", response);
+
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange(server, client, &put("/missing/new.txt", "x"), dir.path(), config.clone()).await;
+        assert!(response.starts_with("HTTP/1.1 404"), "{}", response);
+
+        // 服务器会执行的脚本不能被上传，无论是新建还是替换
+        fs::write(dir.path().join("without_index/form.php"), "<?php echo 1;").unwrap();
+        for target in ["/without_index/x.php", "/without_index/X.PHP", "/without_index/form.php"] {
+            let (client, server) = tokio::io::duplex(64 * 1024);
+            let response = exchange(server, client, &put(target, "<?php system('id');"), dir.path(), config.clone()).await;
+            assert!(response.starts_with("HTTP/1.1 405"), "{}: {}", target, response);
+            assert!(!response.contains("PUT"), "{}: {}", target, response);
+        }
+        assert!(!dir.path().join("without_index/x.php").exists());
+        assert!(!dir.path().join("without_index/X.PHP").exists());
+        assert_eq!(fs::read_to_string(dir.path().join("without_index/form.php")).unwrap(), "<?php echo 1;");
+        assert_eq!(fs::read_dir(dir.path().join("without_index")).unwrap().count(), 3);
+
+        // 未开启上传时 PUT 不在 Allow 中
+        let config = test_config(dir.path(), "allowed_methods = [\"GET\", \"HEAD\", \"OPTIONS\", \"PUT\"]");
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange(server, client, &put("/without_index/a.txt", "x"), dir.path(), config).await;
+        assert!(response.starts_with("HTTP/1.1 405"), "{}", response);
+        assert!(response.contains("\r\nAllow: GET, HEAD, OPTIONS\r\n"), "{}", response);
+        assert_eq!(fs::read_to_string(dir.path().join("without_index/a.txt")).unwrap(), "replaced");
+    }
+    /// 中间件的 before 可以短路请求，after 可以修改响应
+    #[tokio::test]
+    async fn test_middleware_hooks() {
+        use webserver::Middleware;
+
+        struct RequireToken;
+        impl Middleware for RequireToken {
+            fn before(&self, req: &Request) -> Option<Response> {
+                match req.header("x-token") {
+                    Some("secret") => None,
+                    _ => Some(Response::response_400(req, req.id())),
+                }
+            }
+        }
+        struct PoweredBy;
+        impl Middleware for PoweredBy {
+            fn after(&self, _req: &Request, resp: &mut Response) {
+                resp.add_header("X-Powered-By", "middleware");
+            }
+        }
+
+        let dir = index_fixture();
+        let config = test_config(dir.path(), "");
+        let chain = || MiddlewareChain::new().with(PoweredBy).with(RequireToken);
+
+        let raw = "GET /without_index/a.txt HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange_with_middlewares(server, client, raw, dir.path(), config.clone(), chain()).await;
+        assert!(response.starts_with("HTTP/1.1 400"), "{}", response);
+        assert!(response.contains("\r\nX-Powered-By: middleware\r\n"), "{}", response);
+
+        let raw = "GET /without_index/a.txt HTTP/1.1\r\nHost: localhost\r\nX-Token: secret\r\n\r\n";
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange_with_middlewares(server, client, raw, dir.path(), config, chain()).await;
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        assert!(response.contains("\r\nX-Powered-By: middleware\r\n"), "{}", response);
+        assert!(response.ends_with("\r\n\r\na"), "{}", response);
+    }
+
+    /// 限流先于鉴权：没有凭据的请求同样消耗令牌，令牌耗尽后得到 429 而不是 401
+    #[tokio::test]
+    async fn test_rate_limit_before_auth() {
+        let dir = index_fixture();
+        let extra = "trust_proxy = true\n[auth]\npaths = [\"/without_index/\"]\n[auth.tokens]\nci = \"s3cret\"";
+        let config = test_config(dir.path(), extra);
+        let limiter = Arc::new(RateLimiter::new(0.001, 1));
+
+        let raw = "GET /without_index/a.txt HTTP/1.1\r\nHost: localhost\r\nX-Forwarded-For: 192.0.2.1\r\n\r\n";
+        let chain = || MiddlewareChain::new().with(Arc::clone(&limiter));
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange_with_middlewares(server, client, raw, dir.path(), config.clone(), chain()).await;
+        assert!(response.starts_with("HTTP/1.1 401 Unauthorized\r\n"), "{}", response);
+
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange_with_middlewares(server, client, raw, dir.path(), config, chain()).await;
+        assert!(response.starts_with("HTTP/1.1 429 Too Many Requests\r\n"), "{}", response);
+        assert!(!response.contains("WWW-Authenticate"), "{}", response);
+        assert_eq!((limiter.allowed(), limiter.limited()), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn test_auth_protects_configured_paths() {
+        let dir = index_fixture();
+        let config = test_config(dir.path(), "[auth]\npaths = [\"/without_index/\"]\n[auth.tokens]\nci = \"s3cret\"");
+
+        let raw = "GET /without_index/a.txt HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange(server, client, raw, dir.path(), config.clone()).await;
+        assert!(response.starts_with("HTTP/1.1 401 Unauthorized\r\n"), "{}", response);
+        assert!(response.contains("\r\nWWW-Authenticate: Bearer realm=\"Restricted\"\r\n"), "{}", response);
+
+        let raw = "GET /without_index/a.txt HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer s3cret\r\n\r\n";
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange(server, client, raw, dir.path(), config.clone()).await;
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        assert!(response.ends_with("\r\n\r\na"), "{}", response);
+
+        // 未受保护的路径不需要凭据
+        let raw = "GET /with_index/ HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange(server, client, raw, dir.path(), config).await;
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    }
+
+    /// CORS 预检请求不需要凭据即得到 204，其余响应（包括 401）都为允许的来源追加 Access-Control-Allow-Origin
+    #[tokio::test]
+    async fn test_cors() {
+        let dir = index_fixture();
+        let extra = "[cors]\norigins = [\"https://app.example\"]\n[auth]\npaths = [\"/without_index/\"]\n[auth.tokens]\nci = \"s3cret\"";
+        let config = test_config(dir.path(), extra);
+
+        let raw = "OPTIONS /without_index/a.txt HTTP/1.1\r\nHost: localhost\r\nOrigin: https://app.example\r\n\
+                   Access-Control-Request-Method: GET\r\nAccess-Control-Request-Headers: authorization\r\n\r\n";
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange(server, client, raw, dir.path(), config.clone()).await;
+        assert!(response.starts_with("HTTP/1.1 204 No Content\r\n"), "{}", response);
+        assert!(response.contains("\r\nAccess-Control-Allow-Methods: GET, HEAD, OPTIONS\r\n"), "{}", response);
+        assert!(response.contains("\r\nAccess-Control-Allow-Origin: https://app.example\r\n"), "{}", response);
+
+        let raw = "GET /without_index/a.txt HTTP/1.1\r\nHost: localhost\r\nOrigin: https://app.example\r\n\r\n";
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange(server, client, raw, dir.path(), config.clone()).await;
+        assert!(response.starts_with("HTTP/1.1 401 Unauthorized\r\n"), "{}", response);
+        assert!(response.contains("\r\nAccess-Control-Allow-Origin: https://app.example\r\n"), "{}", response);
+
+        // 不被允许的来源既得不到预检响应，也得不到 Access-Control-Allow-Origin
+        let raw = "OPTIONS /with_index/ HTTP/1.1\r\nHost: localhost\r\nOrigin: https://evil.example\r\n\
+                   Access-Control-Request-Method: GET\r\n\r\n";
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange(server, client, raw, dir.path(), config).await;
+        assert!(response.contains("\r\nAllow: GET, HEAD, OPTIONS\r\n"), "{}", response);
+        assert!(!response.contains("Access-Control-"), "{}", response);
+        assert!(response.contains("\r\nVary: Origin\r\n"), "{}", response);
+    }
+
+    #[tokio::test]
+    async fn test_router_handlers_before_static_files() {
+        let dir = index_fixture();
+        let config = test_config(dir.path(), "");
+        let router = || {
+            Router::new().get("/without_index/:name", |req, params| async move {
+                let text = format!("handler:{}", params.get("name").unwrap_or_default());
+                Response::from_text(&text, "text/plain", &req, req.id())
+            })
+        };
+
+        // 注册的处理器优先于同名的静态文件，响应同样经过中间件
+        let raw = "GET /without_index/a.txt HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let chain = MiddlewareChain::new().with(LoggingMiddleware);
+        let response = exchange_with_router(server, client, raw, dir.path(), config.clone(), chain, router()).await;
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        assert!(response.ends_with("\r\n\r\nhandler:a.txt"), "{}", response);
+
+        // 没有匹配的路由时回退到静态文件
+        let raw = "GET /with_index/ HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response =
+            exchange_with_router(server, client, raw, dir.path(), config, MiddlewareChain::new(), router()).await;
+        assert!(response.ends_with("<p>index</p>"), "{}", response);
+    }
+
+    /// 未启用的方法在路由、内置端点与中间件之前就返回 405，即使路由表为该方法注册了处理器
+    #[tokio::test]
+    async fn test_disabled_method_rejected_before_handlers() {
+        let dir = index_fixture();
+        let config = test_config(dir.path(), "allowed_methods = [\"GET\", \"HEAD\"]\nhealth_path = \"/healthz\"");
+        let router = || {
+            Router::new().route("/api/:name", |req, _| async move { Response::from_text("handler", "text/plain", &req, req.id()) })
+        };
+        for target in ["/api/echo", "/healthz"] {
+            let raw = format!("POST {} HTTP/1.1\r\nHost: localhost\r\nContent-Length: 0\r\n\r\n", target);
+            let (client, server) = tokio::io::duplex(64 * 1024);
+            let chain = MiddlewareChain::new().with(LoggingMiddleware);
+            let response = exchange_with_router(server, client, &raw, dir.path(), config.clone(), chain, router()).await;
+            assert!(response.starts_with("HTTP/1.1 405"), "{}: {}", target, response);
+            assert!(response.contains("\r\nAllow: GET, HEAD\r\n"), "{}: {}", target, response);
+            assert!(!response.ends_with("handler"), "{}: {}", target, response);
+        }
+
+        // 启用的方法照常交给路由处理
+        let raw = "GET /api/echo HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange_with_router(server, client, raw, dir.path(), config, MiddlewareChain::new(), router()).await;
+        assert!(response.ends_with("\r\n\r\nhandler"), "{}", response);
+    }
+
+    #[test]
+    fn test_resolve_executable() {
+        let sh = resolve_executable("sh").expect("PATH 中应当有 sh");
+        assert!(sh.is_absolute() && sh.ends_with("sh"));
+        assert_eq!(resolve_executable(sh.to_str().unwrap()), Some(sh));
+        assert_eq!(resolve_executable("/nonexistent/php"), None);
+        assert_eq!(resolve_executable("definitely-not-a-php-binary"), None);
+    }
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_route_symlink_outside_root() {
+        let dir = index_fixture();
+        let outside = tempfile::tempdir().unwrap();
+        fs::write(outside.path().join("secret.txt"), "secret").unwrap();
+        std::os::unix::fs::symlink(outside.path().join("secret.txt"), dir.path().join("leak.txt")).unwrap();
+        std::os::unix::fs::symlink(outside.path(), dir.path().join("leak_dir")).unwrap();
+        std::os::unix::fs::symlink(dir.path().join("with_index"), dir.path().join("alias")).unwrap();
+        let roots = [dir.path().to_str().unwrap().to_string()];
+
+        // 默认不跟随：指向根目录之外的链接（文件或目录）返回 404
+        let config = test_config(dir.path(), "");
+        for path in ["/leak.txt", "/leak_dir/secret.txt"] {
+            let result = resolve_path(path, 0, &roots, config.index_files(), false, &config).await;
+            assert!(matches!(result, Err(Exception::FileNotFound)), "{}: {:?}", path, result);
+        }
+        // 指向根目录之内的链接不受影响
+        let resolved = resolve_path("/alias/", 0, &roots, config.index_files(), false, &config).await.unwrap();
+        assert_eq!(resolved, dir.path().join("alias/index.html"));
+
+        // 开启后保持原有行为
+        let config = test_config(dir.path(), "follow_symlinks = true");
+        let resolved = resolve_path("/leak.txt", 0, &roots, config.index_files(), false, &config).await.unwrap();
+        assert_eq!(resolved, dir.path().join("leak.txt"));
+    }
+    /// 运行时线程按前缀命名，阻塞线程池的并发数受 max_blocking_threads 限制
+    #[test]
+    fn test_build_runtime_applies_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(dir.path(), "max_blocking_threads = 1");
+        assert_eq!(config.max_blocking_threads(), 1);
+        let runtime = build_runtime(&config).unwrap();
+
+        let name = runtime
+            .block_on(runtime.spawn(async { std::thread::current().name().map(str::to_string) }))
+            .unwrap()
+            .unwrap();
+        assert!(name.starts_with("ws-worker-"), "{}", name);
+
+        let active = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let tasks: Vec<_> = (0..4)
+            .map(|_| {
+                let (active, peak) = (Arc::clone(&active), Arc::clone(&peak));
+                runtime.spawn_blocking(move || {
+                    let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    std::thread::sleep(Duration::from_millis(20));
+                    active.fetch_sub(1, Ordering::SeqCst);
+                    std::thread::current().name().map(str::to_string)
+                })
+            })
+            .collect();
+        for task in tasks {
+            let name = runtime.block_on(task).unwrap().unwrap();
+            assert!(name.starts_with("ws-worker-"), "{}", name);
+        }
+        assert_eq!(peak.load(Ordering::SeqCst), 1);
+    }
+    /// 请求体的一部分与请求头同时到达、其余部分分多次到达时，按 Content-Length 恰好读完
+    #[tokio::test]
+    async fn test_read_request_body_across_reads() {
+        let (mut client, mut server) = tokio::io::duplex(64 * 1024);
+        let writer = tokio::spawn(async move {
+            for part in ["POST /form HTTP/1.1\r\nContent-Length: 10\r\n\r\nna", "me=", "value", "EXTRA"] {
+                client.write_all(part.as_bytes()).await.unwrap();
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            client
+        });
+        let head = read_request_head(&mut server, 0, 8192).await.unwrap().unwrap();
+        let buffer = read_request_body(&mut server, head, 0, 1024).await.unwrap();
+        assert!(buffer.ends_with(b"\r\n\r\nname=value"), "{:?}", String::from_utf8_lossy(&buffer));
+        let _client = writer.await.unwrap();
+
+        // 分块编码读到结束块为止
+        let (mut client, mut server) = tokio::io::duplex(64 * 1024);
+        let raw = "POST /form HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n";
+        let (head, rest) = raw.split_at(raw.find("llo").unwrap());
+        client.write_all(head.as_bytes()).await.unwrap();
+        let head = read_request_head(&mut server, 0, 8192).await.unwrap().unwrap();
+        client.write_all(rest.as_bytes()).await.unwrap();
+        let buffer = read_request_body(&mut server, head, 0, 1024).await.unwrap();
+        assert_eq!(buffer, raw.as_bytes());
+
+        // 声明的长度超过上限时不再读取；连接提前关闭时报告请求体不完整
+        let (_client, mut server) = tokio::io::duplex(1024);
+        let head = b"POST / HTTP/1.1\r\nContent-Length: 4096\r\n\r\n".to_vec();
+        assert!(matches!(read_request_body(&mut server, head, 0, 1024).await, Err(Exception::BodyTooLarge)));
+        let (client, mut server) = tokio::io::duplex(1024);
+        drop(client);
+        let head = b"POST / HTTP/1.1\r\nContent-Length: 4\r\n\r\nab".to_vec();
+        assert!(matches!(read_request_body(&mut server, head, 0, 1024).await, Err(Exception::IncompleteBody)));
+    }
+
+    /// 分多次到达的表单请求体完整地传给 PHP 脚本（以 sh 充当解释器，脚本把标准输入原样输出）
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_post_body_reaches_script() {
+        let dir = index_fixture();
+        fs::write(dir.path().join("echo.php"), "cat").unwrap();
+        let config = test_config(dir.path(), "php_path = \"sh\"\nallowed_methods = [\"GET\", \"HEAD\", \"POST\"]");
+        let roots = [dir.path().to_str().unwrap().to_string()];
+        let cache = Arc::new(RwLock::new(FileCache::from_capacity(5)));
+        let (mut client, mut server) = tokio::io::duplex(64 * 1024);
+        let handle = tokio::spawn(async move {
+            let (middlewares, router) = (MiddlewareChain::new(), Router::new());
+            let metrics = Metrics::new();
+            handle_connection(&mut server, None, 0, &roots, cache, Arc::new(config), &metrics, &middlewares, &router)
+                .await;
+        });
+
+        let body = "a=".to_string() + &"x".repeat(3000);
+        let head = format!(
+            "POST /echo.php HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/x-www-form-urlencoded\r\nContent-Length: {}\r\n\r\n",
+            body.len()
+        );
+        client.write_all((head + &body[..100]).as_bytes()).await.unwrap();
+        for part in body.as_bytes()[100..].chunks(1000) {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            client.write_all(part).await.unwrap();
+        }
+        handle.await.unwrap();
+        let mut response = Vec::new();
+        client.read_to_end(&mut response).await.unwrap();
+        let response = String::from_utf8_lossy(&response);
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        assert!(response.ends_with(&body), "{}", response);
+    }
+    /// HTTPS 监听器完成握手后与明文连接走同一套处理逻辑；明文请求打到 HTTPS 端口时直接关闭连接，
+    /// ALPN 协商出 h2 的连接改用 HTTP/2
+    #[tokio::test]
+    async fn test_https_listener_serves_requests() {
+        use tokio_rustls::rustls::pki_types::{pem::PemObject, CertificateDer, ServerName};
+        use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+        use tokio_rustls::TlsConnector;
+
+        const CERT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/localhost.crt");
+        const KEY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/localhost.key");
+
+        let dir = index_fixture();
+        let config = Arc::new(test_config(dir.path(), ""));
+        let cache = Arc::new(RwLock::new(FileCache::from_capacity(5)));
+        let metrics = Arc::new(Metrics::new());
+        let middlewares = Arc::new(MiddlewareChain::new());
+        let router = Arc::new(Router::new());
+        let tcp = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = tcp.local_addr().unwrap();
+        let listener = Listener::Tls(tcp, load_acceptor(CERT, KEY, true).unwrap());
+        tokio::spawn(async move {
+            for id in 0.. {
+                match listener.accept().await.unwrap() {
+                    Accepted::Tls(stream, peer, acceptor) => spawn_tls_handshake(
+                        stream, peer, acceptor, id, false, &config, &cache, &metrics, &middlewares, &router,
+                    ),
+                    _ => unreachable!(),
+                }
+            }
+        });
+
+        let mut roots = RootCertStore::empty();
+        roots.add(CertificateDer::from_pem_file(CERT).unwrap()).unwrap();
+        let client_config = ClientConfig::builder().with_root_certificates(roots).with_no_client_auth();
+        let connector = TlsConnector::from(Arc::new(client_config));
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let mut stream = connector
+            .connect(ServerName::try_from("localhost").unwrap(), stream)
+            .await
+            .unwrap();
+        stream
+            .write_all(b"GET /without_index/a.txt HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await.unwrap();
+        let response = String::from_utf8_lossy(&response);
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(response.ends_with("\r\n\r\na"), "{}", response);
+
+        let mut plain = TcpStream::connect(addr).await.unwrap();
+        plain.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").await.unwrap();
+        let mut response = Vec::new();
+        let _ = plain.read_to_end(&mut response).await;
+        assert!(!String::from_utf8_lossy(&response).contains("HTTP/1.1 200"));
+
+        // 通过 ALPN 协商出 h2 的客户端改用 HTTP/2，请求同样经过路由与文件系统
+        let mut client_config = (**connector.config()).clone();
+        client_config.alpn_protocols = vec![b"h2".to_vec()];
+        let connector = TlsConnector::from(Arc::new(client_config));
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let mut stream = connector
+            .connect(ServerName::try_from("localhost").unwrap(), stream)
+            .await
+            .unwrap();
+        assert_eq!(stream.get_ref().1.alpn_protocol(), Some(&b"h2"[..]));
+        let mut block = Vec::new();
+        webserver::hpack::encode(
+            [(":method", "GET"), (":scheme", "https"), (":path", "/without_index/a.txt"), (":authority", "localhost")],
+            &mut block,
+        );
+        let mut request = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n\0\0\0\x04\0\0\0\0\0".to_vec();
+        request.extend_from_slice(&(block.len() as u32).to_be_bytes()[1..]);
+        request.extend_from_slice(&[0x1, 0x5, 0, 0, 0, 1]);
+        request.extend_from_slice(&block);
+        stream.write_all(&request).await.unwrap();
+
+        // 依次读取帧，直到流 1 的响应结束
+        let mut decoder = webserver::hpack::Decoder::new(4096);
+        let (mut status, mut body) = (None, Vec::new());
+        loop {
+            let mut header = [0u8; 9];
+            stream.read_exact(&mut header).await.unwrap();
+            let mut payload = vec![0u8; u32::from_be_bytes([0, header[0], header[1], header[2]]) as usize];
+            stream.read_exact(&mut payload).await.unwrap();
+            match (header[3], header[8]) {
+                (0x1, 1) => {
+                    let headers = decoder.decode(&payload, 16384).unwrap();
+                    status = headers.into_iter().find(|(n, _)| n == ":status").map(|(_, v)| v);
+                }
+                (0x0, 1) => body.extend_from_slice(&payload),
+                _ => continue,
+            }
+            if header[4] & 0x1 != 0 {
+                break;
+            }
+        }
+        assert_eq!(status.as_deref(), Some("200"));
+        assert_eq!(body, b"a");
+    }
+    /// 明文与各种编码形式的路径遍历都在访问文件系统之前以 InvalidPath 拒绝
+    #[tokio::test]
+    async fn test_route_rejects_traversal() {
+        let dir = index_fixture();
+        let roots = [dir.path().join("with_index").to_str().unwrap().to_string()];
+        let config = test_config(dir.path(), "");
+
+        for path in [
+            "/../etc/passwd",
+            "/../../../etc/passwd",
+            "/a/../../without_index/a.txt",
+            "/%2e%2e/without_index/a.txt",
+            "/%2e%2e%2fwithout_index%2fa.txt",
+            "/..%2fwithout_index/a.txt",
+            "/%2E%2E/%2e%2E/etc/passwd",
+            "/..%5cwithout_index%5ca.txt",
+            "/a\\..\\..\\etc\\passwd",
+        ] {
+            let result = resolve_path(path, 0, &roots, config.index_files(), false, &config).await;
+            assert!(matches!(result, Err(Exception::InvalidPath)), "{}: {:?}", path, result);
+        }
+
+        // 在根目录之内消解的 `..` 照常解析
+        let resolved = resolve_path("/x/../index.html", 0, &roots, config.index_files(), false, &config).await.unwrap();
+        assert_eq!(resolved, dir.path().join("with_index/index.html"));
+    }
+    /// 声明或实际发送的请求体超过 max_body_bytes 时返回 413，未超限的请求体照常处理
+    #[tokio::test]
+    async fn test_oversized_body_returns_413() {
+        let dir = index_fixture();
+        let config = || test_config(dir.path(), "[limits]\nmax_body_bytes = 16");
+
+        let raw = "POST /without_index/a.txt HTTP/1.1\r\nHost: localhost\r\nContent-Length: 1000000\r\n\r\npartial";
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange(server, client, raw, dir.path(), config()).await;
+        assert!(response.starts_with("HTTP/1.1 413 Content Too Large"), "{}", response);
+        assert!(response.contains("Connection: close\r\n"), "{}", response);
+        assert_eq!(response.matches("Connection:").count(), 1, "{}", response);
+
+        let raw = format!(
+            "POST /without_index/a.txt HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n20\r\n{}\r\n0\r\n\r\n",
+            "x".repeat(32)
+        );
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange(server, client, &raw, dir.path(), config()).await;
+        assert!(response.starts_with("HTTP/1.1 413 Content Too Large"), "{}", response);
+
+        let raw = "GET /without_index/a.txt HTTP/1.1\r\nHost: localhost\r\nContent-Length: 4\r\n\r\ndata";
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange(server, client, raw, dir.path(), config()).await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+    }
+    /// 请求头或请求体未能在时限内收完时返回 408 并关闭连接
+    #[tokio::test]
+    async fn test_slow_request_returns_408() {
+        let dir = index_fixture();
+        let config = || test_config(dir.path(), "[limits]\nread_timeout_secs = 1\nrequest_timeout_secs = 1");
+
+        // 请求头始终没有结束
+        let raw = "GET /without_index/a.txt HTTP/1.1\r\nHost: loc";
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange(server, client, raw, dir.path(), config()).await;
+        assert!(response.starts_with("HTTP/1.1 408 Request Timeout"), "{}", response);
+        assert!(response.contains("Connection: close\r\n"), "{}", response);
+        assert_eq!(response.matches("Connection:").count(), 1, "{}", response);
+
+        // 请求头及时到达，但请求体只发送了一部分
+        let raw = "POST /without_index/a.txt HTTP/1.1\r\nHost: localhost\r\nContent-Length: 10\r\n\r\nabc";
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange(server, client, raw, dir.path(), config()).await;
+        assert!(response.starts_with("HTTP/1.1 408 Request Timeout"), "{}", response);
+        assert_eq!(response.matches("Connection:").count(), 1, "{}", response);
+    }
+    /// 配置了自定义错误页面的状态码返回该文件的内容，状态码不变；未配置或文件缺失时使用内置页面
+    #[tokio::test]
+    async fn test_custom_error_pages() {
+        let dir = index_fixture();
+        fs::create_dir(dir.path().join("errors")).unwrap();
+        fs::write(dir.path().join("errors/404.html"), "<h1>custom 404</h1>").unwrap();
+        let config = || {
+            test_config(
+                dir.path(),
+                "[error_pages]\n404 = \"errors/404.html\"\n400 = \"errors/missing.html\"",
+            )
+        };
+
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange(server, client, "GET /nope.html HTTP/1.1\r\nHost: localhost\r\n\r\n", dir.path(), config()).await;
+        assert!(response.starts_with("HTTP/1.1 404 Not Found"), "{}", response);
+        assert!(response.contains("Content-Type: text/html;charset=utf-8\r\n"), "{}", response);
+        assert!(response.ends_with("\r\n\r\n<h1>custom 404</h1>"), "{}", response);
+
+        let raw = "HEAD /nope.html HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange(server, client, raw, dir.path(), config()).await;
+        assert!(response.contains("Content-Length: 19\r\n"), "{}", response);
+        assert!(response.ends_with("\r\n\r\n"), "{}", response);
+
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange(server, client, "GET /../etc/passwd HTTP/1.1\r\nHost: localhost\r\n\r\n", dir.path(), config()).await;
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request"), "{}", response);
+        assert!(!response.contains("custom"), "{}", response);
+    }
+    /// Range 响应经由流式发送阶段写出：单段只发送请求的区间，HEAD 只发送响应头
+    #[tokio::test]
+    async fn test_range_response_is_streamed() {
+        let dir = index_fixture();
+        fs::write(dir.path().join("digits.txt"), "0123456789".repeat(100)).unwrap();
+        let config = || test_config(dir.path(), "chunk_size = 16");
+
+        let raw = "GET /digits.txt HTTP/1.1\r\nHost: localhost\r\nRange: bytes=995-\r\n\r\n";
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange(server, client, raw, dir.path(), config()).await;
+        assert!(response.starts_with("HTTP/1.1 206 Partial Content"), "{}", response);
+        assert!(response.contains("Content-Range: bytes 995-999/1000\r\n"), "{}", response);
+        assert!(response.ends_with("\r\n\r\n56789"), "{}", response);
+
+        let raw = "HEAD /digits.txt HTTP/1.1\r\nHost: localhost\r\nRange: bytes=0-1,4-5\r\n\r\n";
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange(server, client, raw, dir.path(), config()).await;
+        assert!(response.contains("Content-Type: multipart/byteranges; boundary="), "{}", response);
+        assert!(response.ends_with("\r\n\r\n"), "{}", response);
+    }
+    /// 分块响应体以 Transfer-Encoding: chunked 发送；HTTP/1.0 客户端改为原样发送并关闭连接，HEAD 只发送响应头
+    #[tokio::test]
+    async fn test_chunked_response() {
+        use bytes::Bytes;
+        use webserver::Middleware;
+
+        struct Generated;
+        impl Middleware for Generated {
+            fn after(&self, _req: &Request, resp: &mut Response) {
+                let (tx, rx) = tokio::sync::mpsc::channel(4);
+                for chunk in ["hello, ", "world"] {
+                    tx.try_send(Ok(Bytes::from_static(chunk.as_bytes()))).unwrap();
+                }
+                resp.set_chunked_body(rx);
+            }
+        }
+
+        let dir = index_fixture();
+        let send = |raw: &'static str| {
+            let config = test_config(dir.path(), "");
+            let (client, server) = tokio::io::duplex(64 * 1024);
+            exchange_with_middlewares(server, client, raw, dir.path(), config, MiddlewareChain::new().with(Generated))
+        };
+
+        let response = send("GET /without_index/a.txt HTTP/1.1\r\nHost: localhost\r\n\r\n").await;
+        assert!(response.contains("\r\nTransfer-Encoding: chunked\r\n"), "{}", response);
+        assert!(!response.contains("Content-Length"), "{}", response);
+        assert!(response.ends_with("\r\n\r\n7\r\nhello, \r\n5\r\nworld\r\n0\r\n\r\n"), "{}", response);
+
+        let response = send("GET /without_index/a.txt HTTP/1.0\r\n\r\n").await;
+        assert!(!response.contains("Content-Length") && !response.contains("Transfer-Encoding"), "{}", response);
+        assert!(response.contains("\r\nConnection: close\r\n"), "{}", response);
+        assert!(response.ends_with("\r\n\r\nhello, world"), "{}", response);
+
+        let response = send("HEAD /without_index/a.txt HTTP/1.1\r\nHost: localhost\r\n\r\n").await;
+        assert!(response.contains("\r\nTransfer-Encoding: chunked\r\n"), "{}", response);
+        assert!(response.ends_with("\r\n\r\n"), "{}", response);
+    }
+    /// `[proxy]` 前缀下的请求转发给上游，上游响应以分块传输返回；上游不可达时返回 502
+    #[tokio::test]
+    async fn test_reverse_proxy() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let upstream = tokio::spawn(async move {
+            let (mut conn, _) = listener.accept().await.unwrap();
+            let mut head = Vec::new();
+            while !head.ends_with(b"\r\n\r\n") {
+                let mut byte = [0u8; 1];
+                conn.read_exact(&mut byte).await.unwrap();
+                head.push(byte[0]);
+            }
+            conn.write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 7\r\n\r\nno user").await.unwrap();
+            String::from_utf8(head).unwrap()
+        });
+
+        let dir = index_fixture();
+        let config = test_config(dir.path(), &format!("[error_pages]\n404 = \"with_index/index.html\"\n[proxy]\n\"/api/\" = \"http://{}/v1\"", addr));
+        let raw = "GET /api/users/7 HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange(server, client, raw, dir.path(), config.clone()).await;
+        let head = upstream.await.unwrap();
+        assert!(head.starts_with("GET /v1/users/7 HTTP/1.1\r\n"), "{}", head);
+        assert!(head.contains("Via: 1.1 "), "{}", head);
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"), "{}", response);
+        assert!(response.contains("\r\nTransfer-Encoding: chunked\r\n"), "{}", response);
+        assert!(response.ends_with("\r\n\r\n7\r\nno user\r\n0\r\n\r\n"), "{}", response);
+
+        // 上游已关闭，连接被拒绝
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let response = exchange(server, client, raw, dir.path(), config).await;
+        assert!(response.starts_with("HTTP/1.1 502 Bad Gateway"), "{}", response);
+    }
 }
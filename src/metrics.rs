@@ -0,0 +1,200 @@
+// Copyright (c) 2026 shaneyale (shaneyale86@gmail.com)
+// All rights reserved.
+
+//! # 运行指标模块
+//!
+//! 使用原子计数器统计服务器的运行状况（请求总数、各类状态码、发送字节数、活跃连接数、被拒绝的连接数），
+//! 并结合 [`FileCache`] 的命中统计，以 Prometheus 文本格式（exposition format 0.0.4）导出，
+//! 供 `/metrics` 端点抓取。所有计数器都可以在多个连接任务之间无锁更新。
+
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::cache::FileCache;
+
+/// Prometheus 文本格式的 Content-Type。
+pub const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// 服务器运行指标集合。
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /// 收到的请求总数。
+    requests_total: AtomicU64,
+    /// 按状态码类别（1xx ~ 5xx）统计的响应数量。
+    responses_by_class: [AtomicU64; 5],
+    /// 已发送的字节总数（包括响应头）。
+    bytes_sent: AtomicU64,
+    /// 当前活跃连接数。
+    active_connections: AtomicU64,
+    /// 因达到连接数上限而被拒绝的连接数。
+    connections_rejected: AtomicU64,
+}
+
+impl Metrics {
+    /// 构造一个所有计数均为 0 的指标集合。
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 记录收到一个请求。
+    pub fn record_request(&self) {
+        self.requests_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 记录发送了一个响应：按状态码类别计数，并累加发送的字节数。
+    pub fn record_response(&self, status_code: u16, bytes: u64) {
+        if let Some(counter) = (status_code / 100)
+            .checked_sub(1)
+            .and_then(|i| self.responses_by_class.get(i as usize))
+        {
+            counter.fetch_add(1, Ordering::Relaxed);
+        }
+        self.bytes_sent.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// 记录一个连接建立。
+    pub fn connection_opened(&self) {
+        self.active_connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 记录一个连接关闭。
+    pub fn connection_closed(&self) {
+        self.active_connections.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// 记录一个连接因达到连接数上限而被拒绝。
+    pub fn connection_rejected(&self) {
+        self.connections_rejected.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 获取收到的请求总数。
+    pub fn requests_total(&self) -> u64 {
+        self.requests_total.load(Ordering::Relaxed)
+    }
+
+    /// 获取已发送的字节总数。
+    pub fn bytes_sent(&self) -> u64 {
+        self.bytes_sent.load(Ordering::Relaxed)
+    }
+
+    /// 获取当前活跃连接数。
+    pub fn active_connections(&self) -> u64 {
+        self.active_connections.load(Ordering::Relaxed)
+    }
+
+    /// 获取因达到连接数上限而被拒绝的连接数。
+    pub fn connections_rejected(&self) -> u64 {
+        self.connections_rejected.load(Ordering::Relaxed)
+    }
+
+    /// 以 Prometheus 文本格式导出所有指标。
+    ///
+    /// 缓存命中与未命中次数取自 `cache` 的统计。
+    pub fn render(&self, cache: &FileCache) -> String {
+        let mut out = String::new();
+        let mut counter = |name: &str, help: &str, kind: &str, samples: &[(&str, u64)]| {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+            for (labels, value) in samples {
+                let _ = writeln!(out, "{}{} {}", name, labels, value);
+            }
+        };
+
+        counter(
+            "webserver_requests_total",
+            "Total number of HTTP requests received.",
+            "counter",
+            &[("", self.requests_total())],
+        );
+        let classes: Vec<(String, u64)> = self
+            .responses_by_class
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (format!("{{class=\"{}xx\"}}", i + 1), c.load(Ordering::Relaxed)))
+            .collect();
+        let classes: Vec<(&str, u64)> = classes.iter().map(|(l, v)| (l.as_str(), *v)).collect();
+        counter(
+            "webserver_responses_total",
+            "Total number of HTTP responses by status class.",
+            "counter",
+            &classes,
+        );
+        counter(
+            "webserver_bytes_sent_total",
+            "Total number of bytes sent to clients.",
+            "counter",
+            &[("", self.bytes_sent())],
+        );
+        counter(
+            "webserver_cache_hits_total",
+            "Total number of file cache hits.",
+            "counter",
+            &[("", cache.hits())],
+        );
+        counter(
+            "webserver_cache_misses_total",
+            "Total number of file cache misses.",
+            "counter",
+            &[("", cache.misses())],
+        );
+        counter(
+            "webserver_cache_bytes",
+            "Total size of the contents held in the file cache.",
+            "gauge",
+            &[("", cache.used_bytes() as u64)],
+        );
+        counter(
+            "webserver_active_connections",
+            "Number of connections currently being served.",
+            "gauge",
+            &[("", self.active_connections())],
+        );
+        counter(
+            "webserver_connections_rejected_total",
+            "Total number of connections rejected by the connection limit.",
+            "counter",
+            &[("", self.connections_rejected())],
+        );
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use std::time::SystemTime;
+
+    #[test]
+    fn test_record_and_render() {
+        let metrics = Metrics::new();
+        metrics.record_request();
+        metrics.record_request();
+        metrics.record_response(200, 100);
+        metrics.record_response(404, 50);
+        metrics.record_response(999, 0); // 非法状态码不计入任何类别
+        metrics.connection_opened();
+        metrics.connection_rejected();
+
+        let mut cache = FileCache::from_capacity(5);
+        let now = SystemTime::now();
+        cache.push("a", Bytes::from_static(b"a"), now);
+        cache.find("a", now);
+        cache.find("b", now);
+
+        let text = metrics.render(&cache);
+        assert!(text.contains("# TYPE webserver_requests_total counter\nwebserver_requests_total 2\n"));
+        assert!(text.contains("webserver_responses_total{class=\"2xx\"} 1\n"));
+        assert!(text.contains("webserver_responses_total{class=\"4xx\"} 1\n"));
+        assert!(text.contains("webserver_responses_total{class=\"5xx\"} 0\n"));
+        assert!(text.contains("webserver_bytes_sent_total 150\n"));
+        assert!(text.contains("webserver_cache_hits_total 1\n"));
+        assert!(text.contains("webserver_cache_misses_total 1\n"));
+        assert!(text.contains("# TYPE webserver_cache_bytes gauge\nwebserver_cache_bytes 1\n"));
+        assert!(text.contains("# TYPE webserver_active_connections gauge\nwebserver_active_connections 1\n"));
+        assert!(text.contains("webserver_connections_rejected_total 1\n"));
+
+        metrics.connection_closed();
+        assert_eq!(metrics.active_connections(), 0);
+    }
+}
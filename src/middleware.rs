@@ -0,0 +1,185 @@
+// Copyright (c) 2026 shaneyale (shaneyale86@gmail.com)
+// All rights reserved.
+
+//! # 中间件模块
+//!
+//! 提供请求/响应钩子，用于在不修改连接处理流程的前提下加入横切逻辑（日志、鉴权、注入响应头等）。
+//!
+//! - [`Middleware::admit`] 在请求解析成功后、CORS 预检与鉴权之前调用。返回 `Some` 时直接以该响应作答，
+//!   用于需要覆盖所有请求（包括鉴权失败的请求）的准入控制，例如限流。
+//! - [`Middleware::before`] 在鉴权通过后、生成响应之前调用。返回 `Some` 时直接以该响应作答，
+//!   跳过后续中间件与路由（例如鉴权失败时返回 401）。
+//! - [`Middleware::after`] 在响应生成之后、发送之前调用，可以修改响应（例如追加响应头）。
+//!
+//! [`MiddlewareChain`] 按注册顺序调用 `admit` 与 `before`，按相反顺序调用 `after`，
+//! 因此最先注册的中间件最先看到请求、最后看到响应。
+
+use std::sync::Arc;
+
+use log::info;
+
+use crate::request::Request;
+use crate::response::Response;
+
+/// 请求/响应钩子。各方法都有默认的空实现，中间件只需实现关心的部分。
+///
+/// 中间件在多个连接任务之间共享，因此要求 `Send + Sync`。
+pub trait Middleware: Send + Sync {
+    /// 在 CORS 预检与鉴权之前调用。返回 `Some(response)` 时短路，直接发送该响应。
+    fn admit(&self, _req: &Request) -> Option<Response> {
+        None
+    }
+
+    /// 在生成响应之前调用。返回 `Some(response)` 时短路，直接发送该响应。
+    fn before(&self, _req: &Request) -> Option<Response> {
+        None
+    }
+
+    /// 在响应发送之前调用，可以修改响应。短路产生的响应同样会经过所有中间件的 `after`。
+    fn after(&self, _req: &Request, _resp: &mut Response) {}
+}
+
+/// 以 `Arc` 共享的中间件：调用方保留一份 `Arc`，即可在链外读取中间件的状态（如限流计数）。
+impl<M: Middleware + ?Sized> Middleware for Arc<M> {
+    fn admit(&self, req: &Request) -> Option<Response> {
+        (**self).admit(req)
+    }
+
+    fn before(&self, req: &Request) -> Option<Response> {
+        (**self).before(req)
+    }
+
+    fn after(&self, req: &Request, resp: &mut Response) {
+        (**self).after(req, resp)
+    }
+}
+
+/// 按顺序执行的中间件链。
+#[derive(Clone, Default)]
+pub struct MiddlewareChain {
+    middlewares: Vec<Arc<dyn Middleware>>,
+}
+
+impl MiddlewareChain {
+    /// 构造一个空的中间件链。
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 在链的末尾追加一个中间件。
+    pub fn with(mut self, middleware: impl Middleware + 'static) -> Self {
+        self.middlewares.push(Arc::new(middleware));
+        self
+    }
+
+    /// 按注册顺序调用各中间件的 `admit`，遇到第一个返回 `Some` 的中间件即停止。
+    pub fn admit(&self, req: &Request) -> Option<Response> {
+        self.middlewares.iter().find_map(|m| m.admit(req))
+    }
+
+    /// 按注册顺序调用各中间件的 `before`，遇到第一个返回 `Some` 的中间件即停止。
+    pub fn before(&self, req: &Request) -> Option<Response> {
+        self.middlewares.iter().find_map(|m| m.before(req))
+    }
+
+    /// 按注册的相反顺序调用各中间件的 `after`。
+    pub fn after(&self, req: &Request, resp: &mut Response) {
+        for middleware in self.middlewares.iter().rev() {
+            middleware.after(req, resp);
+        }
+    }
+
+    /// 链中中间件的数量。
+    pub fn len(&self) -> usize {
+        self.middlewares.len()
+    }
+
+    /// 链是否为空。
+    pub fn is_empty(&self) -> bool {
+        self.middlewares.is_empty()
+    }
+}
+
+/// 内置的访问日志中间件：每个请求以 `info` 级别输出一行结构化日志，
+/// 依次为客户端 IP、认证用户名（未认证时为 `-`）、协议版本、路径、方法、状态码、原因短语与 User-Agent。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LoggingMiddleware;
+
+impl Middleware for LoggingMiddleware {
+    fn after(&self, req: &Request, resp: &mut Response) {
+        info!(
+            "[ID{}] {}, {}, {}, {}, {}, {}, {}, {}, ",
+            req.id(),
+            req.client_ip().map_or_else(|| "-".to_string(), |ip| ip.to_string()),
+            req.remote_user().unwrap_or("-"),
+            req.version(),
+            req.path(),
+            req.method(),
+            resp.status_code(),
+            resp.information(),
+            req.user_agent(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    fn request(raw: &str) -> Request {
+        Request::try_from(&raw.as_bytes().to_vec(), 7).unwrap()
+    }
+
+    /// 记录调用顺序的中间件
+    struct Recorder {
+        name: &'static str,
+        calls: Arc<Mutex<Vec<String>>>,
+        reject: bool,
+    }
+
+    impl Middleware for Recorder {
+        fn before(&self, req: &Request) -> Option<Response> {
+            self.calls.lock().unwrap().push(format!("before:{}", self.name));
+            self.reject
+                .then(|| Response::from_text("denied", "text/plain", req, req.id()))
+        }
+
+        fn after(&self, _req: &Request, resp: &mut Response) {
+            self.calls.lock().unwrap().push(format!("after:{}", self.name));
+            resp.add_header("X-Middleware", self.name);
+        }
+    }
+
+    #[test]
+    fn test_chain_order_and_short_circuit() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let recorder = |name, reject| Recorder { name, calls: Arc::clone(&calls), reject };
+        let chain = MiddlewareChain::new()
+            .with(recorder("a", false))
+            .with(recorder("b", true))
+            .with(recorder("c", false));
+        assert_eq!(chain.len(), 3);
+
+        let req = request("GET / HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        let mut resp = chain.before(&req).expect("b 应当短路");
+        chain.after(&req, &mut resp);
+
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec!["before:a", "before:b", "after:c", "after:b", "after:a"]
+        );
+        let text = String::from_utf8(resp.as_bytes()).unwrap();
+        assert!(text.contains("X-Middleware: c\r\nX-Middleware: b\r\nX-Middleware: a\r\n"), "{}", text);
+        assert!(text.ends_with("denied"));
+    }
+
+    #[test]
+    fn test_logging_middleware_passes_through() {
+        let chain = MiddlewareChain::new().with(LoggingMiddleware);
+        let req = request("GET / HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        assert_eq!(req.id(), 7);
+        assert!(chain.before(&req).is_none());
+        assert!(MiddlewareChain::new().is_empty());
+    }
+}
@@ -17,13 +17,24 @@ pub const HTML_INDEX: &str = r"static/index.html";
 /// 服务器名称标识，用于 HTTP 响应头的 `Server` 字段
 pub const SERVER_NAME: &str = "shaneyale-webserver";
 
+/// 默认的 PHP 解释器命令，在 PATH 中查找
+pub const DEFAULT_PHP_PATH: &str = "php";
+
 /// HTTP 协议规定的换行符（Carriage Return Line Feed）
 pub const CRLF: &str = "\r\n";
 
+/// 站点没有 `robots.txt` 时返回的内置默认内容：允许所有爬虫访问
+pub const DEFAULT_ROBOTS_TXT: &str = "User-agent: *\nAllow: /\n";
+
+/// 客户端对多种压缩编码给出相同权重时，服务器默认的选择顺序
+pub const DEFAULT_ENCODING_PRIORITY: [HttpEncoding; 3] = [HttpEncoding::Br, HttpEncoding::Gzip, HttpEncoding::Deflate];
+
 lazy_static! {
-    /// 服务器当前允许处理的 HTTP 方法列表。
+    /// 默认启用的 HTTP 方法列表，即配置项 `allowed_methods` 的默认值。
     ///
-    /// 用于在收到请求时进行初步过滤，不在该列表中的方法将触发 405 Method Not Allowed。
+    /// 实际启用的方法以 `Config::allowed_methods` 为准，它也是 `OPTIONS *` 返回的全局方法集合；
+    /// 具体资源允许的方法由 `Response::allowed_methods_for` 在此基础上计算，
+    /// 不在其中的方法将触发 405 Method Not Allowed。
     pub static ref ALLOWED_METHODS: Vec<HttpRequestMethod> = {
         vec![
             HttpRequestMethod::Get,
@@ -86,6 +97,8 @@ lazy_static! {
         map.insert(421, "Misdirected Request");
         map.insert(422, "Unprocessable Content");
         map.insert(426, "Upgrade Required");
+        map.insert(429, "Too Many Requests");
+        map.insert(431, "Request Header Fields Too Large");
         
         // 5xx: 服务端错误 (Server Error)
         map.insert(500, "Internal Server Error");
@@ -144,6 +157,8 @@ lazy_static! {
         map.insert("jsonld", "application/ld+json");
         map.insert("jpg", "image/jpeg");
         map.insert("jpeg", "image/jpeg");
+        map.insert("markdown", "text/markdown;charset=utf-8");
+        map.insert("md", "text/markdown;charset=utf-8");
         map.insert("mid", "audio/x-midi");
         map.insert("midi", "audio/x-midi");
         map.insert("mjs", "text/javascript");
@@ -207,8 +222,10 @@ lazy_static! {
 }
 
 /// 支持的 HTTP 协议版本
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum HttpVersion {
+    /// HTTP/1.0 版本，默认在响应后关闭连接
+    V1_0,
     /// HTTP/1.1 版本
     V1_1,
 }
@@ -224,6 +241,8 @@ pub enum HttpRequestMethod {
     Options,
     /// 提交数据或执行操作
     Post,
+    /// 上传文件，创建或替换目标资源
+    Put,
 }
 
 /// 支持的内容编码（压缩）格式
@@ -243,6 +262,7 @@ impl fmt::Display for HttpVersion {
     /// 将枚举格式化为 HTTP 报文中的版本字符串
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match *self {
+            HttpVersion::V1_0 => write!(f, "1.0"),
             HttpVersion::V1_1 => write!(f, "1.1"),
         }
     }
@@ -256,6 +276,37 @@ impl fmt::Display for HttpRequestMethod {
             HttpRequestMethod::Head => write!(f, "HEAD"),
             HttpRequestMethod::Options => write!(f, "OPTIONS"),
             HttpRequestMethod::Post => write!(f, "POST"),
+            HttpRequestMethod::Put => write!(f, "PUT"),
+        }
+    }
+}
+
+impl std::str::FromStr for HttpRequestMethod {
+    type Err = ();
+
+    /// 按方法名解析，不区分大小写；不支持的方法返回 `Err(())`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "GET" => Ok(HttpRequestMethod::Get),
+            "HEAD" => Ok(HttpRequestMethod::Head),
+            "OPTIONS" => Ok(HttpRequestMethod::Options),
+            "POST" => Ok(HttpRequestMethod::Post),
+            "PUT" => Ok(HttpRequestMethod::Put),
+            _ => Err(()),
+        }
+    }
+}
+
+impl std::str::FromStr for HttpEncoding {
+    type Err = ();
+
+    /// 按 `Accept-Encoding` 中的编码记号解析，不区分大小写；`x-gzip` 视为 `gzip`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "gzip" | "x-gzip" => Ok(HttpEncoding::Gzip),
+            "deflate" => Ok(HttpEncoding::Deflate),
+            "br" => Ok(HttpEncoding::Br),
+            _ => Err(()),
         }
     }
 }
@@ -0,0 +1,636 @@
+// Copyright (c) 2026 shaneyale (shaneyale86@gmail.com)
+// All rights reserved.
+
+//! # 反向代理模块
+//!
+//! 按配置中的 `[proxy]` 表把指定路径前缀下的请求转发给上游 HTTP 服务器：
+//! 转发请求头与请求体，附加 `X-Forwarded-For` 与 `Via`，再把上游响应以分块传输流式返回给客户端，
+//! 上游响应体不会整体读入内存。
+//!
+//! 目前只支持明文 HTTP/1.1 上游，每个请求使用一条新的 TCP 连接（`Connection: close`）。
+
+use crate::{
+    config::Config,
+    exception::Exception,
+    param::{HttpRequestMethod, CRLF, SERVER_NAME},
+    request::Request,
+    response::Response,
+    util::{normalize_path, percent_decode, percent_encode_path_segment},
+};
+
+use bytes::Bytes;
+use log::{debug, error, warn};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+    sync::mpsc,
+    time::timeout,
+};
+
+use std::{fmt, io, str::FromStr, time::Duration};
+
+/// 逐跳（hop-by-hop）头部只对单条连接有意义，代理转发时必须丢弃（RFC 9110 §7.6.1）。
+const HOP_BY_HOP_HEADERS: [&str; 8] = [
+    "connection",
+    "keep-alive",
+    "proxy-connection",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+];
+
+/// 转发上游响应体时通道中最多缓存的块数，客户端读取较慢时上游读取随之暂停
+const BODY_CHANNEL_CAPACITY: usize = 4;
+
+/// 反向代理的上游服务器地址，由 `http://host[:port][/path]` 形式的字符串解析得到。
+#[derive(Debug, Clone, PartialEq)]
+pub struct Upstream {
+    /// 主机名或 IP 地址（IPv6 地址不含方括号）
+    host: String,
+    /// 端口，未指定时为 80
+    port: u16,
+    /// 路径前缀，替换请求中匹配的代理前缀；未指定时为空
+    base_path: String,
+}
+
+impl FromStr for Upstream {
+    type Err = String;
+
+    /// 解析上游地址。只接受 `http://` 地址，端口缺省为 80。
+    ///
+    /// ```
+    /// use webserver::proxy::Upstream;
+    ///
+    /// let upstream: Upstream = "http://127.0.0.1:9000/api".parse().unwrap();
+    /// assert_eq!(upstream.authority(), "127.0.0.1:9000");
+    /// assert!("https://example.com".parse::<Upstream>().is_err());
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rest = s
+            .strip_prefix("http://")
+            .ok_or_else(|| format!("上游地址 {} 必须以 http:// 开头", s))?;
+        let (authority, base_path) = match rest.find('/') {
+            Some(i) => rest.split_at(i),
+            None => (rest, ""),
+        };
+        // IPv6 地址写作 [::1]:9000，端口在右方括号之后
+        let (host, port) = match authority.strip_prefix('[') {
+            Some(v6) => {
+                let (host, after) = v6.split_once(']').ok_or_else(|| format!("上游地址 {} 格式错误", s))?;
+                (host, after.strip_prefix(':'))
+            }
+            None => match authority.split_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (authority, None),
+            },
+        };
+        let port = match port {
+            Some(port) => port.parse::<u16>().ok().filter(|&p| p != 0).ok_or_else(|| format!("上游地址 {} 的端口无效", s))?,
+            None => 80,
+        };
+        if host.is_empty() || host.chars().any(|c| c.is_whitespace() || c == '@') {
+            return Err(format!("上游地址 {} 的主机名无效", s));
+        }
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            base_path: base_path.trim_end_matches('/').to_string(),
+        })
+    }
+}
+
+impl fmt::Display for Upstream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "http://{}{}", self.authority(), self.base_path)
+    }
+}
+
+impl Upstream {
+    /// 获取 `host:port` 形式的地址，用作上游请求的 `Host` 头
+    pub fn authority(&self) -> String {
+        if self.host.contains(':') {
+            format!("[{}]:{}", self.host, self.port)
+        } else {
+            format!("{}:{}", self.host, self.port)
+        }
+    }
+
+    /// 将客户端的请求目标改写为上游的请求目标：去掉匹配的代理前缀，换成上游地址中的路径。
+    ///
+    /// 例如前缀 `/api/` 映射到 `http://backend/v1` 时，`/api/users?page=2` 转发为 `/v1/users?page=2`。
+    /// 路径按 [`route_paths`] 规范化后再去掉前缀，查询字符串原样保留。
+    pub fn target(&self, prefix: &str, path: &str) -> String {
+        let (raw, query) = match path.split_once('?') {
+            Some((raw, query)) => (raw, Some(query)),
+            None => (path, None),
+        };
+        let prefix = prefix.trim_end_matches('/');
+        let rest = route_paths(raw)
+            .into_iter()
+            .find(|candidate| prefix_matches(prefix, candidate))
+            .map_or_else(|| raw.to_string(), |candidate| candidate[prefix.len()..].to_string());
+        let mut target = format!("{}/{}", self.base_path, rest.trim_start_matches('/'));
+        if let Some(query) = query {
+            target.push('?');
+            target.push_str(query);
+        }
+        target
+    }
+}
+
+/// 代理规则匹配时使用的请求路径形式（不含查询字符串）：
+///
+/// 1. 移除 `.`、`..` 段与重复斜杠后的原始路径，不解码百分号编码，转发给上游时保持客户端的编码；
+/// 2. 解码、规范化后再逐段编码的路径，使 `/%61pi/` 等编码写法同样匹配，与 [`crate::auth::is_protected`] 一致。
+///
+/// 两种形式都保留路径末尾的斜杠；无法规范化（如越过根目录）的形式被略去。
+pub fn route_paths(path: &str) -> Vec<String> {
+    let raw = path.split('?').next().unwrap_or_default();
+    let trailing_slash = |mut path: String| {
+        if raw.ends_with('/') && path != "/" {
+            path.push('/');
+        }
+        path
+    };
+    let normalized = normalize_path(raw).ok();
+    let decoded = percent_decode(raw).and_then(|decoded| normalize_path(&decoded)).ok().map(|decoded| {
+        decoded.split('/').map(percent_encode_path_segment).collect::<Vec<_>>().join("/")
+    });
+    let mut paths: Vec<String> = [normalized, decoded].into_iter().flatten().map(trailing_slash).collect();
+    paths.dedup();
+    paths
+}
+
+/// 判断路径是否落在代理前缀之下。按路径段边界匹配：`/api/` 与 `/api` 都匹配 `/api` 与 `/api/users`，不匹配 `/apiary`。
+pub fn prefix_matches(prefix: &str, path: &str) -> bool {
+    let prefix = prefix.trim_end_matches('/');
+    path == prefix || path.strip_prefix(prefix).is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// 上游响应体的长度划分方式
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum UpstreamBody {
+    /// 没有响应体（HEAD 请求，或 1xx/204/304 响应）
+    None,
+    /// 由 `Content-Length` 给出长度
+    Length(u64),
+    /// `Transfer-Encoding: chunked`
+    Chunked,
+    /// 读到上游关闭连接为止
+    UntilClose,
+}
+
+/// 将请求转发给上游服务器，返回状态码与响应头取自上游、响应体以分块传输流式转发的响应。
+///
+/// 连接上游与读取上游响应头都受 `proxy_timeout_secs` 限制，转发响应体时每次读取同样受其限制。
+///
+/// # 错误
+///
+/// - 无法连接上游、上游响应格式错误或最终状态码不在 200–599 之间时返回 [`Exception::BadGateway`]（对应 502）。
+/// - 连接或读取响应头超时时返回 [`Exception::GatewayTimeout`]（对应 504）。
+pub async fn forward(request: &Request, prefix: &str, upstream: &Upstream, config: &Config) -> Result<Response, Exception> {
+    let id = request.id();
+    let wait = Duration::from_secs(config.proxy_timeout_secs());
+    let target = upstream.target(prefix, request.path());
+    debug!("[ID{}]转发请求{} {}到上游{}{}", id, request.method(), request.path(), upstream.authority(), target);
+
+    let mut stream = match timeout(wait, TcpStream::connect((upstream.host.as_str(), upstream.port))).await {
+        Ok(Ok(stream)) => stream,
+        Ok(Err(e)) => {
+            error!("[ID{}]无法连接上游{}：{}", id, upstream, e);
+            return Err(Exception::BadGateway);
+        }
+        Err(_) => {
+            error!("[ID{}]连接上游{}超时", id, upstream);
+            return Err(Exception::GatewayTimeout);
+        }
+    };
+    let head = upstream_request_head(request, upstream, &target);
+    let sent = async {
+        stream.write_all(head.as_bytes()).await?;
+        stream.write_all(request.body()).await?;
+        stream.flush().await
+    };
+    match timeout(wait, sent).await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => {
+            error!("[ID{}]向上游{}发送请求失败：{}", id, upstream, e);
+            return Err(Exception::BadGateway);
+        }
+        Err(_) => {
+            error!("[ID{}]向上游{}发送请求超时", id, upstream);
+            return Err(Exception::GatewayTimeout);
+        }
+    }
+
+    let mut reader = BufReader::new(stream);
+    let max_header_bytes = config.limits().max_header_bytes();
+    let (status, headers) = loop {
+        let (status, headers) = match timeout(wait, read_response_head(&mut reader, max_header_bytes)).await {
+            Ok(Ok(head)) => head,
+            Ok(Err(e)) => {
+                error!("[ID{}]读取上游{}的响应头失败：{}", id, upstream, e);
+                return Err(Exception::BadGateway);
+            }
+            Err(_) => {
+                error!("[ID{}]等待上游{}的响应超时", id, upstream);
+                return Err(Exception::GatewayTimeout);
+            }
+        };
+        // 1xx 临时响应（如 100 Continue）之后才是最终响应
+        if !(100..200).contains(&status) {
+            break (status, headers);
+        }
+    };
+    // 任意 2xx–5xx 状态码都原样转发，STATUS_CODES 中没有的状态码使用所属类别的通用原因短语
+    if !(200..600).contains(&status) {
+        warn!("[ID{}]上游{}返回了无效的状态码{}", id, upstream, status);
+        return Err(Exception::BadGateway);
+    }
+
+    let framing = response_framing(request.method(), status, &headers).ok_or_else(|| {
+        warn!("[ID{}]上游{}的Content-Length无效", id, upstream);
+        Exception::BadGateway
+    })?;
+    let connection_tokens = header_tokens(&headers, "connection");
+    let mut forwarded: Vec<(String, String)> = headers
+        .into_iter()
+        .filter(|(name, _)| {
+            let lower = name.to_ascii_lowercase();
+            !HOP_BY_HOP_HEADERS.contains(&lower.as_str())
+                && !connection_tokens.contains(&lower)
+                // 以下头部由本服务器生成
+                && !matches!(lower.as_str(), "content-length" | "date" | "server" | "upgrade")
+        })
+        .collect();
+    let via = via_value(forwarded.iter().find(|(n, _)| n.eq_ignore_ascii_case("via")).map(|(_, v)| v.as_str()));
+    forwarded.retain(|(name, _)| !name.eq_ignore_ascii_case("via"));
+    forwarded.push(("Via".to_string(), via));
+
+    let mut response = Response::from_upstream(status, &forwarded);
+    debug!("[ID{}]上游{}返回{}，响应体：{:?}", id, upstream, status, framing);
+    if framing != UpstreamBody::None || request.method() == HttpRequestMethod::Head {
+        let (tx, rx) = mpsc::channel(BODY_CHANNEL_CAPACITY);
+        response.set_chunked_body(rx);
+        if framing != UpstreamBody::None {
+            tokio::spawn(pump_body(reader, framing, tx, config.chunk_size(), wait));
+        }
+    }
+    Ok(response)
+}
+
+/// 构建发往上游的请求头：改写请求目标与 `Host`，丢弃逐跳头部，追加 `X-Forwarded-For` 与 `Via`。
+///
+/// 普通请求体已按 `Content-Encoding` 解压，因此重新计算 `Content-Length` 并去掉 `Content-Encoding`；
+/// 分块编码的请求体按原始字节转发，保留 `Transfer-Encoding: chunked`。
+fn upstream_request_head(request: &Request, upstream: &Upstream, target: &str) -> String {
+    let chunked = header_tokens(request.headers(), "transfer-encoding").iter().any(|t| t == "chunked");
+    let connection_tokens = header_tokens(request.headers(), "connection");
+    let mut head = format!("{} {} HTTP/1.1{}Host: {}{}", request.method(), target, CRLF, upstream.authority(), CRLF);
+    for (name, value) in request.headers() {
+        let skipped = HOP_BY_HOP_HEADERS.contains(&name.as_str())
+            || connection_tokens.contains(name)
+            || matches!(name.as_str(), "host" | "content-length" | "upgrade" | "x-forwarded-for" | "via")
+            || (name == "content-encoding" && !chunked);
+        if !skipped {
+            head.push_str(&format!("{}: {}{}", name, value, CRLF));
+        }
+    }
+    let forwarded_for = match (request.header("x-forwarded-for"), request.peer_addr()) {
+        (Some(existing), Some(peer)) => Some(format!("{}, {}", existing, peer.ip())),
+        (Some(existing), None) => Some(existing.to_string()),
+        (None, Some(peer)) => Some(peer.ip().to_string()),
+        (None, None) => None,
+    };
+    if let Some(forwarded_for) = forwarded_for {
+        head.push_str(&format!("X-Forwarded-For: {}{}", forwarded_for, CRLF));
+    }
+    head.push_str(&format!("Via: {}{}", via_value(request.header("via")), CRLF));
+    if chunked {
+        head.push_str(&format!("Transfer-Encoding: chunked{}", CRLF));
+    } else if !request.body().is_empty() || request.header("content-length").is_some() {
+        head.push_str(&format!("Content-Length: {}{}", request.body().len(), CRLF));
+    }
+    head.push_str(&format!("Connection: close{}{}", CRLF, CRLF));
+    head
+}
+
+/// 在已有的 `Via` 值之后追加本服务器，形如 `1.1 shaneyale-webserver`。
+fn via_value(existing: Option<&str>) -> String {
+    let hop = format!("1.1 {}", SERVER_NAME);
+    match existing {
+        Some(existing) => format!("{}, {}", existing, hop),
+        None => hop,
+    }
+}
+
+/// 收集某个头部中以逗号分隔的全部取值（小写），头部名不区分大小写
+fn header_tokens(headers: &[(String, String)], name: &str) -> Vec<String> {
+    headers
+        .iter()
+        .filter(|(n, _)| n.eq_ignore_ascii_case(name))
+        .flat_map(|(_, v)| v.split(','))
+        .map(|t| t.trim().to_ascii_lowercase())
+        .filter(|t| !t.is_empty())
+        .collect()
+}
+
+/// 读取上游的状态行与响应头，总长度超过 `max_header_bytes` 时视为错误。
+async fn read_response_head<R: AsyncRead + Unpin>(
+    reader: &mut BufReader<R>,
+    max_header_bytes: usize,
+) -> io::Result<(u16, Vec<(String, String)>)> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+    let mut total = 0;
+    let mut lines = Vec::new();
+    loop {
+        let mut line = String::new();
+        let n = (&mut *reader).take((max_header_bytes - total + 1) as u64).read_line(&mut line).await?;
+        if n == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "上游在响应头结束前关闭了连接"));
+        }
+        total += n;
+        if total > max_header_bytes {
+            return Err(invalid("上游响应头过大"));
+        }
+        let line = line.trim_end_matches(['\r', '\n']);
+        if line.is_empty() {
+            break;
+        }
+        lines.push(line.to_string());
+    }
+    let status_line = lines.first().ok_or_else(|| invalid("缺少状态行"))?;
+    let mut parts = status_line.splitn(3, ' ');
+    let version = parts.next().unwrap_or_default();
+    if !version.starts_with("HTTP/1.") {
+        return Err(invalid("上游响应不是 HTTP/1.x"));
+    }
+    let status = parts
+        .next()
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| invalid("状态码无效"))?;
+    let headers = lines[1..]
+        .iter()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .collect();
+    Ok((status, headers))
+}
+
+/// 根据请求方法、状态码与响应头确定上游响应体的划分方式；`Content-Length` 无效时返回 `None`。
+fn response_framing(method: HttpRequestMethod, status: u16, headers: &[(String, String)]) -> Option<UpstreamBody> {
+    if method == HttpRequestMethod::Head || matches!(status, 204 | 304) {
+        return Some(UpstreamBody::None);
+    }
+    if header_tokens(headers, "transfer-encoding").last().is_some_and(|t| t == "chunked") {
+        return Some(UpstreamBody::Chunked);
+    }
+    match headers.iter().find(|(n, _)| n.eq_ignore_ascii_case("content-length")) {
+        Some((_, len)) => len.parse::<u64>().ok().map(UpstreamBody::Length),
+        None => Some(UpstreamBody::UntilClose),
+    }
+}
+
+/// 从上游读取响应体并逐块送入通道。上游出错或超时时向通道发送错误，客户端看到不完整的分块流；
+/// 客户端断开（通道关闭）时停止读取，随后关闭上游连接。
+async fn pump_body<R: AsyncRead + Unpin>(
+    mut reader: BufReader<R>,
+    framing: UpstreamBody,
+    tx: mpsc::Sender<io::Result<Bytes>>,
+    chunk_size: usize,
+    wait: Duration,
+) {
+    let result = match framing {
+        UpstreamBody::None => Ok(()),
+        UpstreamBody::Length(len) => copy_bounded(&mut reader, Some(len), &tx, chunk_size, wait).await,
+        UpstreamBody::UntilClose => copy_bounded(&mut reader, None, &tx, chunk_size, wait).await,
+        UpstreamBody::Chunked => copy_chunked(&mut reader, &tx, chunk_size, wait).await,
+    };
+    if let Err(e) = result {
+        if e.kind() != io::ErrorKind::BrokenPipe {
+            warn!("转发上游响应体失败：{}", e);
+            let _ = tx.send(Err(e)).await;
+        }
+    }
+}
+
+/// 复制至多 `len` 字节（`None` 表示读到连接关闭），每次最多读取 `chunk_size` 字节。
+async fn copy_bounded<R: AsyncRead + Unpin>(
+    reader: &mut BufReader<R>,
+    len: Option<u64>,
+    tx: &mpsc::Sender<io::Result<Bytes>>,
+    chunk_size: usize,
+    wait: Duration,
+) -> io::Result<()> {
+    let mut remaining = len;
+    let mut buffer = vec![0u8; chunk_size.max(1)];
+    while remaining != Some(0) {
+        let want = remaining.map_or(buffer.len(), |r| r.min(buffer.len() as u64) as usize);
+        let n = with_timeout(wait, reader.read(&mut buffer[..want])).await?;
+        if n == 0 {
+            return match remaining {
+                Some(_) => Err(io::Error::new(io::ErrorKind::UnexpectedEof, "上游响应体不完整")),
+                None => Ok(()),
+            };
+        }
+        send(tx, Bytes::copy_from_slice(&buffer[..n])).await?;
+        remaining = remaining.map(|r| r - n as u64);
+    }
+    Ok(())
+}
+
+/// 解码上游的分块响应体并逐块转发，结束块之后的尾部字段被丢弃。
+async fn copy_chunked<R: AsyncRead + Unpin>(
+    reader: &mut BufReader<R>,
+    tx: &mpsc::Sender<io::Result<Bytes>>,
+    chunk_size: usize,
+    wait: Duration,
+) -> io::Result<()> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "上游分块编码格式错误");
+    loop {
+        let mut line = String::new();
+        with_timeout(wait, (&mut *reader).take(1024).read_line(&mut line)).await?;
+        let size = line.trim_end().split(';').next().unwrap_or_default().trim();
+        let size = u64::from_str_radix(size, 16).map_err(|_| invalid())?;
+        if size == 0 {
+            // 跳过尾部字段，直到空行
+            loop {
+                let mut trailer = String::new();
+                let n = with_timeout(wait, (&mut *reader).take(8192).read_line(&mut trailer)).await?;
+                if n == 0 || trailer.trim_end().is_empty() {
+                    return Ok(());
+                }
+            }
+        }
+        copy_bounded(reader, Some(size), tx, chunk_size, wait).await?;
+        let mut crlf = [0u8; 2];
+        with_timeout(wait, reader.read_exact(&mut crlf)).await?;
+        if &crlf != b"\r\n" {
+            return Err(invalid());
+        }
+    }
+}
+
+/// 为单次读取加上超时，超时转换为 `TimedOut` 错误
+async fn with_timeout<T>(wait: Duration, future: impl std::future::Future<Output = io::Result<T>>) -> io::Result<T> {
+    timeout(wait, future)
+        .await
+        .unwrap_or_else(|_| Err(io::Error::new(io::ErrorKind::TimedOut, "读取上游响应体超时")))
+}
+
+/// 将一块数据送入通道；接收端已关闭（客户端断开）时返回 `BrokenPipe`
+async fn send(tx: &mpsc::Sender<io::Result<Bytes>>, chunk: Bytes) -> io::Result<()> {
+    tx.send(Ok(chunk))
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "客户端已断开"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response::write_chunked;
+    use tokio::net::TcpListener;
+
+    /// 上游地址的解析与请求目标改写
+    #[test]
+    fn test_parse_upstream() {
+        let upstream: Upstream = "http://127.0.0.1:9000/v1/".parse().unwrap();
+        assert_eq!(upstream.authority(), "127.0.0.1:9000");
+        assert_eq!(upstream.target("/api/", "/api/users?page=2"), "/v1/users?page=2");
+        assert_eq!(upstream.to_string(), "http://127.0.0.1:9000/v1");
+
+        let upstream: Upstream = "http://backend".parse().unwrap();
+        assert_eq!(upstream.authority(), "backend:80");
+        assert_eq!(upstream.target("/api", "/api"), "/");
+        assert_eq!(upstream.target("/api/", "/api/a/b"), "/a/b");
+        // 规范化后去掉前缀，保留客户端的百分号编码与末尾的斜杠
+        assert_eq!(upstream.target("/api/", "//api/./a/../b%20c/"), "/b%20c/");
+        assert_eq!(upstream.target("/api/", "/%61pi/x?q=%2F"), "/x?q=%2F");
+
+        let upstream: Upstream = "http://[::1]:8080".parse().unwrap();
+        assert_eq!(upstream.authority(), "[::1]:8080");
+
+        for bad in ["https://example.com", "example.com", "http://", "http://host:0", "http://host:x", "http://u@host"] {
+            assert!(bad.parse::<Upstream>().is_err(), "{}", bad);
+        }
+    }
+
+    /// 代理前缀按路径段边界匹配规范化后的路径
+    #[test]
+    fn test_route_paths() {
+        assert_eq!(route_paths("/api/../static/a.css?x=1"), vec!["/static/a.css"]);
+        assert_eq!(route_paths("//api//users/"), vec!["/api/users/"]);
+        assert_eq!(route_paths("/%61pi/a%20b"), vec!["/%61pi/a%20b", "/api/a%20b"]);
+        assert!(route_paths("/../etc/passwd").is_empty());
+
+        for path in ["/api", "/api/", "/api/users"] {
+            assert!(prefix_matches("/api/", path) && prefix_matches("/api", path), "{}", path);
+        }
+        assert!(!prefix_matches("/api/", "/apiary") && !prefix_matches("/api", "/apiary/x"));
+        assert!(prefix_matches("/", "/anything"));
+    }
+
+    /// 启动只处理一个连接的上游：记录收到的请求头，然后返回给定的原始响应
+    async fn one_shot_upstream(reply: &'static [u8]) -> (Upstream, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let upstream = format!("http://{}", listener.local_addr().unwrap()).parse().unwrap();
+        let handle = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut head = String::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).await.unwrap();
+                head.push_str(&line);
+                if line == "\r\n" {
+                    break;
+                }
+            }
+            reader.get_mut().write_all(reply).await.unwrap();
+            head
+        });
+        (upstream, handle)
+    }
+
+    async fn body_of(response: &mut Response) -> Vec<u8> {
+        let mut body = response.take_chunked_body().unwrap();
+        let mut out = Vec::new();
+        write_chunked(&mut body, &mut out).await.unwrap();
+        out
+    }
+
+    /// 转发时改写请求目标与 Host，丢弃逐跳头部，追加 X-Forwarded-For 与 Via；上游的分块响应体被重新分块转发
+    #[tokio::test]
+    async fn test_forward_request_and_stream_response() {
+        let (upstream, handle) = one_shot_upstream(
+            b"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nTransfer-Encoding: chunked\r\nConnection: close\r\nX-Upstream: yes\r\n\r\n5\r\n{\"a\":\r\n2\r\n1}\r\n0\r\n\r\n",
+        )
+        .await;
+        let raw = "GET /api/items?x=1 HTTP/1.1\r\nHost: example.com\r\nConnection: keep-alive, x-secret\r\nX-Secret: 1\r\nX-Forwarded-For: 10.0.0.1\r\n\r\n";
+        let mut request = Request::try_from(&raw.as_bytes().to_vec(), 0).unwrap();
+        request.set_peer_addr(Some("192.0.2.7:5000".parse().unwrap()), false);
+
+        let mut response = forward(&request, "/api/", &upstream, &Config::new()).await.unwrap();
+        let head = handle.await.unwrap();
+        assert!(head.starts_with("GET /items?x=1 HTTP/1.1\r\n"), "{}", head);
+        assert!(head.contains(&format!("Host: {}\r\n", upstream.authority())), "{}", head);
+        assert!(head.contains("X-Forwarded-For: 10.0.0.1, 192.0.2.7\r\n"), "{}", head);
+        assert!(head.contains(&format!("Via: 1.1 {}\r\n", SERVER_NAME)), "{}", head);
+        assert!(head.contains("Connection: close\r\n"), "{}", head);
+        assert!(!head.contains("keep-alive") && !head.to_lowercase().contains("x-secret"), "{}", head);
+
+        assert_eq!(response.status_code(), 200);
+        let text = String::from_utf8(response.as_bytes()).unwrap();
+        assert!(text.contains("Content-Type: application/json\r\n"), "{}", text);
+        assert!(text.contains("X-Upstream: yes\r\n"), "{}", text);
+        assert!(text.contains(&format!("Via: 1.1 {}\r\n", SERVER_NAME)), "{}", text);
+        assert_eq!(text.matches("Transfer-Encoding").count(), 1, "{}", text);
+        assert_eq!(body_of(&mut response).await, b"5\r\n{\"a\":\r\n2\r\n1}\r\n0\r\n\r\n");
+    }
+
+    /// POST 请求体与 Content-Length 一并转发；上游按 Content-Length 给出的响应体完整转发
+    #[tokio::test]
+    async fn test_forward_body_with_content_length() {
+        let (upstream, handle) =
+            one_shot_upstream(b"HTTP/1.1 201 Created\r\nContent-Length: 7\r\n\r\ncreated").await;
+        let raw = "POST /api/items HTTP/1.1\r\nHost: example.com\r\nContent-Length: 4\r\n\r\nabcd";
+        let request = Request::try_from(&raw.as_bytes().to_vec(), 0).unwrap();
+
+        let mut response = forward(&request, "/api/", &upstream, &Config::new()).await.unwrap();
+        let head = handle.await.unwrap();
+        assert!(head.starts_with("POST /items HTTP/1.1\r\n"), "{}", head);
+        assert!(head.contains("Content-Length: 4\r\n"), "{}", head);
+        assert_eq!(response.status_code(), 201);
+        assert_eq!(body_of(&mut response).await, b"7\r\ncreated\r\n0\r\n\r\n");
+    }
+
+    /// 不在 STATUS_CODES 中的状态码原样转发，原因短语取所属类别的通用短语
+    #[tokio::test]
+    async fn test_forward_unlisted_status() {
+        let request = Request::try_from(&b"GET /api/ HTTP/1.1\r\nHost: a\r\n\r\n".to_vec(), 0).unwrap();
+        for (reply, status, reason) in [
+            (&b"HTTP/1.1 299 Odd\r\nContent-Length: 0\r\n\r\n"[..], 299, "Success"),
+            (&b"HTTP/1.1 425 Too Early\r\nContent-Length: 0\r\n\r\n"[..], 425, "Client Error"),
+            (&b"HTTP/1.1 599 Whatever\r\nContent-Length: 0\r\n\r\n"[..], 599, "Server Error"),
+        ] {
+            let (upstream, _handle) = one_shot_upstream(reply).await;
+            let response = forward(&request, "/api/", &upstream, &Config::new()).await.unwrap();
+            assert_eq!((response.status_code(), response.information()), (status, reason));
+        }
+    }
+
+    /// 上游不可达时返回 BadGateway，状态码无效时同样返回 BadGateway
+    #[tokio::test]
+    async fn test_forward_errors() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let upstream: Upstream = format!("http://{}", listener.local_addr().unwrap()).parse().unwrap();
+        drop(listener);
+        let request = Request::try_from(&b"GET /api/ HTTP/1.1\r\nHost: a\r\n\r\n".to_vec(), 0).unwrap();
+        assert!(matches!(forward(&request, "/api/", &upstream, &Config::new()).await, Err(Exception::BadGateway)));
+
+        let (upstream, _handle) = one_shot_upstream(b"HTTP/1.1 600 Odd\r\nContent-Length: 0\r\n\r\n").await;
+        assert!(matches!(forward(&request, "/api/", &upstream, &Config::new()).await, Err(Exception::BadGateway)));
+    }
+}
@@ -0,0 +1,262 @@
+// Copyright (c) 2026 shaneyale (shaneyale86@gmail.com)
+// All rights reserved.
+
+//! # 限流模块
+//!
+//! 按客户端 IP 的令牌桶限流器：每个 IP 拥有一个容量为 `burst` 的令牌桶，令牌以每秒 `rate` 个的速度补充，
+//! 每个请求消耗一个令牌，令牌不足时拒绝请求并给出需要等待的时间。
+//!
+//! [`RateLimiter`] 实现了 [`Middleware::admit`]，注册到中间件链后在 CORS 预检与鉴权之前对每个请求生效（包括同一 keep-alive 连接上的后续请求
+//! 与 HTTP/2 连接上的每个流），被拒绝的请求收到带 `Retry-After` 的 429 响应。
+//! 客户端 IP 取自 [`Request::client_ip`]，因此开启 `trust_proxy` 时按 `X-Forwarded-For` 中的地址限流；
+//! 没有 IP 的连接（如 Unix 域套接字）不受限制。
+
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, SystemTime},
+};
+
+use log::warn;
+
+use crate::{
+    clock::{Clock, SystemClock},
+    config::RateLimit,
+    middleware::Middleware,
+    request::Request,
+    response::Response,
+};
+
+/// 跟踪的 IP 数量达到该值时淘汰最久未活动的桶，避免大量一次性客户端撑大内存。
+const MAX_TRACKED_CLIENTS: usize = 10000;
+
+/// 达到上限时一次淘汰的桶数，使淘汰的开销分摊到随后的多个新客户端上。
+const EVICTION_BATCH: usize = MAX_TRACKED_CLIENTS / 10;
+
+/// 单个 IP 的令牌桶。
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    /// 当前剩余的令牌数。
+    tokens: f64,
+    /// 上次补充令牌的时间。
+    updated: SystemTime,
+}
+
+/// 按客户端 IP 的令牌桶限流器，在多个连接任务之间共享。
+pub struct RateLimiter {
+    /// 每秒补充的令牌数。
+    rate: f64,
+    /// 令牌桶容量。
+    burst: f64,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+    clock: Arc<dyn Clock>,
+    /// 放行的请求数。
+    allowed: AtomicU64,
+    /// 被拒绝的请求数。
+    limited: AtomicU64,
+}
+
+impl RateLimiter {
+    /// 构造一个每秒补充 `requests_per_second` 个令牌、容量为 `burst` 的限流器。
+    pub fn new(requests_per_second: f64, burst: u32) -> Self {
+        Self {
+            rate: requests_per_second,
+            burst: burst as f64,
+            buckets: Mutex::new(HashMap::new()),
+            clock: Arc::new(SystemClock),
+            allowed: AtomicU64::new(0),
+            limited: AtomicU64::new(0),
+        }
+    }
+
+    /// 按 `[rate_limit]` 配置构造限流器，未启用限流时返回 `None`。
+    pub fn from_config(config: &RateLimit) -> Option<Self> {
+        config
+            .requests_per_second()
+            .map(|rate| Self::new(rate, config.burst()))
+    }
+
+    /// 替换时间来源，主要用于在测试中注入 [`crate::clock::FakeClock`]。
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// 为来自 `ip` 的一个请求消耗一个令牌。
+    ///
+    /// # Errors
+    ///
+    /// 令牌不足时返回距离补充出一个令牌还需等待的时间；速率极低以致等待时间无法表示时返回 [`Duration::MAX`]。
+    pub fn check(&self, ip: IpAddr) -> Result<(), Duration> {
+        let now = self.clock.now();
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        if buckets.len() >= MAX_TRACKED_CLIENTS && !buckets.contains_key(&ip) {
+            evict_oldest(&mut buckets, EVICTION_BATCH);
+        }
+        let bucket = buckets.entry(ip).or_insert(Bucket {
+            tokens: self.burst,
+            updated: now,
+        });
+        *bucket = self.refill(*bucket, now);
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            self.allowed.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        } else {
+            self.limited.fetch_add(1, Ordering::Relaxed);
+            Err(Duration::try_from_secs_f64((1.0 - bucket.tokens) / self.rate).unwrap_or(Duration::MAX))
+        }
+    }
+
+    /// 按经过的时间补充令牌，不超过桶的容量。时钟回拨时不补充。
+    fn refill(&self, bucket: Bucket, now: SystemTime) -> Bucket {
+        let elapsed = now.duration_since(bucket.updated).unwrap_or_default();
+        Bucket {
+            tokens: (bucket.tokens + elapsed.as_secs_f64() * self.rate).min(self.burst),
+            updated: now.max(bucket.updated),
+        }
+    }
+
+    /// 放行的请求数。
+    pub fn allowed(&self) -> u64 {
+        self.allowed.load(Ordering::Relaxed)
+    }
+
+    /// 因令牌不足被拒绝的请求数。
+    pub fn limited(&self) -> u64 {
+        self.limited.load(Ordering::Relaxed)
+    }
+
+    /// 当前跟踪的客户端 IP 数量。
+    pub fn tracked_clients(&self) -> usize {
+        self.buckets.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+}
+
+/// 淘汰 `count` 个最久未补充令牌（即最久未活动）的桶。
+fn evict_oldest(buckets: &mut HashMap<IpAddr, Bucket>, count: usize) {
+    let mut by_age: Vec<(SystemTime, IpAddr)> = buckets.iter().map(|(ip, bucket)| (bucket.updated, *ip)).collect();
+    let count = count.min(by_age.len());
+    if count < by_age.len() {
+        by_age.select_nth_unstable(count);
+    }
+    for (_, ip) in &by_age[..count] {
+        buckets.remove(ip);
+    }
+}
+
+impl Middleware for RateLimiter {
+    fn admit(&self, req: &Request) -> Option<Response> {
+        let ip = req.client_ip()?;
+        let wait = self.check(ip).err()?;
+        // Retry-After 只能是整数秒，向上取整以免客户端过早重试
+        let retry_after = wait.as_secs().saturating_add(u64::from(wait.subsec_nanos() > 0));
+        warn!("[ID{}]客户端{}请求过于频繁，返回429", req.id(), ip);
+        Some(Response::response_429(req.id(), retry_after.max(1)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FakeClock;
+
+    fn ip(last: u8) -> IpAddr {
+        IpAddr::from([192, 0, 2, last])
+    }
+
+    #[test]
+    fn test_token_bucket() {
+        let clock = Arc::new(FakeClock::new(SystemTime::UNIX_EPOCH));
+        let limiter = RateLimiter::new(2.0, 3).with_clock(clock.clone());
+
+        // 突发容量用完后拒绝，并报告补充一个令牌所需的时间
+        for _ in 0..3 {
+            assert_eq!(limiter.check(ip(1)), Ok(()));
+        }
+        assert_eq!(limiter.check(ip(1)), Err(Duration::from_millis(500)));
+        // 不同 IP 的桶互不影响
+        assert_eq!(limiter.check(ip(2)), Ok(()));
+
+        clock.advance(Duration::from_millis(500));
+        assert_eq!(limiter.check(ip(1)), Ok(()));
+        assert!(limiter.check(ip(1)).is_err());
+
+        // 长时间空闲后最多补满到桶的容量
+        clock.advance(Duration::from_secs(60));
+        for _ in 0..3 {
+            assert_eq!(limiter.check(ip(1)), Ok(()));
+        }
+        assert!(limiter.check(ip(1)).is_err());
+
+        assert_eq!(limiter.allowed(), 8);
+        assert_eq!(limiter.limited(), 3);
+        assert_eq!(limiter.tracked_clients(), 2);
+    }
+
+    #[test]
+    fn test_middleware_returns_429() {
+        let limiter = RateLimiter::new(0.5, 1);
+        let raw = b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n".to_vec();
+        let mut req = Request::try_from(&raw, 3).unwrap();
+        // 没有客户端 IP 的请求不受限制
+        assert!(limiter.admit(&req).is_none());
+        assert!(limiter.admit(&req).is_none());
+
+        req.set_peer_addr(Some("192.0.2.1:5000".parse().unwrap()), false);
+        assert!(limiter.admit(&req).is_none());
+        let response = limiter.admit(&req).expect("令牌已耗尽");
+        let text = String::from_utf8(response.as_bytes()).unwrap();
+        assert!(
+            text.starts_with("HTTP/1.1 429 Too Many Requests\r\n"),
+            "{}",
+            text
+        );
+        assert!(text.contains("\r\nRetry-After: 2\r\n"), "{}", text);
+    }
+
+    /// 速率极低时等待时间无法用 Duration 表示，返回 Duration::MAX 而不是 panic
+    #[test]
+    fn test_tiny_rate_does_not_panic() {
+        let limiter = RateLimiter::new(1e-20, 1);
+        assert_eq!(limiter.check(ip(1)), Ok(()));
+        assert_eq!(limiter.check(ip(1)), Err(Duration::MAX));
+
+        let raw = b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n".to_vec();
+        let mut req = Request::try_from(&raw, 0).unwrap();
+        req.set_peer_addr(Some("192.0.2.1:5000".parse().unwrap()), false);
+        let text = String::from_utf8(limiter.admit(&req).unwrap().as_bytes()).unwrap();
+        assert!(text.contains(&format!("\r\nRetry-After: {}\r\n", u64::MAX)), "{}", text);
+    }
+
+    /// 跟踪的 IP 达到上限后一次淘汰一批最久未活动的桶，活跃客户端的桶保留
+    #[test]
+    fn test_evicts_oldest_clients() {
+        let clock = Arc::new(FakeClock::new(SystemTime::UNIX_EPOCH));
+        let limiter = RateLimiter::new(1.0, 5).with_clock(clock.clone());
+        let client = |n: usize| IpAddr::from(std::net::Ipv4Addr::from(0x0a00_0000 + n as u32));
+        for n in 0..MAX_TRACKED_CLIENTS {
+            assert_eq!(limiter.check(client(n)), Ok(()));
+            clock.advance(Duration::from_millis(1));
+        }
+        // 最早的客户端再次活动后不会被淘汰
+        assert_eq!(limiter.check(client(0)), Ok(()));
+        assert_eq!(limiter.tracked_clients(), MAX_TRACKED_CLIENTS);
+
+        assert_eq!(limiter.check(ip(1)), Ok(()));
+        assert_eq!(limiter.tracked_clients(), MAX_TRACKED_CLIENTS - EVICTION_BATCH + 1);
+        let buckets = limiter.buckets.lock().unwrap();
+        assert!(buckets.contains_key(&client(0)) && buckets.contains_key(&ip(1)));
+        assert!(!buckets.contains_key(&client(1)) && !buckets.contains_key(&client(EVICTION_BATCH)));
+        assert!(buckets.contains_key(&client(EVICTION_BATCH + 1)));
+    }
+
+    #[test]
+    fn test_from_config_disabled_by_default() {
+        assert!(RateLimiter::from_config(&RateLimit::default()).is_none());
+    }
+}
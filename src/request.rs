@@ -10,14 +10,19 @@
 //! 3. 范围请求（Range Requests）的解析。
 //! 4. 内容协商（Content Negotiation）相关的编码解析。
 
-use crate::{exception::Exception, param::*};
-use log::error;
+use crate::{config::Limits, exception::Exception, param::*, util::percent_decode};
+use flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder};
+use log::{debug, error, warn};
+use std::io::Read;
+use std::net::{IpAddr, SocketAddr};
 
 /// 表示一个完整的 HTTP 请求元数据。
 /// 
-/// 该结构体不包含请求体（Body）的大数据部分，主要用于路由分发和权限校验。
+/// 主要用于路由分发和权限校验；请求体（Body）以解压后的原始字节保存，见 [`Request::body`]。
 #[derive(Debug, Clone)]
 pub struct Request {
+    /// 请求所属连接的 ID，用于日志
+    id: u128,
     /// HTTP 请求方法（GET, POST 等）
     method: HttpRequestMethod,
     /// 请求的资源路径（包含查询字符串）
@@ -26,13 +31,116 @@ pub struct Request {
     version: HttpVersion,
     /// 客户端标识字符串
     user_agent: String,
-    /// 客户端支持的压缩编码列表（按解析顺序排列）
+    /// 客户端支持的压缩编码列表，按偏好从高到低排列，见 [`rank_encodings`]
     accept_encoding: Vec<HttpEncoding>,
+    /// `Accept-Encoding` 中各编码的权重（q 值），按出现顺序排列，用于按服务器优先级重新排序
+    accept_encoding_weights: Vec<(HttpEncoding, f32)>,
     /// 客户端接受的内容类型（MIME）
     accept: Option<String>,
-    /// 范围请求参数：(起始字节, 结束字节)
+    /// 解析后的 `Accept` 媒体范围及其权重，按出现顺序排列；没有 `Accept` 头时为空
+    accept_media: Vec<MediaRange>,
+    /// 范围请求参数，按请求中的顺序排列；多段范围（`bytes=0-99,200-299`）包含多项，见 [`ByteRange`]
+    ranges: Vec<ByteRange>,
+    /// 是否携带了无法解析的 `Range` 头（如 `bytes=abc-def`）。
+    /// 用于区分“没有 Range”与“Range 格式错误”两种情况。
+    range_malformed: bool,
+    /// `Connection` 请求头的值，如 `close`、`keep-alive`
+    connection: Option<String>,
+    /// 全部请求头，按出现顺序保存。字段名统一转为小写，字段值去除首尾空白。
+    headers: Vec<(String, String)>,
+    /// 请求体。携带 `Content-Encoding` 时为解压后的内容。
+    body: Vec<u8>,
+    /// 连接对端的套接字地址。Unix 域套接字等没有 IP 的连接为 `None`。
+    peer_addr: Option<SocketAddr>,
+    /// 客户端的真实 IP：默认取对端地址，信任代理时取自 `X-Forwarded-For`。
+    client_ip: Option<IpAddr>,
+    /// 通过鉴权的用户名（Basic 认证的用户名或 Bearer 令牌名），未经鉴权时为 `None`，见 [`crate::auth`]。
+    remote_user: Option<String>,
+}
+
+/// `Accept` 请求头中带权重的单个媒体范围，如 `text/*;q=0.8`。
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaRange {
+    /// 主类型，可以是 `*`
+    main_type: String,
+    /// 子类型，可以是 `*`
+    sub_type: String,
+    /// 权重（q 值），范围 0.0 ~ 1.0，缺省为 1.0
+    quality: f32,
+}
+
+impl MediaRange {
+    /// 解析单个媒体范围，格式错误时返回 `None`。
+    fn parse(item: &str) -> Option<Self> {
+        let mut parts = item.split(';').map(str::trim);
+        let (main_type, sub_type) = parts.next()?.split_once('/')?;
+        if main_type.is_empty() || sub_type.is_empty() {
+            return None;
+        }
+        let mut quality = 1.0;
+        for param in parts {
+            if let Some((name, value)) = param.split_once('=') {
+                if name.trim().eq_ignore_ascii_case("q") {
+                    quality = value.trim().parse::<f32>().unwrap_or(1.0).clamp(0.0, 1.0);
+                }
+            }
+        }
+        Some(Self {
+            main_type: main_type.to_lowercase(),
+            sub_type: sub_type.to_lowercase(),
+            quality,
+        })
+    }
+
+    /// 媒体范围与具体 MIME 类型匹配时返回其具体程度（精确匹配 2，`type/*` 为 1，`*/*` 为 0）。
+    fn specificity(&self, main_type: &str, sub_type: &str) -> Option<u8> {
+        match (self.main_type.as_str(), self.sub_type.as_str()) {
+            ("*", _) => Some(0),
+            (t, "*") if t == main_type => Some(1),
+            (t, s) if t == main_type && s == sub_type => Some(2),
+            _ => None,
+        }
+    }
+
+    /// 获取权重（q 值）
+    pub fn quality(&self) -> f32 {
+        self.quality
+    }
+}
+
+/// `Range` 请求头中的单个字节范围（RFC 9110 §14.1.2）。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ByteRange {
+    /// `bytes=start-end` 或 `bytes=start-`：(起始字节, 结束字节)，
     /// 其中结束字节为 `None` 表示请求从起始位置到文件末尾的所有数据。
-    range: Option<(u64, Option<u64>)>,
+    FromTo(u64, Option<u64>),
+    /// `bytes=-N`：文件末尾的 N 个字节。
+    Suffix(u64),
+}
+
+impl ByteRange {
+    /// 结合文件大小计算实际的闭区间 `(start, end)`。
+    ///
+    /// 范围无法满足时（起点越界、起点大于终点、空文件或 `bytes=-0`）返回 `None`，
+    /// 对应 `416 Range Not Satisfiable`。超出文件末尾的终点和超过文件大小的后缀会被截断到文件末尾。
+    pub fn resolve(&self, file_size: u64) -> Option<(u64, u64)> {
+        if file_size == 0 {
+            return None;
+        }
+        let last = file_size - 1;
+        match *self {
+            ByteRange::FromTo(start, end) => {
+                let end = end.map_or(last, |e| e.min(last));
+                if start > end {
+                    None
+                } else {
+                    Some((start, end))
+                }
+            }
+            ByteRange::Suffix(0) => None,
+            ByteRange::Suffix(n) => Some((file_size.saturating_sub(n), last)),
+        }
+    }
 }
 
 impl Request {
@@ -50,9 +158,27 @@ impl Request {
     /// 
     /// # 错误处理
     /// 如果请求格式不符合 HTTP 规范或使用了不支持的方法/版本，将返回相应的 `Exception`。
+    ///
+    /// 使用默认的 [`Limits`]；需要按配置限制请求时使用 [`Request::try_from_with_limits`]。
     pub fn try_from(buffer: &Vec<u8>, id: u128) -> Result<Self, Exception> {
-        // 1. 将字节流转换为字符串，失败则判定为非法的 HTTP 请求
-        let request_string = match String::from_utf8(buffer.to_vec()) {
+        Self::try_from_with_limits(buffer, id, &Limits::default())
+    }
+
+    /// 按给定的上限从原始字节缓冲区构建 `Request` 实例。
+    ///
+    /// 请求目标（URI）超过 `max_uri_length` 时返回 `Exception::UriTooLong`（414）。
+    /// 该检查直接在原始字节上进行，早于任何字符串转换与拷贝，超长的路径不会被复制。
+    pub fn try_from_with_limits(buffer: &[u8], id: u128, limits: &Limits) -> Result<Self, Exception> {
+        check_uri_length(buffer, limits.max_uri_length(), id)?;
+
+        // 1. 以空行为界分离头部与请求体。请求体可能是压缩后的二进制数据，只有头部需要是合法的 UTF-8
+        let (head, raw_body) = match buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+            Some(pos) => (&buffer[..pos], &buffer[pos + 4..]),
+            None => (buffer, &buffer[buffer.len()..]),
+        };
+
+        // 将头部转换为字符串，失败则判定为非法的 HTTP 请求
+        let request_string = match String::from_utf8(head.to_vec()) {
             Ok(string) => string,
             Err(_) => {
                 error!("[ID{}]无法解析HTTP请求", id);
@@ -67,17 +193,14 @@ impl Request {
 
         if first_line_parts.len() < 3 {
             error!("[ID{}]HTTP请求行格式不正确：{}", id, request_lines[0]);
-            return Err(Exception::UnSupportedRequestMethod);
+            return Err(Exception::MalformedRequestLine);
         }
 
         // 解析方法名
         let method_str = first_line_parts[0].to_uppercase();
-        let method = match method_str.as_str() {
-            "GET" => HttpRequestMethod::Get,
-            "HEAD" => HttpRequestMethod::Head,
-            "OPTIONS" => HttpRequestMethod::Options,
-            "POST" => HttpRequestMethod::Post,
-            _ => {
+        let method = match method_str.parse::<HttpRequestMethod>() {
+            Ok(method) => method,
+            Err(_) => {
                 error!("[ID{}]不支持的HTTP请求方法：{}", id, &method_str);
                 return Err(Exception::UnSupportedRequestMethod);
             }
@@ -86,6 +209,7 @@ impl Request {
         // 解析协议版本
         let version_str = first_line_parts.last().unwrap().to_uppercase();
         let version = match version_str.as_str() {
+            "HTTP/1.0" => HttpVersion::V1_0,
             "HTTP/1.1" => HttpVersion::V1_1,
             _ => {
                 error!("[ID{}]不支持的HTTP协议版本：{}", id, &version_str);
@@ -100,80 +224,383 @@ impl Request {
             first_line_parts[1..first_line_parts.len() - 1].join(" ")
         };
 
-        // 3. 迭代各行解析 Headers
+        // 路径中的 NUL 与其他控制字符可能在文件系统或日志中造成截断与注入，在访问文件系统前直接拒绝
+        if path.chars().any(|c| c.is_ascii_control()) {
+            error!("[ID{}]请求路径包含控制字符：{:?}", id, path);
+            return Err(Exception::InvalidPath);
+        }
+
+        // 3. 收集通用头部表（请求行之后、空行之前的所有行）
+        let mut headers = Vec::new();
+        for line in request_lines.iter().skip(1) {
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                headers.push((name.trim().to_lowercase(), value.trim().to_string()));
+            }
+        }
+
+        let connection = headers
+            .iter()
+            .find(|(name, _)| name == "connection")
+            .map(|(_, value)| value.clone());
+
+        // 消息长度必须无歧义，否则前后端对请求边界的理解可能不一致（请求走私）
+        check_message_length(&headers, id)?;
+
+        // 按 Content-Length 截取请求体，并根据 Content-Encoding 解压
+        let body = decode_body(raw_body, &headers, limits.max_body_bytes(), id)?;
+
+        // 从已拆分的头部表中提取常用字段：字段名已统一为小写，只匹配完整的字段名
         let mut user_agent = "".to_string();
-        let mut accept_encoding = vec![];
+        let mut accept_encoding_values = vec![];
         let mut accept = None;
-        let mut range = None;
-        for line in &request_lines {
-            let line_lower = line.to_lowercase();
-            // 处理 User-Agent
-            if line_lower.starts_with("user-agent") {
-                if let Some(val) = line.split(": ").nth(1) {
-                    user_agent = val.to_string();
-                }
-            } 
-            // 处理 Accept
-            else if line_lower.starts_with("accept:") {
-                if let Some(val) = line.split(": ").nth(1) {
-                    accept = Some(val.to_string());
-                }
-            } 
-            // 处理 Range 请求 (RFC 7233)
-            // 格式示例: Range: bytes=0-1023
-            else if line_lower.starts_with("range:") {
-                if let Some(val) = line.split(": ").nth(1) {
-                    if let Some(bytes_part) = val.strip_prefix("bytes=") {
-                        let parts: Vec<&str> = bytes_part.split('-').collect();
-                        if parts.len() == 2 {
-                            let start = parts[0].parse::<u64>().ok();
-                            let end = if parts[1].is_empty() {
-                                None
-                            } else {
-                                parts[1].parse::<u64>().ok()
-                            };
-                            if let Some(s) = start {
-                                range = Some((s, end));
+        let mut ranges = Vec::new();
+        let mut range_malformed = false;
+        for (name, value) in &headers {
+            match name.as_str() {
+                "user-agent" => user_agent = value.clone(),
+                "accept" => accept = Some(value.clone()),
+                // 处理 Range 请求 (RFC 7233)
+                // 格式示例: Range: bytes=0-1023 或多段 bytes=0-99,200-299
+                "range" => {
+                    if let Some(bytes_part) = value.strip_prefix("bytes=") {
+                        let parsed: Option<Vec<ByteRange>> =
+                            bytes_part.split(',').map(parse_byte_range).collect();
+                        match parsed {
+                            // 分段过多的请求按 RFC 9110 忽略 Range 返回完整内容，避免被用来放大响应
+                            Some(parsed) if parsed.len() > MAX_RANGES => {
+                                debug!("[ID{}]Range分段数{}超过上限{}，忽略", id, parsed.len(), MAX_RANGES);
+                            }
+                            Some(parsed) => ranges = parsed,
+                            None => {
+                                warn!("[ID{}]无法解析的Range请求头: {}", id, value);
+                                range_malformed = true;
                             }
                         }
                     }
                 }
+                "accept-encoding" => accept_encoding_values.push(value.as_str()),
+                _ => {}
             }
         }
 
-        // 4. 解析 Accept-Encoding 标头
-        // 这里的逻辑比较简单，只要包含关键词即视为支持
-        for line in &request_lines {
-            if line.starts_with("accept-encoding") || line.starts_with("Accept-Encoding") {
-                let parts: Vec<&str> = line.split(": ").collect();
-                if parts.len() > 1 {
-                    let encoding = parts[1];
-                    if encoding.contains("gzip") {
-                        accept_encoding.push(HttpEncoding::Gzip);
-                    }
-                    if encoding.contains("deflate") {
-                        accept_encoding.push(HttpEncoding::Deflate);
-                    }
-                    if encoding.contains("br") {
-                        accept_encoding.push(HttpEncoding::Br);
-                    }
-                }
-                break;
-            }
-        }
+        // 多个 Accept-Encoding 头等价于用逗号连接的一个头
+        let accept_encoding_weights = parse_accept_encoding(&accept_encoding_values.join(","));
+        let accept_encoding = rank_encodings(&accept_encoding_weights, &DEFAULT_ENCODING_PRIORITY);
+
+        // 解析 Accept 的媒体范围及权重，忽略格式错误的条目
+        let accept_media = accept
+            .as_deref()
+            .map(|a| a.split(',').filter_map(MediaRange::parse).collect())
+            .unwrap_or_default();
 
         Ok(Self {
+            id,
             method,
             path,
             version,
             user_agent,
             accept_encoding,
+            accept_encoding_weights,
             accept,
-            range,
+            accept_media,
+            ranges,
+            range_malformed,
+            connection,
+            headers,
+            body,
+            peer_addr: None,
+            client_ip: None,
+            remote_user: None,
         })
     }
 }
 
+/// 解析 `Accept-Encoding` 头的值，按出现顺序返回可以使用的编码及其权重。
+///
+/// 值按逗号拆分为编码记号，记号名不区分大小写并且必须完整匹配（`gzip`、`deflate`、`br`），
+/// 除 `q` 外的参数不参与匹配；`q=0` 表示客户端明确拒绝该编码，因此被排除。按 RFC 9110：
+///
+/// - `*` 匹配所有未单独列出的编码，它们以 `*` 的权重按 gzip、deflate、br 的顺序追加在后面；
+/// - `identity;q=0`（或未列出 `identity` 时的 `*;q=0`）禁止发送未压缩的内容，
+///   此时若没有可用的 gzip/deflate，则以最低权重回退到 gzip。
+fn parse_accept_encoding(value: &str) -> Vec<(HttpEncoding, f32)> {
+    let items: Vec<(String, f32)> = value
+        .split(',')
+        .filter_map(|item| {
+            let mut parts = item.split(';');
+            let token = parts.next()?.trim().to_ascii_lowercase();
+            // 无法解析的 q 值按缺省的 1 处理
+            let q = parts
+                .find_map(|param| param.trim().strip_prefix("q=")?.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            (!token.is_empty()).then_some((token, q))
+        })
+        .collect();
+    let encoding_of = |token: &str| token.parse::<HttpEncoding>().ok();
+    let q_of = |token: &str| items.iter().find(|(t, _)| t == token).map(|(_, q)| *q);
+    let contains = |encodings: &[(HttpEncoding, f32)], encoding| encodings.iter().any(|(e, _)| *e == encoding);
+
+    let mut encodings = Vec::new();
+    for (token, q) in &items {
+        match encoding_of(token) {
+            Some(encoding) if *q > 0.0 && !contains(&encodings, encoding) => encodings.push((encoding, *q)),
+            _ => {}
+        }
+    }
+    if let Some(q) = q_of("*").filter(|&q| q > 0.0) {
+        for encoding in [HttpEncoding::Gzip, HttpEncoding::Deflate, HttpEncoding::Br] {
+            let listed = items.iter().any(|(t, _)| encoding_of(t) == Some(encoding));
+            if !listed && !contains(&encodings, encoding) {
+                encodings.push((encoding, q));
+            }
+        }
+    }
+
+    let identity_forbidden = match q_of("identity") {
+        Some(q) => q == 0.0,
+        None => q_of("*") == Some(0.0),
+    };
+    let has_fallback = encodings
+        .iter()
+        .any(|(e, _)| matches!(e, HttpEncoding::Gzip | HttpEncoding::Deflate));
+    if identity_forbidden && !has_fallback {
+        encodings.push((HttpEncoding::Gzip, 0.001));
+    }
+    encodings
+}
+
+/// 按偏好从高到低排列客户端接受的编码：先比较权重，权重相同时按 `priority` 中的先后排列。
+///
+/// 不在 `priority` 中的编码被视为服务器不愿使用，不会出现在结果中。
+fn rank_encodings(weights: &[(HttpEncoding, f32)], priority: &[HttpEncoding]) -> Vec<HttpEncoding> {
+    let rank = |encoding: &HttpEncoding| priority.iter().position(|p| p == encoding);
+    let mut ranked: Vec<(HttpEncoding, f32)> = weights
+        .iter()
+        .copied()
+        .filter(|(e, _)| rank(e).is_some())
+        .collect();
+    ranked.sort_by(|(a, qa), (b, qb)| qb.total_cmp(qa).then_with(|| rank(a).cmp(&rank(b))));
+    ranked.into_iter().map(|(e, _)| e).collect()
+}
+
+/// 单个 `Range` 请求头中允许的最大分段数，超过时忽略 Range 返回完整内容
+const MAX_RANGES: usize = 16;
+
+/// 解析 `bytes=` 之后的单个范围说明，格式错误时返回 `None`。
+///
+/// 支持 `start-end`、`start-` 与后缀形式 `-N`。
+fn parse_byte_range(spec: &str) -> Option<ByteRange> {
+    let (start, end) = spec.trim().split_once('-')?;
+    // 后缀形式 bytes=-N：请求文件末尾的 N 个字节
+    if start.is_empty() {
+        return end.parse::<u64>().ok().map(ByteRange::Suffix);
+    }
+    let start = start.parse::<u64>().ok()?;
+    let end = if end.is_empty() {
+        None
+    } else {
+        Some(end.parse::<u64>().ok()?)
+    };
+    Some(ByteRange::FromTo(start, end))
+}
+
+/// 在原始字节上校验请求目标（请求行中方法与协议版本之间的部分）的长度。
+///
+/// 请求行尚未完整接收时按已有部分计算，确保超长 URI 在转换为字符串之前就被拒绝。
+fn check_uri_length(buffer: &[u8], max_uri_length: usize, id: u128) -> Result<(), Exception> {
+    let line_end = buffer
+        .windows(2)
+        .position(|w| w == b"\r\n")
+        .unwrap_or(buffer.len());
+    let line = &buffer[..line_end];
+    let start = line.iter().position(|&b| b == b' ').map_or(0, |p| p + 1);
+    let end = line
+        .iter()
+        .rposition(|&b| b == b' ')
+        .filter(|&p| p >= start)
+        .unwrap_or(line.len());
+    if end - start > max_uri_length {
+        warn!(
+            "[ID{}]请求URI长度{}超过上限{}",
+            id,
+            end - start,
+            max_uri_length
+        );
+        return Err(Exception::UriTooLong);
+    }
+    Ok(())
+}
+
+/// 按 `Content-Length` 截取请求体，并依照 `Content-Encoding` 透明解压。
+///
+/// - 支持 `gzip`（`x-gzip`）、`deflate`（zlib 封装，兼容裸 deflate 流）与 `br`，
+///   多个编码按声明的逆序依次解码；`identity` 视为未编码。
+/// - `max_body_bytes` 作用于解压后的大小，超出时立即停止解压并返回 `BodyTooLarge`（413），防止压缩炸弹。
+/// - 压缩数据损坏时返回 `MalformedBody`（400），不支持的编码返回 `UnsupportedContentEncoding`（415）。
+fn decode_body(
+    raw: &[u8],
+    headers: &[(String, String)],
+    max_body_bytes: usize,
+    id: u128,
+) -> Result<Vec<u8>, Exception> {
+    let header = |name: &str| headers.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str());
+    let raw = match header("content-length").and_then(|v| v.parse::<usize>().ok()) {
+        Some(len) => &raw[..len.min(raw.len())],
+        None => raw,
+    };
+
+    let mut body = raw.to_vec();
+    let codings = header("content-encoding").unwrap_or("");
+    for coding in codings.rsplit(',').map(|c| c.trim().to_lowercase()) {
+        body = match coding.as_str() {
+            "" | "identity" => body,
+            "gzip" | "x-gzip" => read_limited(GzDecoder::new(body.as_slice()), max_body_bytes)?,
+            "deflate" => match read_limited(ZlibDecoder::new(body.as_slice()), max_body_bytes) {
+                Err(Exception::MalformedBody) => {
+                    read_limited(DeflateDecoder::new(body.as_slice()), max_body_bytes)?
+                }
+                other => other?,
+            },
+            "br" => read_limited(brotli::Decompressor::new(body.as_slice(), 4096), max_body_bytes)?,
+            other => {
+                warn!("[ID{}]不支持的请求体编码：{}", id, other);
+                return Err(Exception::UnsupportedContentEncoding);
+            }
+        };
+    }
+
+    if body.len() > max_body_bytes {
+        warn!("[ID{}]请求体长度{}超过上限{}", id, body.len(), max_body_bytes);
+        return Err(Exception::BodyTooLarge);
+    }
+    Ok(body)
+}
+
+/// 从解码器中读取至多 `limit` 字节，超出上限返回 `BodyTooLarge`，数据损坏返回 `MalformedBody`。
+fn read_limited<R: Read>(reader: R, limit: usize) -> Result<Vec<u8>, Exception> {
+    let mut out = Vec::new();
+    reader
+        .take(limit as u64 + 1)
+        .read_to_end(&mut out)
+        .map_err(|_| Exception::MalformedBody)?;
+    if out.len() > limit {
+        return Err(Exception::BodyTooLarge);
+    }
+    Ok(out)
+}
+
+/// 请求体的分帧方式（RFC 9112 §6.3），决定读取请求头之后还需要从连接中读取多少字节。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BodyFraming {
+    /// 没有请求体。长度有歧义或无法解析时同样不再读取，由 [`Request::try_from_with_limits`] 报告错误。
+    None,
+    /// `Content-Length` 声明的固定长度。
+    Length(usize),
+    /// `Transfer-Encoding: chunked`，读到结束块为止，见 [`chunked_body_len`]。
+    Chunked,
+}
+
+impl BodyFraming {
+    /// 从原始请求头（请求行与各头部字段，可以带结尾的空行）中识别请求体的分帧方式。
+    pub fn from_head(head: &[u8]) -> Self {
+        let head = String::from_utf8_lossy(head);
+        let mut content_length = None;
+        let mut chunked = false;
+        for (name, value) in head.split(CRLF).skip(1).filter_map(|line| line.split_once(':')) {
+            let name = name.trim();
+            if name.eq_ignore_ascii_case("content-length") {
+                for v in value.split(',').map(|v| v.trim().parse::<usize>().ok()) {
+                    // 取值不一致视为无法解析，与 `check_message_length` 的判断保持一致
+                    content_length = match content_length {
+                        Some(existing) if existing != v => Some(None),
+                        _ => Some(v),
+                    };
+                }
+            } else if name.eq_ignore_ascii_case("transfer-encoding") {
+                chunked = value
+                    .rsplit(',')
+                    .next()
+                    .is_some_and(|coding| coding.trim().eq_ignore_ascii_case("chunked"));
+            }
+        }
+        match (content_length, chunked) {
+            (None, true) => BodyFraming::Chunked,
+            (Some(Some(len)), false) => BodyFraming::Length(len),
+            _ => BodyFraming::None,
+        }
+    }
+}
+
+/// 计算分块编码的请求体在 `body` 中占用的字节数（包括结束块与尾部字段之后的空行）。
+///
+/// 数据尚不完整时返回 `Ok(None)`，调用方应继续读取；块大小无法解析或块数据之后缺少 CRLF 时返回 `MalformedChunk`；
+/// 单个块的大小已超过 `max_body_bytes` 时立即返回 `BodyTooLarge`，不必等到数据读完。
+/// 块扩展（`;name=value`）被忽略。
+pub fn chunked_body_len(body: &[u8], max_body_bytes: usize) -> Result<Option<usize>, Exception> {
+    let line_end = |from: usize| {
+        body.get(from..)
+            .and_then(|rest| rest.windows(2).position(|w| w == b"\r\n"))
+            .map(|pos| from + pos)
+    };
+    let mut pos = 0;
+    loop {
+        let Some(end) = line_end(pos) else { return Ok(None) };
+        let size_line = String::from_utf8_lossy(&body[pos..end]);
+        let size_str = size_line.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size_str, 16).map_err(|_| Exception::MalformedChunk)?;
+        if size > max_body_bytes {
+            return Err(Exception::BodyTooLarge);
+        }
+        pos = end + 2;
+        if size == 0 {
+            // 尾部字段逐行跳过，直到空行
+            loop {
+                let Some(end) = line_end(pos) else { return Ok(None) };
+                let empty = end == pos;
+                pos = end + 2;
+                if empty {
+                    return Ok(Some(pos));
+                }
+            }
+        }
+        let data_end = pos.checked_add(size).ok_or(Exception::MalformedChunk)?;
+        let crlf_end = data_end.checked_add(2).ok_or(Exception::MalformedChunk)?;
+        match body.get(data_end..crlf_end) {
+            Some(b"\r\n") => pos = crlf_end,
+            Some(_) => return Err(Exception::MalformedChunk),
+            None => return Ok(None),
+        }
+    }
+}
+
+/// 校验请求的消息长度是否无歧义（RFC 9110 §8.6 / RFC 9112 §6.3）。
+///
+/// - 多个 `Content-Length` 的取值不一致时拒绝；取值相同的重复字段视为一个。
+/// - 同时出现 `Content-Length` 与 `Transfer-Encoding` 时拒绝。
+fn check_message_length(headers: &[(String, String)], id: u128) -> Result<(), Exception> {
+    let mut content_length: Option<&str> = None;
+    for (_, value) in headers.iter().filter(|(name, _)| name == "content-length") {
+        // 单个字段也可能是逗号分隔的列表，例如 `Content-Length: 5, 5`
+        for v in value.split(',').map(str::trim) {
+            match content_length {
+                Some(existing) if existing != v => {
+                    error!("[ID{}]请求包含多个不一致的Content-Length：{} / {}", id, existing, v);
+                    return Err(Exception::AmbiguousLength);
+                }
+                _ => content_length = Some(v),
+            }
+        }
+    }
+    if content_length.is_some() && headers.iter().any(|(name, _)| name == "transfer-encoding") {
+        error!("[ID{}]请求同时包含Content-Length与Transfer-Encoding", id);
+        return Err(Exception::AmbiguousLength);
+    }
+    Ok(())
+}
+
 // --- Getter 访向器实现 ---
 
 impl Request {
@@ -182,11 +609,51 @@ impl Request {
         &self.version
     }
 
+    /// 获取 `Connection` 请求头的值
+    pub fn connection(&self) -> Option<&str> {
+        self.connection.as_deref()
+    }
+
+    /// 客户端是否希望在响应后保持连接。
+    ///
+    /// HTTP/1.1 默认保持连接，除非 `Connection` 头包含 `close`；
+    /// HTTP/1.0 默认关闭连接，除非 `Connection` 头包含 `keep-alive`。
+    pub fn keep_alive(&self) -> bool {
+        let has_token = |token: &str| {
+            self.connection
+                .as_deref()
+                .is_some_and(|v| v.split(',').any(|t| t.trim().eq_ignore_ascii_case(token)))
+        };
+        match self.version {
+            HttpVersion::V1_0 => has_token("keep-alive"),
+            HttpVersion::V1_1 => !has_token("close"),
+        }
+    }
+
     /// 获取请求路径（含查询参数）
     pub fn path(&self) -> &str {
         &self.path
     }
 
+    /// 获取查询字符串（`?` 之后的部分），没有查询参数时返回 `None`
+    pub fn query(&self) -> Option<&str> {
+        self.path.split_once('?').map(|(_, q)| q)
+    }
+
+    /// 按名称获取第一个匹配的查询参数的值（不做百分号解码），没有值的参数返回空字符串
+    pub fn query_param(&self, name: &str) -> Option<&str> {
+        self.query()?
+            .split('&')
+            .map(|pair| pair.split_once('=').unwrap_or((pair, "")))
+            .find(|(n, _)| *n == name)
+            .map(|(_, v)| v)
+    }
+
+    /// 获取请求所属连接的 ID
+    pub fn id(&self) -> u128 {
+        self.id
+    }
+
     /// 获取请求方法
     pub fn method(&self) -> HttpRequestMethod {
         self.method
@@ -197,19 +664,146 @@ impl Request {
         &self.user_agent
     }
 
-    /// 获取客户端支持的压缩算法列表
+    /// 获取客户端支持的压缩算法列表，按偏好从高到低排列
     pub fn accept_encoding(&self) -> &Vec<HttpEncoding> {
         &self.accept_encoding
     }
 
+    /// 按服务器的编码优先级重新排列 [`Request::accept_encoding`]，用于客户端给出相同权重的情形。
+    ///
+    /// 解析时使用 [`DEFAULT_ENCODING_PRIORITY`]；不在 `priority` 中的编码不会被选用。
+    pub fn set_encoding_priority(&mut self, priority: &[HttpEncoding]) {
+        self.accept_encoding = rank_encodings(&self.accept_encoding_weights, priority);
+    }
+
     /// 获取客户端接受的文件 MIME 类型
     pub fn accept(&self) -> Option<&String> {
         self.accept.as_ref()
     }
 
-    /// 获取 Range 请求的分片范围
-    pub fn range(&self) -> Option<(u64, Option<u64>)> {
-        self.range
+    /// 获取解析后的 Accept 媒体范围
+    pub fn accept_media(&self) -> &[MediaRange] {
+        &self.accept_media
+    }
+
+    /// 判断客户端是否接受指定的 MIME 类型（忽略 `;charset=` 等参数）。
+    ///
+    /// 没有 `Accept` 头时接受任何类型；否则取与该类型匹配的最具体的媒体范围，其权重大于 0 即为接受。
+    pub fn accepts(&self, mime: &str) -> bool {
+        if self.accept_media.is_empty() {
+            return true;
+        }
+        let essence = mime.split(';').next().unwrap_or_default().trim().to_lowercase();
+        let (main_type, sub_type) = essence.split_once('/').unwrap_or((essence.as_str(), ""));
+        self.accept_media
+            .iter()
+            .filter_map(|m| m.specificity(main_type, sub_type).map(|s| (s, m.quality)))
+            .max_by_key(|(s, _)| *s)
+            .is_some_and(|(_, q)| q > 0.0)
+    }
+
+    /// 获取 Range 请求的第一个分片范围
+    pub fn range(&self) -> Option<ByteRange> {
+        self.ranges.first().copied()
+    }
+
+    /// 获取 Range 请求的全部分片范围；单段请求只有一项，没有 Range 时为空
+    pub fn ranges(&self) -> &[ByteRange] {
+        &self.ranges
+    }
+
+    /// 获取 If-Range 请求头（ETag 或 HTTP 日期），用于断点续传前确认资源未被修改
+    pub fn if_range(&self) -> Option<&str> {
+        self.header("if-range")
+    }
+
+    /// 获取 If-None-Match 请求头（一个或多个 ETag，或 `*`），用于条件请求
+    pub fn if_none_match(&self) -> Option<&str> {
+        self.header("if-none-match")
+    }
+
+    /// 获取 If-Modified-Since 请求头（HTTP 日期），用于条件请求
+    pub fn if_modified_since(&self) -> Option<&str> {
+        self.header("if-modified-since")
+    }
+
+    /// 是否携带了格式错误的 Range 请求头
+    pub fn range_malformed(&self) -> bool {
+        self.range_malformed
+    }
+
+    /// 获取全部请求头（字段名均为小写）
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+
+    /// 按名称（大小写不敏感）获取第一个匹配的请求头的值
+    pub fn header(&self, name: &str) -> Option<&str> {
+        let name = name.to_lowercase();
+        self.headers
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// 按名称（大小写敏感）获取 `Cookie` 请求头中的值，并解码百分号编码。
+    ///
+    /// 多个 `Cookie` 头会依次查找；值两侧的双引号会被去掉，无法解码的值按原样返回。
+    pub fn cookie(&self, name: &str) -> Option<String> {
+        self.headers
+            .iter()
+            .filter(|(n, _)| n == "cookie")
+            .flat_map(|(_, v)| v.split(';'))
+            .filter_map(|pair| pair.trim().split_once('='))
+            .find(|(n, _)| n.trim() == name)
+            .map(|(_, value)| {
+                let value = value.trim();
+                let value = value
+                    .strip_prefix('"')
+                    .and_then(|v| v.strip_suffix('"'))
+                    .unwrap_or(value);
+                percent_decode(value).unwrap_or_else(|_| value.to_string())
+            })
+    }
+
+    /// 记录连接的对端地址，并据此确定客户端 IP。
+    ///
+    /// `trust_proxy` 为真且请求携带 `X-Forwarded-For` 时，取其中最右侧的合法地址，
+    /// 即离服务器最近的可信代理所看到的客户端；该头缺失或无法解析时退回对端地址。
+    pub fn set_peer_addr(&mut self, peer_addr: Option<SocketAddr>, trust_proxy: bool) {
+        self.peer_addr = peer_addr;
+        let forwarded = match trust_proxy {
+            true => self
+                .header("x-forwarded-for")
+                .and_then(|v| v.rsplit(',').find_map(|ip| ip.trim().parse::<IpAddr>().ok())),
+            false => None,
+        };
+        self.client_ip = forwarded.or(peer_addr.map(|a| a.ip()));
+    }
+
+    /// 获取连接的对端地址
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        self.peer_addr
+    }
+
+    /// 获取客户端 IP，可用于访问日志、限流以及 CGI 的 `REMOTE_ADDR`
+    pub fn client_ip(&self) -> Option<IpAddr> {
+        self.client_ip
+    }
+
+    /// 记录通过鉴权的用户名，供访问日志与后续处理使用
+    pub fn set_remote_user(&mut self, user: Option<String>) {
+        self.remote_user = user;
+    }
+
+    /// 获取通过鉴权的用户名
+    pub fn remote_user(&self) -> Option<&str> {
+        self.remote_user.as_deref()
+    }
+
+    /// 获取请求体。客户端以 `Content-Encoding` 压缩发送时，返回的是解压后的内容
+    pub fn body(&self) -> &[u8] {
+        &self.body
     }
 }
 
@@ -286,6 +880,17 @@ mod tests {
         }
     }
 
+    /// 请求行缺少部分字段（例如 CRLF 把请求行截断）时不应被当作不支持的方法
+    #[test]
+    fn test_malformed_request_line() {
+        for request_str in ["GET /\r\nX-Injected: header HTTP/1.1\r\nHost: localhost\r\n\r\n", "GET\r\n\r\n"] {
+            match Request::try_from(&request_str.as_bytes().to_vec(), 0) {
+                Err(Exception::MalformedRequestLine) => {}
+                other => panic!("Expected MalformedRequestLine error for {:?}, got {:?}", request_str, other.err()),
+            }
+        }
+    }
+
     /// 确保不支持的版本（如 HTTP/2.0）被正确拒绝
     #[test]
     fn test_unsupported_http_version() {
@@ -360,6 +965,130 @@ mod tests {
         let request = Request::try_from(&buffer, 0).unwrap();
 
         assert_eq!(request.path(), "/page?id=123&name=test");
+        assert_eq!(request.query(), Some("id=123&name=test"));
+        assert_eq!(request.query_param("name"), Some("test"));
+        assert_eq!(request.query_param("missing"), None);
+    }
+
+    /// 后缀形式的 Range：bytes=-500 表示最后 500 个字节
+    #[test]
+    fn test_parse_suffix_range() {
+        let request_str = "GET /video.mp4 HTTP/1.1\r\nHost: localhost\r\nRange: bytes=-500\r\n\r\n";
+        let request = Request::try_from(&request_str.as_bytes().to_vec(), 0).unwrap();
+        assert_eq!(request.range(), Some(ByteRange::Suffix(500)));
+
+        let request_str = "GET /video.mp4 HTTP/1.1\r\nHost: localhost\r\nRange: bytes=-0\r\n\r\n";
+        let request = Request::try_from(&request_str.as_bytes().to_vec(), 0).unwrap();
+        assert_eq!(request.range(), Some(ByteRange::Suffix(0)));
+
+        let request_str = "GET /video.mp4 HTTP/1.1\r\nHost: localhost\r\nRange: bytes=100-\r\n\r\n";
+        let request = Request::try_from(&request_str.as_bytes().to_vec(), 0).unwrap();
+        assert_eq!(request.range(), Some(ByteRange::FromTo(100, None)));
+    }
+
+    /// 多段 Range 按顺序解析；分段过多时忽略 Range
+    #[test]
+    fn test_parse_multi_range() {
+        let request_str = "GET /video.mp4 HTTP/1.1\r\nHost: localhost\r\nRange: bytes=0-99, 200-, -50\r\n\r\n";
+        let request = Request::try_from(&request_str.as_bytes().to_vec(), 0).unwrap();
+        assert_eq!(
+            request.ranges(),
+            &[ByteRange::FromTo(0, Some(99)), ByteRange::FromTo(200, None), ByteRange::Suffix(50)]
+        );
+        assert_eq!(request.range(), Some(ByteRange::FromTo(0, Some(99))));
+
+        let spec = (0..=MAX_RANGES).map(|i| format!("{}-{}", i * 10, i * 10 + 1)).collect::<Vec<_>>().join(",");
+        let request_str = format!("GET /video.mp4 HTTP/1.1\r\nHost: localhost\r\nRange: bytes={}\r\n\r\n", spec);
+        let request = Request::try_from(&request_str.into_bytes(), 0).unwrap();
+        assert!(request.ranges().is_empty());
+        assert!(!request.range_malformed());
+    }
+
+    /// If-Range 从通用头部表中读取
+    #[test]
+    fn test_parse_if_range() {
+        let request_str = "GET /video.mp4 HTTP/1.1\r\nHost: localhost\r\nRange: bytes=0-99\r\nIf-Range: \"abc\"\r\n\r\n";
+        let request = Request::try_from(&request_str.as_bytes().to_vec(), 0).unwrap();
+        assert_eq!(request.if_range(), Some("\"abc\""));
+
+        let request_str = "GET /video.mp4 HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        let request = Request::try_from(&request_str.as_bytes().to_vec(), 0).unwrap();
+        assert_eq!(request.if_range(), None);
+    }
+
+    /// 区分“没有 Range”与“Range 格式错误”
+    #[test]
+    fn test_parse_malformed_range() {
+        let request_str = "GET /video.mp4 HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        let request = Request::try_from(&request_str.as_bytes().to_vec(), 0).unwrap();
+        assert_eq!(request.range(), None);
+        assert!(!request.range_malformed());
+
+        for spec in ["bytes=abc-def", "bytes=5", "bytes=-", "bytes=1-x", "bytes=0-9,x"] {
+            let request_str = format!("GET /video.mp4 HTTP/1.1\r\nHost: localhost\r\nRange: {}\r\n\r\n", spec);
+            let request = Request::try_from(&request_str.into_bytes(), 0).unwrap();
+            assert_eq!(request.range(), None, "{}", spec);
+            assert!(request.range_malformed(), "{}", spec);
+        }
+    }
+
+    /// 字节范围结合文件大小换算为闭区间
+    #[test]
+    fn test_byte_range_resolve() {
+        assert_eq!(ByteRange::Suffix(500).resolve(1000), Some((500, 999)));
+        assert_eq!(ByteRange::Suffix(0).resolve(1000), None);
+        assert_eq!(ByteRange::Suffix(5000).resolve(1000), Some((0, 999)));
+        assert_eq!(ByteRange::FromTo(0, Some(99)).resolve(1000), Some((0, 99)));
+        assert_eq!(ByteRange::FromTo(900, Some(5000)).resolve(1000), Some((900, 999)));
+        assert_eq!(ByteRange::FromTo(1000, None).resolve(1000), None);
+        assert_eq!(ByteRange::Suffix(10).resolve(0), None);
+    }
+
+    /// 通用头部表：字段名小写、值去除空白，且不包含请求体中的内容
+    #[test]
+    fn test_header_map() {
+        let request_str = "POST /submit HTTP/1.1\r\nHost: localhost\r\nX-Custom-Header:  value \r\nContent-Length: 9\r\n\r\nfake: body";
+        let buffer = request_str.as_bytes().to_vec();
+
+        let request = Request::try_from(&buffer, 0).unwrap();
+
+        assert_eq!(request.header("x-custom-header"), Some("value"));
+        assert_eq!(request.header("HOST"), Some("localhost"));
+        assert_eq!(request.header("fake"), None);
+        assert_eq!(request.headers().len(), 3);
+    }
+
+    /// 取值不一致的多个 Content-Length 必须被拒绝
+    #[test]
+    fn test_multiple_differing_content_length() {
+        let request_str = "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 10\r\nContent-Length: 5\r\n\r\ntest";
+        let buffer = request_str.as_bytes().to_vec();
+
+        match Request::try_from(&buffer, 0) {
+            Err(Exception::AmbiguousLength) => {}
+            _ => panic!("Expected AmbiguousLength error"),
+        }
+    }
+
+    /// 取值相同的重复 Content-Length 可以视为一个
+    #[test]
+    fn test_multiple_identical_content_length() {
+        let request_str = "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 4\r\nContent-Length: 4\r\n\r\ntest";
+        let buffer = request_str.as_bytes().to_vec();
+
+        assert!(Request::try_from(&buffer, 0).is_ok());
+    }
+
+    /// Content-Length 与 Transfer-Encoding 同时出现必须被拒绝
+    #[test]
+    fn test_content_length_with_transfer_encoding() {
+        let request_str = "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 4\r\nTransfer-Encoding: chunked\r\n\r\ntest";
+        let buffer = request_str.as_bytes().to_vec();
+
+        match Request::try_from(&buffer, 0) {
+            Err(Exception::AmbiguousLength) => {}
+            _ => panic!("Expected AmbiguousLength error"),
+        }
     }
 
     /// 验证请求方法的小写兼容性处理
@@ -372,4 +1101,292 @@ mod tests {
 
         assert_eq!(request.method(), HttpRequestMethod::Get);
     }
+    /// Accept 按权重解析为媒体范围
+    #[test]
+    fn test_parse_accept_media_ranges() {
+        let request_str = "GET / HTTP/1.1\r\nHost: localhost\r\nAccept: text/html, application/*;q=0.5, */*;q=0.1, bogus\r\n\r\n";
+        let request = Request::try_from(&request_str.as_bytes().to_vec(), 0).unwrap();
+        let media = request.accept_media();
+        assert_eq!(media.len(), 3);
+        assert_eq!(media[0].quality(), 1.0);
+        assert_eq!(media[1].quality(), 0.5);
+        assert_eq!(media[2].quality(), 0.1);
+    }
+
+    /// 通配符与最具体匹配的规则
+    #[test]
+    fn test_accepts_wildcards() {
+        let with_accept = |accept: &str| {
+            let request_str = format!("GET / HTTP/1.1\r\nHost: localhost\r\nAccept: {}\r\n\r\n", accept);
+            Request::try_from(&request_str.into_bytes(), 0).unwrap()
+        };
+
+        let request = with_accept("*/*");
+        assert!(request.accepts("text/html;charset=utf-8"));
+        assert!(request.accepts("image/png"));
+
+        let request = with_accept("text/*");
+        assert!(request.accepts("text/css"));
+        assert!(!request.accepts("image/png"));
+
+        let request = with_accept("image/png");
+        assert!(!request.accepts("text/html"));
+
+        // 更具体的范围优先：明确拒绝 HTML，其它类型仍可接受
+        let request = with_accept("text/html;q=0, */*");
+        assert!(!request.accepts("text/html"));
+        assert!(request.accepts("text/plain"));
+
+        // 没有 Accept 头时接受任何类型
+        let request_str = "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        let request = Request::try_from(&request_str.as_bytes().to_vec(), 0).unwrap();
+        assert!(request.accepts("application/octet-stream"));
+    }
+    /// 请求目标超过 max_uri_length 时返回 UriTooLong，恰好等于上限时正常解析
+    #[test]
+    fn test_uri_too_long() {
+        let config = crate::config::Config::from_toml_str(
+            "www_root = \".\"\nport = 7878\nworker_threads = 1\ncache_size = 5\nlocal = true\n[limits]\nmax_uri_length = 16\n",
+        );
+        let raw = |path: &str| format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).into_bytes();
+
+        let ok = Request::try_from_with_limits(&raw(&format!("/{}", "a".repeat(15))), 0, config.limits());
+        assert!(ok.is_ok());
+        match Request::try_from_with_limits(&raw(&format!("/{}", "a".repeat(16))), 0, config.limits()) {
+            Err(Exception::UriTooLong) => {}
+            other => panic!("Expected UriTooLong, got {:?}", other),
+        }
+        // 请求行尚未接收完整时同样按已有部分拒绝
+        let partial = format!("GET /{}", "a".repeat(100)).into_bytes();
+        assert!(matches!(
+            Request::try_from_with_limits(&partial, 0, config.limits()),
+            Err(Exception::UriTooLong)
+        ));
+    }
+    /// 构造携带二进制请求体的 POST 请求
+    fn post_with_body(encoding: &str, body: &[u8]) -> Vec<u8> {
+        let mut raw = format!(
+            "POST /api HTTP/1.1\r\nHost: localhost\r\nContent-Encoding: {}\r\nContent-Length: {}\r\n\r\n",
+            encoding,
+            body.len()
+        )
+        .into_bytes();
+        raw.extend_from_slice(body);
+        raw
+    }
+
+    /// gzip、deflate 与 br 编码的请求体被透明解压
+    #[test]
+    fn test_compressed_body_round_trip() {
+        use flate2::write::{GzEncoder, ZlibEncoder};
+        use flate2::Compression;
+        use std::io::Write;
+
+        let plain = b"{\"name\":\"webserver\",\"items\":[1,2,3]}".repeat(10);
+
+        let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+        gz.write_all(&plain).unwrap();
+        let request = Request::try_from(&post_with_body("gzip", &gz.finish().unwrap()), 0).unwrap();
+        assert_eq!(request.body(), &plain[..]);
+
+        let mut zlib = ZlibEncoder::new(Vec::new(), Compression::default());
+        zlib.write_all(&plain).unwrap();
+        let request = Request::try_from(&post_with_body("deflate", &zlib.finish().unwrap()), 0).unwrap();
+        assert_eq!(request.body(), &plain[..]);
+
+        let mut br = Vec::new();
+        brotli::CompressorWriter::new(&mut br, 4096, 5, 22).write_all(&plain).unwrap();
+        let request = Request::try_from(&post_with_body("br", &br), 0).unwrap();
+        assert_eq!(request.body(), &plain[..]);
+
+        // 未压缩的请求体按 Content-Length 原样保存
+        let request = Request::try_from(&post_with_body("identity", b"plain"), 0).unwrap();
+        assert_eq!(request.body(), b"plain");
+    }
+
+    /// 损坏的压缩数据、不支持的编码以及解压后超限的请求体都被拒绝
+    #[test]
+    fn test_compressed_body_errors() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+        use std::io::Write;
+
+        assert!(matches!(
+            Request::try_from(&post_with_body("gzip", b"not gzip at all"), 0),
+            Err(Exception::MalformedBody)
+        ));
+        assert!(matches!(
+            Request::try_from(&post_with_body("compress", b"data"), 0),
+            Err(Exception::UnsupportedContentEncoding)
+        ));
+
+        // 压缩炸弹：很小的压缩数据解压后超过 max_body_bytes
+        let config = crate::config::Config::from_toml_str(
+            "www_root = \".\"\nport = 7878\nworker_threads = 1\ncache_size = 5\nlocal = true\n[limits]\nmax_body_bytes = 1024\n",
+        );
+        let mut gz = GzEncoder::new(Vec::new(), Compression::best());
+        gz.write_all(&vec![0u8; 1024 * 1024]).unwrap();
+        let bomb = gz.finish().unwrap();
+        assert!(bomb.len() < 1024 * 1024);
+        assert!(matches!(
+            Request::try_from_with_limits(&post_with_body("gzip", &bomb), 0, config.limits()),
+            Err(Exception::BodyTooLarge)
+        ));
+    }
+    /// 客户端 IP 默认取对端地址；信任代理时取 X-Forwarded-For 最右侧的合法地址
+    #[test]
+    fn test_client_ip_from_peer_and_forwarded_for() {
+        let raw = "GET / HTTP/1.1\r\nHost: localhost\r\nX-Forwarded-For: 203.0.113.7, 198.51.100.2, bogus\r\n\r\n";
+        let peer: SocketAddr = "10.0.0.1:54321".parse().unwrap();
+
+        let mut request = Request::try_from(&raw.as_bytes().to_vec(), 0).unwrap();
+        assert_eq!(request.client_ip(), None);
+
+        request.set_peer_addr(Some(peer), false);
+        assert_eq!(request.peer_addr(), Some(peer));
+        assert_eq!(request.client_ip(), Some(peer.ip()));
+
+        request.set_peer_addr(Some(peer), true);
+        assert_eq!(request.client_ip(), Some("198.51.100.2".parse().unwrap()));
+
+        // 没有 X-Forwarded-For 时即使信任代理也退回对端地址；Unix 套接字没有地址
+        let mut request = Request::try_from(&b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n".to_vec(), 0).unwrap();
+        request.set_peer_addr(Some(peer), true);
+        assert_eq!(request.client_ip(), Some(peer.ip()));
+        request.set_peer_addr(None, false);
+        assert_eq!(request.client_ip(), None);
+    }
+    /// HTTP/1.0 请求可以被解析，并且默认不保持连接
+    #[test]
+    fn test_http10_request_and_keep_alive() {
+        let parse = |raw: &str| Request::try_from(&raw.as_bytes().to_vec(), 0).unwrap();
+
+        let request = parse("GET /index.html HTTP/1.0\r\n\r\n");
+        assert_eq!(*request.version(), HttpVersion::V1_0);
+        assert_eq!(request.version().to_string(), "1.0");
+        assert!(!request.keep_alive());
+        assert!(parse("GET / HTTP/1.0\r\nConnection: Keep-Alive\r\n\r\n").keep_alive());
+
+        assert!(parse("GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").keep_alive());
+        let request = parse("GET / HTTP/1.1\r\nHost: localhost\r\nConnection: upgrade, close\r\n\r\n");
+        assert_eq!(request.connection(), Some("upgrade, close"));
+        assert!(!request.keep_alive());
+    }
+    /// Cookie 解析：多个 Cookie 头、带引号的值与无法解码的值
+    #[test]
+    fn test_cookie_parsing() {
+        let raw = "GET / HTTP/1.1\r\nHost: localhost\r\nCookie: a=1; b=\"quoted%20value\"\r\nCookie: c=100%\r\n\r\n";
+        let request = Request::try_from(&raw.as_bytes().to_vec(), 0).unwrap();
+        assert_eq!(request.cookie("a").as_deref(), Some("1"));
+        assert_eq!(request.cookie("b").as_deref(), Some("quoted value"));
+        assert_eq!(request.cookie("c").as_deref(), Some("100%"));
+        assert_eq!(request.cookie("d"), None);
+    }
+    /// 编码只从 Accept-Encoding 头中按完整记号匹配，其它头中出现的 gzip/br 不会被误认
+    #[test]
+    fn test_accept_encoding_tokens_only_from_header() {
+        let parse = |headers: &str| {
+            let raw = format!("GET / HTTP/1.1\r\nHost: localhost\r\n{}\r\n", headers);
+            Request::try_from(&raw.into_bytes(), 0).unwrap()
+        };
+
+        let request = parse("Referer: http://gzip.example.br/deflate\r\nUser-Agent: brotli-deflate-bot\r\n");
+        assert!(request.accept_encoding().is_empty());
+        assert_eq!(request.user_agent(), "brotli-deflate-bot");
+
+        let request = parse("accept-encoding: GZIP, brotli, x-deflate\r\n");
+        assert_eq!(request.accept_encoding(), &vec![HttpEncoding::Gzip]);
+
+        // q=0 表示拒绝；多个 Accept-Encoding 头合并，重复的编码只保留一次
+        let request = parse("Accept-Encoding: br;q=1.0, gzip;q=0, deflate;q=0.5\r\nAccept-Encoding: br\r\n");
+        assert_eq!(request.accept_encoding(), &vec![HttpEncoding::Br, HttpEncoding::Deflate]);
+    }
+    /// `*` 接受所有未列出的编码，identity;q=0 禁止未压缩内容时回退到 gzip
+    #[test]
+    fn test_accept_encoding_wildcard_and_identity() {
+        use HttpEncoding::*;
+        let ranked = |value: &str| rank_encodings(&parse_accept_encoding(value), &DEFAULT_ENCODING_PRIORITY);
+        assert_eq!(parse_accept_encoding("br, *;q=0.5"), vec![(Br, 1.0), (Gzip, 0.5), (Deflate, 0.5)]);
+        assert_eq!(ranked("*"), vec![Br, Gzip, Deflate]);
+        assert_eq!(ranked("br, *;q=0.5"), vec![Br, Gzip, Deflate]);
+        assert_eq!(ranked("gzip;q=0, *"), vec![Br, Deflate]);
+        assert_eq!(ranked("gzip, *;q=0"), vec![Gzip]);
+        assert_eq!(ranked("identity;q=0"), vec![Gzip]);
+        assert_eq!(ranked("br, identity;q=0"), vec![Br, Gzip]);
+        assert_eq!(ranked("deflate, identity;q=0"), vec![Deflate]);
+        assert_eq!(ranked("br, *;q=0"), vec![Br, Gzip]);
+        // 显式允许 identity 时，*;q=0 不影响未压缩内容
+        assert_eq!(ranked("identity, *;q=0"), vec![]);
+        assert_eq!(ranked("identity"), vec![]);
+    }
+    /// 按 q 值从高到低排列，权重相同时按服务器优先级排列，不在优先级中的编码不会被选用
+    #[test]
+    fn test_accept_encoding_q_values_and_priority() {
+        use HttpEncoding::*;
+        let raw = "GET / HTTP/1.1\r\nHost: localhost\r\nAccept-Encoding: deflate, gzip;q=0.8, br;q=0.8\r\n\r\n";
+        let mut request = Request::try_from(&raw.as_bytes().to_vec(), 0).unwrap();
+        assert_eq!(request.accept_encoding(), &vec![Deflate, Br, Gzip]);
+
+        request.set_encoding_priority(&[Gzip, Deflate, Br]);
+        assert_eq!(request.accept_encoding(), &vec![Deflate, Gzip, Br]);
+        request.set_encoding_priority(&[Gzip]);
+        assert_eq!(request.accept_encoding(), &vec![Gzip]);
+
+        let ranked = |value: &str| rank_encodings(&parse_accept_encoding(value), &DEFAULT_ENCODING_PRIORITY);
+        assert_eq!(ranked("gzip, deflate, br"), vec![Br, Gzip, Deflate]);
+        assert_eq!(ranked("gzip;q=1.0, br;q=0.9"), vec![Gzip, Br]);
+        // 无法解析的 q 值按 1 处理
+        assert_eq!(ranked("deflate;q=abc, br;q=0.5"), vec![Deflate, Br]);
+    }
+    /// 请求路径中的 NUL 与其他控制字符在解析阶段即被拒绝
+    #[test]
+    fn test_control_characters_in_path_rejected() {
+        for path in ["/index.html\0.jpg", "/a\x01b", "/log\x1b[31m", "/a\x7f"] {
+            let raw = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path);
+            match Request::try_from(&raw.into_bytes(), 0) {
+                Err(Exception::InvalidPath) => {}
+                other => panic!("{:?}: expected InvalidPath, got {:?}", path, other.map(|r| r.path().to_string())),
+            }
+        }
+    }
+    #[test]
+    fn test_body_framing_from_head() {
+        let framing = |head: &str| BodyFraming::from_head(head.as_bytes());
+        assert_eq!(framing("GET / HTTP/1.1\r\nHost: a\r\n\r\n"), BodyFraming::None);
+        assert_eq!(framing("POST / HTTP/1.1\r\ncontent-LENGTH: 12\r\n\r\n"), BodyFraming::Length(12));
+        assert_eq!(framing("POST / HTTP/1.1\r\nContent-Length: 5, 5\r\n\r\n"), BodyFraming::Length(5));
+        assert_eq!(framing("POST / HTTP/1.1\r\nContent-Length: 10\r\nContent-Length: 5\r\n\r\n"), BodyFraming::None);
+        assert_eq!(framing("POST / HTTP/1.1\r\nContent-Length: 5, 6\r\n\r\n"), BodyFraming::None);
+        assert_eq!(framing("POST / HTTP/1.1\r\nTransfer-Encoding: gzip, chunked\r\n\r\n"), BodyFraming::Chunked);
+        // 无法解析或有歧义时不读取请求体，交给解析器报错
+        assert_eq!(framing("POST / HTTP/1.1\r\nContent-Length: abc\r\n\r\n"), BodyFraming::None);
+        assert_eq!(
+            framing("POST / HTTP/1.1\r\nContent-Length: 4\r\nTransfer-Encoding: chunked\r\n\r\n"),
+            BodyFraming::None
+        );
+    }
+
+    #[test]
+    fn test_chunked_body_len() {
+        let body = b"4\r\nWiki\r\n5;ext=1\r\npedia\r\n0\r\n\r\nNEXT";
+        assert_eq!(chunked_body_len(body, usize::MAX).unwrap(), Some(body.len() - 4));
+        // 尾部字段之后的空行才是结束
+        let trailers = b"1\r\na\r\n0\r\nX-Trailer: 1\r\n\r\n";
+        assert_eq!(chunked_body_len(trailers, usize::MAX).unwrap(), Some(trailers.len()));
+
+        // 任意位置截断都只是“尚不完整”
+        for cut in 0..body.len() - 4 {
+            assert_eq!(chunked_body_len(&body[..cut], usize::MAX).unwrap(), None, "cut at {}", cut);
+        }
+
+        assert!(matches!(chunked_body_len(b"zz\r\n", usize::MAX), Err(Exception::MalformedChunk)));
+        assert!(matches!(chunked_body_len(b"2\r\nabc\r\n", usize::MAX), Err(Exception::MalformedChunk)));
+
+        // 块大小加上 CRLF 会溢出 usize，不能因此 panic
+        assert!(matches!(chunked_body_len(b"ffffffffffffffed\r\n", usize::MAX), Err(Exception::MalformedChunk)));
+        // 超过上限的块在解析到大小行时就被拒绝
+        assert!(matches!(chunked_body_len(b"ffffffffffffffed\r\n", 1024), Err(Exception::BodyTooLarge)));
+        assert!(matches!(chunked_body_len(b"401\r\n", 1024), Err(Exception::BodyTooLarge)));
+        assert_eq!(chunked_body_len(b"400\r\n", 1024).unwrap(), None);
+    }
 }
@@ -9,28 +9,48 @@
 
 use crate::{
     cache::FileCache,
+    clock::{Clock, SystemClock},
     config::Config,
+    exception::Exception,
+    fastcgi,
     param::*,
     request::Request,
-    util::{format_file_size, handle_php, HtmlBuilder},
+    util::{
+        content_disposition_attachment, format_file_size, handle_php, is_ignored, parse_cgi_output, percent_encode_path_segment, sanitize_header_value,
+        sort_dir_entries, DirSummary,
+        HtmlBuilder, ListingSort, Pagination,
+    },
 };
 
 use brotli::enc::{self, backward_references::BrotliEncoderParams};
 use bytes::Bytes;
 use chrono::prelude::*;
 use flate2::{
-    write::{DeflateEncoder, GzEncoder},
+    write::{GzEncoder, ZlibEncoder},
     Compression,
 };
 use log::{debug, error, warn};
 
+use tokio::{
+    fs::File as TokioFile,
+    io::{AsyncReadExt, AsyncSeekExt, AsyncWrite, AsyncWriteExt},
+    sync::{mpsc, RwLock},
+};
+use xxhash_rust::xxh3::Xxh3;
+
 use std::{
+    borrow::Cow,
     ffi::OsStr,
-    fs::{self, metadata, File},
-    io::{self, Read, Seek, SeekFrom, Write},
+    fs::{self, metadata, Metadata},
+    hash::{DefaultHasher, Hasher},
+    io::{self, SeekFrom, Write},
     path::{Path, PathBuf},
     str,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
 };
 
 /// 表示一个 HTTP 响应结构体。
@@ -52,8 +72,8 @@ pub struct Response {
     date: DateTime<Utc>,
     /// Content-Encoding 响应头，表示使用的压缩算法
     content_encoding: Option<HttpEncoding>,
-    /// Server 响应头，表示服务器名称
-    server_name: String,
+    /// Server 响应头，表示服务器名称；为 `None` 时不发送
+    server_name: Option<String>,
     /// Allow 响应头，用于 OPTIONS 请求或 405 错误
     allow: Option<Vec<HttpRequestMethod>>,
     /// 响应体内容（二进制数据）
@@ -62,6 +82,24 @@ pub struct Response {
     content_range: Option<String>,
     /// Accept-Ranges 响应头，告知客户端服务器支持范围请求
     accept_ranges: Option<String>,
+    /// ETag 响应头（弱校验器），见 [`validators`]
+    etag: Option<String>,
+    /// Last-Modified 响应头，见 [`validators`]
+    last_modified: Option<DateTime<Utc>>,
+    /// 附加的自定义响应头，按添加顺序输出在其它响应头之后
+    extra_headers: Vec<(String, String)>,
+    /// 发送响应后是否保持连接，决定 `Connection` 响应头取 `keep-alive` 还是 `close`
+    keep_alive: bool,
+    /// 待设置的 Cookie，每一项是一个完整的 `Set-Cookie` 头部值，见 [`Response::add_cookie`]
+    cookies: Vec<String>,
+    /// Content-Disposition 响应头，用于强制下载，见 [`content_disposition_attachment`]
+    content_disposition: Option<String>,
+    /// 流式响应体的来源；为 `Some` 时响应体在写出响应头之后从文件中分块发送
+    stream_source: Option<StreamSource>,
+    /// 长度未知的响应体；为 `Some` 时以 `Transfer-Encoding: chunked` 发送，见 [`Response::set_chunked_body`]
+    chunked_body: Option<ChunkedBody>,
+    /// 分块响应体是否改为原样发送、以关闭连接表示结束，见 [`Response::set_close_delimited`]
+    close_delimited: bool,
 }
 
 impl Response {
@@ -77,15 +115,24 @@ impl Response {
             content_length: 0,
             date: Utc::now(),
             content_encoding: None,
-            server_name: SERVER_NAME.to_string(),
+            server_name: Some(SERVER_NAME.to_string()),
             allow: Some(ALLOWED_METHODS.to_vec()),
             content: None,
             content_range: None,
             accept_ranges: None,
+            etag: None,
+            last_modified: None,
+            extra_headers: Vec::new(),
+            keep_alive: false,
+            cookies: Vec::new(),
+            content_disposition: None,
+            stream_source: None,
+            chunked_body: None,
+            close_delimited: false,
         }
     }
 
-    /// 从文件系统构建响应。
+    /// 从文件系统异步构建响应。
     ///
     /// 此方法是处理静态文件请求的核心逻辑，包含以下功能：
     /// 1. 获取文件元数据。
@@ -94,6 +141,16 @@ impl Response {
     /// 4. 协商内容压缩（Gzip/Br/Deflate）。
     /// 5. 与内存缓存（FileCache）交互，处理缓存命中与更新。
     ///
+    /// 文件 I/O 全部通过 `tokio::fs` 完成，CPU 密集的压缩在 `spawn_blocking` 中执行，
+    /// 因此多个大文件请求不会互相阻塞运行时的工作线程。缓存锁只在查找和写入时短暂持有，
+    /// 不会跨越任何 `.await`。
+    ///
+    /// # Errors
+    ///
+    /// 文件在获取元数据或读取时已不存在返回 [`Exception::FileNotFound`]，其余 I/O 错误返回
+    /// [`Exception::FileUnreadable`]，由调用方转换为 404 / 500 响应。流式与 Range 响应的内容在发送阶段才读取，
+    /// 因此会先确认文件可以打开，避免响应头发出后才发现文件无法读取。
+    ///
     /// # 参数
     ///
     /// * `path` - 请求的文件路径。
@@ -103,28 +160,26 @@ impl Response {
     /// * `headonly` - 是否仅处理 HEAD 请求（不返回 body）。
     /// * `mime` - 文件的 MIME 类型。
     /// * `config` - 服务器配置。
-    fn from_file(
+    async fn from_file_async(
         path: &str,
         request: &Request,
         id: u128,
-        cache: &Arc<Mutex<FileCache>>,
+        cache: &Arc<RwLock<FileCache>>,
         headonly: bool,
         mime: &str,
         config: &Config,
-    ) -> Self {
+    ) -> Result<Self, Exception> {
         let accept_encoding = request.accept_encoding().to_vec();
         let mut response = Self::new();
         // 静态文件通常不需要 Allow 头，除非特定策略
         response.allow = None;
 
-        let file_path = Path::new(path);
-        
         // 1. 获取文件元数据
-        let file_metadata = match metadata(file_path) {
+        let file_metadata = match tokio::fs::metadata(path).await {
             Ok(meta) => meta,
             Err(e) => {
                 error!("[ID{}]无法获取文件{}的元数据: {}", id, path, e);
-                panic!(); // 注意：此处 panic 会导致当前线程崩溃，生产环境建议返回 500 错误
+                return Err(file_error(&e));
             }
         };
         let file_size = file_metadata.len();
@@ -132,16 +187,53 @@ impl Response {
             Ok(time) => time,
             Err(e) => {
                 error!("[ID{}]无法获取文件{}的修改时间: {}", id, path, e);
-                panic!();
+                return Err(file_error(&e));
+            }
+        };
+        let (mut etag, last_modified) = validators(&file_metadata, None);
+        if config.strong_etag() {
+            match strong_etag(path, &file_metadata, config.chunk_size(), cache).await {
+                Ok(strong) => etag = strong,
+                Err(e) => warn!("[ID{}]计算文件{}的强ETag失败，退回弱ETag: {}", id, path, e),
             }
+        }
+        response.etag = Some(etag.clone());
+        response.last_modified = last_modified;
+
+        // 条件请求：校验器只依赖已获取的元数据，命中时直接返回 304，
+        // 不获取缓存锁、不打开文件（开启 strong_etag 时仅需查询一次已缓存的哈希）。
+        // 强 ETag 因编码而异，按本次请求将会协商出的编码比较
+        let negotiated = match headonly || should_skip_compression(mime) || file_size > config.streaming_threshold() {
+            true => None,
+            false => decide_encoding(&accept_encoding),
         };
+        let negotiated_etag = representation_etag(&etag, negotiated);
+        if is_not_modified(request, Some(&negotiated_etag), response.last_modified) {
+            debug!("[ID{}]资源未修改，返回304", id);
+            response.etag = Some(negotiated_etag);
+            response.set_code(304);
+            response.content_length = 0;
+            return Ok(response);
+        }
 
-        // 告知客户端支持 Range 请求
-        if config.enable_range_requests() {
+        // 告知客户端支持 Range 请求。该头在完整响应与 206 分片响应中都会发送；
+        // 关闭 Range 支持时忽略请求中的 Range 头，始终返回完整内容
+        let ranges_enabled = config.enable_range_requests();
+        if ranges_enabled {
             response.accept_ranges = Some("bytes".to_string());
         }
 
-        let range_request = request.range();
+        let mut range_request = request.range().filter(|_| ranges_enabled);
+
+        // If-Range：资源已变化时忽略 Range，返回完整内容，避免客户端拼接出损坏的文件
+        if range_request.is_some() {
+            if let Some(if_range) = request.if_range() {
+                if !if_range_matches(if_range, response.etag.as_deref(), response.last_modified) {
+                    debug!("[ID{}]If-Range不匹配({})，忽略Range请求", id, if_range);
+                    range_request = None;
+                }
+            }
+        }
         
         // 判断是否触发流式传输逻辑：
         // 1. 文件大小超过阈值
@@ -153,67 +245,61 @@ impl Response {
             id, file_size, config.streaming_threshold(), use_streaming, range_request
         );
 
-        // 获取缓存锁，如果锁中毒则恢复
-        let mut cache_lock = match cache.lock() {
-            Ok(lock) => lock,
-            Err(poisoned) => {
-                warn!("[ID{}]缓存锁被污染，恢复并继续", id);
-                poisoned.into_inner()
+        // Range 头存在但无法解析：返回 416，避免静默返回完整内容掩盖客户端错误
+        if ranges_enabled && request.range_malformed() {
+            warn!("[ID{}]Range请求头格式错误，返回416", id);
+            response.set_range_not_satisfiable(file_size);
+            return Ok(response);
+        }
+
+        // 分片与流式内容在发送阶段才读取文件，届时响应头已经发出，无法再改为错误响应，因此先确认文件可以打开
+        if use_streaming && !headonly {
+            if let Err(e) = TokioFile::open(path).await {
+                error!("[ID{}]无法打开文件{}: {}", id, path, e);
+                return Err(file_error(&e));
             }
-        };
-        
+        }
+
         // 2. 处理 Range 请求 (HTTP 206 Partial Content)
-        if let Some((start, end)) = range_request {
-            let end = end.unwrap_or(file_size - 1);
-            
-            // 验证 Range 有效性
-            if start >= file_size || end >= file_size || start > end {
-                error!("[ID{}]无效的Range请求: start={}, end={}, file_size={}", id, start, end, file_size);
-                response.set_code(416); // Range Not Satisfiable
-                response.content_range = Some(format!("bytes */{}", file_size));
-                response.content_length = 0;
-                return response;
+        // 注意：206 响应必须在压缩协商之前返回。压缩后的长度与 Content-Range 描述的字节区间不再一致，
+        // 因此分片内容永远以原始字节发送，不设置 Content-Encoding。
+        if range_request.is_some() {
+            // 验证各个 Range 的有效性并换算为闭区间（后缀形式 bytes=-N 从文件末尾倒数），
+            // 多段请求中无法满足的分段被丢弃，全部无法满足时返回 416；重叠或相邻的区间合并为一段
+            let spans = merge_spans(
+                request
+                    .ranges()
+                    .iter()
+                    .filter_map(|byte_range| byte_range.resolve(file_size))
+                    .collect(),
+            );
+            if spans.is_empty() {
+                error!("[ID{}]无效的Range请求: {:?}, file_size={}", id, request.ranges(), file_size);
+                response.set_range_not_satisfiable(file_size);
+                return Ok(response);
             }
-            
-            let content_length = end - start + 1;
-            debug!("[ID{}]处理Range请求: bytes {}-{}/{} ({}字节)", 
-                   id, start, end, file_size, content_length);
-            
             response.set_code(206);
-            response.content_range = Some(format!("bytes {}-{}/{}", start, end, file_size));
             response.content_type = Some(mime.to_string());
-            response.content_length = content_length;
-            
-            // 对于 HEAD 请求，不读取实际内容
+
+            // 分片内容不读入内存：响应体由发送阶段按分段定位文件并分块复制
+            let segments = if let [(start, end)] = spans[..] {
+                debug!("[ID{}]处理Range请求: bytes {}-{}/{} ({}字节)",
+                       id, start, end, file_size, end - start + 1);
+                response.content_range = Some(format!("bytes {}-{}/{}", start, end, file_size));
+                vec![StreamSegment { prefix: Bytes::new(), range: Some((start, end)) }]
+            } else {
+                let boundary = multipart_boundary(id, response.etag.as_deref().unwrap_or_default());
+                debug!("[ID{}]处理多段Range请求: {:?}, boundary={}", id, spans, boundary);
+                response.content_type = Some(format!("multipart/byteranges; boundary={}", boundary));
+                multipart_segments(&spans, &boundary, mime, file_size)
+            };
+            response.content_length = segments.iter().map(StreamSegment::len).sum();
+
+            // 对于 HEAD 请求，只发送响应头
             if !headonly {
-                let mut file = match File::open(path) {
-                    Ok(f) => f,
-                    Err(e) => {
-                        error!("[ID{}]无法打开文件{}: {}", id, path, e);
-                        panic!();
-                    }
-                };
-                
-                // 定位并读取指定范围
-                if let Err(e) = file.seek(SeekFrom::Start(start)) {
-                    error!("[ID{}]无法定位到文件位置{}: {}", id, start, e);
-                    panic!();
-                }
-                
-                let mut buffer = vec![0u8; content_length as usize];
-                match file.read_exact(&mut buffer) {
-                    Ok(_) => {
-                        response.content = Some(Bytes::from(buffer));
-                        debug!("[ID{}]Range内容读取成功", id);
-                    }
-                    Err(e) => {
-                        error!("[ID{}]读取Range内容失败: {}", id, e);
-                        panic!();
-                    }
-                }
+                response.stream_source = Some(StreamSource { path: path.to_string(), segments });
             }
-            
-            return response;
+            return Ok(response);
         }
         
         // 3. 处理流式传输模式（非 Range 的大文件）
@@ -224,8 +310,9 @@ impl Response {
             response.content_type = Some(mime.to_string());
             response.content_length = file_size;
             response.content = None; // content 为 None 触发流式发送逻辑
+            response.stream_source = Some(StreamSource { path: path.to_string(), segments: Vec::new() });
 
-            return response;
+            return Ok(response);
         }
         
         // 4. 压缩协商
@@ -256,8 +343,11 @@ impl Response {
             None => debug!("[ID{}]不进行压缩", id),
         };
         
-        // 5. 缓存查找与处理
-        match cache_lock.find(path, file_modified_time) {
+        let levels = CompressionLevels::from_config(config);
+
+        // 5. 缓存查找与处理（仅在查找期间持有共享读锁，命中时不会阻塞其他请求）
+        let cached = cache.read().await.find(path, file_modified_time).cloned();
+        match cached {
             Some(bytes) => {
                 // --- 缓存命中 ---
                 debug!("[ID{}]缓存命中，原始大小: {} bytes", id, bytes.len());
@@ -272,7 +362,7 @@ impl Response {
                         "[ID{}]对缓存内容进行压缩，编码方式: {:?}",
                         id, response.content_encoding
                     );
-                    contents = match compress(contents, response.content_encoding) {
+                    contents = match compress_async(contents, response.content_encoding, levels).await {
                         Ok(c) => c,
                         Err(e) => {
                             error!("[ID{}]压缩缓存内容失败: {}，返回未压缩内容", id, e);
@@ -302,47 +392,32 @@ impl Response {
                 // --- 缓存未命中 ---
                 debug!("[ID{}]缓存未命中或文件已修改", id);
                 if headonly {
-                    let path = Path::new(path);
-                    let metadata = metadata(path).unwrap();
                     let content_type_str = mime.to_string();
                     debug!("[ID{}]Content-Type: {}", id, &content_type_str);
                     response.content_type = Some(content_type_str);
                     response.content = None;
-                    response.content_length = metadata.len();
+                    response.content_length = file_size;
                 } else {
                     debug!("[ID{}]读取文件: {}", id, path);
-                    let mut file = match File::open(path) {
-                        Ok(f) => f,
-                        Err(e) => {
-                            error!("[ID{}]无法打开路径{}指定的文件。错误：{}", id, path, e);
-                            panic!();
-                        }
-                    };
-                    let mut contents = Vec::new();
-                    match file.read_to_end(&mut contents) {
-                        Ok(_) => {}
+                    let original_contents = match tokio::fs::read(path).await {
+                        Ok(contents) => Bytes::from(contents),
                         Err(e) => {
                             error!("[ID{}]无法读取文件{}。错误：{}", id, path, e);
-                            panic!();
+                            return Err(file_error(&e));
                         }
-                    }
-                    let original_size = contents.len();
+                    };
                     
                     // 压缩文件内容
                     debug!(
                         "[ID{}]开始压缩文件，原始大小: {} bytes, 编码方式: {:?}",
-                        id, original_size, response.content_encoding
+                        id, original_contents.len(), response.content_encoding
                     );
-                    contents = match compress(contents, response.content_encoding) {
+                    let contents = match compress_async(original_contents.to_vec(), response.content_encoding, levels).await {
                         Ok(c) => c,
                         Err(e) => {
                             error!("[ID{}]压缩文件{}失败: {}，返回未压缩内容", id, path, e);
                             response.content_encoding = None;
-                            // 压缩失败回退到读取原始文件（虽然上面 contents 已被所有权转移，这里重新读）
-                            let mut file = File::open(path).unwrap();
-                            let mut buf = Vec::new();
-                            file.read_to_end(&mut buf).unwrap();
-                            buf
+                            original_contents.to_vec()
                         }
                     };
 
@@ -353,22 +428,11 @@ impl Response {
                     debug!("[ID{}]Content-Type: {}", id, &content_type_str);
                     response.content_type = Some(content_type_str);
 
-                    response.content = Some(Bytes::from(contents.clone()));
-                    
-                    // 为了存入缓存，需要原始的未压缩数据
-                    let original_contents = match response.content_encoding {
-                        Some(_) => {
-                            let mut file = File::open(path).unwrap();
-                            let mut buf = Vec::new();
-                            file.read_to_end(&mut buf).unwrap();
-                            buf
-                        }
-                        None => contents,
-                    };
+                    response.content = Some(Bytes::from(contents));
                     
-                    // 判断文件大小是否适合放入缓存
+                    // 判断文件大小是否适合放入缓存（缓存中保存未压缩的原始数据）
                     if FileCache::should_cache(file_size, config.streaming_threshold()) {
-                        cache_lock.push(path, Bytes::from(original_contents), file_modified_time);
+                        cache.write().await.push(path, original_contents, file_modified_time);
                         debug!("[ID{}]文件已加入缓存", id);
                     } else {
                         debug!("[ID{}]文件过大({} bytes)，跳过缓存", id, file_size);
@@ -376,7 +440,8 @@ impl Response {
                 }
             }
         }
-        response
+        response.etag = Some(representation_etag(&etag, response.content_encoding));
+        Ok(response)
     }
 
     /// 根据 HTTP 状态码创建响应。
@@ -406,11 +471,23 @@ impl Response {
         
         // 构建默认的错误页面 HTML
         let content = match code {
+            401 => HtmlBuilder::from_status_code(401, Some(
+                r"<h2>噢！</h2><p>访问该页面需要登录。</p>"
+            )),
+            403 => HtmlBuilder::from_status_code(403, Some(
+                r"<h2>噢！</h2><p>你没有权限访问该页面。</p>"
+            )),
             404 => HtmlBuilder::from_status_code(404, Some(
                 r"<h2>噢！</h2><p>你指定的网页无法找到。</p>"
             )),
             405 => HtmlBuilder::from_status_code(405, Some(
-                r"<h2>噢！</h2><p>你的浏览器发出了一个非GET方法的HTTP请求。本服务器目前仅支持GET方法。</p>"
+                r"<h2>噢！</h2><p>该资源不支持你的浏览器发出的请求方法。</p>"
+            )),
+            406 => HtmlBuilder::from_status_code(406, Some(
+                r"<h2>噢！</h2><p>服务器无法以你的浏览器可以接受的格式提供该内容。</p>"
+            )),
+            429 => HtmlBuilder::from_status_code(429, Some(
+                r"<h2>噢！</h2><p>你的请求过于频繁，请稍后再试。</p>"
             )),
             500 => HtmlBuilder::from_status_code(500, Some(
                 r"<h2>噢！</h2><p>服务器出现了一个内部错误。</p>"
@@ -418,30 +495,103 @@ impl Response {
             _ => HtmlBuilder::from_status_code(code, None),
         }.build();
         
-        let content_compressed = compress(content.into_bytes(), response.content_encoding).unwrap();
-        let bytes = Bytes::from(content_compressed);
-        response.content_length = bytes.len() as u64;
-        response.content = Some(bytes);
-        response.content_type = Some("text/html;charset=utf-8".to_string());
+        response.set_html_content(content);
         response.set_code(code);
         response
     }
 
+    /// 以自定义错误页面文件的内容替换内置的错误页面，状态码与其它响应头（如 `Allow`）保持不变。
+    ///
+    /// 页面内容以文件路径为键存入文件缓存，并以修改时间校验，文件未修改时不再读取磁盘。
+    /// 按请求协商压缩；HEAD 请求只给出 `Content-Length`，不携带响应体。
+    pub async fn set_error_page(
+        &mut self,
+        path: &str,
+        request: &Request,
+        id: u128,
+        cache: &Arc<RwLock<FileCache>>,
+        config: &Config,
+    ) -> io::Result<()> {
+        let modified_time = tokio::fs::metadata(path).await?.modified()?;
+        let cached = cache.read().await.find(path, modified_time).cloned();
+        let contents = match cached {
+            Some(bytes) => bytes,
+            None => {
+                debug!("[ID{}]读取自定义错误页面: {}", id, path);
+                let bytes = Bytes::from(tokio::fs::read(path).await?);
+                cache.write().await.push(path, bytes.clone(), modified_time);
+                bytes
+            }
+        };
+
+        let mime = Path::new(path).extension().map_or("text/html", get_mime);
+        self.content_type = Some(with_charset(mime, config.default_charset()).into_owned());
+        if request.method() == HttpRequestMethod::Head {
+            self.content_encoding = None;
+            self.content_length = contents.len() as u64;
+            self.content = None;
+            return Ok(());
+        }
+        self.content_encoding = match should_skip_compression(mime) {
+            true => None,
+            false => decide_encoding(request.accept_encoding()),
+        };
+        let levels = CompressionLevels::from_config(config);
+        let body = match compress_async(contents.to_vec(), self.content_encoding, levels).await {
+            Ok(body) => body,
+            Err(e) => {
+                error!("[ID{}]压缩自定义错误页面失败: {}，返回未压缩内容", id, e);
+                self.content_encoding = None;
+                contents.to_vec()
+            }
+        };
+        self.content_length = body.len() as u64;
+        self.content = Some(Bytes::from(body));
+        Ok(())
+    }
+
+    /// 以 `html` 作为响应体，按已协商的 `content_encoding` 压缩，并设置 Content-Type 与 Content-Length。
+    fn set_html_content(&mut self, html: String) -> &mut Self {
+        let content_compressed = compress(html.into_bytes(), self.content_encoding, CompressionLevels::default()).unwrap();
+        let bytes = Bytes::from(content_compressed);
+        self.content_length = bytes.len() as u64;
+        self.content = Some(bytes);
+        self.content_type = Some("text/html;charset=utf-8".to_string());
+        self
+    }
+
     /// 处理目录请求，生成目录列表（HTML 或 JSON）。
     ///
     /// # 参数
     ///
     /// * `path` - 目录路径。
+    /// * `request` - 客户端请求，用于协商压缩编码，并读取排序（`sort` / `order`）与分页（`page` / `per_page`）查询参数。
     /// * `is_json` - 是否请求 JSON 格式（通过 Accept 头判断）。
-    fn from_dir(
+    ///
+    /// # 错误
+    ///
+    /// 无法读取目录（或其元数据）时返回底层的 I/O 错误，由调用方转换为 500 响应。
+    async fn from_dir(
         path: &str,
-        accept_encoding: Vec<HttpEncoding>,
+        request: &Request,
         id: u128,
-        cache: &Arc<Mutex<FileCache>>,
+        cache: &Arc<RwLock<FileCache>>,
         headonly: bool,
         is_json: bool,
-    ) -> Self {
-        debug!("[ID{}]from_dir: path={}, is_json={}", id, path, is_json);
+        config: &Config,
+    ) -> io::Result<Self> {
+        let accept_encoding = request.accept_encoding();
+        let sort = ListingSort::new(request.query_param("sort"), request.query_param("order"));
+        let page = Pagination::new(
+            request.query_param("page"),
+            request.query_param("per_page"),
+            config.listing_per_page(),
+        )
+        .map(|p| p.with_sort(sort));
+        debug!(
+            "[ID{}]from_dir: path={}, is_json={}, sort={:?}, page={:?}",
+            id, path, is_json, sort, page
+        );
         let mut response = Self::new();
         response.allow = None;
         response.content_encoding = match headonly {
@@ -468,40 +618,44 @@ impl Response {
         }
 
         let dir_path = Path::new(path);
-        let dir_modified_time = match metadata(dir_path) {
-            Ok(meta) => match meta.modified() {
-                Ok(time) => time,
-                Err(e) => {
-                    error!("[ID{}]无法获取目录{}的修改时间: {}", id, path, e);
-                    panic!();
-                }
-            },
-            Err(e) => {
-                error!("[ID{}]无法获取目录{}的元数据: {}", id, path, e);
-                panic!();
-            }
-        };
-
-        let mut cache_lock = match cache.lock() {
-            Ok(lock) => lock,
-            Err(poisoned) => {
-                warn!("[ID{}]缓存锁被污染，恢复并继续", id);
-                poisoned.into_inner()
-            }
-        };
-
-        // 区分 JSON 和 HTML 的缓存 Key
-        let cache_key = if is_json {
+        let dir_metadata = metadata(dir_path).inspect_err(|e| {
+            error!("[ID{}]无法获取目录{}的元数据: {}", id, path, e);
+        })?;
+        let dir_modified_time = dir_metadata.modified().inspect_err(|e| {
+            error!("[ID{}]无法获取目录{}的修改时间: {}", id, path, e);
+        })?;
+        // JSON 与 HTML 是同一目录的两种表示，校验器需要区分
+        let (etag, last_modified) = validators(&dir_metadata, is_json.then_some("json"));
+        response.etag = Some(etag);
+        response.last_modified = last_modified;
+
+        // 区分 JSON 和 HTML 的缓存 Key，还要区分排序方式以及分页的页码与每页条目数
+        let mut cache_key = if is_json {
             format!("{}:json", path)
         } else {
             path.to_string()
         };
+        match page {
+            Some(page) => cache_key.push_str(&page.query_string(page.page)),
+            None => cache_key.push_str(&sort.query_suffix()),
+        }
 
-        match cache_lock.find(&cache_key, dir_modified_time) {
-            Some(bytes) => {
+        // 缓存锁只在 find / push 期间持有，读取目录、生成页面与压缩时均已释放，
+        // 慢速磁盘不会让其他请求排队等待缓存锁
+        let cached = {
+            let cache = cache.read().await;
+            let total = cache.item_count(&cache_key, dir_modified_time);
+            cache.find(&cache_key, dir_modified_time).cloned().map(|bytes| (bytes, total))
+        };
+        match cached {
+            Some((bytes, total)) => {
                 // --- 缓存命中 ---
                 debug!("[ID{}]缓存命中，原始大小: {} bytes", id, bytes.len());
                 let mut content_data = bytes.to_vec();
+                // 分页结果的条目总数与缓存内容一起记录，用于还原分页响应头
+                if let Some(page) = page {
+                    response.add_pagination_headers(&page, total.unwrap_or_default());
+                }
                 let original_size = content_data.len();
 
                 if response.content_encoding.is_some() {
@@ -509,12 +663,12 @@ impl Response {
                         "[ID{}]对缓存的目录内容进行厊缩，编码方式: {:?}",
                         id, response.content_encoding
                     );
-                    content_data = match compress(content_data, response.content_encoding) {
+                    content_data = match compress_async(content_data.clone(), response.content_encoding, CompressionLevels::from_config(config)).await {
                         Ok(c) => c,
                         Err(e) => {
                             error!("[ID{}]厊缩缓存的目录内容失败: {}，返回未厊缩内容", id, e);
                             response.content_encoding = None;
-                            bytes.to_vec()
+                            content_data
                         }
                     };
                     debug!(
@@ -536,14 +690,33 @@ impl Response {
                 // --- 缓存未命中，重新生成目录列表 ---
                 debug!("[ID{}]缓存未命中或目录已修改", id);
                 let mut dir_vec = Vec::<PathBuf>::new();
-                let entries = fs::read_dir(path).unwrap();
-                for entry in entries.into_iter() {
-                    dir_vec.push(entry.unwrap().path());
+                let entries = fs::read_dir(path).inspect_err(|e| {
+                    error!("[ID{}]无法读取目录{}: {}", id, path, e);
+                })?;
+                for entry in entries {
+                    match entry {
+                        Ok(entry) => dir_vec.push(entry.path()),
+                        Err(e) => warn!("[ID{}]读取目录{}中的条目失败，已跳过: {}", id, path, e),
+                    }
+                }
+                // 隐藏匹配 listing_ignore 的条目（默认为点文件）
+                dir_vec.retain(|p| {
+                    let name = p.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+                    !is_ignored(&name, config.listing_ignore())
+                });
+
+                // 先汇总整个目录并排序，分页时再截取当前页的条目
+                let total = dir_vec.len();
+                let summary = DirSummary::from_entries(&dir_vec);
+                sort_dir_entries(&mut dir_vec, sort);
+                if let Some(page) = page {
+                    dir_vec = dir_vec.drain(page.bounds(total)).collect();
+                    response.add_pagination_headers(&page, total);
                 }
 
                 // 根据请求类型生成 JSON 数据或 HTML 页面
                 let content_bytes = if is_json {
-                    let json_struct: Vec<_> = dir_vec
+                    let entries: Vec<_> = dir_vec
                         .iter()
                         .map(|p| {
                             let meta = fs::metadata(p).ok();
@@ -565,10 +738,26 @@ impl Response {
                             })
                         })
                         .collect();
+                    let json_struct = if config.listing_json_summary() {
+                        serde_json::json!({
+                            "entries": entries,
+                            "summary": {
+                                "files": summary.files,
+                                "dirs": summary.dirs,
+                                "total_size": format_file_size(summary.total_size),
+                                "raw_total_size": summary.total_size
+                            }
+                        })
+                    } else {
+                        serde_json::Value::from(entries)
+                    };
                     serde_json::to_vec(&json_struct).unwrap()
                 } else {
-                    let content = HtmlBuilder::from_dir(path, &mut dir_vec).build();
-                    content.into_bytes()
+                    let content = match page {
+                        Some(page) => HtmlBuilder::from_dir_page(path, &mut dir_vec, &page, &summary),
+                        None => HtmlBuilder::from_dir(path, &mut dir_vec, sort),
+                    };
+                    content.build().into_bytes()
                 };
 
                 debug!(
@@ -577,7 +766,7 @@ impl Response {
                     content_bytes.len()
                 );
                 let content_compressed =
-                    match compress(content_bytes.clone(), response.content_encoding) {
+                    match compress_async(content_bytes.clone(), response.content_encoding, CompressionLevels::from_config(config)).await {
                         Ok(c) => c,
                         Err(e) => {
                             error!("[ID{}]压缩目录{}内容失败: {}，返回未压缩内容", id, path, e);
@@ -591,30 +780,73 @@ impl Response {
                     false => Some(Bytes::from(content_compressed.clone())),
                 };
 
-                // 更新缓存
-                cache_lock.push(
-                    &cache_key,
-                    Bytes::from(content_bytes),
-                    dir_modified_time,
-                );
+                // 更新缓存；分页结果同时记录条目总数
+                let content_bytes = Bytes::from(content_bytes);
+                match page {
+                    Some(_) => cache.write().await.push_with_item_count(&cache_key, content_bytes, total, dir_modified_time),
+                    None => cache.write().await.push(&cache_key, content_bytes, dir_modified_time),
+                }
             }
         }
-        response
+        Ok(response)
+    }
+
+    /// 读取 Markdown 文件并渲染为 HTML 页面构建响应。
+    ///
+    /// 渲染结果以 `路径:md` 为键缓存，并以文件修改时间校验，文件未修改时直接复用渲染结果。
+    async fn from_markdown(
+        path: &str,
+        metadata: &Metadata,
+        accept_encoding: Vec<HttpEncoding>,
+        id: u128,
+        cache: &Arc<RwLock<FileCache>>,
+        headonly: bool,
+        levels: CompressionLevels,
+    ) -> io::Result<Response> {
+        let modified_time = metadata.modified()?;
+        let cache_key = format!("{}:md", path);
+        let cached = cache.read().await.find(&cache_key, modified_time).cloned();
+        let html = match cached {
+            Some(bytes) => {
+                debug!("[ID{}]Markdown渲染结果缓存命中", id);
+                String::from_utf8_lossy(&bytes).into_owned()
+            }
+            None => {
+                debug!("[ID{}]Markdown渲染结果缓存未命中，开始渲染", id);
+                let markdown = tokio::fs::read_to_string(path).await?;
+                let title = Path::new(path)
+                    .file_name()
+                    .map_or_else(|| path.to_string(), |n| n.to_string_lossy().into_owned());
+                let html = HtmlBuilder::from_markdown(&title, &markdown).build();
+                cache.write().await.push(&cache_key, Bytes::from(html.clone()), modified_time);
+                html
+            }
+        };
+        let mut response = Self::from_html(&html, accept_encoding, id, headonly, levels);
+        let (etag, last_modified) = validators(metadata, Some("md"));
+        response.etag = Some(etag);
+        response.last_modified = last_modified;
+        Ok(response)
     }
 
     /// 从 HTML 字符串直接构建响应（主要用于 PHP 处理结果）。
+    ///
+    /// HEAD 请求与静态文件一致：不压缩、不携带响应体，但 `Content-Type` 与
+    /// `Content-Length`（未压缩的 HTML 长度）照常给出，与 GET 的头部语义保持一致。
     fn from_html(
         html: &str,
         accept_encoding: Vec<HttpEncoding>,
         id: u128,
         headonly: bool,
+        levels: CompressionLevels,
     ) -> Response {
         let mut response = Self::new();
         response.allow = None;
+        response.content_type = Some("text/html;charset=utf-8".to_string());
         if headonly {
             response.content_encoding = None;
-            response.content_type = None;
             response.content = None;
+            response.content_length = html.len() as u64;
             return response;
         }
         response.content_encoding = decide_encoding(&accept_encoding);
@@ -625,7 +857,7 @@ impl Response {
             None => debug!("[ID{}]不进行压缩", id),
         };
         debug!("[ID{}]开始压缩HTML，原始大小: {} bytes", id, html.len());
-        let content_compressed = match compress(Vec::from(html), response.content_encoding) {
+        let content_compressed = match compress(Vec::from(html), response.content_encoding, levels) {
             Ok(c) => c,
             Err(e) => {
                 error!("[ID{}]压缩HTML失败: {}，返回未压缩内容", id, e);
@@ -634,16 +866,84 @@ impl Response {
             }
         };
         response.content_length = content_compressed.len() as u64;
-        response.content_type = Some("text/html;charset=utf-8".to_string());
         response.content = Some(Bytes::from(content_compressed));
         response
     }
 
+    /// 从内存中的文本构建 200 响应（如 `/metrics` 指标），按请求协商压缩，HEAD 请求不携带响应体。
+    pub fn from_text(text: &str, content_type: &str, request: &Request, id: u128) -> Self {
+        Self::from_bytes(text.to_owned(), content_type, request, id)
+    }
+
+    /// 根据上游服务器返回的状态码与响应头构建反向代理的响应，见 [`crate::proxy::forward`]。
+    ///
+    /// `Content-Type` 写入对应字段，其余响应头按顺序原样附加；逐跳头部与由本服务器生成的头部
+    /// （`Content-Length`、`Date`、`Server`）应由调用方事先过滤。响应体由调用方通过
+    /// [`Response::set_chunked_body`] 设置。`status_code` 可以是任意 100–599 之间的状态码，
+    /// 不在 [`STATUS_CODES`] 中的状态码使用所属类别的通用原因短语（如 299 为 `Success`）。
+    pub fn from_upstream(status_code: u16, headers: &[(String, String)]) -> Self {
+        let mut response = Self::new();
+        response.allow = None;
+        for (name, value) in headers {
+            if name.eq_ignore_ascii_case("content-type") {
+                response.content_type = Some(value.clone());
+            } else {
+                response.add_header(name, value);
+            }
+        }
+        response.status_code = status_code;
+        response.information = STATUS_CODES
+            .get(&status_code)
+            .copied()
+            .unwrap_or_else(|| generic_reason(status_code))
+            .to_string();
+        response.set_date().set_version().to_owned()
+    }
+
+    /// 从内存中的任意字节构建 200 响应，按请求协商压缩，HEAD 请求不携带响应体。
+    ///
+    /// 内容除压缩外原样发送，适合自定义处理器生成的内容（包括图片等二进制数据）。
+    pub fn from_bytes(body: impl Into<Bytes>, content_type: &str, request: &Request, id: u128) -> Self {
+        let body: Bytes = body.into();
+        let mut response = Self::new();
+        response.allow = None;
+        response.content_type = Some(content_type.to_string());
+        if request.method() == HttpRequestMethod::Head {
+            response.content_length = body.len() as u64;
+        } else {
+            response.content_encoding = decide_encoding(request.accept_encoding());
+            let content = match response.content_encoding {
+                None => body,
+                Some(_) => match compress(body.to_vec(), response.content_encoding, CompressionLevels::default()) {
+                    Ok(c) => Bytes::from(c),
+                    Err(e) => {
+                        error!("[ID{}]压缩响应体失败: {}，返回未压缩内容", id, e);
+                        response.content_encoding = None;
+                        body
+                    }
+                },
+            };
+            response.content_length = content.len() as u64;
+            response.content = Some(content);
+        }
+        response
+            .set_date()
+            .set_code(200)
+            .set_version()
+            .set_server_name()
+            .to_owned()
+    }
+
     // --- 构建者模式 Setter 方法 ---
 
     /// 设置响应日期为当前 UTC 时间。
     fn set_date(&mut self) -> &mut Self {
-        self.date = Utc::now();
+        self.set_date_with(&SystemClock)
+    }
+
+    /// 以指定时钟的当前时间作为响应日期。
+    fn set_date_with(&mut self, clock: &dyn Clock) -> &mut Self {
+        self.date = clock.now().into();
         self
     }
 
@@ -653,9 +953,24 @@ impl Response {
         self
     }
 
-    /// 设置服务器名称头。
+    /// 设置默认的服务器名称头，按配置覆盖见 [`Response::set_server_header`]。
     fn set_server_name(&mut self) -> &mut Self {
-        self.server_name = SERVER_NAME.to_string();
+        self.server_name = Some(SERVER_NAME.to_string());
+        self
+    }
+
+    /// 将响应设置为 416 Range Not Satisfiable（RFC 7233 §4.4）。
+    ///
+    /// 携带 `Content-Range: bytes */LEN` 告知客户端资源的实际大小，并携带 `Accept-Ranges: bytes`；
+    /// 响应没有响应体，因此清除内容、Content-Type 与 Content-Encoding。
+    fn set_range_not_satisfiable(&mut self, file_size: u64) -> &mut Self {
+        self.set_code(416);
+        self.accept_ranges = Some("bytes".to_string());
+        self.content_range = Some(format!("bytes */{}", file_size));
+        self.content_type = None;
+        self.content_encoding = None;
+        self.content = None;
+        self.content_length = 0;
         self
     }
 
@@ -672,6 +987,38 @@ impl Response {
         self
     }
 
+    /// 静态工厂方法：构建不带响应体的 204 No Content 响应。
+    pub fn response_204(request: &Request, id: u128) -> Self {
+        let accept_encoding = request.accept_encoding().to_vec();
+        let mut response = Self::from_status_code(204, accept_encoding, id);
+        response.allow = None;
+        response
+            .set_date()
+            .set_version()
+            .set_server_name()
+            .to_owned()
+    }
+
+    /// 静态工厂方法：构建 401 Unauthorized 响应。`WWW-Authenticate` 质询由调用方附加，见 [`crate::auth::challenge`]。
+    pub fn response_401(request: &Request, id: u128) -> Self {
+        let accept_encoding = request.accept_encoding().to_vec();
+        Self::from_status_code(401, accept_encoding, id)
+            .set_date()
+            .set_code(401)
+            .set_version()
+            .to_owned()
+    }
+
+    /// 静态工厂方法：构建 403 Forbidden 响应。
+    pub fn response_403(request: &Request, id: u128) -> Self {
+        let accept_encoding = request.accept_encoding().to_vec();
+        Self::from_status_code(403, accept_encoding, id)
+            .set_date()
+            .set_code(403)
+            .set_version()
+            .to_owned()
+    }
+
     /// 静态工厂方法：构建 404 Not Found 响应。
     pub fn response_404(request: &Request, id: u128) -> Self {
         let accept_encoding = request.accept_encoding().to_vec();
@@ -682,6 +1029,120 @@ impl Response {
             .to_owned()
     }
 
+    /// 静态工厂方法：构建 405 Method Not Allowed 响应，`Allow` 头列出目标资源实际支持的方法。
+    pub fn response_405_with_allow(request: &Request, id: u128, methods: Vec<HttpRequestMethod>) -> Self {
+        let accept_encoding = request.accept_encoding().to_vec();
+        let mut response = Self::from_status_code(405, accept_encoding, id);
+        // 错误页面与 Allow 头列出相同的方法
+        let listed = methods.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ");
+        let note = format!(
+            "<h2>噢！</h2><p>该资源不支持你的浏览器发出的请求方法。</p><p>支持的方法：{}</p>",
+            listed
+        );
+        response.set_html_content(HtmlBuilder::from_status_code(405, Some(&note)).build());
+        response.allow = Some(methods);
+        response
+            .set_date()
+            .set_code(405)
+            .set_version()
+            .to_owned()
+    }
+
+    /// 计算目标资源允许的请求方法，用于 405 响应的 `Allow` 头。
+    ///
+    /// 以配置启用的方法 `enabled` 为基础：只有交给 PHP 处理的脚本能处理 POST（表单提交），
+    /// 因此 POST 只在配置启用时对 PHP 脚本开放，对静态文件与目录始终不开放。
+    /// PUT 只在开启上传（`uploads`）时对文件开放，目录不能被上传替换，服务器会执行的脚本（见 [`is_script`]）
+    /// 也不能被上传，否则任何客户端都可以上传并执行代码。
+    /// `OPTIONS *` 的目标 `*` 不指向具体资源，返回 `enabled` 本身。
+    pub fn allowed_methods_for(path: &str, is_dir: bool, enabled: &[HttpRequestMethod], uploads: bool) -> Vec<HttpRequestMethod> {
+        let is_php = !is_dir && Path::new(path).extension().is_some_and(|e| e == "php");
+        enabled
+            .iter()
+            .copied()
+            .filter(|&m| match m {
+                HttpRequestMethod::Post => is_php || path == "*",
+                HttpRequestMethod::Put => (uploads && !is_dir && !is_script(path)) || path == "*",
+                _ => true,
+            })
+            .collect()
+    }
+
+    /// 静态工厂方法：构建 406 Not Acceptable 响应。
+    pub fn response_406(request: &Request, id: u128) -> Self {
+        let accept_encoding = request.accept_encoding().to_vec();
+        Self::from_status_code(406, accept_encoding, id)
+            .set_date()
+            .set_code(406)
+            .set_version()
+            .to_owned()
+    }
+
+    /// 静态工厂方法：构建 414 URI Too Long 响应。
+    pub fn response_414(request: &Request, id: u128) -> Self {
+        let accept_encoding = request.accept_encoding().to_vec();
+        Self::from_status_code(414, accept_encoding, id)
+            .set_date()
+            .set_code(414)
+            .set_version()
+            .to_owned()
+    }
+
+    /// 静态工厂方法：构建 408 Request Timeout 响应。
+    ///
+    /// 客户端未能在时限内发送完整请求时使用，此时尚未解析出 `Request`，因此只接收连接 ID，响应体不做压缩。
+    pub fn response_408(id: u128) -> Self {
+        Self::from_status_code(408, vec![], id)
+            .set_date()
+            .set_code(408)
+            .set_version()
+            .to_owned()
+    }
+
+    /// 静态工厂方法：构建 413 Content Too Large 响应。
+    ///
+    /// 请求体超限时不再读取剩余数据，也尚未解析出 `Request`，因此只接收连接 ID，响应体不做压缩。
+    pub fn response_413(id: u128) -> Self {
+        Self::from_status_code(413, vec![], id)
+            .set_date()
+            .set_code(413)
+            .set_version()
+            .to_owned()
+    }
+
+    /// 静态工厂方法：构建 431 Request Header Fields Too Large 响应。
+    ///
+    /// 请求头超限时尚未解析出 `Request`，因此只接收连接 ID，响应体不做压缩。
+    pub fn response_431(id: u128) -> Self {
+        Self::from_status_code(431, vec![], id)
+            .set_date()
+            .set_code(431)
+            .set_version()
+            .to_owned()
+    }
+
+    /// 静态工厂方法：构建 503 Service Unavailable 响应，`Retry-After` 头建议客户端在若干秒后重试。
+    ///
+    /// 用于停机期间拒绝新连接，此时不再解析请求，因此只接收连接 ID，响应体不做压缩。
+    pub fn response_503(id: u128, retry_after_secs: u64) -> Self {
+        Self::from_status_code(503, vec![], id)
+            .set_date()
+            .set_code(503)
+            .set_version()
+            .add_header("Retry-After", &retry_after_secs.to_string())
+            .to_owned()
+    }
+
+    /// 静态工厂方法：构建 429 Too Many Requests 响应，`Retry-After` 给出建议的重试间隔（秒）。
+    pub fn response_429(id: u128, retry_after_secs: u64) -> Self {
+        Self::from_status_code(429, vec![], id)
+            .set_date()
+            .set_code(429)
+            .set_version()
+            .add_header("Retry-After", &retry_after_secs.to_string())
+            .to_owned()
+    }
+
     /// 静态工厂方法：构建 500 Internal Server Error 响应。
     pub fn response_500(request: &Request, id: u128) -> Self {
         let accept_encoding = request.accept_encoding().to_vec();
@@ -692,6 +1153,26 @@ impl Response {
             .to_owned()
     }
 
+    /// 静态工厂方法：构建 502 Bad Gateway 响应，用于 PHP 脚本输出过大、反向代理的上游不可达等后端异常。
+    pub fn response_502(request: &Request, id: u128) -> Self {
+        let accept_encoding = request.accept_encoding().to_vec();
+        Self::from_status_code(502, accept_encoding, id)
+            .set_date()
+            .set_code(502)
+            .set_version()
+            .to_owned()
+    }
+
+    /// 静态工厂方法：构建 504 Gateway Timeout 响应，用于 PHP 脚本运行超时或反向代理的上游响应超时。
+    pub fn response_504(request: &Request, id: u128) -> Self {
+        let accept_encoding = request.accept_encoding().to_vec();
+        Self::from_status_code(504, accept_encoding, id)
+            .set_date()
+            .set_code(504)
+            .set_version()
+            .to_owned()
+    }
+
     /// 静态工厂方法：构建 400 Bad Request 响应。
     pub fn response_400(request: &Request, id: u128) -> Self {
         let accept_encoding = request.accept_encoding().to_vec();
@@ -702,42 +1183,94 @@ impl Response {
             .to_owned()
     }
 
+    /// 处理 PUT 上传：把请求体写入 `path`，新建文件返回 201 Created，替换已有文件返回 204 No Content。
+    ///
+    /// 请求体先写入同一目录下的临时文件再重命名为目标文件，上传中途失败时不会留下写了一半的文件。
+    /// 临时文件名带有进程内唯一的序号并以 `create_new` 创建：同一 HTTP/2 连接上的多个流共用连接 ID，
+    /// 并发上传同一文件时不会互相覆盖或删除对方的临时文件。
+    /// 写入后以所在目录为前缀清除缓存，文件本身与该目录的各种列表（HTML、JSON、分页）都不再返回旧内容。
+    ///
+    /// 服务器会执行的脚本（见 [`is_script`]）一律以 405 拒绝，不依赖调用方事先检查。
+    async fn from_upload(path: &str, request: &Request, id: u128, cache: &Arc<RwLock<FileCache>>) -> Response {
+        if is_script(path) {
+            warn!("[ID{}]拒绝上传可执行的脚本{}，返回405", id, path);
+            return Self::response_405_with_allow(request, id, vec![HttpRequestMethod::Get, HttpRequestMethod::Head]);
+        }
+        let target = Path::new(path);
+        let (Some(dir), Some(name)) = (target.parent(), target.file_name()) else {
+            warn!("[ID{}]上传路径{}没有文件名，返回400", id, path);
+            return Self::response_400(request, id);
+        };
+        let created = tokio::fs::metadata(target).await.is_err();
+        let seq = UPLOAD_SEQ.fetch_add(1, Ordering::Relaxed);
+        let temp = dir.join(format!(".{}.upload-{}-{}", name.to_string_lossy(), id, seq));
+        let result = match write_new_file(&temp, request.body()).await {
+            Ok(()) => tokio::fs::rename(&temp, target).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            error!("[ID{}]写入上传文件{}失败: {}", id, path, e);
+            // 临时文件已存在时不是本次请求创建的，不能删除
+            if e.kind() != io::ErrorKind::AlreadyExists {
+                let _ = tokio::fs::remove_file(&temp).await;
+            }
+            return Self::response_500(request, id);
+        }
+        debug!("[ID{}]上传文件{}，共{}字节", id, path, request.body().len());
+        cache.write().await.invalidate_prefix(&dir.to_string_lossy());
+
+        let accept_encoding = request.accept_encoding().to_vec();
+        let mut response = match created {
+            true => Self::from_status_code(201, accept_encoding, id),
+            false => Self::from_status_code(204, accept_encoding, id),
+        };
+        response.allow = None;
+        response
+            .set_date()
+            .set_version()
+            .set_server_name()
+            .to_owned()
+    }
+
     /// 处理请求的主入口函数。
     ///
     /// 根据请求的方法（Method）和路径（Path）分发到具体的处理逻辑（文件、目录、PHP 等）。
-    pub fn from(
+    /// 静态文件经由 [`Response::from_file_async`] 异步读取。
+    pub async fn from(
         path: &str,
         request: &Request,
         id: u128,
-        cache: &Arc<Mutex<FileCache>>,
+        cache: &Arc<RwLock<FileCache>>,
         config: &Config,
     ) -> Response {
         let accept_encoding = request.accept_encoding().to_vec();
         let method = request.method();
-        let metadata_result = fs::metadata(path);
-
-        // 验证请求方法是否支持
-        if method != HttpRequestMethod::Get
-            && method != HttpRequestMethod::Head
-            && method != HttpRequestMethod::Options
-        {
-            return Self::from_status_code(405, accept_encoding, id)
-                .set_date()
-                .set_version()
-                .set_server_name()
-                .to_owned();
+        let metadata_result = tokio::fs::metadata(path).await;
+
+        // 验证目标资源是否支持该请求方法
+        let is_dir = metadata_result.as_ref().is_ok_and(|m| m.is_dir());
+        let allowed = Self::allowed_methods_for(path, is_dir, &config.allowed_methods(), config.uploads());
+        if !allowed.contains(&method) {
+            warn!("[ID{}]{}不支持{}方法，返回405", id, path, method);
+            return Self::response_405_with_allow(request, id, allowed);
         }
 
-        // 处理 OPTIONS 请求
+        // 处理 OPTIONS 请求：`OPTIONS *` 返回配置启用的全局方法集合，具体资源返回该资源允许的方法
         if method == HttpRequestMethod::Options {
             debug!("[ID{}]请求方法为OPTIONS", id);
-            return Self::from_status_code(204, accept_encoding, id)
+            let mut response = Self::from_status_code(204, accept_encoding, id);
+            response.allow = Some(allowed);
+            return response
                 .set_date()
                 .set_version()
                 .set_server_name()
                 .to_owned();
         }
 
+        if method == HttpRequestMethod::Put {
+            return Self::from_upload(path, request, id, cache).await;
+        }
+
         let headonly = match method {
             HttpRequestMethod::Head => {
                 debug!("[ID{}]请求方法为HEAD", id);
@@ -750,57 +1283,181 @@ impl Response {
             Ok(metadata) => {
                 if metadata.is_dir() {
                     debug!("[ID{}]请求的路径是目录", id);
+                    if !config.autoindex() {
+                        warn!("[ID{}]目录列表已禁用，返回403", id);
+                        return Self::response_403(request, id);
+                    }
                     let is_json = request
                         .accept()
                         .map_or(false, |a| a.contains("application/json"));
-                    Self::from_dir(path, accept_encoding, id, cache, headonly, is_json)
-                        .set_date()
-                        .set_code(200)
-                        .set_version()
-                        .set_server_name()
-                        .to_owned()
+                    let mime = if is_json { "application/json" } else { "text/html" };
+                    if !is_acceptable(request, config, mime) {
+                        warn!("[ID{}]客户端不接受{}，返回406", id, mime);
+                        return Self::response_406(request, id);
+                    }
+                    match Self::from_dir(path, request, id, cache, headonly, is_json, config).await {
+                        Ok(mut response) => response
+                            .set_date()
+                            .set_code(200)
+                            .set_version()
+                            .set_server_name()
+                            .to_owned(),
+                        Err(_) => Self::response_500(request, id),
+                    }
                 } else {
                     debug!("[ID{}]请求的路径是文件", id);
                     let extention = match Path::new(path).extension() {
                         Some(e) => e,
+                        // 没有扩展名的文件（如 LICENSE、Dockerfile）稍后通过内容嗅探确定 MIME 类型
+                        None if config.sniff_extensionless() => OsStr::new(""),
                         None => {
                             error!("[ID{}]无法确定请求路径{}的文件扩展名", id, path);
                             return Self::response_404(request, id);
                         }
                     };
-                    debug!("[ID{}]文件扩展名: {}", id, extention.to_str().unwrap());
+                    debug!("[ID{}]文件扩展名: {}", id, extention.to_string_lossy());
                     
                     // 特殊处理 PHP 文件
                     if extention == "php" {
                         debug!("[ID{}]请求的文件是PHP，启用PHP处理", id);
-                        let html = match handle_php(path, id) {
-                            Ok(html) => html,
+                        if !is_acceptable(request, config, "text/html") {
+                            warn!("[ID{}]客户端不接受text/html，返回406", id);
+                            return Self::response_406(request, id);
+                        }
+                        let timeout = Duration::from_secs(config.script_timeout_secs());
+                        let max_output = config.limits().max_script_output_bytes();
+                        let env = cgi_env(request, path, config.port());
+                        // 配置了 FastCGI 应用时交给常驻进程处理，响应体流式返回
+                        if let Some(address) = config.fastcgi_address() {
+                            return match fastcgi::run(&address, &env, request.body(), id, timeout, max_output).await {
+                                Ok(output) => {
+                                    let mut response = Self::new();
+                                    response.allow = None;
+                                    response.content_type = Some(
+                                        output.head.content_type.unwrap_or_else(|| "text/html;charset=utf-8".to_string()),
+                                    );
+                                    add_script_headers(&mut response, &output.head.headers, id);
+                                    response
+                                        .set_chunked_body(output.body)
+                                        .set_date()
+                                        .set_code(cgi_status(output.head.status, id))
+                                        .set_version()
+                                        .set_server_name()
+                                        .to_owned()
+                                }
+                                Err(e) => {
+                                    error!("[ID{}]通过FastCGI执行{}时出错：{}", id, path, e);
+                                    match e {
+                                        Exception::ScriptTimeout => Self::response_504(request, id),
+                                        _ => Self::response_502(request, id),
+                                    }
+                                }
+                            };
+                        }
+                        let output = match handle_php(config.php_path(), path, &env, request.body(), id, timeout, max_output)
+                            .await
+                        {
+                            Ok(output) => output,
                             Err(e) => {
                                 error!("[ID{}]解析PHP文件{}时出错：{}", id, path, e);
-                                return Self::response_500(request, id);
+                                return match e {
+                                    Exception::ScriptTimeout => Self::response_504(request, id),
+                                    Exception::ScriptOutputTooLarge => Self::response_502(request, id),
+                                    _ => Self::response_500(request, id),
+                                };
                             }
                         };
-                        return Self::from_html(&html, accept_encoding, id, headonly)
+                        // 脚本可以按 CGI 约定在输出开头给出状态码与响应头
+                        let cgi = parse_cgi_output(&output);
+                        let status = cgi_status(cgi.status, id);
+                        let mut response =
+                            Self::from_html(&cgi.body, accept_encoding, id, headonly, CompressionLevels::from_config(config));
+                        if let Some(content_type) = cgi.content_type {
+                            response.content_type = Some(content_type);
+                        }
+                        add_script_headers(&mut response, &cgi.headers, id);
+                        return response
                             .set_date()
-                            .set_code(200)
+                            .set_code(status)
                             .set_version()
                             .set_server_name()
                             .to_owned();
                     }
                     
+                    // Markdown 文件渲染为 HTML 页面
+                    if (extention == "md" || extention == "markdown") && config.render_markdown() {
+                        debug!("[ID{}]请求的文件是Markdown，渲染为HTML", id);
+                        if !is_acceptable(request, config, "text/html") {
+                            warn!("[ID{}]客户端不接受text/html，返回406", id);
+                            return Self::response_406(request, id);
+                        }
+                        return match Self::from_markdown(
+                            path,
+                            &metadata,
+                            accept_encoding,
+                            id,
+                            cache,
+                            headonly,
+                            CompressionLevels::from_config(config),
+                        )
+                        .await {
+                            Ok(mut response) => response
+                                .set_date()
+                                .set_code(200)
+                                .set_version()
+                                .set_server_name()
+                                .to_owned(),
+                            Err(e) => {
+                                error!("[ID{}]读取Markdown文件{}时出错：{}", id, path, e);
+                                Self::response_500(request, id)
+                            }
+                        };
+                    }
+
                     // 处理普通静态文件
-                    let mime = get_mime(extention);
+                    let mime = match extention.is_empty() {
+                        true => sniff_mime(path).await,
+                        false => get_mime(extention),
+                    };
+                    let mime = with_charset(mime, config.default_charset());
+                    let mime = mime.as_ref();
                     debug!("[ID{}]MIME类型: {}", id, mime);
-                    Self::from_file(path, request, id, cache, headonly, mime, config)
+                    if !is_acceptable(request, config, mime) {
+                        warn!("[ID{}]客户端不接受{}，返回406", id, mime);
+                        return Self::response_406(request, id);
+                    }
+                    // 状态码由 from_file_async 决定（200 / 206 / 304 / 416），此处不再覆盖
+                    let mut response = match Self::from_file_async(path, request, id, cache, headonly, mime, config).await {
+                        Ok(response) => response,
+                        Err(Exception::FileNotFound) => {
+                            warn!("[ID{}]文件{}在读取前被删除，返回404", id, path);
+                            return Self::response_404(request, id);
+                        }
+                        Err(e) => {
+                            error!("[ID{}]读取文件{}失败({})，返回500", id, path, e);
+                            return Self::response_500(request, id);
+                        }
+                    };
+                    let force_download = request.query_param("download") == Some("1")
+                        || config.is_download_extension(&extention.to_string_lossy());
+                    if force_download && matches!(response.status_code, 200 | 206) {
+                        let filename = Path::new(path).file_name().unwrap_or_default().to_string_lossy();
+                        debug!("[ID{}]强制下载{}", id, filename);
+                        response.content_disposition = Some(content_disposition_attachment(&filename));
+                    }
+                    response
                         .set_date()
-                        .set_code(200)
                         .set_version()
                         .set_server_name()
                         .to_owned()
                 }
             }
-            Err(_) => {
-                warn!("[ID{}]无法获取{}的元数据，产生500 response", id, path);
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                warn!("[ID{}]{}已不存在，返回404", id, path);
+                Self::response_404(request, id)
+            }
+            Err(e) => {
+                warn!("[ID{}]无法获取{}的元数据({})，产生500 response", id, path, e);
                 Self::response_500(request, id)
             }
         }
@@ -810,96 +1467,178 @@ impl Response {
     ///
     /// 包含状态行、Headers 和 Body。
     pub fn as_bytes(&self) -> Vec<u8> {
-        if self.content == None && self.content_type == None {
+        if self.content.is_none() && self.content_type.is_none() {
             assert_eq!(self.content_encoding, None);
         }
         let version: &str = match self.version {
+            HttpVersion::V1_0 => "HTTP/1.0",
             HttpVersion::V1_1 => "HTTP/1.1",
         };
-        let status_code: &str = &self.status_code.to_string();
-        let information: &str = &self.information;
-        let content_length: &str = &self.content_length.to_string();
-        let date: &str = &format_date(&self.date);
-        let server: &str = &self.server_name;
 
         // 手动构建 HTTP 头部字符串
-        let header = [
-            version,
-            " ",
-            status_code,
-            " ",
-            information,
-            CRLF,
-            match &self.content_type {
-                Some(t) => ["Content-Type: ", &t, CRLF].concat(),
-                None => "".to_string(),
-            }
-            .as_str(),
-            match self.content_encoding {
-                Some(e) => [
-                    "Content-encoding: ",
-                    match e {
-                        HttpEncoding::Gzip => "gzip",
-                        HttpEncoding::Deflate => "deflate",
-                        HttpEncoding::Br => "br",
-                    },
-                    CRLF,
-                ]
-                .concat()
-                .to_string(),
-                None => "".to_string(),
-            }
-            .as_str(),
-            "Content-Length: ",
-            content_length,
-            CRLF,
-            "Date: ",
-            date,
-            CRLF,
-            "Server: ",
-            server,
-            CRLF,
-            match &self.allow {
-                Some(a) => {
-                    let mut allow_str = String::new();
-                    for (index, method) in a.iter().enumerate() {
-                        allow_str.push_str(&format!("{}", method));
-                        if index < a.len() - 1 {
-                            allow_str.push_str(", ");
-                        }
-                    }
-                    ["Allow: ", &allow_str, CRLF].concat()
-                }
-                None => "".to_string(),
-            }
-            .as_str(),
-            match &self.accept_ranges {
-                Some(r) => ["Accept-Ranges: ", r, CRLF].concat(),
-                None => "".to_string(),
-            }
-            .as_str(),
-            match &self.content_range {
-                Some(r) => ["Content-Range: ", r, CRLF].concat(),
-                None => "".to_string(),
-            }
-            .as_str(),
-            CRLF,
-        ]
-        .concat();
-        
+        let mut header = [version, " ", &self.status_code.to_string(), " ", &self.information, CRLF].concat();
+        for (name, value) in self.header_fields() {
+            header.push_str(&[name, ": ", &value, CRLF].concat());
+        }
+        header.push_str(CRLF);
+
         // 拼接头部和内容
         [
             header.as_bytes(),
             match &self.content {
-                Some(c) => &c,
+                Some(c) => c,
                 None => b"",
             },
         ]
         .concat()
     }
+
+    /// 按发送顺序列出响应头（不含状态行），HTTP/1.1 序列化与 HTTP/2 的 HEADERS 帧共用。
+    ///
+    /// 值可能来自请求或配置的响应头已去掉 CR/LF，防止响应拆分。
+    pub fn header_fields(&self) -> Vec<(&str, String)> {
+        let mut fields = Vec::new();
+        if let Some(t) = &self.content_type {
+            fields.push(("Content-Type", sanitize_header_value(t)));
+        }
+        if let Some(e) = self.content_encoding {
+            let encoding = match e {
+                HttpEncoding::Gzip => "gzip",
+                HttpEncoding::Deflate => "deflate",
+                HttpEncoding::Br => "br",
+            };
+            fields.push(("Content-encoding", encoding.to_string()));
+        }
+        // 304 不携带响应体，Content-Length 只能描述被省略的表示，因此直接不发送；
+        // 分块响应的长度未知，由 Transfer-Encoding 代替 Content-Length；以关闭连接结束的响应两者都不发送
+        match self.status_code {
+            304 => {}
+            _ if self.chunked_body.is_some() && self.close_delimited => {}
+            _ if self.chunked_body.is_some() => fields.push(("Transfer-Encoding", "chunked".to_string())),
+            _ => fields.push(("Content-Length", self.content_length.to_string())),
+        }
+        fields.push(("Date", format_date(&self.date)));
+        if let Some(name) = &self.server_name {
+            fields.push(("Server", sanitize_header_value(name)));
+        }
+        fields.push(("Connection", if self.keep_alive { "keep-alive" } else { "close" }.to_string()));
+        if let Some(a) = &self.allow {
+            let methods: Vec<String> = a.iter().map(ToString::to_string).collect();
+            fields.push(("Allow", methods.join(", ")));
+        }
+        if let Some(r) = &self.accept_ranges {
+            fields.push(("Accept-Ranges", r.clone()));
+        }
+        if let Some(r) = &self.content_range {
+            fields.push(("Content-Range", r.clone()));
+        }
+        if let Some(e) = &self.etag {
+            fields.push(("ETag", sanitize_header_value(e)));
+        }
+        if let Some(t) = &self.last_modified {
+            fields.push(("Last-Modified", format_date(t)));
+        }
+        if let Some(d) = &self.content_disposition {
+            fields.push(("Content-Disposition", sanitize_header_value(d)));
+        }
+        for cookie in &self.cookies {
+            fields.push(("Set-Cookie", sanitize_header_value(cookie)));
+        }
+        for (name, value) in &self.extra_headers {
+            fields.push((name.as_str(), sanitize_header_value(value)));
+        }
+        fields
+    }
 }
 
 impl Response {
+    /// 追加一个自定义响应头（如 `X-Response-Time`）。
+    ///
+    /// 响应头不影响响应体，因此可以在内容与 `Content-Length` 确定之后再添加。
+    pub fn add_header(&mut self, name: &str, value: &str) -> &mut Self {
+        self.extra_headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// 添加一个 Cookie，序列化时输出为单独的一行 `Set-Cookie` 响应头。
+    ///
+    /// Cookie 值会进行百分号编码，因此可以包含 `;`、空格、引号与非 ASCII 字符，
+    /// 由 [`Request::cookie`] 解码还原。名称必须是合法的 HTTP token，否则忽略该 Cookie。
+    pub fn add_cookie(&mut self, name: &str, value: &str, attrs: &CookieAttributes) -> &mut Self {
+        let is_token_char = |c: char| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c);
+        if name.is_empty() || !name.chars().all(is_token_char) {
+            warn!("Cookie名称{:?}不是合法的token，已忽略", name);
+            return self;
+        }
+        let mut cookie = format!("{}={}", name, percent_encode_path_segment(value));
+        if let Some(path) = &attrs.path {
+            cookie.push_str(&format!("; Path={}", path));
+        }
+        if let Some(max_age) = attrs.max_age {
+            cookie.push_str(&format!("; Max-Age={}", max_age));
+        }
+        if attrs.http_only {
+            cookie.push_str("; HttpOnly");
+        }
+        if attrs.secure {
+            cookie.push_str("; Secure");
+        }
+        if let Some(same_site) = attrs.same_site {
+            cookie.push_str(&format!("; SameSite={}", same_site));
+        }
+        self.cookies.push(cookie);
+        self
+    }
+
+    /// 按配置覆盖 `Server` 响应头的内容，`None` 表示不发送该响应头（见 `Config::server_name`）。
+    pub fn set_server_header(&mut self, name: Option<&str>) -> &mut Self {
+        self.server_name = name.map(str::to_string);
+        self
+    }
+
+    /// 设置发送响应后是否保持连接（默认关闭）。
+    ///
+    /// 只有在客户端请求保持连接（见 [`Request::keep_alive`]）且服务器确实会继续读取下一个请求时才应设为 `true`。
+    pub fn set_keep_alive(&mut self, keep_alive: bool) -> &mut Self {
+        self.keep_alive = keep_alive;
+        self
+    }
+
+    /// 将响应体设置为长度未知的分块响应体：生产者通过通道逐块发送内容，
+    /// 发送阶段以 `Transfer-Encoding: chunked` 写出，见 [`write_chunked`]。
+    ///
+    /// 已有的内容被丢弃。生产者发送 `Err` 时响应体被中止，客户端会看到不完整的分块流。
+    pub fn set_chunked_body(&mut self, body: mpsc::Receiver<io::Result<Bytes>>) -> &mut Self {
+        self.content = None;
+        self.content_length = 0;
+        self.stream_source = None;
+        self.chunked_body = Some(ChunkedBody(Arc::new(std::sync::Mutex::new(Some(body)))));
+        self
+    }
+
+    /// 取出分块响应体的接收端；响应头仍然按分块响应序列化。
+    ///
+    /// 接收端只能取出一次，克隆出的响应共享同一个响应体。
+    pub fn take_chunked_body(&mut self) -> Option<mpsc::Receiver<io::Result<Bytes>>> {
+        self.chunked_body.as_ref()?.0.lock().ok()?.take()
+    }
+
+    /// 分块响应体改为不带 `Transfer-Encoding` 与 `Content-Length` 原样发送，以关闭连接表示响应体结束，
+    /// 见 [`write_close_delimited`]。
+    ///
+    /// 用于不支持分块传输的 HTTP/1.0 客户端。响应体不必读入内存，内存占用与响应体大小无关；
+    /// 代价是客户端无法区分响应体被中止与正常结束。不是分块响应时不做任何处理。
+    pub fn set_close_delimited(&mut self) -> &mut Self {
+        self.close_delimited = self.chunked_body.is_some();
+        self
+    }
+
+    /// 附加目录列表的分页响应头：`X-Total-Count` 与 `Link`。
+    fn add_pagination_headers(&mut self, page: &Pagination, total: usize) -> &mut Self {
+        self.add_header("X-Total-Count", &total.to_string())
+            .add_header("Link", &page.link_header(total))
+    }
+
     /// 获取 HTTP 状态码。
     pub fn status_code(&self) -> u16 {
         self.status_code
@@ -921,6 +1660,37 @@ impl Response {
     pub fn get_content_length(&self) -> u64 {
         self.content_length
     }
+
+    /// 是否为长度未知的分块响应（包括以关闭连接结束的响应）。
+    pub fn is_chunked(&self) -> bool {
+        self.chunked_body.is_some()
+    }
+
+    /// 分块响应体是否原样发送、以关闭连接表示结束，见 [`Response::set_close_delimited`]。
+    pub fn is_close_delimited(&self) -> bool {
+        self.close_delimited
+    }
+
+    /// 获取流式响应体的来源，见 [`StreamSource`]。
+    pub fn stream_source(&self) -> Option<&StreamSource> {
+        self.stream_source.as_ref()
+    }
+
+    /// 获取已在内存中的响应体；流式、分块响应与 HEAD 响应为 `None`。
+    pub fn content(&self) -> Option<&Bytes> {
+        self.content.as_ref()
+    }
+}
+
+/// 状态码所属类别的通用原因短语，用于 [`STATUS_CODES`] 中没有的状态码。
+fn generic_reason(code: u16) -> &'static str {
+    match code / 100 {
+        1 => "Informational",
+        2 => "Success",
+        3 => "Redirection",
+        4 => "Client Error",
+        _ => "Server Error",
+    }
 }
 
 /// 格式化日期为 HTTP Date 头所需的 RFC 2822 格式。
@@ -928,29 +1698,516 @@ fn format_date(date: &DateTime<Utc>) -> String {
     date.to_rfc2822()
 }
 
+/// 根据元数据生成资源的校验器：(弱 ETag, Last-Modified)。
+///
+/// 文件与目录共用此函数，保证文件管理器无论请求列表还是文件，都能得到一致的条件请求行为。
+/// ETag 由大小与修改时间（纳秒精度）组成，形如 `W/"1a2b-18f3c..."`；
+/// `variant` 用于区分同一资源的不同表示（如目录的 JSON 列表），会追加在引号内。
+/// 文件系统不支持修改时间时，ETag 仅由大小构成，Last-Modified 为 `None`。
+fn validators(metadata: &fs::Metadata, variant: Option<&str>) -> (String, Option<DateTime<Utc>>) {
+    let modified = metadata.modified().ok();
+    let nanos = modified
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_nanos());
+    let etag = match variant {
+        Some(v) => format!("W/\"{:x}-{:x}-{}\"", metadata.len(), nanos, v),
+        None => format!("W/\"{:x}-{:x}\"", metadata.len(), nanos),
+    };
+    (etag, modified.map(DateTime::<Utc>::from))
+}
+
+/// 根据文件内容计算强 ETag，形如 `"1a2b-0123456789abcdef"`（大小 + 64 位 XXH3 内容哈希）。
+///
+/// 与流式传输一样按 `chunk_size` 分块读取，大文件不会被整体读入内存。
+/// 哈希按修改时间记录在该文件的缓存条目上（见 [`FileCache::set_content_hash`]），文件未变化时直接复用。
+/// 压缩后的表示另有后缀，见 [`representation_etag`]。
+async fn strong_etag(
+    path: &str,
+    metadata: &Metadata,
+    chunk_size: usize,
+    cache: &Arc<RwLock<FileCache>>,
+) -> io::Result<String> {
+    let modified_time = metadata.modified()?;
+    let cached = cache.read().await.content_hash(path, modified_time);
+    let hash = match cached {
+        Some(hash) => hash,
+        None => {
+            let mut file = TokioFile::open(path).await?;
+            let mut buffer = vec![0u8; chunk_size.max(1)];
+            let mut hasher = Xxh3::new();
+            loop {
+                let n = file.read(&mut buffer).await?;
+                if n == 0 {
+                    break;
+                }
+                hasher.update(&buffer[..n]);
+            }
+            let hash = hasher.digest();
+            cache.write().await.set_content_hash(path, hash, modified_time);
+            hash
+        }
+    };
+    Ok(format!("\"{:x}-{:016x}\"", metadata.len(), hash))
+}
+
+/// 以 `encoding` 编码发送时该表示的 ETag。
+///
+/// 强 ETag 标识的是字节完全相同的表示，压缩后的内容与原始字节不同，因此在引号内追加编码名（如 `"1a2b-…-gzip"`），
+/// 使缓存与 `If-Range` 不会把两种表示混用。弱 ETag 与未压缩的表示保持不变。
+fn representation_etag(etag: &str, encoding: Option<HttpEncoding>) -> String {
+    match (etag.strip_suffix('"'), encoding) {
+        (Some(tag), Some(encoding)) if !etag.starts_with("W/") => format!("{}-{}\"", tag, encoding),
+        _ => etag.to_string(),
+    }
+}
+
+/// 为 PHP 脚本构建 CGI/1.1 环境变量（RFC 3875 §4.1）。
+///
+/// 路由只会匹配到脚本文件本身，因此 `PATH_INFO` 总是为空。每个请求头以 `HTTP_` 前缀镜像，
+/// 名称转为大写并将 `-` 替换为 `_`；`Content-Type` 与 `Content-Length` 以不带前缀的变量传递。
+/// 这些值只经由进程环境传给解释器，不会经过 shell 解释。
+///
+/// `SERVER_NAME` 取自 `Host` 请求头（去掉端口），`SERVER_PORT` 为监听端口 `server_port`。
+fn cgi_env(request: &Request, script_filename: &str, server_port: u16) -> Vec<(String, String)> {
+    let script_name = request.path().split('?').next().unwrap_or("");
+    let mut env = vec![
+        ("GATEWAY_INTERFACE".to_string(), "CGI/1.1".to_string()),
+        ("SERVER_PROTOCOL".to_string(), format!("HTTP/{}", request.version())),
+        ("SERVER_SOFTWARE".to_string(), SERVER_NAME.to_string()),
+        ("REQUEST_METHOD".to_string(), request.method().to_string()),
+        ("REQUEST_URI".to_string(), request.path().to_string()),
+        ("QUERY_STRING".to_string(), request.query().unwrap_or("").to_string()),
+        ("SCRIPT_NAME".to_string(), script_name.to_string()),
+        ("SCRIPT_FILENAME".to_string(), script_filename.to_string()),
+        ("PATH_INFO".to_string(), String::new()),
+        // php-cgi 在 force-cgi-redirect 模式下要求该变量存在
+        ("REDIRECT_STATUS".to_string(), "200".to_string()),
+    ];
+    if let Some(host) = request.header("host") {
+        // 去掉端口；IPv6 地址保留方括号，如 [::1]:7878 → [::1]
+        let name = match host.rfind(':') {
+            Some(i) if !host[i..].contains(']') => &host[..i],
+            _ => host,
+        };
+        env.push(("SERVER_NAME".to_string(), name.to_string()));
+    }
+    env.push(("SERVER_PORT".to_string(), server_port.to_string()));
+    if let Some(ip) = request.client_ip() {
+        env.push(("REMOTE_ADDR".to_string(), ip.to_string()));
+    }
+    if let Some(peer) = request.peer_addr() {
+        env.push(("REMOTE_PORT".to_string(), peer.port().to_string()));
+    }
+    if !request.body().is_empty() {
+        env.push(("CONTENT_LENGTH".to_string(), request.body().len().to_string()));
+    }
+    for (name, value) in request.headers() {
+        match name.as_str() {
+            "content-type" => env.push(("CONTENT_TYPE".to_string(), value.clone())),
+            // `Proxy` 头会变成 HTTP_PROXY，被脚本中的 HTTP 客户端当作代理设置（httpoxy，CVE-2016-5385）
+            "content-length" | "proxy" => {}
+            _ => {
+                let key: String = name
+                    .chars()
+                    .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+                    .collect();
+                env.push((format!("HTTP_{}", key), value.clone()));
+            }
+        }
+    }
+    env
+}
+
+/// 上传临时文件名的序号，保证同一进程内的临时文件名互不相同。
+static UPLOAD_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// 判断路径是否指向服务器会执行的脚本，目前即交给 PHP 处理的 `.php` 文件。
+///
+/// 扩展名不区分大小写：大小写不敏感的文件系统上 `X.PHP` 与 `x.php` 是同一个文件。
+fn is_script(path: &str) -> bool {
+    Path::new(path).extension().is_some_and(|e| e.eq_ignore_ascii_case("php"))
+}
+
+/// 以 `create_new` 创建文件并写入 `contents`，文件已存在时返回 `AlreadyExists` 而不是覆盖它。
+async fn write_new_file(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut file = tokio::fs::OpenOptions::new().write(true).create_new(true).open(path).await?;
+    file.write_all(contents).await?;
+    file.flush().await
+}
+
+/// 脚本（CGI 或 FastCGI）不能给出的响应头：分帧头部、逐跳头部，以及由服务器生成的 `Date` 与 `Server`。
+/// 脚本给出的这些头部会与服务器的取值重复或冲突（例如分块响应同时带有 `Content-Length`），一律丢弃。
+const SERVER_MANAGED_HEADERS: [&str; 10] = [
+    "content-length",
+    "transfer-encoding",
+    "connection",
+    "keep-alive",
+    "proxy-connection",
+    "te",
+    "trailer",
+    "upgrade",
+    "date",
+    "server",
+];
+
+/// 把脚本输出的响应头追加到响应中，跳过 [`SERVER_MANAGED_HEADERS`]。
+fn add_script_headers(response: &mut Response, headers: &[(String, String)], id: u128) {
+    for (name, value) in headers {
+        if SERVER_MANAGED_HEADERS.iter().any(|h| h.eq_ignore_ascii_case(name)) {
+            debug!("[ID{}]忽略脚本输出的响应头{}", id, name);
+            continue;
+        }
+        response.add_header(name, value);
+    }
+}
+
+/// 脚本按 CGI 约定给出的状态码；没有给出或无法识别时按 200 处理。
+fn cgi_status(status: Option<u16>, id: u128) -> u16 {
+    match status {
+        Some(code) if STATUS_CODES.contains_key(&code) => code,
+        Some(code) => {
+            warn!("[ID{}]PHP脚本给出了无法识别的状态码{}，按200处理", id, code);
+            200
+        }
+        None => 200,
+    }
+}
+
+/// 判断资源的 MIME 类型是否可以返回给客户端。开启 `lenient_accept` 时总是可以。
+fn is_acceptable(request: &Request, config: &Config, mime: &str) -> bool {
+    config.lenient_accept() || request.accepts(mime)
+}
+
+/// 判断条件请求是否可以以 `304 Not Modified` 应答（RFC 9110 §13.1.2 / §13.1.3）。
+///
+/// - 存在 `If-None-Match` 时只依据它判断（见 [`if_none_match_matches`]），此时忽略 `If-Modified-Since`。
+/// - 否则比较 `If-Modified-Since` 与 Last-Modified（按秒），资源不晚于该日期即视为未修改；
+///   无法解析的日期视为不满足条件。
+fn is_not_modified(request: &Request, etag: Option<&str>, last_modified: Option<DateTime<Utc>>) -> bool {
+    if let Some(value) = request.if_none_match() {
+        return etag.is_some_and(|etag| if_none_match_matches(value, etag));
+    }
+    match (request.if_modified_since().map(DateTime::parse_from_rfc2822), last_modified) {
+        (Some(Ok(date)), Some(modified)) => modified.timestamp() <= date.timestamp(),
+        _ => false,
+    }
+}
+
+/// 判断 `If-None-Match` 的值是否与当前 ETag 匹配。
+///
+/// `*` 匹配任何存在的表示；否则按逗号分隔的实体标签列表解析（标签内的引号串可以包含逗号），
+/// 任一成员匹配即可。按 RFC 9110 §8.8.3.2 的弱比较：忽略双方的 `W/` 前缀，只比较引号内的值。
+/// 遇到格式错误的成员时停止解析，之前的成员仍然有效。
+fn if_none_match_matches(value: &str, etag: &str) -> bool {
+    let current = etag.strip_prefix("W/").unwrap_or(etag);
+    let mut rest = value.trim_start();
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix(',') {
+            rest = after.trim_start();
+            continue;
+        }
+        if rest.starts_with('*') {
+            return true;
+        }
+        let tag = rest.strip_prefix("W/").unwrap_or(rest);
+        let end = match tag.strip_prefix('"').and_then(|t| t.find('"')) {
+            Some(end) => end + 2,
+            None => return false,
+        };
+        if &tag[..end] == current {
+            return true;
+        }
+        rest = tag[end..].trim_start();
+    }
+    false
+}
+
+/// 判断 `If-Range` 的值是否与当前资源的校验器匹配。
+///
+/// - 实体标签形式（以 `"` 或 `W/` 开头）按 RFC 9110 §13.1.5 的强比较：任一方为弱 ETag 时不匹配，
+///   因此只有开启 `strong_etag` 时 ETag 形式的 `If-Range` 才可能匹配。
+/// - 日期形式与 Last-Modified 按秒比较；无法解析的日期视为不匹配。
+fn if_range_matches(value: &str, etag: Option<&str>, last_modified: Option<DateTime<Utc>>) -> bool {
+    let value = value.trim();
+    if value.starts_with("W/") {
+        return false;
+    }
+    if value.starts_with('"') {
+        return etag.is_some_and(|etag| !etag.starts_with("W/") && etag == value);
+    }
+    match (DateTime::parse_from_rfc2822(value), last_modified) {
+        (Ok(date), Some(modified)) => date.timestamp() == modified.timestamp(),
+        _ => false,
+    }
+}
+
+/// 流式响应体的来源：在写出响应头之后，从文件中分块复制响应体，避免大文件或分片内容整体读入内存。
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamSource {
+    /// 文件的物理路径
+    pub path: String,
+    /// 依次发送的分段；为空时发送整个文件
+    pub segments: Vec<StreamSegment>,
+}
+
+/// 流式响应体中的一个分段：先发送 `prefix`，再发送文件中的字节区间。
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamSegment {
+    /// 在文件内容之前发送的字节，如 `multipart/byteranges` 的分隔符与分段头部
+    pub prefix: Bytes,
+    /// 文件中的闭区间 `(start, end)`；为 `None` 时只发送 `prefix`（如多段响应的结束分隔符）
+    pub range: Option<(u64, u64)>,
+}
+
+impl StreamSegment {
+    /// 分段在响应体中占用的字节数
+    pub fn len(&self) -> u64 {
+        self.prefix.len() as u64 + self.range.map_or(0, |(start, end)| end - start + 1)
+    }
+
+    /// 分段是否不包含任何字节
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl StreamSource {
+    /// 将响应体写入 `writer`，每次最多读取 `chunk_size` 字节，返回写出的字节数。
+    ///
+    /// 文件在发送过程中被截断时返回 `UnexpectedEof`，避免发送的字节数少于已声明的 Content-Length。
+    pub async fn write_to<W: AsyncWrite + Unpin>(&self, writer: &mut W, chunk_size: usize) -> io::Result<u64> {
+        let mut file = TokioFile::open(&self.path).await?;
+        let mut buffer = vec![0u8; chunk_size.max(1)];
+        let mut total = 0u64;
+        if self.segments.is_empty() {
+            loop {
+                let n = file.read(&mut buffer).await?;
+                if n == 0 {
+                    return Ok(total);
+                }
+                writer.write_all(&buffer[..n]).await?;
+                total += n as u64;
+            }
+        }
+        for segment in &self.segments {
+            writer.write_all(&segment.prefix).await?;
+            total += segment.prefix.len() as u64;
+            let Some((start, end)) = segment.range else {
+                continue;
+            };
+            file.seek(SeekFrom::Start(start)).await?;
+            let mut remaining = end - start + 1;
+            while remaining > 0 {
+                let want = remaining.min(buffer.len() as u64) as usize;
+                let n = file.read(&mut buffer[..want]).await?;
+                if n == 0 {
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "文件在发送过程中被截断"));
+                }
+                writer.write_all(&buffer[..n]).await?;
+                remaining -= n as u64;
+                total += n as u64;
+            }
+        }
+        Ok(total)
+    }
+}
+
+/// 把读取静态文件时的 I/O 错误映射为异常：文件已不存在时为 [`Exception::FileNotFound`]，其余为 [`Exception::FileUnreadable`]。
+fn file_error(e: &io::Error) -> Exception {
+    match e.kind() {
+        io::ErrorKind::NotFound => Exception::FileNotFound,
+        _ => Exception::FileUnreadable,
+    }
+}
+
+/// 分块响应体的接收端。`Response` 需要支持克隆，因此接收端放在共享的 `Option` 中，只能取出一次。
+#[derive(Debug, Clone)]
+struct ChunkedBody(Arc<std::sync::Mutex<Option<mpsc::Receiver<io::Result<Bytes>>>>>);
+
+/// 以 `Transfer-Encoding: chunked` 格式写出分块响应体，返回写出的字节数（含分块框架）。
+///
+/// 空块被跳过，因为长度为 0 的块表示响应体结束。生产者发送 `Err` 时立即返回该错误且不写出结束块，
+/// 客户端据此得知响应体不完整。
+pub async fn write_chunked<W: AsyncWrite + Unpin>(
+    body: &mut mpsc::Receiver<io::Result<Bytes>>,
+    writer: &mut W,
+) -> io::Result<u64> {
+    let mut total = 0u64;
+    while let Some(chunk) = body.recv().await {
+        let chunk = chunk?;
+        if chunk.is_empty() {
+            continue;
+        }
+        let size_line = format!("{:x}{}", chunk.len(), CRLF);
+        writer.write_all(size_line.as_bytes()).await?;
+        writer.write_all(&chunk).await?;
+        writer.write_all(CRLF.as_bytes()).await?;
+        total += (size_line.len() + chunk.len() + CRLF.len()) as u64;
+    }
+    let last_chunk = ["0", CRLF, CRLF].concat();
+    writer.write_all(last_chunk.as_bytes()).await?;
+    Ok(total + last_chunk.len() as u64)
+}
+
+/// 原样写出分块响应体，不加分块框架，返回写出的字节数。
+///
+/// 用于以关闭连接表示结束的响应，见 [`Response::set_close_delimited`]。生产者发送 `Err` 时立即返回该错误，
+/// 调用方随后关闭连接。
+pub async fn write_close_delimited<W: AsyncWrite + Unpin>(
+    body: &mut mpsc::Receiver<io::Result<Bytes>>,
+    writer: &mut W,
+) -> io::Result<u64> {
+    let mut total = 0u64;
+    while let Some(chunk) = body.recv().await {
+        let chunk = chunk?;
+        writer.write_all(&chunk).await?;
+        total += chunk.len() as u64;
+    }
+    Ok(total)
+}
+
+/// 为 `multipart/byteranges` 响应生成分隔符。
+///
+/// 分隔符由连接 ID、资源 ETag 与当前时间散列得到，同一资源的不同响应也互不相同。
+fn multipart_boundary(id: u128, etag: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    hasher.write_u128(id);
+    hasher.write(etag.as_bytes());
+    hasher.write_i64(Utc::now().timestamp_nanos_opt().unwrap_or_default());
+    format!("{:016x}", hasher.finish())
+}
+
+/// 按起点排序并合并重叠或相邻的闭区间（RFC 9110 §14.2 允许服务器合并）。
+///
+/// 客户端可以用大量互相重叠的区间（如 `bytes=0-,0-,0-`）把很小的文件放大成巨大的响应体，
+/// 合并后响应体的大小不会超过文件本身加上各分段的头部。
+fn merge_spans(mut spans: Vec<(u64, u64)>) -> Vec<(u64, u64)> {
+    spans.sort_unstable();
+    let mut merged: Vec<(u64, u64)> = Vec::with_capacity(spans.len());
+    for (start, end) in spans {
+        match merged.last_mut() {
+            Some(last) if start <= last.1.saturating_add(1) => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+/// 按 RFC 9110 §14.6 生成 `multipart/byteranges` 响应体的分段：
+/// 每个区间前是分隔符与 `Content-Type`、`Content-Range` 头部，最后是结束分隔符。
+fn multipart_segments(spans: &[(u64, u64)], boundary: &str, mime: &str, file_size: u64) -> Vec<StreamSegment> {
+    let mut segments: Vec<StreamSegment> = spans
+        .iter()
+        .enumerate()
+        .map(|(i, &(start, end))| {
+            let head = format!(
+                "{}--{}{}Content-Type: {}{}Content-Range: bytes {}-{}/{}{}{}",
+                if i == 0 { "" } else { CRLF },
+                boundary, CRLF, mime, CRLF, start, end, file_size, CRLF, CRLF
+            );
+            StreamSegment { prefix: Bytes::from(head), range: Some((start, end)) }
+        })
+        .collect();
+    segments.push(StreamSegment {
+        prefix: Bytes::from(format!("{}--{}--{}", CRLF, boundary, CRLF)),
+        range: None,
+    });
+    segments
+}
+
+/// `Set-Cookie` 的可选属性，见 [`Response::add_cookie`]。
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CookieAttributes {
+    /// `Path` 属性，限定 Cookie 生效的路径前缀
+    pub path: Option<String>,
+    /// `Max-Age` 属性（秒），0 或负数表示立即删除该 Cookie
+    pub max_age: Option<i64>,
+    /// `HttpOnly` 属性，禁止脚本读取
+    pub http_only: bool,
+    /// `Secure` 属性，只在 HTTPS 连接中发送
+    pub secure: bool,
+    /// `SameSite` 属性
+    pub same_site: Option<SameSite>,
+}
+
+/// Cookie 的 `SameSite` 属性取值。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SameSite {
+    /// 只在同站请求中发送
+    Strict,
+    /// 同站请求与跨站的顶级导航中发送
+    Lax,
+    /// 所有请求中都发送（浏览器要求同时设置 `Secure`）
+    None,
+}
+
+impl std::fmt::Display for SameSite {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SameSite::Strict => write!(f, "Strict"),
+            SameSite::Lax => write!(f, "Lax"),
+            SameSite::None => write!(f, "None"),
+        }
+    }
+}
+
+/// 各压缩算法使用的级别，取自配置中的 `gzip_level` 与 `brotli_quality`。
+///
+/// 错误页、指标等内置的小型响应不依赖配置，使用默认级别。
+#[derive(Debug, Clone, Copy)]
+struct CompressionLevels {
+    /// gzip 与 deflate 的压缩级别（0–9）
+    gzip: u32,
+    /// brotli 的压缩质量（0–11）
+    brotli: u32,
+}
+
+impl CompressionLevels {
+    fn from_config(config: &Config) -> Self {
+        Self {
+            gzip: config.gzip_level(),
+            brotli: config.brotli_quality(),
+        }
+    }
+}
+
+impl Default for CompressionLevels {
+    /// 与配置的默认值一致：gzip 6，brotli 5。
+    fn default() -> Self {
+        Self { gzip: 6, brotli: 5 }
+    }
+}
+
 /// 压缩数据。
 ///
 /// 支持 Gzip, Deflate, Brotli 算法。
 ///
+/// `deflate` 编码输出 zlib 封装（RFC 1950）的数据而不是裸 DEFLATE 流：HTTP 规范中的 `deflate`
+/// 指的正是 zlib 格式，部分浏览器无法解码裸 DEFLATE，而各类客户端都能正确处理 zlib 封装。
+///
 /// # 参数
 ///
 /// * `data` - 待压缩的原始字节数据。
 /// * `mode` - 指定的压缩编码。
-fn compress(data: Vec<u8>, mode: Option<HttpEncoding>) -> io::Result<Vec<u8>> {
+/// * `levels` - 各算法的压缩级别，见 [`CompressionLevels`]。
+fn compress(data: Vec<u8>, mode: Option<HttpEncoding>, levels: CompressionLevels) -> io::Result<Vec<u8>> {
     let original_size = data.len();
     let result = match mode {
         Some(HttpEncoding::Gzip) => {
-            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::new(levels.gzip));
             encoder.write_all(&data)?;
             encoder.finish()
         }
         Some(HttpEncoding::Deflate) => {
-            let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::new(levels.gzip));
             encoder.write_all(&data)?;
             encoder.finish()
         }
         Some(HttpEncoding::Br) => {
-            let params = BrotliEncoderParams::default();
+            let params = BrotliEncoderParams {
+                quality: levels.brotli as i32,
+                ..Default::default()
+            };
             let mut output = Vec::new();
             enc::BrotliCompress(&mut io::Cursor::new(data), &mut output, &params)?;
             Ok(output)
@@ -976,6 +2233,24 @@ fn compress(data: Vec<u8>, mode: Option<HttpEncoding>) -> io::Result<Vec<u8>> {
     result
 }
 
+/// 在阻塞线程池中压缩数据。
+///
+/// 压缩是 CPU 密集型操作，放在 `spawn_blocking` 中执行以免占用异步运行时的工作线程。
+/// 不需要压缩时直接返回原数据。
+async fn compress_async(
+    data: Vec<u8>,
+    mode: Option<HttpEncoding>,
+    levels: CompressionLevels,
+) -> io::Result<Vec<u8>> {
+    if mode.is_none() {
+        return Ok(data);
+    }
+    match tokio::task::spawn_blocking(move || compress(data, mode, levels)).await {
+        Ok(result) => result,
+        Err(e) => Err(io::Error::other(e)),
+    }
+}
+
 /// 判断特定的 MIME 类型是否应该跳过压缩。
 ///
 /// 对于已经是压缩格式的文件（如 zip, jpeg, mp4），再次压缩通常效果不佳且浪费 CPU。
@@ -1007,16 +2282,10 @@ fn should_skip_compression(mime_type: &str) -> bool {
 
 /// 协商压缩编码。
 ///
-/// 根据客户端的 Accept-Encoding 头选择合适的压缩算法。
-/// 优先级：Gzip > Deflate (当前实现忽略了 Brotli 的优先级选择，除非只有 Brotli 也不支持)。
-fn decide_encoding(accept_encoding: &Vec<HttpEncoding>) -> Option<HttpEncoding> {
-    if accept_encoding.contains(&HttpEncoding::Gzip) {
-        Some(HttpEncoding::Gzip)
-    } else if accept_encoding.contains(&HttpEncoding::Deflate) {
-        Some(HttpEncoding::Deflate)
-    } else {
-        None
-    }
+/// [`Request::accept_encoding`] 已按客户端的 q 值与服务器的编码优先级排好序，
+/// 三种编码均受支持，因此直接选用排在最前面的编码；列表为空时不压缩。
+fn decide_encoding(accept_encoding: &[HttpEncoding]) -> Option<HttpEncoding> {
+    accept_encoding.first().copied()
 }
 
 /// 根据文件扩展名获取 MIME 类型。
@@ -1034,6 +2303,51 @@ fn get_mime(extension: &OsStr) -> &str {
     }
 }
 
+/// 读取文件开头的少量字节，推断没有扩展名的文件的 MIME 类型。
+///
+/// 先匹配常见格式的魔数，再检查内容是否为不含 NUL 的 UTF-8 文本，
+/// 都不满足（或读取失败）时视为二进制文件。
+async fn sniff_mime(path: &str) -> &'static str {
+    const MAGIC: &[(&[u8], &str)] = &[
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"%PDF-", "application/pdf"),
+        (b"PK\x03\x04", "application/zip"),
+        (b"\x1f\x8b", "application/gzip"),
+    ];
+    let mut prefix = vec![0u8; 512];
+    let len = match TokioFile::open(path).await {
+        Ok(mut file) => file.read(&mut prefix).await.unwrap_or(0),
+        Err(_) => 0,
+    };
+    let prefix = &prefix[..len];
+
+    if let Some((_, mime)) = MAGIC.iter().find(|(magic, _)| prefix.starts_with(magic)) {
+        return mime;
+    }
+    if len == 0 || prefix.contains(&0) {
+        return "application/octet-stream";
+    }
+    match str::from_utf8(prefix) {
+        Ok(_) => "text/plain",
+        // 截断处可能恰好切断一个多字节字符，只要错误出现在末尾就仍视为文本
+        Err(e) if e.error_len().is_none() => "text/plain",
+        Err(_) => "application/octet-stream",
+    }
+}
+
+/// 为未声明字符集的 `text/*` 类型追加 `;charset=<charset>`。
+///
+/// 已带有 `charset=` 参数的类型、非文本类型以及 `charset` 为空时原样返回。
+fn with_charset<'a>(mime: &'a str, charset: &str) -> Cow<'a, str> {
+    if charset.is_empty() || !mime.starts_with("text/") || mime.to_lowercase().contains("charset=") {
+        return Cow::Borrowed(mime);
+    }
+    Cow::Owned(format!("{};charset={}", mime, charset))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1050,14 +2364,14 @@ mod tests {
     #[test]
     fn test_compress_none() {
         let data = b"Hello, World!".to_vec();
-        let result = compress(data.clone(), None).unwrap();
+        let result = compress(data.clone(), None, CompressionLevels::default()).unwrap();
         assert_eq!(result, data);
     }
 
     #[test]
     fn test_compress_gzip() {
         let data = b"Hello, World! This is a test string for compression.".to_vec();
-        let result = compress(data.clone(), Some(HttpEncoding::Gzip)).unwrap();
+        let result = compress(data.clone(), Some(HttpEncoding::Gzip), CompressionLevels::default()).unwrap();
 
         assert_ne!(result, data);
         assert_eq!(&result[0..2], &[0x1f, 0x8b]);
@@ -1066,16 +2380,23 @@ mod tests {
     #[test]
     fn test_compress_deflate() {
         let data = b"Hello, World! This is a test string for compression.".to_vec();
-        let result = compress(data.clone(), Some(HttpEncoding::Deflate)).unwrap();
+        let result = compress(data.clone(), Some(HttpEncoding::Deflate), CompressionLevels::default()).unwrap();
 
         assert_ne!(result, data);
-        assert!(!result.is_empty());
+        // zlib 封装：CMF 字节 0x78（32K 窗口的 DEFLATE），且首两字节组成的数能被 31 整除
+        assert_eq!(result[0], 0x78);
+        assert_eq!(u16::from_be_bytes([result[0], result[1]]) % 31, 0);
+
+        let mut decoded = Vec::new();
+        let mut decoder = flate2::read::ZlibDecoder::new(&result[..]);
+        io::Read::read_to_end(&mut decoder, &mut decoded).unwrap();
+        assert_eq!(decoded, data);
     }
 
     #[test]
     fn test_compress_brotli() {
         let data = b"Hello, World! This is a test string for compression.".to_vec();
-        let result = compress(data.clone(), Some(HttpEncoding::Br)).unwrap();
+        let result = compress(data.clone(), Some(HttpEncoding::Br), CompressionLevels::default()).unwrap();
 
         assert_ne!(result, data);
         assert!(!result.is_empty());
@@ -1103,10 +2424,10 @@ mod tests {
     }
 
     #[test]
-    fn test_decide_encoding_br_ignored() {
+    fn test_decide_encoding_br_preferred() {
         let encodings = vec![HttpEncoding::Br, HttpEncoding::Gzip];
         let result = decide_encoding(&encodings);
-        assert_eq!(result, Some(HttpEncoding::Gzip));
+        assert_eq!(result, Some(HttpEncoding::Br));
     }
 
     #[test]
@@ -1152,8 +2473,21 @@ mod tests {
     }
 
     #[test]
-    fn test_get_mime_pdf() {
-        let ext = OsStr::new("pdf");
+    fn test_with_charset() {
+        let txt = get_mime(OsStr::new("txt"));
+        assert_eq!(with_charset(txt, "utf-8"), "text/plain;charset=utf-8");
+        assert_eq!(with_charset(txt, "gbk"), "text/plain;charset=gbk");
+        assert_eq!(with_charset(txt, ""), "text/plain");
+        // 表中已声明字符集的类型不会重复追加
+        let html = get_mime(OsStr::new("html"));
+        assert_eq!(with_charset(html, "utf-8"), "text/html;charset=utf-8");
+        assert_eq!(with_charset(html, "gbk"), "text/html;charset=utf-8");
+        assert_eq!(with_charset(get_mime(OsStr::new("png")), "utf-8"), "image/png");
+    }
+
+    #[test]
+    fn test_get_mime_pdf() {
+        let ext = OsStr::new("pdf");
         assert_eq!(get_mime(ext), "application/pdf");
     }
 
@@ -1168,14 +2502,25 @@ mod tests {
 
     #[test]
     fn test_response_as_bytes_basic() {
-        let response = Response::new();
+        let config = Config::new();
+        let mut response = Response::new();
+        response.set_server_header(config.server_name());
         let bytes = response.as_bytes();
         let response_str = String::from_utf8_lossy(&bytes);
 
         assert!(response_str.starts_with("HTTP/1.1 200 OK"));
         assert!(response_str.contains("Content-Length: 0"));
-        assert!(response_str.contains("Server: shaneyale-webserver"));
+        assert!(response_str.contains(&format!("Server: {}\r\n", config.server_name().unwrap())));
         assert!(response_str.contains("\r\n\r\n"));
+
+        // server_name 为空时不发送 Server 头，非空时覆盖默认值
+        let hidden = Config::from_toml_str(
+            "www_root = \".\"\nport = 7878\nworker_threads = 1\ncache_size = 5\nlocal = true\nserver_name = \"\"\n",
+        );
+        let bytes = response.set_server_header(hidden.server_name()).as_bytes();
+        assert!(!String::from_utf8_lossy(&bytes).contains("Server:"));
+        let bytes = response.set_server_header(Some("edge")).as_bytes();
+        assert!(String::from_utf8_lossy(&bytes).contains("\r\nServer: edge\r\n"));
     }
 
     #[test]
@@ -1248,19 +2593,19 @@ mod tests {
     #[test]
     fn test_compress_empty_data() {
         let data = vec![];
-        let result = compress(data.clone(), None).unwrap();
+        let result = compress(data.clone(), None, CompressionLevels::default()).unwrap();
         assert_eq!(result, data);
 
-        let result_gzip = compress(data, Some(HttpEncoding::Gzip)).unwrap();
+        let result_gzip = compress(data, Some(HttpEncoding::Gzip), CompressionLevels::default()).unwrap();
         assert!(!result_gzip.is_empty());
     }
 
     #[test]
     fn test_compress_large_data() {
         let data = vec![b'A'; 10000];
-        let result_gzip = compress(data.clone(), Some(HttpEncoding::Gzip)).unwrap();
-        let result_deflate = compress(data.clone(), Some(HttpEncoding::Deflate)).unwrap();
-        let result_br = compress(data.clone(), Some(HttpEncoding::Br)).unwrap();
+        let result_gzip = compress(data.clone(), Some(HttpEncoding::Gzip), CompressionLevels::default()).unwrap();
+        let result_deflate = compress(data.clone(), Some(HttpEncoding::Deflate), CompressionLevels::default()).unwrap();
+        let result_br = compress(data.clone(), Some(HttpEncoding::Br), CompressionLevels::default()).unwrap();
 
         assert!(result_gzip.len() < data.len());
         assert!(result_deflate.len() < data.len());
@@ -1276,20 +2621,39 @@ mod tests {
         assert!(response_str.contains("Date: "));
     }
 
+    /// 注入假时钟后，Date 头应精确反映时钟给出的时间
     #[test]
-    fn test_head_request_response() {
+    fn test_response_date_with_fake_clock() {
+        use crate::clock::FakeClock;
+        use std::time::{Duration, SystemTime};
+
+        let clock = FakeClock::new(SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000));
+        let mut response = Response::new();
+        response.set_date_with(&clock);
+        let response_str = String::from_utf8_lossy(&response.as_bytes()).to_string();
+        assert!(response_str.contains("Date: Sun, 9 Sep 2001 01:46:40 +0000"));
+
+        clock.advance(Duration::from_secs(60));
+        response.set_date_with(&clock);
+        let response_str = String::from_utf8_lossy(&response.as_bytes()).to_string();
+        assert!(response_str.contains("Date: Sun, 9 Sep 2001 01:47:40 +0000"));
+    }
+
+    #[tokio::test]
+    async fn test_head_request_response() {
         use crate::cache::FileCache;
         use crate::config::Config;
-        use std::sync::{Arc, Mutex};
+        use std::sync::Arc;
+        use tokio::sync::RwLock;
 
         let request_str = "HEAD /index.html HTTP/1.1\r\nHost: localhost:7878\r\n\r\n";
         let buffer = request_str.as_bytes().to_vec();
         let request = Request::try_from(&buffer, 1).unwrap();
 
-        let cache = Arc::new(Mutex::new(FileCache::from_capacity(10)));
+        let cache = Arc::new(RwLock::new(FileCache::from_capacity(10)));
         let config = Config::new();
 
-        let response = Response::from("static/index.html", &request, 1, &cache, &config);
+        let response = Response::from("static/index.html", &request, 1, &cache, &config).await;
         let bytes = response.as_bytes();
 
         let response_str = String::from_utf8_lossy(&bytes);
@@ -1299,4 +2663,1382 @@ mod tests {
 
         assert!(!response_str.contains("<!DOCTYPE html>"));
     }
+
+    /// 大文件的读取与压缩不应阻塞运行时：在单线程运行时中，
+    /// 两个并发的大文件请求进行期间，其它任务仍然能够被调度并先行完成。
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_concurrent_large_files_do_not_block() {
+        use crate::cache::FileCache;
+        use crate::config::Config;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        use tokio::sync::RwLock;
+
+        let dir = tempfile::tempdir().unwrap();
+        let content = "large file content\n".repeat(256 * 1024); // 约 5MB，低于默认流式阈值
+        for name in ["a.txt", "b.txt"] {
+            fs::write(dir.path().join(name), &content).unwrap();
+        }
+        let path_a = dir.path().join("a.txt");
+        let path_b = dir.path().join("b.txt");
+
+        let request_str = "GET /a.txt HTTP/1.1\r\nHost: localhost\r\nAccept-Encoding: gzip\r\n\r\n";
+        let request = Request::try_from(&request_str.as_bytes().to_vec(), 0).unwrap();
+        let cache = Arc::new(RwLock::new(FileCache::from_capacity(10)));
+        let config = Config::new();
+
+        // 记录各任务的完成顺序
+        let order = AtomicUsize::new(0);
+        let (response_a, response_b, ticker) = tokio::join!(
+            async {
+                let response = Response::from(path_a.to_str().unwrap(), &request, 1, &cache, &config).await;
+                (response, order.fetch_add(1, Ordering::SeqCst))
+            },
+            async {
+                let response = Response::from(path_b.to_str().unwrap(), &request, 2, &cache, &config).await;
+                (response, order.fetch_add(1, Ordering::SeqCst))
+            },
+            async {
+                tokio::task::yield_now().await;
+                order.fetch_add(1, Ordering::SeqCst)
+            },
+        );
+
+        assert_eq!(ticker, 0, "大文件请求阻塞了运行时");
+        for (response, _) in [&response_a, &response_b] {
+            assert_eq!(response.status_code(), 200);
+            assert_eq!(response.content_encoding, Some(HttpEncoding::Gzip));
+            assert!(response.get_content_length() < content.len() as u64);
+        }
+    }
+
+    /// 后缀 Range：bytes=-N 返回文件末尾 N 字节，N 为 0 时返回 416，超过文件大小时返回整个文件
+    #[tokio::test]
+    async fn test_suffix_range_request() {
+        use crate::cache::FileCache;
+        use crate::config::Config;
+        use std::sync::Arc;
+        use tokio::sync::RwLock;
+
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("data.bin");
+        let data: Vec<u8> = (0..1000u32).map(|i| (i % 256) as u8).collect();
+        fs::write(&file_path, &data).unwrap();
+        let path = file_path.to_str().unwrap();
+
+        let cache = Arc::new(RwLock::new(FileCache::from_capacity(10)));
+        let config = Config::new();
+        let request_with_range = |range: &str| {
+            let request_str = format!("GET /data.bin HTTP/1.1\r\nHost: localhost\r\nRange: {}\r\n\r\n", range);
+            Request::try_from(&request_str.into_bytes(), 0).unwrap()
+        };
+
+        let response = Response::from(path, &request_with_range("bytes=-500"), 0, &cache, &config).await;
+        assert_eq!(response.status_code(), 206);
+        assert_eq!(response.content_range.as_deref(), Some("bytes 500-999/1000"));
+        assert_eq!(streamed_body(&response).await, &data[500..]);
+
+        let response = Response::from(path, &request_with_range("bytes=-0"), 0, &cache, &config).await;
+        assert_eq!(response.status_code(), 416);
+        assert_eq!(response.content_range.as_deref(), Some("bytes */1000"));
+
+        let response = Response::from(path, &request_with_range("bytes=-5000"), 0, &cache, &config).await;
+        assert_eq!(response.status_code(), 206);
+        assert_eq!(response.content_range.as_deref(), Some("bytes 0-999/1000"));
+        assert_eq!(response.get_content_length(), 1000);
+    }
+
+    /// 分块响应体以 Transfer-Encoding: chunked 序列化；生产者出错时不写出结束块
+    #[tokio::test]
+    async fn test_chunked_body() {
+        let (tx, rx) = mpsc::channel(4);
+        let mut response = Response::new();
+        response.content_type = Some("text/plain".to_string());
+        response.set_chunked_body(rx);
+        assert!(response.is_chunked());
+        let text = String::from_utf8(response.as_bytes()).unwrap();
+        assert!(text.contains("\r\nTransfer-Encoding: chunked\r\n"), "{}", text);
+        assert!(!text.contains("Content-Length"), "{}", text);
+
+        for chunk in ["hello, ", "", "chunked world"] {
+            tx.send(Ok(Bytes::from(chunk))).await.unwrap();
+        }
+        drop(tx);
+        let mut body = response.take_chunked_body().unwrap();
+        assert!(response.take_chunked_body().is_none());
+        let mut out = Vec::new();
+        let written = write_chunked(&mut body, &mut out).await.unwrap();
+        assert_eq!(out, b"7\r\nhello, \r\nd\r\nchunked world\r\n0\r\n\r\n");
+        assert_eq!(written, out.len() as u64);
+
+        let (tx, mut rx) = mpsc::channel(4);
+        tx.send(Ok(Bytes::from("partial"))).await.unwrap();
+        tx.send(Err(io::Error::other("upstream closed"))).await.unwrap();
+        let mut out = Vec::new();
+        assert!(write_chunked(&mut rx, &mut out).await.is_err());
+        assert_eq!(out, b"7\r\npartial\r\n");
+    }
+
+    /// HTTP/1.0 客户端不支持分块传输，分块响应体不带长度原样发送，以关闭连接结束
+    #[tokio::test]
+    async fn test_close_delimited_body() {
+        let (tx, rx) = mpsc::channel(4);
+        let mut response = Response::new();
+        response.content_type = Some("text/plain".to_string());
+        response.set_chunked_body(rx).set_close_delimited();
+        tx.send(Ok(Bytes::from("abc"))).await.unwrap();
+        tx.send(Ok(Bytes::from("def"))).await.unwrap();
+        drop(tx);
+        assert!(response.is_chunked() && response.is_close_delimited());
+        let text = String::from_utf8(response.as_bytes()).unwrap();
+        assert!(!text.contains("Content-Length") && !text.contains("Transfer-Encoding"), "{}", text);
+        assert!(text.contains("\r\nConnection: close\r\n"), "{}", text);
+
+        let mut body = response.take_chunked_body().unwrap();
+        let mut out = Vec::new();
+        assert_eq!(write_close_delimited(&mut body, &mut out).await.unwrap(), 6);
+        assert_eq!(out, b"abcdef");
+
+        // 不是分块响应时不做任何处理
+        assert!(!Response::new().set_close_delimited().is_close_delimited());
+    }
+
+    /// 按响应记录的来源写出流式响应体
+    async fn streamed_body(response: &Response) -> Vec<u8> {
+        let mut body = Vec::new();
+        let source = response.stream_source().expect("响应没有流式来源");
+        let written = source.write_to(&mut body, 7).await.unwrap();
+        assert_eq!(written, response.get_content_length());
+        body
+    }
+
+    /// 多段 Range 返回 multipart/byteranges，每个分段携带各自的 Content-Range；
+    /// 无法满足的分段被丢弃，只剩一段时退回普通的 206 响应
+    #[tokio::test]
+    async fn test_multi_range_request() {
+        use crate::cache::FileCache;
+        use crate::config::Config;
+        use std::sync::Arc;
+        use tokio::sync::RwLock;
+
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("data.txt");
+        fs::write(&file_path, "0123456789").unwrap();
+        let path = file_path.to_str().unwrap();
+
+        let cache = Arc::new(RwLock::new(FileCache::from_capacity(10)));
+        let config = Config::new();
+        let request_with_range = |range: &str| {
+            let request_str = format!("GET /data.txt HTTP/1.1\r\nHost: localhost\r\nRange: {}\r\n\r\n", range);
+            Request::try_from(&request_str.into_bytes(), 0).unwrap()
+        };
+
+        let response = Response::from(path, &request_with_range("bytes=0-1,-3"), 0, &cache, &config).await;
+        assert_eq!(response.status_code(), 206);
+        assert_eq!(response.content_range, None);
+        let content_type = response.content_type.clone().unwrap();
+        let boundary = content_type.strip_prefix("multipart/byteranges; boundary=").unwrap();
+        let expected = format!(
+            "--{b}\r\nContent-Type: text/plain;charset=utf-8\r\nContent-Range: bytes 0-1/10\r\n\r\n01\r\n\
+             --{b}\r\nContent-Type: text/plain;charset=utf-8\r\nContent-Range: bytes 7-9/10\r\n\r\n789\r\n\
+             --{b}--\r\n",
+            b = boundary
+        );
+        assert_eq!(String::from_utf8(streamed_body(&response).await).unwrap(), expected);
+
+        let response = Response::from(path, &request_with_range("bytes=2-4,50-60"), 0, &cache, &config).await;
+        assert_eq!(response.status_code(), 206);
+        assert_eq!(response.content_range.as_deref(), Some("bytes 2-4/10"));
+        assert_eq!(streamed_body(&response).await, b"234");
+
+        let response = Response::from(path, &request_with_range("bytes=50-60,70-"), 0, &cache, &config).await;
+        assert_eq!(response.status_code(), 416);
+
+        // 重叠与相邻的区间被合并，合并成一段时退回普通的 206 响应
+        let response = Response::from(path, &request_with_range("bytes=0-,0-,0-"), 0, &cache, &config).await;
+        assert_eq!(response.content_range.as_deref(), Some("bytes 0-9/10"));
+        assert_eq!(streamed_body(&response).await, b"0123456789");
+        let response = Response::from(path, &request_with_range("bytes=3-4,0-1,1-2"), 0, &cache, &config).await;
+        assert_eq!(response.content_range.as_deref(), Some("bytes 0-4/10"));
+        assert_eq!(merge_spans(vec![(7, 9), (0, 1), (1, 2), (8, 8)]), vec![(0, 2), (7, 9)]);
+    }
+
+    /// 没有 Range 时返回 200，Range 格式错误时返回 416
+    #[tokio::test]
+    async fn test_malformed_range_request() {
+        use crate::cache::FileCache;
+        use crate::config::Config;
+        use std::sync::Arc;
+        use tokio::sync::RwLock;
+
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("data.txt");
+        fs::write(&file_path, "0123456789").unwrap();
+        let path = file_path.to_str().unwrap();
+
+        let cache = Arc::new(RwLock::new(FileCache::from_capacity(10)));
+        let config = Config::new();
+
+        let request_str = "GET /data.txt HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        let request = Request::try_from(&request_str.as_bytes().to_vec(), 0).unwrap();
+        let response = Response::from(path, &request, 0, &cache, &config).await;
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(response.get_content_length(), 10);
+
+        let request_str = "GET /data.txt HTTP/1.1\r\nHost: localhost\r\nRange: bytes=abc-def\r\n\r\n";
+        let request = Request::try_from(&request_str.as_bytes().to_vec(), 0).unwrap();
+        let response = Response::from(path, &request, 0, &cache, &config).await;
+        assert_eq!(response.status_code(), 416);
+        assert_eq!(response.content_range.as_deref(), Some("bytes */10"));
+    }
+
+    /// 文件与目录通过同一个辅助函数生成格式正确且互不相同的校验器
+    #[test]
+    fn test_validators_for_file_and_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("a.txt");
+        fs::write(&file_path, "hello").unwrap();
+
+        let file_meta = fs::metadata(&file_path).unwrap();
+        let dir_meta = fs::metadata(dir.path()).unwrap();
+        let (file_etag, file_modified) = validators(&file_meta, None);
+        let (dir_etag, dir_modified) = validators(&dir_meta, None);
+        let (json_etag, _) = validators(&dir_meta, Some("json"));
+
+        for etag in [&file_etag, &dir_etag, &json_etag] {
+            assert!(etag.starts_with("W/\"") && etag.ends_with('"'), "{}", etag);
+        }
+        assert!(file_etag.starts_with("W/\"5-"));
+        assert_ne!(file_etag, dir_etag);
+        assert_ne!(dir_etag, json_etag);
+        assert!(file_modified.is_some());
+        assert!(dir_modified.is_some());
+        // 同一元数据重复计算结果稳定
+        assert_eq!(validators(&file_meta, None).0, file_etag);
+    }
+
+    /// 文件和目录响应都携带 ETag 与 Last-Modified 头
+    #[tokio::test]
+    async fn test_file_and_dir_responses_carry_validators() {
+        use crate::cache::FileCache;
+        use crate::config::Config;
+        use std::sync::Arc;
+        use tokio::sync::RwLock;
+
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("a.txt");
+        fs::write(&file_path, "hello").unwrap();
+
+        let cache = Arc::new(RwLock::new(FileCache::from_capacity(10)));
+        let config = Config::new();
+        let request_str = "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        let request = Request::try_from(&request_str.as_bytes().to_vec(), 0).unwrap();
+
+        for path in [file_path.as_path(), dir.path()] {
+            let response = Response::from(path.to_str().unwrap(), &request, 0, &cache, &config).await;
+            let expected = validators(&fs::metadata(path).unwrap(), None).0;
+            let text = String::from_utf8_lossy(&response.as_bytes()).to_string();
+            assert!(text.contains(&format!("ETag: {}\r\n", expected)), "{}", text);
+            assert!(text.contains("Last-Modified: "), "{}", text);
+        }
+    }
+
+    /// If-Range 匹配时返回 206，不匹配时忽略 Range 返回完整的 200
+    #[tokio::test]
+    async fn test_if_range_request() {
+        use crate::cache::FileCache;
+        use crate::config::Config;
+        use std::sync::Arc;
+        use tokio::sync::RwLock;
+
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("data.txt");
+        fs::write(&file_path, "0123456789").unwrap();
+        let path = file_path.to_str().unwrap();
+        let metadata = fs::metadata(&file_path).unwrap();
+        let (etag, last_modified) = validators(&metadata, None);
+        let last_modified = format_date(&last_modified.unwrap());
+
+        let cache = Arc::new(RwLock::new(FileCache::from_capacity(10)));
+        let config = Config::new();
+        let request_with_if_range = |if_range: &str| {
+            let request_str = format!(
+                "GET /data.txt HTTP/1.1\r\nHost: localhost\r\nRange: bytes=0-3\r\nIf-Range: {}\r\n\r\n",
+                if_range
+            );
+            Request::try_from(&request_str.into_bytes(), 0).unwrap()
+        };
+
+        // 匹配：日期一致，返回请求的片段
+        let response = Response::from(path, &request_with_if_range(&last_modified), 0, &cache, &config).await;
+        assert_eq!(response.status_code(), 206);
+        assert_eq!(streamed_body(&response).await, b"0123");
+
+        // 不匹配：资源已变化，或 ETag 是弱校验器（强比较下弱 ETag 即使相同也不匹配），返回完整文件
+        let stale_date = "Thu, 01 Jan 1970 00:00:00 +0000";
+        let strong_of_weak = etag.trim_start_matches("W/");
+        for if_range in [etag.as_str(), strong_of_weak, "W/\"stale-etag\"", stale_date] {
+            let response = Response::from(path, &request_with_if_range(if_range), 0, &cache, &config).await;
+            assert_eq!(response.status_code(), 200, "{}", if_range);
+            assert_eq!(response.content_range, None);
+            assert_eq!(response.get_content_length(), 10);
+        }
+
+        // 开启 strong_etag 后，与当前强 ETag 一致的 If-Range 返回片段，加上 W/ 前缀则不匹配
+        let config = Config::from_toml_str("www_root = \".\"\nport = 7878\nworker_threads = 1\ncache_size = 5\nlocal = true\nstrong_etag = true\n");
+        let strong = Response::from(path, &request_with_if_range(stale_date), 0, &cache, &config).await.etag.unwrap();
+        let response = Response::from(path, &request_with_if_range(&strong), 0, &cache, &config).await;
+        assert_eq!(response.status_code(), 206, "{}", strong);
+        let weak = format!("W/{}", strong);
+        let response = Response::from(path, &request_with_if_range(&weak), 0, &cache, &config).await;
+        assert_eq!(response.status_code(), 200, "{}", weak);
+    }
+    /// 即使客户端接受 gzip，Range 请求也返回未压缩的 206，且 Content-Length 等于区间大小
+    #[tokio::test]
+    async fn test_range_response_is_never_compressed() {
+        use crate::cache::FileCache;
+        use crate::config::Config;
+        use std::sync::Arc;
+        use tokio::sync::RwLock;
+
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("page.html");
+        fs::write(&file_path, "<p>compressible</p>".repeat(100)).unwrap();
+        let path = file_path.to_str().unwrap();
+
+        let cache = Arc::new(RwLock::new(FileCache::from_capacity(10)));
+        let config = Config::new();
+        let request_str = "GET /page.html HTTP/1.1\r\nHost: localhost\r\nAccept-Encoding: gzip\r\nRange: bytes=100-299\r\n\r\n";
+        let request = Request::try_from(&request_str.as_bytes().to_vec(), 0).unwrap();
+
+        let response = Response::from(path, &request, 0, &cache, &config).await;
+        assert_eq!(response.status_code(), 206);
+        assert_eq!(response.content_encoding, None);
+        assert_eq!(response.get_content_length(), 200);
+        assert_eq!(streamed_body(&response).await.len(), 200);
+
+        let text = String::from_utf8_lossy(&response.as_bytes()).to_string();
+        assert!(text.contains("Content-Length: 200\r\n"));
+        assert!(!text.contains("Content-encoding"));
+    }
+    #[test]
+    fn test_response_extra_headers() {
+        let mut response = Response::new();
+        response.add_header("X-Response-Time", "12ms").add_header("X-Custom", "a");
+        let text = String::from_utf8(response.as_bytes()).unwrap();
+        assert!(text.contains("X-Response-Time: 12ms\r\nX-Custom: a\r\n\r\n"), "{}", text);
+    }
+    /// 资源类型不在 Accept 范围内时返回 406，通配符与宽松模式照常返回内容
+    #[tokio::test]
+    async fn test_not_acceptable() {
+        use crate::cache::FileCache;
+        use crate::config::Config;
+        use std::sync::Arc;
+        use tokio::sync::RwLock;
+
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("page.html");
+        fs::write(&file_path, "<p>hi</p>").unwrap();
+        let path = file_path.to_str().unwrap();
+
+        let cache = Arc::new(RwLock::new(FileCache::from_capacity(10)));
+        let with_accept = |accept: &str| {
+            let request_str = format!("GET /page.html HTTP/1.1\r\nHost: localhost\r\nAccept: {}\r\n\r\n", accept);
+            Request::try_from(&request_str.into_bytes(), 0).unwrap()
+        };
+
+        let config = Config::new();
+        let response = Response::from(path, &with_accept("image/png"), 0, &cache, &config).await;
+        assert_eq!(response.status_code(), 406);
+        let response = Response::from(dir.path().to_str().unwrap(), &with_accept("image/png"), 0, &cache, &config).await;
+        assert_eq!(response.status_code(), 406);
+
+        for accept in ["*/*", "text/*", "image/png, */*;q=0.1"] {
+            let response = Response::from(path, &with_accept(accept), 0, &cache, &config).await;
+            assert_eq!(response.status_code(), 200, "{}", accept);
+        }
+
+        let config = Config::from_toml_str("www_root = \".\"\nport = 7878\nworker_threads = 1\ncache_size = 5\nlocal = true\nlenient_accept = true\n");
+        let response = Response::from(path, &with_accept("image/png"), 0, &cache, &config).await;
+        assert_eq!(response.status_code(), 200);
+    }
+    #[tokio::test]
+    async fn test_markdown_rendering() {
+        use crate::cache::FileCache;
+        use crate::config::Config;
+        use std::sync::Arc;
+        use tokio::sync::RwLock;
+
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("README.md");
+        fs::write(&file_path, "# Hello\n\n*world*\n").unwrap();
+        let path = file_path.to_str().unwrap();
+
+        let cache = Arc::new(RwLock::new(FileCache::from_capacity(10)));
+        let request_str = "GET /README.md HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        let request = Request::try_from(&request_str.as_bytes().to_vec(), 0).unwrap();
+
+        let config = Config::new();
+        let response = Response::from(path, &request, 0, &cache, &config).await;
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(response.content_type.as_deref(), Some("text/html;charset=utf-8"));
+        let body = String::from_utf8_lossy(response.content.as_ref().unwrap()).into_owned();
+        assert!(body.contains("<title>README.md</title>"));
+        assert!(body.contains("<h1>Hello</h1>"));
+        assert!(body.contains("<em>world</em>"));
+        // 渲染结果进入缓存
+        assert_eq!(cache.read().await.len(), 1);
+
+        let config = Config::from_toml_str("www_root = \".\"\nport = 7878\nworker_threads = 1\ncache_size = 5\nlocal = true\nrender_markdown = false\n");
+        let response = Response::from(path, &request, 0, &cache, &config).await;
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(response.content_type.as_deref(), Some("text/markdown;charset=utf-8"));
+        assert_eq!(response.content.as_deref(), Some(&b"# Hello\n\n*world*\n"[..]));
+    }
+    /// 目录列表按 ?page=&per_page= 分页，不同分页各自缓存且缓存命中后仍带分页响应头
+    #[tokio::test]
+    async fn test_paginated_directory_listing() {
+        use crate::cache::FileCache;
+        use crate::config::Config;
+        use std::sync::Arc;
+        use tokio::sync::RwLock;
+
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["a.txt", "b.txt", "c.txt", "d.txt", "e.txt"] {
+            fs::write(dir.path().join(name), "x").unwrap();
+        }
+        let path = dir.path().to_str().unwrap();
+        let cache = Arc::new(RwLock::new(FileCache::from_capacity(10)));
+        let config = Config::new();
+        let get = |target: &str, accept: &str| {
+            let request_str = format!("GET {} HTTP/1.1\r\nHost: localhost\r\nAccept: {}\r\n\r\n", target, accept);
+            Request::try_from(&request_str.into_bytes(), 0).unwrap()
+        };
+
+        let request = get("/?page=2&per_page=2", "application/json");
+        for _ in 0..2 {
+            let response = Response::from(path, &request, 0, &cache, &config).await;
+            let entries: serde_json::Value = serde_json::from_slice(response.content.as_ref().unwrap()).unwrap();
+            let names: Vec<_> = entries.as_array().unwrap().iter().map(|e| e["name"].as_str().unwrap().to_string()).collect();
+            assert_eq!(names, ["c.txt", "d.txt"]);
+            let text = String::from_utf8_lossy(&response.as_bytes()).into_owned();
+            assert!(text.contains("X-Total-Count: 5\r\n"), "{}", text);
+            assert!(text.contains(r#"<?page=3&per_page=2>; rel="next""#), "{}", text);
+        }
+        assert_eq!(cache.read().await.len(), 1);
+        // 缓存的内容就是当前页本身，条目总数另行记录
+        let key = cache.read().await.keys().remove(0);
+        let cached = cache.read().await.peek(&key).cloned().unwrap();
+        assert!(serde_json::from_slice::<serde_json::Value>(&cached).is_ok());
+        assert_eq!(cache.read().await.item_count(&key, fs::metadata(path).unwrap().modified().unwrap()), Some(5));
+
+        let response = Response::from(path, &get("/?page=3&per_page=2", "text/html"), 0, &cache, &config).await;
+        let body = String::from_utf8_lossy(response.content.as_ref().unwrap()).into_owned();
+        assert!(body.contains("e.txt") && !body.contains("a.txt"));
+        assert!(body.contains(r#"<a href="?page=2&per_page=2">上一页</a>"#));
+        assert!(!body.contains("下一页"));
+        assert_eq!(cache.read().await.len(), 2);
+
+        // 默认每页条目数为 0 时不分页，这也是默认值：内置文件管理器需要完整的目录列表
+        assert_eq!(Config::new().listing_per_page(), 0);
+        let config = Config::from_toml_str("www_root = \".\"\nport = 7878\nworker_threads = 1\ncache_size = 5\nlocal = true\nlisting_per_page = 0\n");
+        let response = Response::from(path, &get("/", "application/json"), 0, &cache, &config).await;
+        let entries: serde_json::Value = serde_json::from_slice(response.content.as_ref().unwrap()).unwrap();
+        assert_eq!(entries.as_array().unwrap().len(), 5);
+        assert!(!String::from_utf8_lossy(&response.as_bytes()).contains("X-Total-Count"));
+    }
+    /// 不同排序方式的目录列表分别缓存，互不覆盖
+    #[tokio::test]
+    async fn test_sorted_directory_listing() {
+        use crate::cache::FileCache;
+        use crate::config::Config;
+        use std::sync::Arc;
+        use tokio::sync::RwLock;
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("big.txt"), "xxxxxxxx").unwrap();
+        fs::write(dir.path().join("small.txt"), "x").unwrap();
+        let path = dir.path().to_str().unwrap();
+        let cache = Arc::new(RwLock::new(FileCache::from_capacity(10)));
+        let config = Config::new();
+        let names = |response: &Response| -> Vec<String> {
+            let entries: serde_json::Value = serde_json::from_slice(response.content.as_ref().unwrap()).unwrap();
+            entries.as_array().unwrap().iter().map(|e| e["name"].as_str().unwrap().to_string()).collect()
+        };
+        let get = |target: &str| {
+            let request_str = format!("GET {} HTTP/1.1\r\nHost: localhost\r\nAccept: application/json\r\n\r\n", target);
+            Request::try_from(&request_str.into_bytes(), 0).unwrap()
+        };
+
+        let response = Response::from(path, &get("/"), 0, &cache, &config).await;
+        assert_eq!(names(&response), ["big.txt", "small.txt"]);
+        let response = Response::from(path, &get("/?sort=size"), 0, &cache, &config).await;
+        assert_eq!(names(&response), ["small.txt", "big.txt"]);
+        let response = Response::from(path, &get("/?sort=size&order=desc"), 0, &cache, &config).await;
+        assert_eq!(names(&response), ["big.txt", "small.txt"]);
+        assert_eq!(cache.read().await.len(), 3);
+    }
+    /// autoindex 关闭时，目录的 HTML 与 JSON 列表都返回 403
+    #[tokio::test]
+    async fn test_autoindex_toggle() {
+        use crate::cache::FileCache;
+        use crate::config::Config;
+        use std::sync::Arc;
+        use tokio::sync::RwLock;
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "a").unwrap();
+        let path = dir.path().to_str().unwrap();
+        let cache = Arc::new(RwLock::new(FileCache::from_capacity(10)));
+        let get = |accept: &str| {
+            let request_str = format!("GET / HTTP/1.1\r\nHost: localhost\r\nAccept: {}\r\n\r\n", accept);
+            Request::try_from(&request_str.into_bytes(), 0).unwrap()
+        };
+
+        let enabled = Config::new();
+        let disabled = Config::from_toml_str("www_root = \".\"\nport = 7878\nworker_threads = 1\ncache_size = 5\nlocal = true\nautoindex = false\n");
+        for accept in ["text/html", "application/json"] {
+            let response = Response::from(path, &get(accept), 0, &cache, &enabled).await;
+            assert_eq!(response.status_code(), 200, "{}", accept);
+            let response = Response::from(path, &get(accept), 0, &cache, &disabled).await;
+            assert_eq!(response.status_code(), 403, "{}", accept);
+            assert_eq!(response.information(), "Forbidden");
+        }
+    }
+    /// 开启 listing_json_summary 后 JSON 列表变为 { entries, summary } 对象
+    #[tokio::test]
+    async fn test_json_listing_summary() {
+        use crate::cache::FileCache;
+        use crate::config::Config;
+        use std::sync::Arc;
+        use tokio::sync::RwLock;
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "aaaa").unwrap();
+        fs::write(dir.path().join("b.txt"), "bb").unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        let path = dir.path().to_str().unwrap();
+        let request_str = "GET / HTTP/1.1\r\nHost: localhost\r\nAccept: application/json\r\n\r\n";
+        let request = Request::try_from(&request_str.as_bytes().to_vec(), 0).unwrap();
+
+        let cache = Arc::new(RwLock::new(FileCache::from_capacity(10)));
+        let response = Response::from(path, &request, 0, &cache, &Config::new()).await;
+        let json: serde_json::Value = serde_json::from_slice(response.content.as_ref().unwrap()).unwrap();
+        assert_eq!(json.as_array().unwrap().len(), 3);
+
+        let cache = Arc::new(RwLock::new(FileCache::from_capacity(10)));
+        let config = Config::from_toml_str("www_root = \".\"\nport = 7878\nworker_threads = 1\ncache_size = 5\nlocal = true\nlisting_json_summary = true\n");
+        let response = Response::from(path, &request, 0, &cache, &config).await;
+        let json: serde_json::Value = serde_json::from_slice(response.content.as_ref().unwrap()).unwrap();
+        assert_eq!(json["entries"].as_array().unwrap().len(), 3);
+        assert_eq!(json["summary"]["files"], 2);
+        assert_eq!(json["summary"]["dirs"], 1);
+        assert_eq!(json["summary"]["raw_total_size"], 6);
+    }
+    /// .txt 响应追加默认字符集，.html 保持表中已有的字符集
+    #[tokio::test]
+    async fn test_default_charset_for_text_files() {
+        use crate::cache::FileCache;
+        use crate::config::Config;
+        use std::sync::Arc;
+        use tokio::sync::RwLock;
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "text").unwrap();
+        fs::write(dir.path().join("a.html"), "<p>html</p>").unwrap();
+        let cache = Arc::new(RwLock::new(FileCache::from_capacity(10)));
+        let request = Request::try_from(&b"GET /a HTTP/1.1\r\nHost: localhost\r\n\r\n".to_vec(), 0).unwrap();
+        let content_type = |response: &Response| response.content_type.clone().unwrap();
+
+        let config = Config::new();
+        let txt = dir.path().join("a.txt");
+        let html = dir.path().join("a.html");
+        let response = Response::from(txt.to_str().unwrap(), &request, 0, &cache, &config).await;
+        assert_eq!(content_type(&response), "text/plain;charset=utf-8");
+        let response = Response::from(html.to_str().unwrap(), &request, 0, &cache, &config).await;
+        assert_eq!(content_type(&response), "text/html;charset=utf-8");
+
+        let config = Config::from_toml_str("www_root = \".\"\nport = 7878\nworker_threads = 1\ncache_size = 5\nlocal = true\ndefault_charset = \"\"\n");
+        let response = Response::from(txt.to_str().unwrap(), &request, 0, &cache, &config).await;
+        assert_eq!(content_type(&response), "text/plain");
+    }
+    /// 没有扩展名的文件按内容嗅探类型：UTF-8 文本为 text/plain，二进制为 application/octet-stream
+    #[tokio::test]
+    async fn test_sniff_extensionless_files() {
+        use crate::cache::FileCache;
+        use crate::config::Config;
+        use std::sync::Arc;
+        use tokio::sync::RwLock;
+
+        let dir = tempfile::tempdir().unwrap();
+        let license = dir.path().join("LICENSE");
+        fs::write(&license, "MIT License\n\n版权所有 (c) 2026\n").unwrap();
+        let binary = dir.path().join("blob");
+        fs::write(&binary, [0x7f, b'E', b'L', b'F', 0, 0, 1, 2, 0xff]).unwrap();
+        let image = dir.path().join("logo");
+        fs::write(&image, b"\x89PNG\r\n\x1a\n....").unwrap();
+
+        let cache = Arc::new(RwLock::new(FileCache::from_capacity(10)));
+        let request = Request::try_from(&b"GET /LICENSE HTTP/1.1\r\nHost: localhost\r\n\r\n".to_vec(), 0).unwrap();
+        let config = Config::new();
+        let serve = |path: &std::path::Path, config: &Config| {
+            let path = path.to_str().unwrap().to_string();
+            let cache = Arc::clone(&cache);
+            let request = request.clone();
+            let config = config.clone();
+            async move { Response::from(&path, &request, 0, &cache, &config).await }
+        };
+
+        let response = serve(&license, &config).await;
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(response.content_type.as_deref(), Some("text/plain;charset=utf-8"));
+        let response = serve(&binary, &config).await;
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(response.content_type.as_deref(), Some("application/octet-stream"));
+        let response = serve(&image, &config).await;
+        assert_eq!(response.content_type.as_deref(), Some("image/png"));
+
+        let config = Config::from_toml_str("www_root = \".\"\nport = 7878\nworker_threads = 1\ncache_size = 5\nlocal = true\nsniff_extensionless = false\n");
+        assert_eq!(serve(&license, &config).await.status_code(), 404);
+    }
+    /// 缓存锁不会跨越磁盘 I/O 持有：其他任务持有读锁时，命中缓存的文件与目录请求照常完成，
+    /// 响应返回后也不会残留写锁
+    #[tokio::test]
+    async fn test_cache_lock_not_held_across_io() {
+        use crate::cache::FileCache;
+        use crate::config::Config;
+        use std::sync::Arc;
+        use std::time::Duration;
+        use tokio::sync::RwLock;
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "a").unwrap();
+        let file = dir.path().join("a.txt");
+        let cache = Arc::new(RwLock::new(FileCache::from_capacity(10)));
+        let request = Request::try_from(&b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n".to_vec(), 0).unwrap();
+        let config = Config::new();
+
+        // 预热：文件与目录列表进入缓存，返回后写锁已释放
+        for path in [file.to_str().unwrap(), dir.path().to_str().unwrap()] {
+            Response::from(path, &request, 0, &cache, &config).await;
+            assert!(cache.try_write().is_ok());
+        }
+        assert_eq!(cache.read().await.len(), 2);
+
+        let reader = cache.read().await;
+        for path in [file.to_str().unwrap(), dir.path().to_str().unwrap()] {
+            let response = tokio::time::timeout(
+                Duration::from_secs(5),
+                Response::from(path, &request, 0, &cache, &config),
+            )
+            .await
+            .expect("命中缓存的请求不应等待其他读者释放锁");
+            assert_eq!(response.status_code(), 200);
+        }
+        drop(reader);
+    }
+    /// 开启 strong_etag 时按内容计算强 ETag，并缓存结果；内容变化后 ETag 随之变化
+    #[tokio::test]
+    async fn test_strong_etag() {
+        use crate::cache::FileCache;
+        use crate::config::Config;
+        use std::sync::Arc;
+        use tokio::sync::RwLock;
+
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("a.txt");
+        fs::write(&file_path, "hello").unwrap();
+        let path = file_path.to_str().unwrap();
+
+        let cache = Arc::new(RwLock::new(FileCache::from_capacity(10)));
+        let config = Config::from_toml_str("www_root = \".\"\nport = 7878\nworker_threads = 1\ncache_size = 5\nlocal = true\nstrong_etag = true\nchunk_size = 2\n");
+        let request_str = "GET /a.txt HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        let request = Request::try_from(&request_str.as_bytes().to_vec(), 0).unwrap();
+
+        let first = Response::from(path, &request, 0, &cache, &config).await.etag.unwrap();
+        assert!(first.starts_with("\"5-"), "{}", first);
+        let modified = fs::metadata(&file_path).unwrap().modified().unwrap();
+        assert!(cache.read().await.content_hash(path, modified).is_some());
+        let second = Response::from(path, &request, 0, &cache, &config).await.etag.unwrap();
+        assert_eq!(first, second);
+
+        // 压缩后的表示带编码后缀，条件请求按协商出的编码比较
+        let gzip_request = |extra: &str| {
+            let raw = format!("GET /a.txt HTTP/1.1\r\nHost: localhost\r\nAccept-Encoding: gzip\r\n{}\r\n", extra);
+            Request::try_from(&raw.into_bytes(), 0).unwrap()
+        };
+        let gzipped = Response::from(path, &gzip_request(""), 0, &cache, &config).await;
+        assert_eq!(gzipped.content_encoding, Some(HttpEncoding::Gzip));
+        let gzip_etag = gzipped.etag.unwrap();
+        assert_eq!(gzip_etag, format!("{}-gzip\"", first.trim_end_matches('"')));
+        let revalidated = Response::from(path, &gzip_request(&format!("If-None-Match: {}\r\n", gzip_etag)), 0, &cache, &config).await;
+        assert_eq!(revalidated.status_code(), 304);
+        assert_eq!(revalidated.etag.as_deref(), Some(gzip_etag.as_str()));
+        let identity = format!("If-None-Match: {}\r\n", first);
+        assert_eq!(Response::from(path, &gzip_request(&identity), 0, &cache, &config).await.status_code(), 200);
+
+        fs::write(&file_path, "world").unwrap();
+        let file = fs::File::options().write(true).open(&file_path).unwrap();
+        file.set_modified(modified + std::time::Duration::from_secs(1)).unwrap();
+        let third = Response::from(path, &request, 0, &cache, &config).await.etag.unwrap();
+        assert!(third.starts_with("\"5-"), "{}", third);
+        assert_ne!(first, third);
+    }
+    /// HTML（PHP 输出）与 Markdown 的 HEAD 响应携带与 GET 一致的 Content-Type 与 Content-Length
+    #[tokio::test]
+    async fn test_head_for_dynamic_content() {
+        use crate::cache::FileCache;
+        use crate::config::Config;
+        use std::sync::Arc;
+        use tokio::sync::RwLock;
+
+        let html = "<html><body>hello</body></html>";
+        let response = Response::from_html(html, vec![], 0, true, CompressionLevels::default());
+        assert_eq!(response.content_type.as_deref(), Some("text/html;charset=utf-8"));
+        assert_eq!(response.content_length, html.len() as u64);
+        assert!(response.content.is_none());
+
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("readme.md");
+        fs::write(&file_path, "# Title\n\nbody\n").unwrap();
+        let path = file_path.to_str().unwrap();
+        let cache = Arc::new(RwLock::new(FileCache::from_capacity(10)));
+        let config = Config::new();
+        let request_of = |method: &str| {
+            let raw = format!("{} /readme.md HTTP/1.1\r\nHost: localhost\r\n\r\n", method);
+            Request::try_from(&raw.into_bytes(), 0).unwrap()
+        };
+        let get = Response::from(path, &request_of("GET"), 0, &cache, &config).await;
+        let head = Response::from(path, &request_of("HEAD"), 0, &cache, &config).await;
+        assert_eq!(head.content_type, get.content_type);
+        assert_eq!(head.content_length, get.content_length);
+        assert!(head.content_length > 0);
+        assert!(head.content.is_none());
+    }
+    /// If-None-Match 与 If-Modified-Since 满足时返回不带响应体和 Content-Length 的 304
+    #[tokio::test]
+    async fn test_conditional_get_not_modified() {
+        use crate::cache::FileCache;
+        use crate::config::Config;
+        use std::sync::Arc;
+        use tokio::sync::RwLock;
+
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("a.txt");
+        fs::write(&file_path, "hello").unwrap();
+        let path = file_path.to_str().unwrap();
+        let (etag, last_modified) = validators(&fs::metadata(&file_path).unwrap(), None);
+        let last_modified = last_modified.unwrap();
+
+        let cache = Arc::new(RwLock::new(FileCache::from_capacity(10)));
+        let config = Config::new();
+        let status_with = |headers: &str| {
+            let raw = format!("GET /a.txt HTTP/1.1\r\nHost: localhost\r\n{}\r\n", headers);
+            let request = Request::try_from(&raw.into_bytes(), 0).unwrap();
+            let cache = cache.clone();
+            let config = config.clone();
+            async move { Response::from(path, &request, 0, &cache, &config).await }
+        };
+
+        let later = format_date(&(last_modified + chrono::TimeDelta::try_seconds(10).unwrap()));
+        let earlier = format_date(&(last_modified - chrono::TimeDelta::try_seconds(10).unwrap()));
+        for headers in [
+            format!("If-None-Match: {}\r\n", etag),
+            format!("If-None-Match: \"other\", {}\r\n", etag),
+            "If-None-Match: *\r\n".to_string(),
+            format!("If-Modified-Since: {}\r\n", format_date(&last_modified)),
+            format!("If-Modified-Since: {}\r\n", later),
+        ] {
+            let response = status_with(&headers).await;
+            assert_eq!(response.status_code(), 304, "{}", headers);
+            let text = String::from_utf8_lossy(&response.as_bytes()).to_string();
+            assert!(text.ends_with("\r\n\r\n"), "{}", text);
+            assert!(!text.contains("Content-Length"), "{}", text);
+            assert!(text.contains(&format!("ETag: {}\r\n", etag)), "{}", text);
+        }
+
+        // 不匹配的 If-None-Match 优先于满足条件的 If-Modified-Since
+        for headers in [
+            "If-None-Match: \"other\"\r\n".to_string(),
+            format!("If-Modified-Since: {}\r\n", earlier),
+            format!("If-None-Match: \"other\"\r\nIf-Modified-Since: {}\r\n", later),
+            "If-Modified-Since: not a date\r\n".to_string(),
+        ] {
+            let response = status_with(&headers).await;
+            assert_eq!(response.status_code(), 200, "{}", headers);
+            assert_eq!(response.content.as_deref(), Some(&b"hello"[..]));
+        }
+    }
+    /// 对可压缩数据，更高的压缩级别得到更小（或相等）的输出
+    #[test]
+    fn test_compression_levels_affect_output_size() {
+        let data: Vec<u8> = (0..2000u32)
+            .flat_map(|i| format!("line {} of some fairly repetitive text {}\n", i, i % 7).into_bytes())
+            .collect();
+
+        for mode in [HttpEncoding::Gzip, HttpEncoding::Deflate] {
+            let fast = compress(data.clone(), Some(mode), CompressionLevels { gzip: 1, brotli: 11 }).unwrap();
+            let best = compress(data.clone(), Some(mode), CompressionLevels { gzip: 9, brotli: 11 }).unwrap();
+            assert!(best.len() <= fast.len(), "{:?}: {} > {}", mode, best.len(), fast.len());
+        }
+
+        let fast = compress(data.clone(), Some(HttpEncoding::Br), CompressionLevels { gzip: 6, brotli: 1 }).unwrap();
+        let best = compress(data, Some(HttpEncoding::Br), CompressionLevels { gzip: 6, brotli: 11 }).unwrap();
+        assert!(best.len() <= fast.len(), "br: {} > {}", best.len(), fast.len());
+    }
+    /// PUT 上传后以所在目录为前缀清除缓存：文件本身与目录列表的各个变体都不再返回旧内容
+    #[tokio::test]
+    async fn test_upload_invalidates_cache() {
+        use crate::cache::FileCache;
+        use crate::config::Config;
+        use std::sync::Arc;
+        use tokio::sync::RwLock;
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "old").unwrap();
+        let dir_path = dir.path().to_str().unwrap();
+        let file_path = dir.path().join("a.txt");
+        let file_path = file_path.to_str().unwrap();
+        let cache = Arc::new(RwLock::new(FileCache::from_capacity(10)));
+        let config = Config::from_toml_str(&format!(
+            "www_root = {:?}\nport = 7878\nworker_threads = 1\ncache_size = 10\nlocal = true\nuploads = true\nallowed_methods = [\"GET\", \"PUT\"]",
+            dir_path
+        ));
+        let request = |raw: &str| Request::try_from(&raw.as_bytes().to_vec(), 0).unwrap();
+
+        for accept in ["text/html", "application/json"] {
+            let raw = format!("GET / HTTP/1.1\r\nHost: localhost\r\nAccept: {}\r\n\r\n", accept);
+            assert_eq!(Response::from(dir_path, &request(&raw), 0, &cache, &config).await.status_code(), 200);
+        }
+        let get = request("GET /a.txt HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        assert_eq!(Response::from(file_path, &get, 0, &cache, &config).await.status_code(), 200);
+        assert_eq!(cache.read().await.len(), 3);
+
+        let put = request("PUT /a.txt HTTP/1.1\r\nHost: localhost\r\nContent-Length: 3\r\n\r\nnew");
+        assert_eq!(Response::from(file_path, &put, 0, &cache, &config).await.status_code(), 204);
+        assert_eq!(cache.read().await.len(), 0);
+        let response = Response::from(file_path, &get, 0, &cache, &config).await;
+        assert_eq!(response.content.as_deref(), Some(&b"new"[..]));
+    }
+
+    /// 上传直接以 405 拒绝 PHP 脚本；同一连接 ID 的并发上传使用不同的临时文件，互不干扰
+    #[tokio::test]
+    async fn test_upload_rejects_scripts_and_isolates_temp_files() {
+        use crate::cache::FileCache;
+        use std::sync::Arc;
+        use tokio::sync::RwLock;
+
+        let dir = tempfile::tempdir().unwrap();
+        let cache = Arc::new(RwLock::new(FileCache::from_capacity(10)));
+        let put = |body: &str| {
+            let raw = format!("PUT /a.txt HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}", body.len(), body);
+            Request::try_from(&raw.into_bytes(), 0).unwrap()
+        };
+
+        let script = dir.path().join("shell.php");
+        let response = Response::from_upload(script.to_str().unwrap(), &put("x"), 0, &cache).await;
+        assert_eq!(response.status_code(), 405);
+        assert!(!script.exists());
+
+        let target = dir.path().join("a.txt");
+        let target = target.to_str().unwrap();
+        let (first, second) = (put(&"1".repeat(4096)), put(&"2".repeat(4096)));
+        let (a, b) = tokio::join!(
+            Response::from_upload(target, &first, 7, &cache),
+            Response::from_upload(target, &second, 7, &cache)
+        );
+        assert!(a.status_code() < 300 && b.status_code() < 300, "{} {}", a.status_code(), b.status_code());
+        let content = fs::read_to_string(target).unwrap();
+        assert!(content == "1".repeat(4096) || content == "2".repeat(4096));
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+    /// 405 的 Allow 头按目标资源计算：启用 POST 时 PHP 脚本接受 POST，开启上传时文件接受 PUT，目录两者都不接受
+    #[tokio::test]
+    async fn test_405_allow_depends_on_resource() {
+        use crate::cache::FileCache;
+        use crate::config::Config;
+        use std::sync::Arc;
+        use tokio::sync::RwLock;
+
+        use HttpRequestMethod::*;
+        let enabled = [Get, Head, Options];
+        assert_eq!(Response::allowed_methods_for("/srv/a.txt", false, &enabled, false), vec![Get, Head, Options]);
+        assert_eq!(Response::allowed_methods_for("/srv/form.php", false, &enabled, false), vec![Get, Head, Options]);
+        assert_eq!(Response::allowed_methods_for("/srv/form.php", false, &[Get, Post], false), vec![Get, Post]);
+        assert_eq!(Response::allowed_methods_for("/srv/dir.php", true, &[Get, Post], false), vec![Get]);
+        assert_eq!(Response::allowed_methods_for("/srv/a.txt", false, &[Get, Post], false), vec![Get]);
+        assert_eq!(Response::allowed_methods_for("*", false, &[Get, Post], false), vec![Get, Post]);
+        assert_eq!(Response::allowed_methods_for("/srv/a.txt", false, &[Get, Put], true), vec![Get, Put]);
+        assert_eq!(Response::allowed_methods_for("/srv/a.txt", false, &[Get, Put], false), vec![Get]);
+        assert_eq!(Response::allowed_methods_for("/srv/dir", true, &[Get, Put], true), vec![Get]);
+        assert_eq!(Response::allowed_methods_for("/srv/x.php", false, &[Get, Put], true), vec![Get]);
+        assert_eq!(Response::allowed_methods_for("/srv/x.PHP", false, &[Get, Put], true), vec![Get]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("a.txt");
+        fs::write(&file_path, "a").unwrap();
+        let cache = Arc::new(RwLock::new(FileCache::from_capacity(10)));
+        let config = Config::new();
+        let raw = b"POST /a.txt HTTP/1.1\r\nHost: localhost\r\nContent-Length: 1\r\n\r\nx".to_vec();
+        let request = Request::try_from(&raw, 0).unwrap();
+
+        let response = Response::from(file_path.to_str().unwrap(), &request, 0, &cache, &config).await;
+        assert_eq!(response.status_code(), 405);
+        let bytes = response.as_bytes();
+        let text = String::from_utf8_lossy(&bytes);
+        assert!(text.contains("\r\nAllow: GET, HEAD, OPTIONS\r\n"), "{}", text);
+    }
+    /// 206 响应同时携带 Content-Range 与 Accept-Ranges；关闭 Range 支持后忽略 Range 返回完整内容
+    #[tokio::test]
+    async fn test_partial_content_headers() {
+        use crate::cache::FileCache;
+        use crate::config::Config;
+        use std::sync::Arc;
+        use tokio::sync::RwLock;
+
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("data.bin");
+        fs::write(&file_path, vec![7u8; 1000]).unwrap();
+        let path = file_path.to_str().unwrap();
+        let cache = Arc::new(RwLock::new(FileCache::from_capacity(10)));
+        let raw = b"GET /data.bin HTTP/1.1\r\nHost: localhost\r\nRange: bytes=0-99\r\n\r\n".to_vec();
+        let request = Request::try_from(&raw, 0).unwrap();
+
+        let response = Response::from(path, &request, 0, &cache, &Config::new()).await;
+        assert_eq!(response.status_code(), 206);
+        let bytes = response.as_bytes();
+        let text = String::from_utf8_lossy(&bytes);
+        assert!(text.contains("\r\nContent-Range: bytes 0-99/1000\r\n"), "{}", text);
+        assert!(text.contains("\r\nAccept-Ranges: bytes\r\n"), "{}", text);
+
+        let config = Config::from_toml_str(
+            "www_root = \".\"\nport = 7878\nworker_threads = 1\ncache_size = 5\nlocal = true\nenable_range_requests = false\n",
+        );
+        let response = Response::from(path, &request, 0, &cache, &config).await;
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(response.get_content_length(), 1000);
+        assert_eq!(response.accept_ranges, None);
+        assert_eq!(response.content_range, None);
+    }
+    /// 响应默认携带 Connection: close，协商保持连接后改为 keep-alive
+    #[test]
+    fn test_connection_header() {
+        let mut response = Response::new();
+        let text = String::from_utf8(response.as_bytes()).unwrap();
+        assert!(text.contains("\r\nConnection: close\r\n"), "{}", text);
+
+        response.set_keep_alive(true);
+        let text = String::from_utf8(response.as_bytes()).unwrap();
+        assert!(text.contains("\r\nConnection: keep-alive\r\n"), "{}", text);
+        assert!(!text.contains("Connection: close"));
+    }
+    /// from_bytes 原样发送二进制内容，压缩后可以还原，HEAD 请求只给出长度
+    #[test]
+    fn test_from_bytes_binary_content() {
+        let data: Vec<u8> = (0..=255u8).cycle().take(4096).collect();
+        let request_with = |raw: &str| Request::try_from(&raw.as_bytes().to_vec(), 0).unwrap();
+
+        let plain = request_with("GET /blob HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        let response = Response::from_bytes(data.clone(), "application/octet-stream", &plain, 0);
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(response.content_type.as_deref(), Some("application/octet-stream"));
+        assert_eq!(response.content_encoding, None);
+        assert_eq!(response.content.as_deref(), Some(&data[..]));
+        assert!(response.as_bytes().ends_with(&data));
+
+        let gzip = request_with("GET /blob HTTP/1.1\r\nHost: localhost\r\nAccept-Encoding: gzip\r\n\r\n");
+        let response = Response::from_bytes(Bytes::from(data.clone()), "application/octet-stream", &gzip, 0);
+        assert_eq!(response.content_encoding, Some(HttpEncoding::Gzip));
+        let mut decoded = Vec::new();
+        let mut decoder = flate2::read::GzDecoder::new(&response.content.as_ref().unwrap()[..]);
+        io::Read::read_to_end(&mut decoder, &mut decoded).unwrap();
+        assert_eq!(decoded, data);
+
+        let head = request_with("HEAD /blob HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        let response = Response::from_bytes(data.clone(), "application/octet-stream", &head, 0);
+        assert_eq!(response.get_content_length(), data.len() as u64);
+        assert!(response.content.is_none());
+    }
+    /// 每个 Cookie 输出为单独的 Set-Cookie 行，含特殊字符的值经请求解析后还原
+    #[test]
+    fn test_cookie_round_trip() {
+        let value = "a b;c=d,\"e\"\\f 中文%";
+        let attrs = CookieAttributes {
+            path: Some("/".to_string()),
+            max_age: Some(3600),
+            http_only: true,
+            secure: true,
+            same_site: Some(SameSite::Lax),
+        };
+        let mut response = Response::new();
+        response
+            .add_cookie("session", value, &attrs)
+            .add_cookie("theme", "dark", &CookieAttributes::default())
+            .add_cookie("bad name", "x", &CookieAttributes::default());
+        let text = String::from_utf8(response.as_bytes()).unwrap();
+        let set_cookies: Vec<&str> = text
+            .lines()
+            .filter_map(|line| line.strip_prefix("Set-Cookie: "))
+            .collect();
+        assert_eq!(set_cookies.len(), 2, "{}", text);
+        assert!(set_cookies[0].ends_with("; Path=/; Max-Age=3600; HttpOnly; Secure; SameSite=Lax"));
+        assert_eq!(set_cookies[1], "theme=dark");
+
+        // 浏览器回传时只带 name=value 部分
+        let pairs: Vec<&str> = set_cookies.iter().map(|c| c.split(';').next().unwrap()).collect();
+        let raw = format!("GET / HTTP/1.1\r\nHost: localhost\r\nCookie: {}\r\n\r\n", pairs.join("; "));
+        let request = Request::try_from(&raw.into_bytes(), 0).unwrap();
+        assert_eq!(request.cookie("session").as_deref(), Some(value));
+        assert_eq!(request.cookie("theme").as_deref(), Some("dark"));
+        assert_eq!(request.cookie("Theme"), None);
+        assert_eq!(request.cookie("missing"), None);
+    }
+    /// `*` 与 identity;q=0 影响最终选用的压缩编码
+    #[test]
+    fn test_decide_encoding_wildcard_and_identity() {
+        let decide = |accept_encoding: &str| {
+            let raw = format!("GET / HTTP/1.1\r\nHost: localhost\r\nAccept-Encoding: {}\r\n\r\n", accept_encoding);
+            let request = Request::try_from(&raw.into_bytes(), 0).unwrap();
+            decide_encoding(request.accept_encoding())
+        };
+        assert_eq!(decide("*"), Some(HttpEncoding::Br));
+        assert_eq!(decide("gzip, *;q=0"), Some(HttpEncoding::Gzip));
+        assert_eq!(decide("identity;q=0"), Some(HttpEncoding::Gzip));
+        assert_eq!(decide("br, identity;q=0"), Some(HttpEncoding::Br));
+        assert_eq!(decide("gzip, br;q=0.5"), Some(HttpEncoding::Gzip));
+        assert_eq!(decide("identity"), None);
+    }
+    /// 动态来源的响应头值中的 CR/LF 被去掉，不会注入新的响应头
+    #[test]
+    fn test_header_values_are_sanitized() {
+        let request = Request::try_from(&b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n".to_vec(), 0).unwrap();
+        let mut response = Response::from_text("ok", "text/plain", &request, 0);
+        response
+            .add_header("Location", "/next\r\nX-Injected: evil")
+            .set_server_header(Some("srv\r\nX-Server-Injected: 1"));
+        let text = String::from_utf8(response.as_bytes()).unwrap();
+        assert!(text.contains("\r\nLocation: /nextX-Injected: evil\r\n"));
+        assert!(text.contains("\r\nServer: srvX-Server-Injected: 1\r\n"));
+        assert!(!text.contains("\r\nX-Injected"));
+        assert!(!text.contains("\r\nX-Server-Injected"));
+    }
+    /// 以 sh 充当 PHP 解释器：脚本运行超过 script_timeout_secs 时返回 504，解释器路径无效时返回 500
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_php_timeout_and_missing_interpreter() {
+        use crate::cache::FileCache;
+        use crate::config::Config;
+        use std::sync::Arc;
+        use tokio::sync::RwLock;
+
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("slow.php");
+        fs::write(&script, "sleep 5").unwrap();
+        let config_with = |extra: &str| {
+            Config::from_toml_str(&format!(
+                "www_root = {:?}\nport = 7878\nworker_threads = 1\ncache_size = 5\nlocal = true\n{}",
+                dir.path().to_str().unwrap(),
+                extra
+            ))
+        };
+        let cache = Arc::new(RwLock::new(FileCache::from_capacity(5)));
+        let request = Request::try_from(&b"GET /slow.php HTTP/1.1\r\nHost: localhost\r\n\r\n".to_vec(), 0).unwrap();
+        let script = script.to_str().unwrap();
+
+        let config = config_with("script_timeout_secs = 1\nphp_path = \"sh\"");
+        let response = Response::from(script, &request, 0, &cache, &config).await;
+        assert_eq!(response.status_code(), 504);
+
+        let config = config_with("php_path = \"/nonexistent/php8.2\"");
+        let response = Response::from(script, &request, 0, &cache, &config).await;
+        assert_eq!(response.status_code(), 500);
+    }
+
+    /// 脚本输出的分帧与逐跳响应头被丢弃，不与服务器生成的头部重复
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_php_cgi_framing_headers_dropped() {
+        use crate::cache::FileCache;
+        use crate::config::Config;
+        use std::sync::Arc;
+        use tokio::sync::RwLock;
+
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("headers.php");
+        fs::write(
+            &script,
+            "printf 'Content-Length: 999\\r\\nConnection: keep-alive\\r\\nDate: yesterday\\r\\nX-Custom: 1\\r\\n\\r\\nhi'\n",
+        )
+        .unwrap();
+        let config = Config::from_toml_str(&format!(
+            "www_root = {:?}\nport = 7878\nworker_threads = 1\ncache_size = 5\nlocal = true\nphp_path = \"sh\"",
+            dir.path().to_str().unwrap()
+        ));
+        let cache = Arc::new(RwLock::new(FileCache::from_capacity(5)));
+        let request = Request::try_from(&b"GET /headers.php HTTP/1.1\r\nHost: localhost\r\n\r\n".to_vec(), 0).unwrap();
+        let response = Response::from(script.to_str().unwrap(), &request, 0, &cache, &config).await;
+        let text = String::from_utf8(response.as_bytes()).unwrap();
+        assert_eq!(text.matches("Content-Length:").count(), 1, "{}", text);
+        assert!(text.contains("\r\nContent-Length: 2\r\n"), "{}", text);
+        assert_eq!(text.matches("Connection:").count(), 1, "{}", text);
+        assert!(!text.contains("yesterday"), "{}", text);
+        assert!(text.contains("\r\nX-Custom: 1\r\n"), "{}", text);
+        assert!(text.ends_with("\r\n\r\nhi"), "{}", text);
+    }
+
+    /// 查询字符串、请求方法与请求头以 CGI 环境变量传给脚本，脚本输出的响应头被解析
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_php_cgi_variables_round_trip() {
+        use crate::cache::FileCache;
+        use crate::config::Config;
+        use std::sync::Arc;
+        use tokio::sync::RwLock;
+
+        // 以 sh 充当解释器，脚本相当于 PHP 中的 `echo $_GET['x'];`
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("echo.php");
+        fs::write(
+            &script,
+            concat!(
+                "printf 'Content-Type: text/plain\\r\\nX-Method: %s\\r\\nX-Agent: %s\\r\\nX-Server: %s:%s\\r\\n' \"$REQUEST_METHOD\" \"$HTTP_X_AGENT\" \"$SERVER_NAME\" \"$SERVER_PORT\"\n",
+                "printf 'X-Proxy: [%s]\\r\\nX-Inherited: [%s]\\r\\nX-Path: %s\\r\\n\\r\\n' \"$HTTP_PROXY\" \"$CARGO_MANIFEST_DIR\" \"$PATH\"\n",
+                "for pair in $(echo \"$QUERY_STRING\" | tr '&' ' '); do\n",
+                "  case \"$pair\" in x=*) printf 'x=%s;' \"${pair#x=}\" ;; esac\n",
+                "done\n",
+                "cat\n",
+            ),
+        )
+        .unwrap();
+        let config = Config::from_toml_str(&format!(
+            "www_root = {:?}\nport = 7878\nworker_threads = 1\ncache_size = 5\nlocal = true\nphp_path = \"sh\"\nallowed_methods = [\"GET\", \"POST\"]",
+            dir.path().to_str().unwrap()
+        ));
+        let cache = Arc::new(RwLock::new(FileCache::from_capacity(5)));
+        let script = script.to_str().unwrap();
+
+        let raw = b"GET /echo.php?y=2&x=hello;ls HTTP/1.1\r\nHost: localhost:8080\r\nX-Agent: probe\r\nProxy: http://evil.example\r\n\r\n".to_vec();
+        let request = Request::try_from(&raw, 0).unwrap();
+        let response = Response::from(script, &request, 0, &cache, &config).await;
+        assert_eq!(response.status_code(), 200);
+        let text = String::from_utf8(response.as_bytes()).unwrap();
+        assert!(text.contains("\r\nContent-Type: text/plain\r\n"), "{}", text);
+        assert!(text.contains("\r\nX-Method: GET\r\n"), "{}", text);
+        assert!(text.contains("\r\nX-Agent: probe\r\n"), "{}", text);
+        assert!(text.contains("\r\nX-Server: localhost:7878\r\n"), "{}", text);
+        // 不传递 Proxy 头（httpoxy），也不继承服务器进程的环境变量（cargo test 会设置 CARGO_MANIFEST_DIR）
+        assert!(text.contains("\r\nX-Proxy: []\r\n"), "{}", text);
+        assert!(text.contains("\r\nX-Inherited: []\r\n"), "{}", text);
+        assert!(text.contains(&format!("\r\nX-Path: {}\r\n", crate::util::CGI_PATH)), "{}", text);
+        assert!(text.ends_with("\r\n\r\nx=hello;ls;"), "{}", text);
+
+        let raw = b"POST /echo.php HTTP/1.1\r\nHost: localhost\r\nContent-Length: 9\r\n\r\nname=test".to_vec();
+        let request = Request::try_from(&raw, 0).unwrap();
+        let response = Response::from(script, &request, 0, &cache, &config).await;
+        let text = String::from_utf8(response.as_bytes()).unwrap();
+        assert!(text.contains("\r\nX-Method: POST\r\n"), "{}", text);
+        assert!(text.ends_with("\r\n\r\nname=test"), "{}", text);
+    }
+    /// 目录无法读取时 from_dir 返回错误（调用方据此返回 500），而不是 panic
+    #[tokio::test]
+    async fn test_unreadable_dir_returns_error() {
+        use crate::cache::FileCache;
+        use crate::config::Config;
+        use std::sync::Arc;
+        use tokio::sync::RwLock;
+
+        let dir = tempfile::tempdir().unwrap();
+        let gone = dir.path().join("gone");
+        let cache = Arc::new(RwLock::new(FileCache::from_capacity(5)));
+        let config = Config::new();
+        let request = Request::try_from(&b"GET /gone/ HTTP/1.1\r\nHost: localhost\r\n\r\n".to_vec(), 0).unwrap();
+
+        let result = Response::from_dir(gone.to_str().unwrap(), &request, 0, &cache, false, false, &config).await;
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+
+    /// 文件无法读取时 from_file_async 返回错误而不是 panic，Response::from 据此返回 404 / 500
+    #[tokio::test]
+    async fn test_unreadable_file_returns_error() {
+        use crate::cache::FileCache;
+        use crate::config::Config;
+        use std::sync::Arc;
+        use tokio::sync::RwLock;
+
+        let dir = tempfile::tempdir().unwrap();
+        let cache = Arc::new(RwLock::new(FileCache::from_capacity(5)));
+        let config = Config::new();
+        let request = Request::try_from(&b"GET /a.txt HTTP/1.1\r\nHost: localhost\r\n\r\n".to_vec(), 0).unwrap();
+
+        // 文件在路由之后被删除
+        let gone = dir.path().join("gone.txt");
+        let result = Response::from_file_async(gone.to_str().unwrap(), &request, 0, &cache, false, "text/plain", &config).await;
+        assert!(matches!(result, Err(Exception::FileNotFound)));
+        let response = Response::from(gone.to_str().unwrap(), &request, 0, &cache, &config).await;
+        assert_eq!(response.status_code(), 404);
+
+        // 元数据可以获取但内容无法读取（目录无法按文件读取）
+        let path = dir.path().to_str().unwrap();
+        let result = Response::from_file_async(path, &request, 0, &cache, false, "text/plain", &config).await;
+        assert!(matches!(result, Err(Exception::FileUnreadable)));
+    }
+    #[tokio::test]
+    async fn test_content_disposition_for_download_extensions() {
+        use crate::cache::FileCache;
+        use crate::config::Config;
+        use std::sync::Arc;
+        use tokio::sync::RwLock;
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("data.csv"), "a,b\n1,2\n").unwrap();
+        fs::write(dir.path().join("报表.txt"), "text").unwrap();
+        let config = Config::from_toml_str(&format!(
+            "www_root = {:?}\nport = 7878\nworker_threads = 1\ncache_size = 5\nlocal = true\ndownload_extensions = [\"CSV\"]\n",
+            dir.path().to_str().unwrap(),
+        ));
+        let cache = Arc::new(RwLock::new(FileCache::from_capacity(5)));
+        let get = |target: &str| {
+            let raw = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", target);
+            Request::try_from(&raw.into_bytes(), 0).unwrap()
+        };
+        let serve = |name: &str, request: Request| {
+            let path = dir.path().join(name);
+            let (cache, config) = (Arc::clone(&cache), &config);
+            async move {
+                let response = Response::from(path.to_str().unwrap(), &request, 0, &cache, config).await;
+                String::from_utf8_lossy(&response.as_bytes()).into_owned()
+            }
+        };
+
+        // 配置的扩展名（不区分大小写）强制下载
+        let text = serve("data.csv", get("/data.csv")).await;
+        assert!(text.contains("Content-Disposition: attachment; filename=\"data.csv\"\r\n"), "{}", text);
+
+        // 其它文件默认内联显示，?download=1 可强制下载，非 ASCII 文件名按 RFC 5987 编码
+        let text = serve("报表.txt", get("/%E6%8A%A5%E8%A1%A8.txt")).await;
+        assert!(!text.contains("Content-Disposition"), "{}", text);
+        let text = serve("报表.txt", get("/%E6%8A%A5%E8%A1%A8.txt?download=1")).await;
+        assert!(
+            text.contains("Content-Disposition: attachment; filename=\"__.txt\"; filename*=UTF-8''%E6%8A%A5%E8%A1%A8.txt\r\n"),
+            "{}",
+            text
+        );
+    }
+    /// 起点超出文件末尾的 Range 返回 416，携带 Accept-Ranges 与 `bytes */LEN`，且不带内容与编码
+    #[tokio::test]
+    async fn test_unsatisfiable_range_headers() {
+        use crate::cache::FileCache;
+        use crate::config::Config;
+        use std::sync::Arc;
+        use tokio::sync::RwLock;
+
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("data.txt");
+        fs::write(&file_path, "0123456789".repeat(10)).unwrap();
+        let path = file_path.to_str().unwrap();
+
+        let cache = Arc::new(RwLock::new(FileCache::from_capacity(10)));
+        let request_str = "GET /data.txt HTTP/1.1\r\nHost: localhost\r\nAccept-Encoding: gzip\r\nRange: bytes=999999-\r\n\r\n";
+        let request = Request::try_from(&request_str.as_bytes().to_vec(), 0).unwrap();
+        let response = Response::from(path, &request, 0, &cache, &Config::new()).await;
+
+        assert_eq!(response.status_code(), 416);
+        assert_eq!(response.content_encoding, None);
+        let text = String::from_utf8(response.as_bytes()).unwrap();
+        assert!(text.starts_with("HTTP/1.1 416 Range Not Satisfiable\r\n"), "{}", text);
+        assert!(text.contains("Accept-Ranges: bytes\r\n"), "{}", text);
+        assert!(text.contains("Content-Range: bytes */100\r\n"), "{}", text);
+        assert!(text.contains("Content-Length: 0\r\n"), "{}", text);
+        assert!(!text.contains("Content-encoding"), "{}", text);
+        assert!(text.ends_with("\r\n\r\n"), "{}", text);
+    }
+    #[test]
+    fn test_if_none_match_star_list_and_weak() {
+        let etag = "W/\"64-1a2b\"";
+        assert!(if_none_match_matches("*", etag));
+        assert!(if_none_match_matches(" * ", "\"strong\""));
+
+        // 列表中任一成员匹配即可，引号内的逗号不拆分
+        assert!(if_none_match_matches("\"a,b\", \"other\",W/\"64-1a2b\"", etag));
+        assert!(!if_none_match_matches("\"a,b\", \"other\"", etag));
+        assert!(if_none_match_matches("\"x\", \"a,b\"", "\"a,b\""));
+
+        // 弱比较忽略双方的 W/ 前缀
+        assert!(if_none_match_matches("\"64-1a2b\"", etag));
+        assert!(if_none_match_matches("W/\"strong\"", "\"strong\""));
+        assert!(!if_none_match_matches("W/\"64-1a2c\"", etag));
+
+        // 格式错误的成员之后不再匹配
+        assert!(!if_none_match_matches("64-1a2b", etag));
+        assert!(!if_none_match_matches("\"unterminated, W/\"64-1a2b\"", etag));
+    }
+    /// FastCGI 应用输出的 Content-Length 被丢弃，分块响应只以 Transfer-Encoding 分帧
+    #[tokio::test]
+    async fn test_php_via_fastcgi_drops_framing_headers() {
+        use crate::cache::FileCache;
+        use crate::config::Config;
+        use std::sync::Arc;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::sync::RwLock;
+
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("index.php");
+        fs::write(&script, "<?php echo 1;").unwrap();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut conn, _) = listener.accept().await.unwrap();
+            // 读到空的 STDIN 记录（请求 ID 为 1）为止
+            let mut request = Vec::new();
+            while !request.ends_with(&[1, 5, 0, 1, 0, 0, 0, 0]) {
+                let mut buf = [0u8; 1024];
+                let n = conn.read(&mut buf).await.unwrap();
+                assert!(n > 0);
+                request.extend_from_slice(&buf[..n]);
+            }
+            let stdout = b"Content-Length: 999\r\nTransfer-Encoding: gzip\r\nX-Custom: 1\r\n\r\nhello";
+            let mut out = vec![1, 6, 0, 1, 0, stdout.len() as u8, 0, 0];
+            out.extend_from_slice(stdout);
+            out.extend_from_slice(&[1, 3, 0, 1, 0, 8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+            conn.write_all(&out).await.unwrap();
+        });
+        let config = Config::from_toml_str(&format!(
+            "www_root = {:?}\nport = 7878\nworker_threads = 1\ncache_size = 5\nlocal = true\nfastcgi_address = \"{}\"",
+            dir.path().to_str().unwrap(),
+            address
+        ));
+        let cache = Arc::new(RwLock::new(FileCache::from_capacity(5)));
+        let request = Request::try_from(&b"GET /index.php HTTP/1.1\r\nHost: localhost\r\n\r\n".to_vec(), 0).unwrap();
+        let response = Response::from(script.to_str().unwrap(), &request, 0, &cache, &config).await;
+        assert!(response.is_chunked());
+        let text = String::from_utf8(response.as_bytes()).unwrap();
+        assert!(!text.contains("Content-Length"), "{}", text);
+        assert_eq!(text.matches("Transfer-Encoding:").count(), 1, "{}", text);
+        assert!(text.contains("\r\nTransfer-Encoding: chunked\r\n"), "{}", text);
+        assert!(text.contains("\r\nX-Custom: 1\r\n"), "{}", text);
+    }
+    /// 配置了 FastCGI 应用时不再启动解释器；应用不可达时返回 502
+    #[tokio::test]
+    async fn test_php_via_unreachable_fastcgi() {
+        use crate::cache::FileCache;
+        use crate::config::Config;
+        use std::sync::Arc;
+        use tokio::sync::RwLock;
+
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("index.php");
+        fs::write(&script, "<?php echo 1;").unwrap();
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        drop(listener);
+        let config = Config::from_toml_str(&format!(
+            "www_root = {:?}\nport = 7878\nworker_threads = 1\ncache_size = 5\nlocal = true\nphp_path = \"/nonexistent/php\"\nfastcgi_address = \"{}\"",
+            dir.path().to_str().unwrap(),
+            address
+        ));
+        let cache = Arc::new(RwLock::new(FileCache::from_capacity(5)));
+        let request = Request::try_from(&b"GET /index.php HTTP/1.1\r\nHost: localhost\r\n\r\n".to_vec(), 0).unwrap();
+        let response = Response::from(script.to_str().unwrap(), &request, 0, &cache, &config).await;
+        assert_eq!(response.status_code(), 502);
+    }
 }
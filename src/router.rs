@@ -0,0 +1,665 @@
+// Copyright (c) 2026 shaneyale (shaneyale86@gmail.com)
+// All rights reserved.
+
+//! # 路由模块
+//!
+//! 提供两部分能力：
+//!
+//! - [`Router`]：可编程路由表。把路径模式映射到异步处理器闭包，使本库的使用者可以在静态文件之外编写动态接口。
+//!   路径模式支持三种形式：
+//!   - 精确匹配：`/health`
+//!   - 前缀匹配：`/api/*`，匹配 `/api` 本身及其下的所有路径，剩余部分通过 `params.get("*")` 取得
+//!   - 参数匹配：`/files/:name`，以 `:` 开头的路径段匹配任意一个非空路径段，按名称取得
+//!
+//!   同一路径有多个模式匹配时，精确匹配优先于参数匹配，参数匹配优先于前缀匹配，前缀之间较长者优先；
+//!   优先级相同时按注册顺序选择。
+//! - [`resolve_path`]：静态文件路由，把请求路径映射到文档根目录下的物理路径；
+//!   PUT 上传的目标文件由 [`resolve_upload_path`] 映射。
+//!
+//! ```
+//! use webserver::{Response, Router};
+//!
+//! let router = Router::new()
+//!     .get("/files/:name", |req, params| async move {
+//!         let name = params.get("name").unwrap_or_default().to_string();
+//!         Response::from_text(&name, "text/plain;charset=utf-8", &req, req.id())
+//!     });
+//! assert_eq!(router.len(), 1);
+//! ```
+
+use crate::{
+    config::Config,
+    exception::Exception,
+    param::HttpRequestMethod,
+    request::Request,
+    response::Response,
+    util::{is_ignored, normalize_path, percent_decode},
+};
+
+use log::{debug, warn};
+
+use std::{
+    fs,
+    future::Future,
+    path::{Component, Path, PathBuf},
+    pin::Pin,
+    sync::Arc,
+};
+
+/// 处理器返回的装箱 Future。
+pub type HandlerFuture = Pin<Box<dyn Future<Output = Response> + Send>>;
+
+/// 类型擦除后的处理器。处理器在多个连接任务之间共享，因此要求 `Send + Sync`。
+type Handler = Arc<dyn Fn(Request, Params) -> HandlerFuture + Send + Sync>;
+
+/// 路径模式匹配得到的参数，按模式中的名称取值；前缀模式的剩余路径以 `*` 为名称。
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Params {
+    values: Vec<(String, String)>,
+}
+
+impl Params {
+    /// 取得名为 `name` 的参数。
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// 按模式中出现的顺序遍历所有参数。
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.values.iter().map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+
+    /// 参数的数量。
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// 是否没有任何参数。
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+/// 路径模式中的一段。
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Literal(String),
+    Param(String),
+}
+
+/// 注册时解析好的路径模式。
+#[derive(Debug, Clone, PartialEq)]
+enum Pattern {
+    /// 精确匹配，保存规范化后的路径。
+    Exact(String),
+    /// 前缀匹配，保存去掉结尾 `/*` 的前缀（根前缀为空字符串）。
+    Prefix(String),
+    /// 含 `:name` 参数段的模式。
+    Segments(Vec<Segment>),
+}
+
+impl Pattern {
+    /// 解析路径模式。
+    ///
+    /// # Panics
+    /// 模式不以 `/` 开头时 panic：模式在编写代码时确定，属于编程错误。
+    fn parse(pattern: &str) -> Self {
+        assert!(pattern.starts_with('/'), "路由模式必须以 / 开头: {:?}", pattern);
+        if let Some(prefix) = pattern.strip_suffix("/*") {
+            return Pattern::Prefix(prefix.trim_end_matches('/').to_string());
+        }
+        let segments: Vec<Segment> = pattern
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(|segment| match segment.strip_prefix(':') {
+                Some(name) => Segment::Param(name.to_string()),
+                None => Segment::Literal(segment.to_string()),
+            })
+            .collect();
+        if segments.iter().any(|segment| matches!(segment, Segment::Param(_))) {
+            Pattern::Segments(segments)
+        } else {
+            Pattern::Exact(normalize_path(pattern).unwrap_or_else(|_| pattern.to_string()))
+        }
+    }
+
+    /// 用规范化后的路径匹配模式，匹配成功时返回提取的参数。
+    fn matches(&self, path: &str) -> Option<Params> {
+        match self {
+            Pattern::Exact(exact) => (exact == path).then(Params::default),
+            Pattern::Prefix(prefix) => {
+                let rest = path.strip_prefix(prefix.as_str())?;
+                let rest = match rest {
+                    "" => "",
+                    _ => rest.strip_prefix('/')?,
+                };
+                Some(Params {
+                    values: vec![("*".to_string(), rest.to_string())],
+                })
+            }
+            Pattern::Segments(segments) => {
+                let parts: Vec<&str> = path.split('/').filter(|part| !part.is_empty()).collect();
+                if parts.len() != segments.len() {
+                    return None;
+                }
+                let mut params = Params::default();
+                for (segment, part) in segments.iter().zip(parts) {
+                    match segment {
+                        Segment::Literal(literal) if literal == part => {}
+                        Segment::Literal(_) => return None,
+                        Segment::Param(name) => params.values.push((name.clone(), part.to_string())),
+                    }
+                }
+                Some(params)
+            }
+        }
+    }
+
+    /// 匹配优先级，数值越大越优先。
+    fn rank(&self) -> (u8, usize) {
+        match self {
+            Pattern::Exact(_) => (2, 0),
+            Pattern::Segments(_) => (1, 0),
+            Pattern::Prefix(prefix) => (0, prefix.len()),
+        }
+    }
+}
+
+/// 一条路由：方法为 `None` 时匹配所有方法。
+#[derive(Clone)]
+struct Route {
+    method: Option<HttpRequestMethod>,
+    pattern: Pattern,
+    handler: Handler,
+}
+
+impl Route {
+    /// 路由是否接受该请求方法。GET 路由同时处理 HEAD 请求，响应体由 `Response` 按方法省略。
+    fn accepts(&self, method: HttpRequestMethod) -> bool {
+        match self.method {
+            None => true,
+            Some(HttpRequestMethod::Get) => matches!(method, HttpRequestMethod::Get | HttpRequestMethod::Head),
+            Some(expected) => expected == method,
+        }
+    }
+}
+
+/// 可编程路由表，以链式调用注册路由。
+///
+/// 服务器在中间件的 `before` 钩子、内置端点与反向代理之后查询路由表，
+/// 没有匹配的路由时回退到静态文件（[`resolve_path`]）。
+#[derive(Clone, Default)]
+pub struct Router {
+    routes: Vec<Route>,
+}
+
+impl Router {
+    /// 构造一个空的路由表。
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 注册一个匹配所有请求方法的路由。
+    ///
+    /// # Panics
+    /// `pattern` 不以 `/` 开头时 panic。
+    pub fn route<H, F>(self, pattern: &str, handler: H) -> Self
+    where
+        H: Fn(Request, Params) -> F + Send + Sync + 'static,
+        F: Future<Output = Response> + Send + 'static,
+    {
+        self.add(None, pattern, handler)
+    }
+
+    /// 注册只处理 `method` 的路由。
+    ///
+    /// # Panics
+    /// `pattern` 不以 `/` 开头时 panic。
+    pub fn on<H, F>(self, method: HttpRequestMethod, pattern: &str, handler: H) -> Self
+    where
+        H: Fn(Request, Params) -> F + Send + Sync + 'static,
+        F: Future<Output = Response> + Send + 'static,
+    {
+        self.add(Some(method), pattern, handler)
+    }
+
+    /// 注册 GET 路由（同时处理 HEAD），等价于 `on(HttpRequestMethod::Get, ..)`。
+    pub fn get<H, F>(self, pattern: &str, handler: H) -> Self
+    where
+        H: Fn(Request, Params) -> F + Send + Sync + 'static,
+        F: Future<Output = Response> + Send + 'static,
+    {
+        self.on(HttpRequestMethod::Get, pattern, handler)
+    }
+
+    /// 注册 POST 路由，等价于 `on(HttpRequestMethod::Post, ..)`。
+    pub fn post<H, F>(self, pattern: &str, handler: H) -> Self
+    where
+        H: Fn(Request, Params) -> F + Send + Sync + 'static,
+        F: Future<Output = Response> + Send + 'static,
+    {
+        self.on(HttpRequestMethod::Post, pattern, handler)
+    }
+
+    fn add<H, F>(mut self, method: Option<HttpRequestMethod>, pattern: &str, handler: H) -> Self
+    where
+        H: Fn(Request, Params) -> F + Send + Sync + 'static,
+        F: Future<Output = Response> + Send + 'static,
+    {
+        let handler: Handler = Arc::new(move |req, params| Box::pin(handler(req, params)) as HandlerFuture);
+        self.routes.push(Route {
+            method,
+            pattern: Pattern::parse(pattern),
+            handler,
+        });
+        self
+    }
+
+    /// 路由表中路由的数量。
+    pub fn len(&self) -> usize {
+        self.routes.len()
+    }
+
+    /// 路由表是否为空。
+    pub fn is_empty(&self) -> bool {
+        self.routes.is_empty()
+    }
+
+    /// 把请求交给匹配的处理器。
+    ///
+    /// 路径与 [`resolve_path`] 一样先去掉查询字符串、解码百分号编码并规范化后再匹配；无法解码或规范化的路径视为不匹配，
+    /// 交由静态文件路由返回 400。没有任何模式匹配时返回 `None`；模式匹配但方法都不接受时返回 405，
+    /// `Allow` 头列出这些路由接受的方法。
+    pub async fn handle(&self, request: &Request) -> Option<Response> {
+        if self.routes.is_empty() {
+            return None;
+        }
+        let path = request.path().split('?').next().unwrap_or_default();
+        let path = percent_decode(path)
+            .ok()
+            .filter(|decoded| !decoded.chars().any(|c| c.is_ascii_control()))
+            .and_then(|decoded| normalize_path(&decoded).ok())?;
+
+        let matched: Vec<(&Route, Params)> = self
+            .routes
+            .iter()
+            .filter_map(|route| route.pattern.matches(&path).map(|params| (route, params)))
+            .collect();
+        if matched.is_empty() {
+            return None;
+        }
+
+        // max_by_key 在优先级相同时返回最后一个，因此逆序查找以保证先注册者优先
+        let method = request.method();
+        let best = matched
+            .iter()
+            .rev()
+            .filter(|(route, _)| route.accepts(method))
+            .max_by_key(|(route, _)| route.pattern.rank());
+        match best {
+            Some((route, params)) => {
+                debug!("[ID{}]路由表匹配路径{}", request.id(), path);
+                Some((route.handler)(request.clone(), params.clone()).await)
+            }
+            None => {
+                // 匹配所有方法的路由一定会被选中，走到这里的路由都限定了方法
+                let mut allow = Vec::new();
+                for route_method in matched.iter().filter_map(|(route, _)| route.method) {
+                    let methods = match route_method {
+                        HttpRequestMethod::Get => vec![HttpRequestMethod::Get, HttpRequestMethod::Head],
+                        other => vec![other],
+                    };
+                    for method in methods {
+                        if !allow.contains(&method) {
+                            allow.push(method);
+                        }
+                    }
+                }
+                warn!("[ID{}]路径{}的路由不接受{}方法，返回405", request.id(), path, method);
+                Some(Response::response_405_with_allow(request, request.id(), allow))
+            }
+        }
+    }
+}
+
+/// 把请求路径转换为规范化的绝对路径（以 `/` 开头），供 [`resolve_path`] 与 [`resolve_upload_path`] 拼接到根目录下。
+///
+/// 去掉查询字符串、解码百分号编码并经过 `normalize_path` 规范化；查询字符串过长返回 `QueryTooLong`，
+/// 可能越过根目录的路径返回 `InvalidPath`，匹配 `listing_ignore` 的路径返回 `FileNotFound`。
+fn normalize_request_path(path: &str, id: u128, config: &Config) -> Result<String, Exception> {
+    // 去掉查询字符串并解码百分号编码（如目录列表生成的 `my%20file.txt`）
+    let (path, query) = match path.split_once('?') {
+        Some((path, query)) => (path, query),
+        None => (path, ""),
+    };
+
+    // 超长的查询字符串会撑大日志与内存，直接拒绝
+    if query.len() > config.limits().max_query_length() {
+        warn!(
+            "[ID{}]查询字符串长度{}超过上限{}",
+            id,
+            query.len(),
+            config.limits().max_query_length()
+        );
+        return Err(Exception::QueryTooLong);
+    }
+    let decoded = percent_decode(path)?;
+    // 百分号编码形式的控制字符（如 `%00`）解码后同样拒绝
+    if decoded.chars().any(|c| c.is_ascii_control()) {
+        warn!("[ID{}]请求的路径解码后包含控制字符", id);
+        return Err(Exception::InvalidPath);
+    }
+
+    // 路径规范化：折叠重复斜杠、消解 `.`/`..`，使等价路径映射到同一文件与缓存键
+    let normalized = normalize_path(&decoded)?;
+    let path = normalized.as_str();
+
+    // 拼接到根目录之前，确认相对路径只由普通路径段组成：反斜杠（包括 `%5c`）在 Windows 上是路径分隔符，
+    // 盘符等前缀会使 `join` 直接替换根目录，都可能绕过上面的 `..` 检查
+    let relative = Path::new(&path[1..]);
+    if path.contains('\\') || !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+        warn!("[ID{}]请求的路径{}可能越过根目录，返回400", id, path);
+        return Err(Exception::InvalidPath);
+    }
+
+    // 目录列表中隐藏的文件（如 .env、.git/）同样不允许直接访问。顶层的 `/.well-known/`（RFC 8615，
+    // 如 ACME 验证与 security.txt）本身不受限制，其中的文件仍按 `listing_ignore` 检查
+    let checked = match path.strip_prefix("/.well-known") {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => rest,
+        _ => path,
+    };
+    if checked
+        .split('/')
+        .any(|segment| !segment.is_empty() && is_ignored(segment, config.listing_ignore()))
+    {
+        warn!("[ID{}]请求的路径{}匹配listing_ignore，返回404", id, path);
+        return Err(Exception::FileNotFound);
+    }
+    Ok(normalized)
+}
+
+/// # 静态文件路由
+/// 
+/// 将抽象的 URI 映射到服务器本地的文件系统路径。
+/// 
+/// ## 路由规则：
+/// 1. `spa_prefix`（默认 `/browser`）-> 前端单页应用（SPA），前缀本身及其下找不到文件的路径
+///    都返回 `spa_fallback` 入口文件，支持 History 模式的前端路由。
+/// 2. `*` -> 特殊通配符匹配。
+/// 3. 静态文件映射 -> 将规范化后的 URI 依次拼接到 `roots`（即 `www_root` 或匹配的虚拟主机配置的各个根目录）下查找，
+///    返回第一个存在的匹配。
+/// 4. 目录 -> 非 JSON 请求依次尝试 `index_files`（虚拟主机可以单独配置）中的索引文件，均不存在时返回目录本身，
+///    由 `Response::from` 决定生成列表还是 403。
+///
+/// 路径中任意一段匹配 `listing_ignore` 时返回 `FileNotFound`（顶层的 `/.well-known` 除外）。未开启 `follow_symlinks` 时，
+/// 经由符号链接解析到根目录之外的静态文件路径（包括索引文件）同样返回 `FileNotFound`。
+///
+/// 除 `*` 外，所有路径都会先去掉查询字符串（超过 `max_query_length` 时返回 `QueryTooLong`）、解码百分号编码，再经过 `normalize_path` 规范化，
+/// 越过根目录的 `..`（包括 `%2e%2e`、`..%2f` 等编码形式）、含有反斜杠（包括 `%5c`）以及解码后含有控制字符（如 `%00`）的路径
+/// 返回 `InvalidPath`。
+pub async fn resolve_path(
+    path: &str,
+    id: u128,
+    roots: &[String],
+    index_files: &[String],
+    is_json: bool,
+    config: &Config,
+) -> Result<PathBuf, Exception> {
+    debug!("[ID{}]路由匹配开始: path='{}', json_mode={}", id, path, is_json);
+
+    // 通配符处理（不参与路径规范化）
+    if path == "*" {
+        return Ok(PathBuf::from("*"));
+    }
+
+    let normalized = normalize_request_path(path, id, config)?;
+    let path = normalized.as_str();
+    let relative = Path::new(&path[1..]);
+
+    // favicon 与 robots.txt 可以指向 www_root 之外的自定义文件
+    let custom = match path {
+        "/favicon.ico" => config.favicon_path(),
+        "/robots.txt" => config.robots_path(),
+        _ => None,
+    };
+    if let Some(custom) = custom {
+        let custom = PathBuf::from(custom);
+        if custom.is_file() {
+            return Ok(custom);
+        }
+        warn!("[ID{}]配置的文件{}不存在，回退到www_root", id, custom.display());
+    }
+
+    // SPA 入口路由：前缀本身直接返回入口文件，JSON 请求返回入口文件所在的目录
+    // 前缀统一去掉结尾的 `/`，与规范化后的路径比较；前缀为 `/` 时回退覆盖整个站点
+    let spa = config
+        .spa_prefix()
+        .map(|prefix| prefix.trim_end_matches('/'))
+        .zip(config.spa_fallback());
+    if let Some((prefix, fallback)) = spa {
+        if path == prefix {
+            let fallback = PathBuf::from(fallback);
+            if is_json {
+                if let Some(spa_dir) = fallback.parent().filter(|p| p.is_dir()) {
+                    return Ok(spa_dir.to_path_buf());
+                }
+            }
+            if fallback.exists() {
+                return Ok(fallback);
+            } else {
+                return Err(Exception::FileNotFound);
+            }
+        }
+    }
+
+    // 标准静态资源路径转换逻辑
+    for root in roots {
+        // 相对路径只含普通路径段，拼接结果在词法上必然位于当前根目录之内；经由符号链接越界的情况在下面检查
+        let full_path = Path::new(root).join(relative);
+
+        // 安全检查与路径存在性校验
+        let path_str_ref = match full_path.to_str() {
+            Some(s) => s,
+            None => return Err(Exception::InvalidPath),
+        };
+
+        debug!("[ID{}]映射物理路径：{}", id, path_str_ref);
+
+        if full_path.exists() {
+            // 目录请求优先返回索引文件；JSON 请求需要的是目录列表本身
+            let mut resolved = full_path;
+            if resolved.is_dir() && !is_json {
+                if let Some(index_path) = find_index_file(&resolved, index_files) {
+                    debug!("[ID{}]目录命中索引文件：{:?}", id, index_path);
+                    resolved = index_path;
+                }
+            }
+            // 不跟随符号链接时，经由链接指向根目录之外的路径一律视为不存在
+            if !config.follow_symlinks() && escapes_root(&resolved, root) {
+                warn!("[ID{}]路径{:?}经由符号链接指向根目录{}之外，返回404", id, resolved, root);
+                return Err(Exception::FileNotFound);
+            }
+            return Ok(resolved);
+        }
+    }
+
+    // SPA (Single Page Application) 回退机制：
+    // 如果在 spa_prefix 下找不到文件，则返回入口文件，交由前端路由处理
+    if let Some((prefix, fallback)) = spa {
+        if path == prefix || path.starts_with(&format!("{}/", prefix)) {
+            let fallback = PathBuf::from(fallback);
+            if fallback.exists() {
+                debug!("[ID{}]SPA 路由触发：返回入口文件{:?}", id, fallback);
+                return Ok(fallback);
+            }
+        }
+    }
+    Err(Exception::FileNotFound)
+}
+
+
+/// # 上传路由
+///
+/// 把 PUT 请求的路径映射为上传的目标文件。路径的规范化与校验同 [`resolve_path`]，但不查找索引文件，
+/// 也不经过 SPA 回退与 favicon、robots.txt 的自定义路径：
+///
+/// - 某个根目录中已存在该路径时返回它（目录同样返回，由 `Response::from` 以 405 拒绝）；
+/// - 否则返回第一个根目录下的新文件路径，所在目录必须已经存在，不会自动创建中间目录。
+///
+/// 所在目录不存在，或未开启 `follow_symlinks` 时经由符号链接指向根目录之外，返回 `FileNotFound`。
+pub async fn resolve_upload_path(path: &str, id: u128, roots: &[String], config: &Config) -> Result<PathBuf, Exception> {
+    let normalized = normalize_request_path(path, id, config)?;
+    let relative = Path::new(&normalized[1..]);
+    let existing = roots
+        .iter()
+        .map(|root| (root, Path::new(root).join(relative)))
+        .find(|(_, full_path)| full_path.exists());
+    if let Some((root, full_path)) = existing {
+        if !config.follow_symlinks() && escapes_root(&full_path, root) {
+            warn!("[ID{}]路径{:?}经由符号链接指向根目录{}之外，返回404", id, full_path, root);
+            return Err(Exception::FileNotFound);
+        }
+        return Ok(full_path);
+    }
+
+    let root = roots.first().ok_or(Exception::FileNotFound)?;
+    let target = Path::new(root).join(relative);
+    let dir = match target.parent() {
+        Some(dir) if dir.is_dir() => dir,
+        _ => {
+            warn!("[ID{}]上传路径{}所在的目录不存在，返回404", id, normalized);
+            return Err(Exception::FileNotFound);
+        }
+    };
+    if !config.follow_symlinks() && escapes_root(dir, root) {
+        warn!("[ID{}]上传目录{:?}经由符号链接指向根目录{}之外，返回404", id, dir, root);
+        return Err(Exception::FileNotFound);
+    }
+    debug!("[ID{}]上传的目标文件：{:?}", id, target);
+    Ok(target)
+}
+
+/// 判断 `path` 解析符号链接后是否位于根目录 `root` 之外。
+///
+/// 路径各级都不是符号链接时无需解析，直接返回 `false`；无法解析（如链接失效）时视为越界。
+fn escapes_root(path: &Path, root: &str) -> bool {
+    let root = Path::new(root);
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    let mut current = root.to_path_buf();
+    let has_symlink = relative.components().any(|component| {
+        current.push(component);
+        fs::symlink_metadata(&current).is_ok_and(|m| m.file_type().is_symlink())
+    });
+    if !has_symlink {
+        return false;
+    }
+    match (fs::canonicalize(path), fs::canonicalize(root)) {
+        (Ok(resolved), Ok(root)) => !resolved.starts_with(root),
+        _ => true,
+    }
+}
+
+/// 在目录中按优先级查找第一个存在的索引文件。
+fn find_index_file(dir: &Path, index_files: &[String]) -> Option<PathBuf> {
+    index_files
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|candidate| candidate.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, path: &str) -> Request {
+        let raw = format!("{} {} HTTP/1.1\r\nHost: localhost\r\n\r\n", method, path);
+        Request::try_from(&raw.into_bytes(), 0).unwrap()
+    }
+
+    fn get_request(path: &str) -> Request {
+        request("GET", path)
+    }
+
+    /// 返回固定文本的处理器
+    fn text(body: &'static str) -> impl Fn(Request, Params) -> HandlerFuture + Send + Sync + 'static {
+        move |req, _| Box::pin(async move { Response::from_text(body, "text/plain", &req, req.id()) })
+    }
+
+    fn body(response: &Response) -> String {
+        String::from_utf8(response.content().unwrap().to_vec()).unwrap()
+    }
+
+    #[test]
+    fn test_pattern_matching() {
+        let params = Pattern::parse("/files/:name").matches("/files/a.txt").unwrap();
+        assert_eq!(params.get("name"), Some("a.txt"));
+        assert!(Pattern::parse("/files/:name").matches("/files").is_none());
+        assert!(Pattern::parse("/files/:name").matches("/files/a/b").is_none());
+
+        let params = Pattern::parse("/users/:id/posts/:post").matches("/users/7/posts/42").unwrap();
+        assert_eq!(params.iter().collect::<Vec<_>>(), vec![("id", "7"), ("post", "42")]);
+
+        let prefix = Pattern::parse("/api/*");
+        assert_eq!(prefix.matches("/api").unwrap().get("*"), Some(""));
+        assert_eq!(prefix.matches("/api/v1/users").unwrap().get("*"), Some("v1/users"));
+        assert!(prefix.matches("/apis").is_none());
+        assert_eq!(Pattern::parse("/*").matches("/a/b").unwrap().get("*"), Some("a/b"));
+
+        assert!(Pattern::parse("/about/").matches("/about").unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_router_dispatch_and_priority() {
+        let router = Router::new()
+            .route("/api/*", text("prefix"))
+            .get("/api/:name", text("param"))
+            .get("/api/status", text("exact"))
+            .route("/api/v1/*", text("longer prefix"))
+            .get("/files/:name", |req, params| async move {
+                let name = params.get("name").unwrap_or_default().to_string();
+                Response::from_text(&name, "text/plain", &req, req.id())
+            });
+        assert_eq!(router.len(), 5);
+
+        let cases = [
+            ("/api/status", "exact"),
+            ("/api/users", "param"),
+            ("/api/v1/users", "longer prefix"),
+            ("/api/a/b", "prefix"),
+            ("/files/my%20file.txt?download=1", "my file.txt"),
+            ("//files/./a.txt", "a.txt"),
+        ];
+        for (path, expected) in cases {
+            let response = router.handle(&get_request(path)).await.unwrap();
+            assert_eq!(response.status_code(), 200);
+            assert_eq!(body(&response), expected, "{}", path);
+        }
+
+        assert!(router.handle(&get_request("/other")).await.is_none());
+        assert!(router.handle(&get_request("/files/%zz")).await.is_none());
+        assert!(Router::new().handle(&get_request("/")).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_router_methods() {
+        let router = Router::new()
+            .get("/items", text("list"))
+            .post("/items", text("created"));
+
+        let response = router.handle(&request("POST", "/items")).await.unwrap();
+        assert_eq!(body(&response), "created");
+
+        // GET 路由同时处理 HEAD，响应不携带响应体
+        let response = router.handle(&request("HEAD", "/items")).await.unwrap();
+        assert_eq!(response.status_code(), 200);
+        assert!(response.content().is_none());
+
+        let response = router.handle(&request("OPTIONS", "/items")).await.unwrap();
+        assert_eq!(response.status_code(), 405);
+        let text = String::from_utf8(response.as_bytes()).unwrap();
+        assert!(text.contains("Allow: GET, HEAD, POST\r\n"), "{}", text);
+    }
+}
@@ -0,0 +1,80 @@
+// Copyright (c) 2026 shaneyale (shaneyale86@gmail.com)
+// All rights reserved.
+
+//! # TLS 模块
+//!
+//! 从 PEM 文件加载证书链与私钥，构建 HTTPS 监听器使用的 [`TlsAcceptor`]。
+//! 握手完成后得到的 TLS 流同样实现 `AsyncRead + AsyncWrite`，因此与明文连接共用同一套连接处理逻辑。
+//!
+//! 加密实现使用 rustls 的 `ring` 后端。ALPN 在启用 HTTP/2 时优先协商 `h2`，否则只协商 `http/1.1`；
+//! 握手后由调用方通过 [`negotiated_h2`] 决定连接使用哪个协议。
+
+use std::io;
+use std::sync::Arc;
+
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::ServerConfig;
+
+pub use tokio_rustls::server::TlsStream;
+pub use tokio_rustls::TlsAcceptor;
+
+/// 从证书链文件与私钥文件构建 TLS 接受器。
+///
+/// 证书文件可以包含多个证书（服务器证书在前，随后是中间证书）；私钥支持 PKCS#8、PKCS#1 与 SEC1 格式。
+/// `http2` 为真时 ALPN 依次提供 `h2` 与 `http/1.1`，不支持 ALPN 的客户端仍使用 HTTP/1.1。
+///
+/// # 错误
+///
+/// 文件无法读取、不含证书或私钥，或私钥与证书不匹配时返回 `InvalidData` 错误，错误信息包含出错的文件路径。
+pub fn load_acceptor(cert_path: &str, key_path: &str, http2: bool) -> io::Result<TlsAcceptor> {
+    let invalid = |path: &str, e: &dyn std::fmt::Display| {
+        io::Error::new(io::ErrorKind::InvalidData, format!("{}：{}", path, e))
+    };
+
+    let certs = CertificateDer::pem_file_iter(cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| invalid(cert_path, &e))?;
+    if certs.is_empty() {
+        return Err(invalid(cert_path, &"文件中没有证书"));
+    }
+    let key = PrivateKeyDer::from_pem_file(key_path).map_err(|e| invalid(key_path, &e))?;
+
+    let mut config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|e| invalid(key_path, &e))?;
+    config.alpn_protocols = match http2 {
+        true => vec![b"h2".to_vec(), b"http/1.1".to_vec()],
+        false => vec![b"http/1.1".to_vec()],
+    };
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// 握手完成的 TLS 连接是否通过 ALPN 协商出了 HTTP/2。
+pub fn negotiated_h2<IO>(stream: &TlsStream<IO>) -> bool {
+    stream.get_ref().1.alpn_protocol() == Some(b"h2")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CERT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/localhost.crt");
+    const KEY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/localhost.key");
+
+    #[test]
+    fn test_load_acceptor() {
+        let acceptor = load_acceptor(CERT, KEY, true).unwrap();
+        assert_eq!(acceptor.config().alpn_protocols, vec![b"h2".to_vec(), b"http/1.1".to_vec()]);
+        let acceptor = load_acceptor(CERT, KEY, false).unwrap();
+        assert_eq!(acceptor.config().alpn_protocols, vec![b"http/1.1".to_vec()]);
+
+        // 证书与私钥互换、文件缺失时都给出带路径的错误
+        let err = load_acceptor(KEY, CERT, true).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("localhost.key"), "{}", err);
+        let err = load_acceptor("/nonexistent/cert.pem", KEY, true).err().unwrap();
+        assert!(err.to_string().contains("/nonexistent/cert.pem"), "{}", err);
+    }
+}
@@ -5,13 +5,16 @@
 //! 
 //! 该模块负责生成 Web 服务器所需的动态 HTML 内容，包括：
 //! 1. 状态码对应的错误页面。
-//! 2. 目录文件的索引列表页面。
-//! 3. 辅助工具函数（文件大小格式化、目录排序）。
-//! 4. 外部 PHP 脚本的解析与执行。
+//! 2. 目录文件的索引列表页面（支持排序与分页）。
+//! 3. Markdown 文档渲染后的页面。
+//! 4. 辅助工具函数（文件大小格式化、目录排序、路径编解码与规范化、HTML 转义）。
+//! 5. 外部 PHP 脚本的解析与执行。
 
-use std::{path::PathBuf, process::Command};
+use std::{ops::Range, path::PathBuf, process::Stdio, str, time::Duration};
 use chrono::{DateTime, Local};
-use log::error;
+use log::{error, warn};
+use pulldown_cmark::{html, Options, Parser};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use crate::{exception::Exception, param::STATUS_CODES};
 
 /// `HtmlBuilder` 用于构建符合 HTML5 标准的页面字符串。
@@ -82,16 +85,28 @@ impl HtmlBuilder {
     /// 1. 对文件列表进行排序（文件夹在前，文件在后）。
     /// 2. 生成包含文件名、大小、修改时间的表格。
     /// 3. 自动处理路径结尾的斜杠并添加“返回上级目录”的链接。
-    pub fn from_dir(path: &str, dir_vec: &mut Vec<PathBuf>) -> Self {
+    /// 4. 在表格下方汇总文件数、文件夹数与文件总大小。
+    pub fn from_dir(path: &str, dir_vec: &mut Vec<PathBuf>, sort: ListingSort) -> Self {
+        let summary = DirSummary::from_entries(dir_vec);
+        Self::from_dir_with_summary(path, dir_vec, sort, &summary)
+    }
+
+    /// 生成目录列表页面，页脚使用给定的汇总信息（分页时汇总的是整个目录而非当前页）。
+    fn from_dir_with_summary(
+        path: &str,
+        dir_vec: &mut Vec<PathBuf>,
+        sort: ListingSort,
+        summary: &DirSummary,
+    ) -> Self {
         let mut body = String::new();
-        sort_dir_entries(dir_vec);
+        sort_dir_entries(dir_vec, sort);
 
         let mut path_mut = path;
         if path_mut.ends_with("/") {
             let len = path_mut.len();
             path_mut = &path_mut[..(len - 1)];
         }
-        body.push_str(&format!("<h1>{}的文件列表</h1><hr>", path_mut));
+        body.push_str(&format!("<h1>{}的文件列表</h1><hr>", escape_html(path_mut)));
         body.push_str("<table>");
         body.push_str(
             r#"
@@ -108,13 +123,40 @@ impl HtmlBuilder {
             "#,
         );
         for entry in dir_vec {
-            let metadata = entry.metadata().unwrap();
-            let local_time: DateTime<Local> = metadata.modified().unwrap().into();
-            let formatted_time = local_time.format("%Y-%m-%d %H:%M:%S %Z").to_string();
+            // 使用 symlink_metadata，符号链接（包括指向不存在目标的）按链接本身展示
+            let metadata = match entry.symlink_metadata() {
+                Ok(metadata) => metadata,
+                Err(e) => {
+                    warn!("无法读取{}的元数据，已从目录列表中跳过: {}", entry.display(), e);
+                    continue;
+                }
+            };
+            let Some(filename) = entry.file_name().map(|name| name.to_string_lossy()) else {
+                continue;
+            };
+            let formatted_time = metadata
+                .modified()
+                .map(|time| DateTime::<Local>::from(time).format("%Y-%m-%d %H:%M:%S %Z").to_string())
+                .unwrap_or_default();
 
-            let filename = entry.file_name().unwrap().to_string_lossy();
+            // href 使用百分号编码，链接文字使用 HTML 转义，二者分别处理
+            let href = percent_encode_path_segment(&filename);
+            let display_name = escape_html(&filename);
 
-            if entry.is_file() {
+            if metadata.is_symlink() {
+                // 指向目录的链接以 `/` 结尾，便于直接进入；失效的链接同样列出，由请求时返回 404
+                let suffix = if entry.is_dir() { "/" } else { "" };
+                body.push_str(&format!(
+                    r#"
+                    <tr>
+                        <td><a href="{}{}">{}{}</a></td>
+                        <td>符号链接</td>
+                        <td>{}</td>
+                    </tr>
+                    "#,
+                    &href, suffix, &display_name, suffix, &formatted_time
+                ));
+            } else if metadata.is_file() {
                 let size = metadata.len();
                 let formatted_size = format_file_size(size);
                 body.push_str(&format!(
@@ -125,10 +167,11 @@ impl HtmlBuilder {
                         <td>{}</td>
                     </tr>
                     "#,
-                    &filename, &filename, &formatted_size, &formatted_time
+                    &href, &display_name, &formatted_size, &formatted_time
                 ));
-            } else if entry.is_dir() {
-                let filename = [&filename, "/"].concat();
+            } else if metadata.is_dir() {
+                let href = [&href, "/"].concat();
+                let display_name = [&display_name, "/"].concat();
                 body.push_str(&format!(
                     r#"
                     <tr>
@@ -137,14 +180,30 @@ impl HtmlBuilder {
                         <td>{}</td>
                     </tr>
                     "#,
-                    &filename, &filename, &formatted_time
+                    &href, &display_name, &formatted_time
                 ));
             } else {
-                panic!();
+                // 套接字、FIFO、设备文件等无法作为静态资源提供，只列出名称
+                body.push_str(&format!(
+                    r#"
+                    <tr>
+                        <td>{}</td>
+                        <td>特殊文件</td>
+                        <td>{}</td>
+                    </tr>
+                    "#,
+                    &display_name, &formatted_time
+                ));
             }
         }
         body.push_str("</table>");
-        let title = format!("{}的文件列表", path);
+        body.push_str(&format!(
+            "<hr><p>{} 个文件夹，{} 个文件，共 {}</p>",
+            summary.dirs,
+            summary.files,
+            format_file_size(summary.total_size)
+        ));
+        let title = format!("{}的文件列表", escape_html(path));
         let css = r"
             table {
                 border-collapse: collapse;
@@ -170,6 +229,69 @@ impl HtmlBuilder {
         }
     }
 
+    /// 生成目录列表中的一页，并在表格下方附加翻页导航。
+    ///
+    /// `dir_vec` 应当只包含当前页的条目，`summary` 为整个目录的汇总信息。
+    pub fn from_dir_page(
+        path: &str,
+        dir_vec: &mut Vec<PathBuf>,
+        page: &Pagination,
+        summary: &DirSummary,
+    ) -> Self {
+        let mut builder = Self::from_dir_with_summary(path, dir_vec, page.sort, summary);
+        let total = summary.entries();
+        let pages = page.total_pages(total);
+        let mut nav = String::from("<hr><p>");
+        if page.page > 1 {
+            nav.push_str(&format!(r#"<a href="{}">上一页</a> "#, page.query_string(page.page - 1)));
+        }
+        nav.push_str(&format!("第 {} / {} 页，共 {} 项", page.page, pages, total));
+        if page.page < pages {
+            nav.push_str(&format!(r#" <a href="{}">下一页</a>"#, page.query_string(page.page + 1)));
+        }
+        nav.push_str("</p>");
+        builder.body.push_str(&nav);
+        builder
+    }
+
+    /// 将 Markdown 文本渲染为 HTML 页面。
+    ///
+    /// 支持表格、删除线、任务列表等 CommonMark 扩展语法。
+    ///
+    /// # 参数
+    /// * `title` - 页面标题，通常为文件名，会进行 HTML 转义。
+    /// * `markdown` - Markdown 源文本。
+    pub fn from_markdown(title: &str, markdown: &str) -> Self {
+        let options = Options::ENABLE_TABLES
+            | Options::ENABLE_STRIKETHROUGH
+            | Options::ENABLE_TASKLISTS
+            | Options::ENABLE_FOOTNOTES;
+        let parser = Parser::new_ext(markdown, options);
+        let mut body = String::new();
+        html::push_html(&mut body, parser);
+
+        let css = r"
+            body {
+                max-width: 50em;
+                margin: 0 auto;
+                padding: 0 1em;
+                font-family: Tahoma, Verdana, Arial, sans-serif;
+                line-height: 1.6;
+            }
+            pre { background: #f6f8fa; padding: 1em; overflow: auto; }
+            code { font-family: Consolas, Monaco, monospace; }
+            table { border-collapse: collapse; }
+            th, td { border: 1px solid #ddd; padding: 0.3em 0.6em; }
+            "
+        .to_string();
+        Self {
+            title: escape_html(title),
+            css,
+            script: "".to_string(),
+            body,
+        }
+    }
+
     /// 组装所有组件，生成最终的 HTML 5 字符串。
     /// 
     /// # 返回
@@ -218,53 +340,577 @@ pub fn format_file_size(size: u64) -> String {
     format!("{:.1} {}", size, units[unit_index])
 }
 
+/// 对单个路径段进行百分号编码，用于生成 `href` 属性。
+///
+/// 仅保留 RFC 3986 中的非保留字符（字母、数字、`-`、`.`、`_`、`~`），
+/// 其余字节（包括空格、`#`、`?` 以及非 ASCII 字符的 UTF-8 字节）均编码为 `%XX`。
+///
+/// # 示例
+/// ```
+/// use webserver::util::percent_encode_path_segment;
+/// assert_eq!(percent_encode_path_segment("my file #2.txt"), "my%20file%20%232.txt");
+/// ```
+pub fn percent_encode_path_segment(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// 解码百分号编码的路径（`percent_encode_path_segment` 的逆操作）。
+///
+/// # 错误
+/// 如果存在非法的 `%XX` 序列，或解码结果不是合法的 UTF-8，返回 `Exception::InvalidPath`。
+pub fn percent_decode(path: &str) -> Result<String, Exception> {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = bytes.get(i + 1..i + 3).ok_or(Exception::InvalidPath)?;
+            let hex = str::from_utf8(hex).map_err(|_| Exception::InvalidPath)?;
+            let byte = u8::from_str_radix(hex, 16).map_err(|_| Exception::InvalidPath)?;
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).map_err(|_| Exception::InvalidPath)
+}
+
+/// 判断文件名是否匹配 glob 模式。
+///
+/// `*` 匹配任意数量（包括零个）的字符，`?` 匹配单个字符，其余字符按字面匹配。
+///
+/// # 示例
+/// ```
+/// use webserver::util::glob_match;
+/// assert!(glob_match(".*", ".env"));
+/// assert!(glob_match("*.bak", "index.html.bak"));
+/// assert!(!glob_match("?.txt", "ab.txt"));
+/// ```
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // 最近一个 `*` 在模式中的位置，以及它当前匹配到的文件名位置，用于回溯
+    let mut backtrack: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    backtrack = Some((star, matched + 1));
+                    p = star + 1;
+                    n = matched + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// 判断文件名是否匹配任意一个 glob 模式（如 `Config::listing_ignore`）。
+pub fn is_ignored(name: &str, patterns: &[String]) -> bool {
+    patterns.iter().any(|pattern| glob_match(pattern, name))
+}
+
+/// 转义 HTML 特殊字符，用于将任意文本安全地嵌入页面。
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// 去掉响应头值中的 CR、LF 与 NUL，防止响应拆分（CRLF 注入）。
+///
+/// 所有来源不固定的响应头值（`Location`、`Set-Cookie`、反射的 `Origin` 等）在序列化时都会经过该函数。
+///
+/// # 示例
+/// ```
+/// use webserver::util::sanitize_header_value;
+/// assert_eq!(sanitize_header_value("/a\r\nX-Injected: 1"), "/aX-Injected: 1");
+/// ```
+pub fn sanitize_header_value(value: &str) -> String {
+    value.chars().filter(|c| !matches!(c, '\r' | '\n' | '\0')).collect()
+}
+
+/// 生成强制下载的 `Content-Disposition` 响应头值。
+///
+/// 纯 ASCII 文件名直接放在 `filename` 中；含有非 ASCII 字符、引号或反斜杠时，
+/// `filename` 给出以 `_` 替换后的兼容文件名，并按 RFC 5987 追加 UTF-8 编码的 `filename*`。
+///
+/// # 示例
+/// ```
+/// use webserver::util::content_disposition_attachment;
+/// assert_eq!(content_disposition_attachment("data.csv"), "attachment; filename=\"data.csv\"");
+/// assert_eq!(
+///     content_disposition_attachment("报表.csv"),
+///     "attachment; filename=\"__.csv\"; filename*=UTF-8''%E6%8A%A5%E8%A1%A8.csv"
+/// );
+/// ```
+pub fn content_disposition_attachment(filename: &str) -> String {
+    let plain = |c: char| c.is_ascii() && !c.is_ascii_control() && c != '"' && c != '\\';
+    if filename.chars().all(plain) {
+        return format!("attachment; filename=\"{}\"", filename);
+    }
+    let fallback: String = filename.chars().map(|c| if plain(c) { c } else { '_' }).collect();
+    format!(
+        "attachment; filename=\"{}\"; filename*=UTF-8''{}",
+        fallback,
+        percent_encode_path_segment(filename)
+    )
+}
+
+/// 按 CGI 约定解析后的脚本输出，见 [`parse_cgi_output`]。
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CgiOutput {
+    /// `Status` 头给出的状态码；只有 `Location` 时为 302
+    pub status: Option<u16>,
+    /// `Content-Type` 头的值
+    pub content_type: Option<String>,
+    /// 其余响应头，按出现顺序保存
+    pub headers: Vec<(String, String)>,
+    /// 响应体
+    pub body: String,
+}
+
+/// 按 CGI 约定解析脚本输出：空行之前的 `Header: value` 行作为响应头，其余部分作为响应体。
+///
+/// 只有当空行之前的每一行都是合法的响应头时才按响应头解析，否则整个输出都视为响应体，
+/// 因此不输出响应头的脚本（如 php-cli 直接输出 HTML）不受影响。
+///
+/// # 示例
+/// ```
+/// use webserver::util::parse_cgi_output;
+/// let output = parse_cgi_output("Status: 404 Not Found\r\nX-A: 1\r\n\r\nmissing");
+/// assert_eq!(output.status, Some(404));
+/// assert_eq!(output.headers, vec![("X-A".to_string(), "1".to_string())]);
+/// assert_eq!(output.body, "missing");
+/// assert_eq!(parse_cgi_output("<p>hi</p>").body, "<p>hi</p>");
+/// ```
+pub fn parse_cgi_output(output: &str) -> CgiOutput {
+    let plain = || CgiOutput {
+        body: output.to_string(),
+        ..CgiOutput::default()
+    };
+    let split = [("\r\n\r\n", 4), ("\n\n", 2)]
+        .iter()
+        .filter_map(|(sep, len)| output.find(sep).map(|pos| (pos, *len)))
+        .min();
+    let Some((pos, len)) = split else {
+        return plain();
+    };
+
+    let mut parsed = CgiOutput {
+        body: output[pos + len..].to_string(),
+        ..CgiOutput::default()
+    };
+    for line in output[..pos].lines() {
+        let Some((name, value)) = line.split_once(':') else {
+            return plain();
+        };
+        let is_token = !name.is_empty()
+            && name
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b));
+        if !is_token {
+            return plain();
+        }
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("status") {
+            parsed.status = value.split(' ').next().and_then(|code| code.parse().ok());
+        } else if name.eq_ignore_ascii_case("content-type") {
+            parsed.content_type = Some(value.to_string());
+        } else {
+            parsed.headers.push((name.to_string(), value.to_string()));
+        }
+    }
+    if parsed.status.is_none() && parsed.headers.iter().any(|(n, _)| n.eq_ignore_ascii_case("location")) {
+        parsed.status = Some(302);
+    }
+    parsed
+}
+
+/// 目录的汇总信息：文件数、子目录数与文件总大小。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DirSummary {
+    /// 文件数
+    pub files: usize,
+    /// 子目录数
+    pub dirs: usize,
+    /// 所有文件的大小之和（字节），不递归统计子目录
+    pub total_size: u64,
+}
+
+impl DirSummary {
+    /// 统计一组目录条目。
+    pub fn from_entries(entries: &[PathBuf]) -> Self {
+        entries.iter().fold(Self::default(), |mut summary, entry| {
+            match entry.metadata() {
+                Ok(meta) if meta.is_dir() => summary.dirs += 1,
+                Ok(meta) => {
+                    summary.files += 1;
+                    summary.total_size += meta.len();
+                }
+                Err(_) => {}
+            }
+            summary
+        })
+    }
+
+    /// 条目总数（文件与子目录之和）。
+    pub fn entries(&self) -> usize {
+        self.files + self.dirs
+    }
+}
+
+/// 目录列表的分页参数，对应查询参数 `?page=N&per_page=M`。
+///
+/// 页码从 1 开始。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pagination {
+    /// 当前页码
+    pub page: usize,
+    /// 每页条目数
+    pub per_page: usize,
+    /// 列表的排序方式，翻页链接中会保留
+    pub sort: ListingSort,
+}
+
+impl Pagination {
+    /// 根据查询参数与默认的每页条目数构造分页参数。
+    ///
+    /// 无法解析或为 0 的参数会被忽略并使用默认值。请求未指定 `per_page`
+    /// 且默认值为 0 时表示不分页，返回 `None`。
+    ///
+    /// # 示例
+    /// ```
+    /// use webserver::util::Pagination;
+    /// let page = Pagination::new(Some("2"), Some("50"), 100).unwrap();
+    /// assert_eq!((page.page, page.per_page), (2, 50));
+    /// assert!(Pagination::new(Some("2"), None, 0).is_none());
+    /// ```
+    pub fn new(page: Option<&str>, per_page: Option<&str>, default_per_page: usize) -> Option<Self> {
+        let parse = |v: Option<&str>| v.and_then(|v| v.parse::<usize>().ok()).filter(|&v| v > 0);
+        let per_page = parse(per_page).or((default_per_page > 0).then_some(default_per_page))?;
+        Some(Self {
+            page: parse(page).unwrap_or(1),
+            per_page,
+            sort: ListingSort::default(),
+        })
+    }
+
+    /// 指定列表的排序方式。
+    pub fn with_sort(mut self, sort: ListingSort) -> Self {
+        self.sort = sort;
+        self
+    }
+
+    /// 计算总页数，空目录也视为 1 页。
+    pub fn total_pages(&self, total: usize) -> usize {
+        total.div_ceil(self.per_page).max(1)
+    }
+
+    /// 当前页在全部条目中的下标范围，页码超出范围时返回空区间。
+    pub fn bounds(&self, total: usize) -> Range<usize> {
+        let start = (self.page - 1).saturating_mul(self.per_page).min(total);
+        let end = start.saturating_add(self.per_page).min(total);
+        start..end
+    }
+
+    /// 生成指向第 `page` 页的相对链接（保留排序参数）。
+    pub fn query_string(&self, page: usize) -> String {
+        format!("?page={}&per_page={}{}", page, self.per_page, self.sort.query_suffix())
+    }
+
+    /// 生成 RFC 8288 格式的 `Link` 响应头，包含 first / prev / next / last 链接。
+    pub fn link_header(&self, total: usize) -> String {
+        let pages = self.total_pages(total);
+        let mut links = vec![format!("<{}>; rel=\"first\"", self.query_string(1))];
+        if self.page > 1 {
+            let prev = (self.page - 1).min(pages);
+            links.push(format!("<{}>; rel=\"prev\"", self.query_string(prev)));
+        }
+        if self.page < pages {
+            links.push(format!("<{}>; rel=\"next\"", self.query_string(self.page + 1)));
+        }
+        links.push(format!("<{}>; rel=\"last\"", self.query_string(pages)));
+        links.join(", ")
+    }
+}
+
+/// 目录列表的排序字段，对应查询参数 `?sort=`。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortKey {
+    /// 按名称排序（默认）
+    #[default]
+    Name,
+    /// 按文件大小排序
+    Size,
+    /// 按修改时间排序
+    Date,
+}
+
+/// 目录列表的排序方向，对应查询参数 `?order=`。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortOrder {
+    /// 升序（默认）
+    #[default]
+    Asc,
+    /// 降序
+    Desc,
+}
+
+/// 目录列表的排序方式。默认按名称升序。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ListingSort {
+    /// 排序字段
+    pub key: SortKey,
+    /// 排序方向
+    pub order: SortOrder,
+}
+
+impl ListingSort {
+    /// 根据查询参数 `sort`（`name` / `size` / `date`）与 `order`（`asc` / `desc`）构造排序方式，
+    /// 缺失或无法识别的参数使用默认值。
+    ///
+    /// # 示例
+    /// ```
+    /// use webserver::util::{ListingSort, SortKey, SortOrder};
+    /// let sort = ListingSort::new(Some("size"), Some("desc"));
+    /// assert_eq!((sort.key, sort.order), (SortKey::Size, SortOrder::Desc));
+    /// assert_eq!(ListingSort::new(Some("bogus"), None), ListingSort::default());
+    /// ```
+    pub fn new(sort: Option<&str>, order: Option<&str>) -> Self {
+        let key = match sort {
+            Some("size") => SortKey::Size,
+            Some("date") => SortKey::Date,
+            _ => SortKey::Name,
+        };
+        let order = match order {
+            Some("desc") => SortOrder::Desc,
+            _ => SortOrder::Asc,
+        };
+        Self { key, order }
+    }
+
+    /// 生成附加在链接与缓存 Key 末尾的查询参数，默认排序方式返回空字符串。
+    pub fn query_suffix(&self) -> String {
+        if *self == Self::default() {
+            return String::new();
+        }
+        let key = match self.key {
+            SortKey::Name => "name",
+            SortKey::Size => "size",
+            SortKey::Date => "date",
+        };
+        let order = match self.order {
+            SortOrder::Asc => "asc",
+            SortOrder::Desc => "desc",
+        };
+        format!("&sort={}&order={}", key, order)
+    }
+}
+
 /// 对文件路径向量进行排序。
 /// 
 /// 排序规则：
-/// 1. 优先排列目录（Directory）。
-/// 2. 同类型（同为目录或同为文件）按照路径名称升序排列。
-fn sort_dir_entries(vec: &mut Vec<PathBuf>) {
-    vec.sort_by(|a, b| {
-        let a_is_dir = a.is_dir();
-        let b_is_dir = b.is_dir();
-
-        if a_is_dir && !b_is_dir {
-            std::cmp::Ordering::Less
-        } else if !a_is_dir && b_is_dir {
-            std::cmp::Ordering::Greater
-        } else {
-            a.cmp(b)
-        }
+/// 1. 优先排列目录（Directory），与排序方向无关。
+/// 2. 同类型（同为目录或同为文件）按照 `sort` 指定的字段与方向排列，
+///    字段相同时按路径名称升序排列。
+pub(crate) fn sort_dir_entries(vec: &mut Vec<PathBuf>, sort: ListingSort) {
+    // 预先读取一次元数据，避免比较时反复访问文件系统
+    let mut keyed: Vec<(bool, u64, PathBuf)> = vec
+        .drain(..)
+        .map(|p| {
+            let meta = p.metadata().ok();
+            let value = match sort.key {
+                SortKey::Name => 0,
+                SortKey::Size => meta.as_ref().map_or(0, |m| m.len()),
+                SortKey::Date => meta
+                    .as_ref()
+                    .and_then(|m| m.modified().ok())
+                    .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                    .map_or(0, |d| d.as_nanos() as u64),
+            };
+            (p.is_dir(), value, p)
+        })
+        .collect();
+    keyed.sort_by(|(a_is_dir, a_value, a), (b_is_dir, b_value, b)| {
+        let ordering = match sort.key {
+            SortKey::Name => a.cmp(b),
+            _ => a_value.cmp(b_value),
+        };
+        let ordering = match sort.order {
+            SortOrder::Asc => ordering,
+            SortOrder::Desc => ordering.reverse(),
+        };
+        b_is_dir.cmp(a_is_dir).then(ordering).then_with(|| a.cmp(b))
     });
+    vec.extend(keyed.into_iter().map(|(_, _, p)| p));
 }
 
-/// 调用系统环境中的 PHP 解释器执行指定的 PHP 文件。
+/// 规范化请求路径。
+///
+/// 折叠重复的 `/`、去掉结尾的 `/`、消解 `.` 段，并在根目录范围内消解 `..` 段。
+/// 规范化后的路径总是以 `/` 开头，根目录规范化为 `/`。
+///
+/// # 错误
+/// 如果 `..` 会越过根目录（例如 `/../etc/passwd`），返回 `Exception::InvalidPath`。
+///
+/// # 示例
+/// ```
+/// use webserver::util::normalize_path;
+/// assert_eq!(normalize_path("/a//b/./c/").unwrap(), "/a/b/c");
+/// assert!(normalize_path("/../etc/passwd").is_err());
+/// ```
+pub fn normalize_path(path: &str) -> Result<String, Exception> {
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                if segments.pop().is_none() {
+                    return Err(Exception::InvalidPath);
+                }
+            }
+            s => segments.push(s),
+        }
+    }
+    Ok(format!("/{}", segments.join("/")))
+}
+
+/// PHP 解释器进程的 `PATH`：解释器不继承服务器的环境变量，只在这些目录中查找命令。
+pub const CGI_PATH: &str = "/usr/local/bin:/usr/bin:/bin";
+
+/// 调用 PHP 解释器执行指定的 PHP 文件。
 /// 
 /// # 参数
+/// * `php_path` - PHP 解释器的路径或命令名（见 `Config::php_path`）。
 /// * `path` - PHP 文件的本地绝对路径或相对路径。
+/// * `env` - 传给解释器的 CGI 环境变量（如 `QUERY_STRING`），通过进程环境传递，不经过 shell。
+///   解释器不继承服务器进程的环境变量（其中可能有 `WEBSERVER_*` 覆盖项或密钥），只额外得到 [`CGI_PATH`]。
+/// * `stdin` - 写入解释器标准输入的内容，通常为 POST 请求体。
 /// * `id` - 当前请求的唯一 ID，用于日志记录。
+/// * `timeout` - 脚本允许运行的最长时间。
+/// * `max_output` - 标准输出允许的最大字节数。
 /// 
 /// # 返回值
 /// * `Ok(String)` - PHP 脚本标准输出的内容。
-/// * `Err(Exception)` - 如果无法调用 PHP 解释器（`PHPExecuteFailed`）或脚本执行报错（`PHPCodeError`）。
+/// * `Err(Exception)` - 如果无法调用 PHP 解释器（`PHPExecuteFailed`）、脚本执行报错（`PHPCodeError`）、
+///   运行超时（`ScriptTimeout`）或输出过大（`ScriptOutputTooLarge`）。
 /// 
 /// # 注意
-/// 运行环境必须在系统 PATH 中安装有 `php` 命令。
-pub fn handle_php(path: &str, id: u128) -> Result<String, Exception> {
-    let result = Command::new("php")
+/// 解释器以 `php_path <path>` 的形式运行，进程在超时或输出超过 `max_output` 字节时被终止；
+/// 标准错误同样按 `max_output` 截断，只用于日志。
+pub async fn handle_php(
+    php_path: &str,
+    path: &str,
+    env: &[(String, String)],
+    stdin: &[u8],
+    id: u128,
+    timeout: Duration,
+    max_output: usize,
+) -> Result<String, Exception> {
+    let mut child = tokio::process::Command::new(php_path)
         .arg(path)
-        .output();
-    let output = match result {
-        Ok(o) => o,
-        Err(_) => return Err(Exception::PHPExecuteFailed),
+        .env_clear()
+        .env("PATH", CGI_PATH)
+        .envs(env.iter().map(|(key, value)| (key, value)))
+        .stdin(if stdin.is_empty() { Stdio::null() } else { Stdio::piped() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| {
+            error!("[ID{}]无法启动PHP解释器{}：{}", id, php_path, e);
+            Exception::PHPExecuteFailed
+        })?;
+    let (Some(stdout), Some(stderr)) = (child.stdout.take(), child.stderr.take()) else {
+        return Err(Exception::PHPExecuteFailed);
     };
+    let mut child_stdin = child.stdin.take();
 
-    if output.status.success() {
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        Ok(String::from(stdout))
+    // 多读一个字节即可判断输出是否超限；同时读取 stdout 与 stderr，避免任一管道写满后脚本阻塞。
+    // 标准输出超限时立即返回并终止进程，不再等待标准错误关闭或请求体写完
+    let limit = max_output as u64 + 1;
+    let run = async {
+        let read_stdout = async {
+            let mut out = Vec::new();
+            stdout.take(limit).read_to_end(&mut out).await.map_err(|_| Exception::PHPExecuteFailed)?;
+            match out.len() > max_output {
+                true => Err(Exception::ScriptOutputTooLarge),
+                false => Ok(out),
+            }
+        };
+        // 标准错误超过上限的部分读出后丢弃，脚本不会因 stderr 写满而阻塞
+        let read_stderr = async {
+            let mut err = Vec::new();
+            let mut stderr = stderr.take(limit);
+            stderr.read_to_end(&mut err).await.map_err(|_| Exception::PHPExecuteFailed)?;
+            let _ = tokio::io::copy(&mut stderr.into_inner(), &mut tokio::io::sink()).await;
+            Ok(err)
+        };
+        // 写完请求体后关闭标准输入，脚本才能读到 EOF；脚本不读取标准输入时写入失败可以忽略
+        let write_stdin = async {
+            if let Some(mut pipe) = child_stdin.take() {
+                let _ = pipe.write_all(stdin).await;
+            }
+            Ok(())
+        };
+        let (out, err, ()) = tokio::try_join!(read_stdout, read_stderr, write_stdin)?;
+        let status = child.wait().await.map_err(|_| Exception::PHPExecuteFailed)?;
+        Ok((status, out, err))
+    };
+    let result = match tokio::time::timeout(timeout, run).await {
+        Ok(result) => result,
+        Err(_) => {
+            error!("[ID{}]脚本{}运行超过{:?}，终止解释器进程", id, path, timeout);
+            let _ = child.kill().await;
+            return Err(Exception::ScriptTimeout);
+        }
+    };
+    let (status, out, err) = match result {
+        Ok(output) => output,
+        Err(e) => {
+            if let Exception::ScriptOutputTooLarge = e {
+                error!("[ID{}]脚本{}的输出超过{}字节，终止解释器进程", id, path, max_output);
+            }
+            let _ = child.kill().await;
+            return Err(e);
+        }
+    };
+
+    if status.success() {
+        Ok(String::from_utf8_lossy(&out).into_owned())
     } else {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        error!("[ID{}]PHP解释器出错：{}", id, stderr);
+        error!("[ID{}]PHP解释器出错：{}", id, String::from_utf8_lossy(&err));
         Err(Exception::PHPCodeError)
     }
 }
@@ -352,12 +998,60 @@ mod tests {
     fn test_sort_dir_entries() {
         let mut entries = vec![PathBuf::from("file1.txt"), PathBuf::from("file2.txt")];
 
-        sort_dir_entries(&mut entries);
+        sort_dir_entries(&mut entries, ListingSort::default());
 
         assert_eq!(entries[0].file_name().unwrap(), "file1.txt");
         assert_eq!(entries[1].file_name().unwrap(), "file2.txt");
     }
 
+    #[test]
+    fn test_percent_encode_path_segment() {
+        assert_eq!(percent_encode_path_segment("plain-name_1.txt"), "plain-name_1.txt");
+        assert_eq!(percent_encode_path_segment("my file #2.txt"), "my%20file%20%232.txt");
+        assert_eq!(percent_encode_path_segment("a?b.txt"), "a%3Fb.txt");
+        assert_eq!(percent_encode_path_segment("中文.txt"), "%E4%B8%AD%E6%96%87.txt");
+    }
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("my%20file%20%232.txt").unwrap(), "my file #2.txt");
+        assert_eq!(percent_decode("%E4%B8%AD%E6%96%87.txt").unwrap(), "中文.txt");
+        assert_eq!(percent_decode("/%2e%2e/etc").unwrap(), "/../etc");
+        assert!(percent_decode("bad%2").is_err());
+        assert!(percent_decode("bad%zz").is_err());
+        assert!(percent_decode("%FF").is_err());
+    }
+
+    #[test]
+    fn test_escape_html() {
+        assert_eq!(
+            escape_html(r#"<a href="x">Tom & 'Jerry'</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; &#39;Jerry&#39;&lt;/a&gt;"
+        );
+    }
+
+    /// 目录列表中的 href 必须经过百分号编码，链接文字保持可读
+    #[test]
+    fn test_from_dir_encodes_href() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["my file #2.txt", "what?.txt", "中文.txt", "<b>.txt"] {
+            std::fs::write(dir.path().join(name), b"x").unwrap();
+        }
+        std::fs::create_dir(dir.path().join("sub dir")).unwrap();
+
+        let mut entries: Vec<PathBuf> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .collect();
+        let html = HtmlBuilder::from_dir("/test/", &mut entries, ListingSort::default()).build();
+
+        assert!(html.contains(r#"<a href="my%20file%20%232.txt">my file #2.txt</a>"#));
+        assert!(html.contains(r#"<a href="what%3F.txt">what?.txt</a>"#));
+        assert!(html.contains(r#"<a href="%E4%B8%AD%E6%96%87.txt">中文.txt</a>"#));
+        assert!(html.contains(r#"<a href="%3Cb%3E.txt">&lt;b&gt;.txt</a>"#));
+        assert!(html.contains(r#"<a href="sub%20dir/">sub dir/</a>"#));
+    }
+
     /// 验证生成的页面结构是否符合 HTML5 标准格式
     #[test]
     fn test_html_builder_structure() {
@@ -377,6 +1071,32 @@ mod tests {
         assert!(html.contains("charset=\"utf-8\""));
     }
 
+    #[test]
+    fn test_normalize_path() {
+        assert_eq!(normalize_path("./test").unwrap(), "/test");
+        assert_eq!(normalize_path("test/../file").unwrap(), "/file");
+        assert_eq!(normalize_path("a/b/../c").unwrap(), "/a/c");
+    }
+
+    /// 重复斜杠、结尾斜杠与 `.` 段应当折叠为同一路径
+    #[test]
+    fn test_normalize_path_collapse() {
+        assert_eq!(normalize_path("/a//b/./c").unwrap(), "/a/b/c");
+        assert_eq!(normalize_path("/a/b/c/").unwrap(), "/a/b/c");
+        assert_eq!(normalize_path("//a///b//").unwrap(), "/a/b");
+        assert_eq!(normalize_path("/").unwrap(), "/");
+        assert_eq!(normalize_path("//").unwrap(), "/");
+    }
+
+    /// 越过根目录的 `..` 必须被拒绝
+    #[test]
+    fn test_normalize_path_escape_root() {
+        assert!(normalize_path("/..").is_err());
+        assert!(normalize_path("/../etc/passwd").is_err());
+        assert!(normalize_path("/a/../../etc/passwd").is_err());
+        assert_eq!(normalize_path("/a/../b").unwrap(), "/b");
+    }
+
     /// 边界值测试：测试文件大小在临界点（如 1023B 转换到 1KB）的切换是否正确
     #[test]
     fn test_format_file_size_edge_cases() {
@@ -385,4 +1105,200 @@ mod tests {
         assert_eq!(format_file_size(1024 * 1024 - 1), "1024.0 KB");
         assert_eq!(format_file_size(1024 * 1024), "1.0 MB");
     }
+    #[test]
+    fn test_from_markdown() {
+        let html = HtmlBuilder::from_markdown("<doc>.md", "# Title\n\n* item\n\n| a | b |\n|---|---|\n| 1 | 2 |\n").build();
+        assert!(html.contains("<title>&lt;doc&gt;.md</title>"));
+        assert!(html.contains("<h1>Title</h1>"));
+        assert!(html.contains("<li>item</li>"));
+        assert!(html.contains("<table>"));
+    }
+    #[test]
+    fn test_pagination() {
+        assert_eq!(Pagination::new(None, None, 0), None);
+        assert_eq!(
+            Pagination::new(None, None, 100),
+            Some(Pagination { page: 1, per_page: 100, sort: ListingSort::default() })
+        );
+        assert_eq!(
+            Pagination::new(Some("0"), Some("abc"), 100),
+            Some(Pagination { page: 1, per_page: 100, sort: ListingSort::default() })
+        );
+
+        let page = Pagination::new(Some("2"), Some("10"), 0).unwrap();
+        assert_eq!(page.total_pages(25), 3);
+        assert_eq!(page.total_pages(0), 1);
+        assert_eq!(page.bounds(25), 10..20);
+        assert_eq!(page.bounds(15), 10..15);
+        assert_eq!(page.bounds(5), 5..5);
+        assert_eq!(
+            page.link_header(25),
+            r#"<?page=1&per_page=10>; rel="first", <?page=1&per_page=10>; rel="prev", <?page=3&per_page=10>; rel="next", <?page=3&per_page=10>; rel="last""#
+        );
+    }
+    /// 创建 a.txt(3B, 最旧)、b.txt(1B)、c.txt(2B, 最新) 与子目录 z/，返回临时目录与条目列表
+    fn sort_fixture() -> (tempfile::TempDir, Vec<PathBuf>) {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, content) in [("a.txt", "aaa"), ("b.txt", "b"), ("c.txt", "cc")] {
+            std::fs::write(dir.path().join(name), content).unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        std::fs::create_dir(dir.path().join("z")).unwrap();
+        let entries = std::fs::read_dir(dir.path()).unwrap().map(|e| e.unwrap().path()).collect();
+        (dir, entries)
+    }
+
+    fn sorted_names(entries: &mut Vec<PathBuf>, sort: Option<&str>, order: Option<&str>) -> Vec<String> {
+        sort_dir_entries(entries, ListingSort::new(sort, order));
+        entries.iter().map(|p| p.file_name().unwrap().to_string_lossy().into_owned()).collect()
+    }
+
+    #[test]
+    fn test_sort_dir_entries_by_name() {
+        let (_dir, mut entries) = sort_fixture();
+        assert_eq!(sorted_names(&mut entries, None, None), ["z", "a.txt", "b.txt", "c.txt"]);
+        assert_eq!(sorted_names(&mut entries, Some("name"), Some("desc")), ["z", "c.txt", "b.txt", "a.txt"]);
+    }
+
+    #[test]
+    fn test_sort_dir_entries_by_size() {
+        let (_dir, mut entries) = sort_fixture();
+        assert_eq!(sorted_names(&mut entries, Some("size"), None), ["z", "b.txt", "c.txt", "a.txt"]);
+        assert_eq!(sorted_names(&mut entries, Some("size"), Some("desc")), ["z", "a.txt", "c.txt", "b.txt"]);
+    }
+
+    #[test]
+    fn test_sort_dir_entries_by_date() {
+        let (_dir, mut entries) = sort_fixture();
+        assert_eq!(sorted_names(&mut entries, Some("date"), Some("asc")), ["z", "a.txt", "b.txt", "c.txt"]);
+        assert_eq!(sorted_names(&mut entries, Some("date"), Some("desc")), ["z", "c.txt", "b.txt", "a.txt"]);
+    }
+
+    #[test]
+    fn test_listing_sort_query_suffix() {
+        assert_eq!(ListingSort::default().query_suffix(), "");
+        assert_eq!(ListingSort::new(Some("size"), Some("desc")).query_suffix(), "&sort=size&order=desc");
+        let page = Pagination::new(Some("2"), Some("10"), 0)
+            .unwrap()
+            .with_sort(ListingSort::new(Some("date"), None));
+        assert_eq!(page.query_string(3), "?page=3&per_page=10&sort=date&order=asc");
+    }
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match(".*", ".env"));
+        assert!(glob_match(".*", ".git"));
+        assert!(!glob_match(".*", "index.html"));
+        assert!(glob_match("*.bak", "a.bak"));
+        assert!(glob_match("*.bak", ".bak"));
+        assert!(!glob_match("*.bak", "a.bak.txt"));
+        assert!(glob_match("a*b*c", "aXXbYYc"));
+        assert!(!glob_match("a*b*c", "aXXbYY"));
+        assert!(glob_match("file?.txt", "file1.txt"));
+        assert!(!glob_match("file?.txt", "file.txt"));
+        assert!(glob_match("*", ""));
+        assert!(is_ignored("secret.key", &["*.pem".to_string(), "*.key".to_string()]));
+        assert!(!is_ignored("public.txt", &["*.pem".to_string(), "*.key".to_string()]));
+    }
+    #[test]
+    fn test_dir_summary_footer() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.txt"), vec![b'a'; 1024]).unwrap();
+        std::fs::write(dir.path().join("b.txt"), vec![b'b'; 1024]).unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        let mut entries: Vec<PathBuf> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .collect();
+
+        let summary = DirSummary::from_entries(&entries);
+        assert_eq!(summary, DirSummary { files: 2, dirs: 1, total_size: 2048 });
+        assert_eq!(summary.entries(), 3);
+
+        let html = HtmlBuilder::from_dir("/test/", &mut entries, ListingSort::default()).build();
+        assert!(html.contains(&format!("1 个文件夹，2 个文件，共 {}", format_file_size(2048))));
+    }
+    /// 以 sh 充当解释器：正常输出、执行失败、超时与输出过大
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_handle_php_limits() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = |name: &str, body: &str| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, body).unwrap();
+            path.to_str().unwrap().to_string()
+        };
+        let timeout = Duration::from_millis(500);
+
+        let ok = script("ok.sh", "printf hello");
+        assert_eq!(handle_php("sh", &ok, &[], &[], 0, timeout, 1024).await.unwrap(), "hello");
+
+        let failing = script("fail.sh", "echo oops >&2; exit 1");
+        assert!(matches!(handle_php("sh", &failing, &[], &[], 0, timeout, 1024).await, Err(Exception::PHPCodeError)));
+
+        let sleeping = script("sleep.sh", "sleep 5");
+        let start = std::time::Instant::now();
+        assert!(matches!(handle_php("sh", &sleeping, &[], &[], 0, timeout, 1024).await, Err(Exception::ScriptTimeout)));
+        assert!(start.elapsed() < Duration::from_secs(3));
+
+        // 输出超过管道缓冲区（64 KiB）且脚本不退出时，超限后立即终止而不是等到超时
+        let flood = script("flood.sh", "head -c 1048576 /dev/zero; sleep 5");
+        let start = std::time::Instant::now();
+        let long_timeout = Duration::from_secs(10);
+        assert!(matches!(
+            handle_php("sh", &flood, &[], &[], 0, long_timeout, 64 * 1024).await,
+            Err(Exception::ScriptOutputTooLarge)
+        ));
+        assert!(start.elapsed() < Duration::from_secs(3));
+
+        let noisy = script("noisy.sh", "head -c 4096 /dev/zero");
+        assert!(matches!(handle_php("sh", &noisy, &[], &[], 0, timeout, 100).await, Err(Exception::ScriptOutputTooLarge)));
+        assert_eq!(handle_php("sh", &noisy, &[], &[], 0, timeout, 4096).await.unwrap().len(), 4096);
+
+        assert!(matches!(
+            handle_php("/nonexistent/interpreter", &ok, &[], &[], 0, timeout, 1024).await,
+            Err(Exception::PHPExecuteFailed)
+        ));
+    }
+    #[test]
+    fn test_parse_cgi_output() {
+        let output = parse_cgi_output("Content-Type: text/plain\nLocation: /next\n\nbody\n\nmore");
+        assert_eq!(output.content_type.as_deref(), Some("text/plain"));
+        assert_eq!(output.status, Some(302));
+        assert_eq!(output.headers, vec![("Location".to_string(), "/next".to_string())]);
+        assert_eq!(output.body, "body\n\nmore");
+
+        // 空行之前不是合法的响应头时，整个输出都是响应体
+        for plain in ["<h1>Title: x</h1>\n\n<p>text</p>", "no headers here", "Bad Name: 1\n\nx"] {
+            assert_eq!(parse_cgi_output(plain), CgiOutput { body: plain.to_string(), ..CgiOutput::default() });
+        }
+    }
+    /// 目录中有失效的符号链接与特殊文件时照常生成列表，符号链接单独标注
+    #[cfg(unix)]
+    #[test]
+    fn test_dir_listing_with_broken_symlink() {
+        use std::os::unix::{fs::symlink, net::UnixListener};
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.txt"), b"x").unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        symlink(dir.path().join("missing.txt"), dir.path().join("broken")).unwrap();
+        symlink(dir.path().join("sub"), dir.path().join("to_sub")).unwrap();
+        let _socket = UnixListener::bind(dir.path().join("sock")).unwrap();
+
+        let mut entries: Vec<PathBuf> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .collect();
+        entries.push(dir.path().join("vanished.txt")); // 读取目录后被删除的条目
+        let html = HtmlBuilder::from_dir("/d/", &mut entries, ListingSort::default()).build();
+
+        assert!(html.contains(r#"<a href="broken">broken</a></td>
+                        <td>符号链接</td>"#), "{}", html);
+        assert!(html.contains(r#"<a href="to_sub/">to_sub/</a></td>
+                        <td>符号链接</td>"#), "{}", html);
+        assert!(html.contains("<td>sock</td>\n                        <td>特殊文件</td>"), "{}", html);
+        assert!(html.contains(r#"<a href="a.txt">a.txt</a>"#));
+        assert!(html.contains(r#"<a href="sub/">sub/</a>"#));
+        assert!(!html.contains("vanished"));
+    }
 }
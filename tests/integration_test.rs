@@ -151,6 +151,36 @@ mod integration_tests {
         }
     }
 
+    /// 验证 PHP 动态内容的 HEAD 请求：响应头应与 GET 一致（Content-Type 与非零的 Content-Length），但不携带实体内容。
+    #[tokio::test]
+    #[ignore]
+    async fn test_head_request_php() {
+        let request = "HEAD /php/time.php HTTP/1.1\r\nHost: localhost:7878\r\n\r\n";
+
+        match send_request(request, 7878).await {
+            Ok(response) => {
+                let (status_code, headers, body) = parse_response(&response);
+                assert_eq!(status_code, 200);
+                assert!(body.is_empty());
+
+                let header_map: std::collections::HashMap<String, String> =
+                    headers.into_iter().collect();
+                let content_length: u64 = header_map
+                    .get("Content-Length")
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(0);
+                assert!(content_length > 0, "HEAD响应的Content-Length应为非零");
+                assert_eq!(
+                    header_map.get("Content-Type").map(String::as_str),
+                    Some("text/html;charset=utf-8")
+                );
+            }
+            Err(e) => {
+                eprintln!("测试失败: {}", e);
+            }
+        }
+    }
+
     /// 验证 OPTIONS 请求：用于跨域资源共享 (CORS) 预检，应返回 Allow 允许的方法。
     #[tokio::test]
     #[ignore]
@@ -178,6 +208,27 @@ mod integration_tests {
         }
     }
 
+    /// 验证针对具体资源的 OPTIONS 请求：返回 204，Allow 只列出该资源支持的方法。
+    #[tokio::test]
+    #[ignore]
+    async fn test_options_on_resource() {
+        let request = "OPTIONS /index.html HTTP/1.1\r\nHost: localhost:7878\r\n\r\n";
+
+        match send_request(request, 7878).await {
+            Ok(response) => {
+                let (status_code, headers, _body) = parse_response(&response);
+                assert_eq!(status_code, 204);
+
+                let header_map: std::collections::HashMap<String, String> =
+                    headers.into_iter().collect();
+                assert_eq!(header_map.get("Allow").map(String::as_str), Some("GET, HEAD, OPTIONS"));
+            }
+            Err(e) => {
+                eprintln!("测试失败: {}", e);
+            }
+        }
+    }
+
     /// 验证 404 错误页面的渲染逻辑。
     #[tokio::test]
     #[ignore]
@@ -236,9 +287,11 @@ mod integration_tests {
                 let header_map: std::collections::HashMap<String, String> =
                     headers.into_iter().collect();
 
-                assert!(header_map.contains_key("Server"));
-                if let Some(server) = header_map.get("Server") {
-                    assert!(server.contains("shaneyale-webserver"));
+                // 期望值取自服务器使用的配置文件，server_name 为空时不应出现 Server 头
+                let config = webserver::config::Config::from_toml("config/development.toml");
+                match config.server_name() {
+                    Some(name) => assert_eq!(header_map.get("Server").map(String::as_str), Some(name)),
+                    None => assert!(!header_map.contains_key("Server")),
                 }
             }
             Err(e) => {